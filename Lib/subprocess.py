@@ -0,0 +1,780 @@
+"""Subprocesses with accessible I/O streams
+
+This module allows you to spawn processes, connect to their
+input/output/error pipes, and obtain their return codes.
+
+It is a POSIX implementation of the CPython module of the same name,
+built on top of _posixsubprocess.fork_exec(), os.pipe(), os.waitpid()
+and select.select().
+
+Main API
+========
+run(...): Runs a command, waits for it to complete, then returns a
+          CompletedProcess instance.
+Popen(...): A class for flexibly executing a command in a new process
+
+Constants
+---------
+DEVNULL: Special value that indicates that os.devnull should be used
+PIPE:    Special value that indicates a pipe should be created
+STDOUT:  Special value that indicates that stderr should go to stdout
+"""
+
+import os
+import time
+import select
+import signal
+import _posixsubprocess
+
+__all__ = ["Popen", "PIPE", "STDOUT", "call", "check_call", "getstatusoutput",
+           "getoutput", "check_output", "run", "CalledProcessError", "DEVNULL",
+           "SubprocessError", "TimeoutExpired", "CompletedProcess"]
+
+PIPE = -1
+STDOUT = -2
+DEVNULL = -3
+
+# Writes of at most PIPE_BUF bytes to a pipe are atomic and never block
+# once select() reported the pipe as writable.
+_PIPE_BUF = 512
+
+
+class SubprocessError(Exception): pass
+
+
+class CalledProcessError(SubprocessError):
+    """Raised when run() is called with check=True and the process
+    returns a non-zero exit status.
+
+    Attributes:
+      cmd, returncode, stdout, stderr, output
+    """
+    def __init__(self, returncode, cmd, output=None, stderr=None):
+        self.returncode = returncode
+        self.cmd = cmd
+        self.output = output
+        self.stderr = stderr
+
+    def __str__(self):
+        if self.returncode and self.returncode < 0:
+            return "Command '%s' died with signal %d." % (
+                self.cmd, -self.returncode)
+        else:
+            return "Command '%s' returned non-zero exit status %d." % (
+                self.cmd, self.returncode)
+
+    @property
+    def stdout(self):
+        """Alias for output attribute, to match stderr"""
+        return self.output
+
+
+class TimeoutExpired(SubprocessError):
+    """This exception is raised when the timeout expires while waiting for a
+    child process.
+
+    Attributes:
+        cmd, output, stdout, stderr, timeout
+    """
+    def __init__(self, cmd, timeout, output=None, stderr=None):
+        self.cmd = cmd
+        self.timeout = timeout
+        self.output = output
+        self.stderr = stderr
+
+    def __str__(self):
+        return ("Command '%s' timed out after %s seconds" %
+                (self.cmd, self.timeout))
+
+    @property
+    def stdout(self):
+        return self.output
+
+
+class _PipeFile:
+    """A file object over one end of a pipe to or from the child."""
+
+    def __init__(self, fd, mode, text=False, encoding=None, errors=None):
+        self._fd = fd
+        self.mode = mode
+        self.closed = False
+        self._text = text
+        self.encoding = encoding or "utf-8"
+        self.errors = errors or "strict"
+        self._buffer = b""
+
+    def fileno(self):
+        return self._fd
+
+    def _check_open(self):
+        if self.closed:
+            raise ValueError("I/O operation on closed file.")
+
+    def _decode(self, data):
+        if not self._text:
+            return data
+        data = data.decode(self.encoding, self.errors)
+        return data.replace("\r\n", "\n").replace("\r", "\n")
+
+    def read(self, size=-1):
+        self._check_open()
+        chunks = [self._buffer]
+        received = len(self._buffer)
+        while size < 0 or received < size:
+            chunk = os.read(self._fd, 32768 if size < 0 else size - received)
+            if not chunk:
+                break
+            chunks.append(chunk)
+            received += len(chunk)
+        data = b"".join(chunks)
+        if size >= 0:
+            data, self._buffer = data[:size], data[size:]
+        else:
+            self._buffer = b""
+        return self._decode(data)
+
+    def readline(self):
+        self._check_open()
+        while b"\n" not in self._buffer:
+            chunk = os.read(self._fd, 32768)
+            if not chunk:
+                break
+            self._buffer += chunk
+        index = self._buffer.find(b"\n")
+        if index < 0:
+            line, self._buffer = self._buffer, b""
+        else:
+            line, self._buffer = self._buffer[:index + 1], self._buffer[index + 1:]
+        return self._decode(line)
+
+    def readlines(self):
+        return list(self)
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        line = self.readline()
+        if not line:
+            raise StopIteration
+        return line
+
+    def write(self, data):
+        self._check_open()
+        if self._text:
+            data = data.encode(self.encoding)
+        written = 0
+        while written < len(data):
+            written += os.write(self._fd, data[written:])
+        return len(data)
+
+    def flush(self):
+        self._check_open()
+
+    def close(self):
+        if not self.closed:
+            self.closed = True
+            os.close(self._fd)
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, exc_type, value, traceback):
+        self.close()
+
+
+def call(*popenargs, timeout=None, **kwargs):
+    """Run command with arguments.  Wait for command to complete or
+    timeout, then return the returncode attribute.
+
+    The arguments are the same as for the Popen constructor.  Example:
+
+    retcode = call(["ls", "-l"])
+    """
+    with Popen(*popenargs, **kwargs) as p:
+        try:
+            return p.wait(timeout=timeout)
+        except:
+            p.kill()
+            # We don't call p.wait() again as p.__exit__ does that for us.
+            raise
+
+
+def check_call(*popenargs, **kwargs):
+    """Run command with arguments.  Wait for command to complete.  If
+    the exit code was zero then return, otherwise raise
+    CalledProcessError.  The CalledProcessError object will have the
+    return code in the returncode attribute.
+
+    The arguments are the same as for the call function.  Example:
+
+    check_call(["ls", "-l"])
+    """
+    retcode = call(*popenargs, **kwargs)
+    if retcode:
+        cmd = kwargs.get("args")
+        if cmd is None:
+            cmd = popenargs[0]
+        raise CalledProcessError(retcode, cmd)
+    return 0
+
+
+def check_output(*popenargs, timeout=None, **kwargs):
+    r"""Run command with arguments and return its output.
+
+    If the exit code was non-zero it raises a CalledProcessError.  The
+    CalledProcessError object will have the return code in the returncode
+    attribute and output in the output attribute.
+
+    The arguments are the same as for the Popen constructor.  Example:
+
+    >>> check_output(["ls", "-l", "/dev/null"])
+    b'crw-rw-rw- 1 root root 1, 3 Oct 18  2007 /dev/null\n'
+    """
+    if 'stdout' in kwargs:
+        raise ValueError('stdout argument not allowed, it will be overridden.')
+
+    if 'input' in kwargs and kwargs['input'] is None:
+        # Explicitly passing input=None was previously equivalent to passing an
+        # empty string. That is maintained here for backwards compatibility.
+        kwargs['input'] = '' if kwargs.get('universal_newlines', False) else b''
+
+    return run(*popenargs, stdout=PIPE, timeout=timeout, check=True,
+               **kwargs).stdout
+
+
+class CompletedProcess(object):
+    """A process that has finished running.
+
+    This is returned by run().
+
+    Attributes:
+      args: The list or str args passed to run().
+      returncode: The exit code of the process, negative for signals.
+      stdout: The standard output (None if not captured).
+      stderr: The standard error (None if not captured).
+    """
+    def __init__(self, args, returncode, stdout=None, stderr=None):
+        self.args = args
+        self.returncode = returncode
+        self.stdout = stdout
+        self.stderr = stderr
+
+    def __repr__(self):
+        args = ['args={!r}'.format(self.args),
+                'returncode={!r}'.format(self.returncode)]
+        if self.stdout is not None:
+            args.append('stdout={!r}'.format(self.stdout))
+        if self.stderr is not None:
+            args.append('stderr={!r}'.format(self.stderr))
+        return "{}({})".format(type(self).__name__, ', '.join(args))
+
+    def check_returncode(self):
+        """Raise CalledProcessError if the exit code is non-zero."""
+        if self.returncode:
+            raise CalledProcessError(self.returncode, self.args, self.stdout,
+                                     self.stderr)
+
+
+def run(*popenargs, input=None, capture_output=False, timeout=None,
+        check=False, **kwargs):
+    """Run command with arguments and return a CompletedProcess instance.
+
+    The returned instance will have attributes args, returncode, stdout and
+    stderr. By default, stdout and stderr are not captured, and those attributes
+    will be None. Pass stdout=PIPE and/or stderr=PIPE in order to capture them.
+
+    If check is True and the exit code was non-zero, it raises a
+    CalledProcessError. The CalledProcessError object will have the return code
+    in the returncode attribute, and output & stderr attributes if those streams
+    were captured.
+
+    If timeout is given, and the process takes too long, a TimeoutExpired
+    exception will be raised.
+
+    There is an optional argument "input", allowing you to
+    pass bytes or a string to the subprocess's stdin.  If you use this argument
+    you may not also use the Popen constructor's "stdin" argument, as
+    it will be used internally.
+    """
+    if input is not None:
+        if kwargs.get('stdin') is not None:
+            raise ValueError('stdin and input arguments may not both be used.')
+        kwargs['stdin'] = PIPE
+
+    if capture_output:
+        if kwargs.get('stdout') is not None or kwargs.get('stderr') is not None:
+            raise ValueError('stdout and stderr arguments may not be used '
+                             'with capture_output.')
+        kwargs['stdout'] = PIPE
+        kwargs['stderr'] = PIPE
+
+    with Popen(*popenargs, **kwargs) as process:
+        try:
+            stdout, stderr = process.communicate(input, timeout=timeout)
+        except TimeoutExpired as exc:
+            process.kill()
+            process.wait()
+            exc.output, exc.stderr = process._stdout_data, process._stderr_data
+            raise
+        except:
+            process.kill()
+            # We don't call process.wait() as .__exit__ does that for us.
+            raise
+        retcode = process.poll()
+        if check and retcode:
+            raise CalledProcessError(retcode, process.args,
+                                     output=stdout, stderr=stderr)
+    return CompletedProcess(process.args, retcode, stdout, stderr)
+
+
+def list2cmdline(seq):
+    """Quote a sequence of arguments for display, POSIX shell style."""
+    result = []
+    for arg in seq:
+        if arg and all(c.isalnum() or c in "@%+=:,./-_" for c in arg):
+            result.append(arg)
+        else:
+            result.append("'" + arg.replace("'", "'\"'\"'") + "'")
+    return ' '.join(result)
+
+
+def getstatusoutput(cmd):
+    """Return (exitcode, output) of executing cmd in a shell.
+
+    Execute the string 'cmd' in a shell with 'check_output' and
+    return a 2-tuple (status, output). The locale encoding is used
+    to decode the output and process newlines.
+
+    A trailing newline is stripped from the output.
+    """
+    try:
+        data = check_output(cmd, shell=True, text=True, stderr=STDOUT)
+        exitcode = 0
+    except CalledProcessError as ex:
+        data = ex.output
+        exitcode = ex.returncode
+    if data[-1:] == '\n':
+        data = data[:-1]
+    return exitcode, data
+
+
+def getoutput(cmd):
+    """Return output (stdout or stderr) of executing cmd in a shell.
+
+    Like getstatusoutput(), except the exit status is ignored and the return
+    value is a string containing the command's output.
+    """
+    return getstatusoutput(cmd)[1]
+
+
+def _exec_path(env):
+    """The directories that will be searched for a named executable."""
+    if env is None:
+        env = os.environ
+    path = env.get('PATH', os.defpath)
+    return path.split(os.pathsep)
+
+
+class Popen(object):
+    """ Execute a child program in a new process.
+
+    Arguments:
+      args: A string, or a sequence of program arguments.
+
+      executable: A replacement program to execute.
+
+      stdin, stdout and stderr: These specify the executed programs' standard
+          input, standard output and standard error file handles, respectively.
+
+      close_fds: Controls closing or inheriting of file descriptors.
+
+      shell: If true, the command will be executed through the shell.
+
+      cwd: Sets the current directory before the child is executed.
+
+      env: Defines the environment variables for the new process.
+
+      text: If true, decode stdin, stdout and stderr using the given encoding
+          (if set) or UTF-8.
+
+      universal_newlines: Alias of text, provided for backwards compatibility.
+
+      restore_signals: If true, signals that Python has set to SIG_IGN are
+          restored to SIG_DFL in the child process before the exec.
+
+      start_new_session: If true, the setsid() system call will be made in the
+          child process prior to the execution of the subprocess.
+
+      pass_fds: List of file descriptors to keep open between the parent and
+          the child.
+
+    Attributes:
+        stdin, stdout, stderr, pid, returncode
+    """
+    def __init__(self, args, bufsize=-1, executable=None,
+                 stdin=None, stdout=None, stderr=None,
+                 preexec_fn=None, close_fds=True,
+                 shell=False, cwd=None, env=None, universal_newlines=None,
+                 startupinfo=None, creationflags=0,
+                 restore_signals=True, start_new_session=False,
+                 pass_fds=(), *, encoding=None, errors=None, text=None):
+        """Create new Popen instance."""
+        if preexec_fn is not None:
+            raise ValueError("preexec_fn is not supported")
+        if startupinfo is not None:
+            raise ValueError("startupinfo is only supported on Windows "
+                             "platforms")
+        if creationflags != 0:
+            raise ValueError("creationflags is only supported on Windows "
+                             "platforms")
+        if pass_fds and not close_fds:
+            close_fds = True
+
+        self.args = args
+        self.stdin = None
+        self.stdout = None
+        self.stderr = None
+        self.pid = None
+        self.returncode = None
+        self.encoding = encoding
+        self.errors = errors
+        self._stdout_data = None
+        self._stderr_data = None
+
+        if (text is not None and universal_newlines is not None
+                and bool(universal_newlines) != bool(text)):
+            raise SubprocessError('Cannot disambiguate when both text '
+                                  'and universal_newlines are supplied but '
+                                  'different. Pass one or the other.')
+
+        self.text_mode = bool(encoding or errors or text or universal_newlines)
+
+        (p2cread, p2cwrite,
+         c2pread, c2pwrite,
+         errread, errwrite, to_close) = self._get_handles(stdin, stdout, stderr)
+
+        try:
+            self._execute_child(args, executable, close_fds, pass_fds, cwd,
+                                env, shell, p2cread, p2cwrite, c2pread, c2pwrite,
+                                errread, errwrite, restore_signals,
+                                start_new_session)
+        except:
+            for fd in to_close + [p2cwrite, c2pread, errread]:
+                if fd != -1:
+                    os.close(fd)
+            raise
+
+        # The child's ends of the pipes are not needed in the parent anymore
+        for fd in to_close:
+            os.close(fd)
+
+        if p2cwrite != -1:
+            self.stdin = _PipeFile(p2cwrite, 'wb', self.text_mode,
+                                   encoding, errors)
+        if c2pread != -1:
+            self.stdout = _PipeFile(c2pread, 'rb', self.text_mode,
+                                    encoding, errors)
+        if errread != -1:
+            self.stderr = _PipeFile(errread, 'rb', self.text_mode,
+                                    encoding, errors)
+
+    def __repr__(self):
+        return "<{}: returncode: {} args: {!r}>".format(
+            type(self).__name__, self.returncode, self.args)
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, exc_type, value, traceback):
+        if self.stdout:
+            self.stdout.close()
+        if self.stderr:
+            self.stderr.close()
+        if self.stdin:
+            try:
+                self.stdin.close()
+            except BrokenPipeError:
+                pass
+        # Wait for the process to terminate, to avoid zombies.
+        self.wait()
+
+    def _get_handles(self, stdin, stdout, stderr):
+        """Construct and return tuple with IO objects:
+        p2cread, p2cwrite, c2pread, c2pwrite, errread, errwrite, to_close
+
+        to_close lists the descriptors that belong to the child only.
+        """
+        to_close = []
+
+        def child_fd(handle, readable):
+            if handle is None:
+                return -1, -1
+            elif handle == PIPE:
+                read_fd, write_fd = os.pipe()
+                if readable:
+                    to_close.append(read_fd)
+                    return read_fd, write_fd
+                to_close.append(write_fd)
+                return write_fd, read_fd
+            elif handle == DEVNULL:
+                fd = os.open(os.devnull, os.O_RDWR)
+                to_close.append(fd)
+                return fd, -1
+            elif isinstance(handle, int):
+                return handle, -1
+            else:
+                # Assuming file-like object
+                return handle.fileno(), -1
+
+        p2cread, p2cwrite = child_fd(stdin, True)
+        c2pwrite, c2pread = child_fd(stdout, False)
+        if stderr == STDOUT:
+            errwrite, errread = (c2pwrite if c2pwrite != -1 else 1), -1
+        else:
+            errwrite, errread = child_fd(stderr, False)
+
+        return (p2cread, p2cwrite,
+                c2pread, c2pwrite,
+                errread, errwrite, to_close)
+
+    def _execute_child(self, args, executable, close_fds, pass_fds, cwd, env,
+                       shell, p2cread, p2cwrite, c2pread, c2pwrite,
+                       errread, errwrite, restore_signals, start_new_session):
+        """Execute program (POSIX version)"""
+        if isinstance(args, (str, bytes)):
+            args = [args]
+        else:
+            args = list(args)
+
+        if shell:
+            args = ["/bin/sh", "-c"] + args
+            if executable:
+                args[0] = executable
+
+        if executable is None:
+            executable = args[0]
+
+        if os.path.dirname(executable):
+            executable_list = [executable]
+        else:
+            # This matches the behavior of os._execvpe().
+            executable_list = [os.path.join(dir, executable)
+                               for dir in _exec_path(env)]
+
+        if env is not None:
+            env_list = ['%s=%s' % (key, value) for key, value in env.items()]
+        else:
+            env_list = None
+
+        fds_to_keep = sorted(set(pass_fds))
+
+        # The child reports failures before exec through this pipe, which
+        # is closed automatically when exec succeeds.
+        errpipe_read, errpipe_write = os.pipe()
+        try:
+            self.pid = _posixsubprocess.fork_exec(
+                args, executable_list,
+                close_fds, fds_to_keep,
+                cwd, env_list,
+                p2cread, p2cwrite, c2pread, c2pwrite,
+                errread, errwrite,
+                errpipe_read, errpipe_write,
+                restore_signals, start_new_session, None)
+        except:
+            os.close(errpipe_read)
+            os.close(errpipe_write)
+            raise
+        os.close(errpipe_write)
+
+        errpipe_data = b''
+        while True:
+            part = os.read(errpipe_read, 50000)
+            if not part:
+                break
+            errpipe_data += part
+        os.close(errpipe_read)
+
+        if errpipe_data:
+            # The child exits with 255 right after writing the error
+            try:
+                pid, sts = os.waitpid(self.pid, 0)
+                if pid == self.pid:
+                    self._handle_exitstatus(sts)
+                else:
+                    self.returncode = 255
+            except OSError:
+                self.returncode = 255
+
+            exception_name, hex_errno, err_msg = errpipe_data.split(b':', 2)
+            errno_num = int(hex_errno.decode(), 16)
+            if err_msg == b'noexec':
+                # The error must be from chdir(cwd)
+                filename = cwd
+            else:
+                filename = executable
+            if errno_num == 0:
+                raise SubprocessError(err_msg.decode())
+            raise OSError(errno_num, os.strerror(errno_num), filename)
+
+    def _handle_exitstatus(self, sts):
+        if os.WIFSIGNALED(sts):
+            self.returncode = -os.WTERMSIG(sts)
+        elif os.WIFEXITED(sts):
+            self.returncode = os.WEXITSTATUS(sts)
+        elif os.WIFSTOPPED(sts):
+            self.returncode = -os.WSTOPSIG(sts)
+        else:
+            raise SubprocessError("Unknown child exit status!")
+
+    def poll(self):
+        """Check if child process has terminated. Set and return returncode
+        attribute."""
+        if self.returncode is None:
+            try:
+                pid, sts = os.waitpid(self.pid, os.WNOHANG)
+                if pid == self.pid:
+                    self._handle_exitstatus(sts)
+            except ChildProcessError:
+                # The child has already been reaped elsewhere
+                self.returncode = 0
+        return self.returncode
+
+    def _remaining_time(self, endtime):
+        """Convenience for _communicate when computing timeouts."""
+        if endtime is None:
+            return None
+        else:
+            return endtime - time.time()
+
+    def wait(self, timeout=None):
+        """Wait for child process to terminate; returns self.returncode."""
+        if self.returncode is not None:
+            return self.returncode
+
+        if timeout is not None:
+            endtime = time.time() + timeout
+            # Poll with an exponential back-off, like CPython does
+            delay = 0.0005
+            while True:
+                if self.poll() is not None:
+                    break
+                remaining = self._remaining_time(endtime)
+                if remaining <= 0:
+                    raise TimeoutExpired(self.args, timeout)
+                delay = min(delay * 2, remaining, .05)
+                time.sleep(delay)
+        else:
+            while self.returncode is None:
+                try:
+                    pid, sts = os.waitpid(self.pid, 0)
+                except ChildProcessError:
+                    pid, sts = self.pid, 0
+                if pid == self.pid:
+                    self._handle_exitstatus(sts)
+        return self.returncode
+
+    def communicate(self, input=None, timeout=None):
+        """Interact with process: Send data to stdin and close it.
+        Read data from stdout and stderr, until end-of-file is
+        reached.  Wait for process to terminate.
+
+        The optional "input" argument should be data to be sent to the
+        child process, or None, if no data should be sent to the child.
+        communicate() returns a tuple (stdout, stderr).
+
+        By default, all communication is in bytes, and therefore any
+        "input" should be bytes, and the (stdout, stderr) will be bytes.
+        If in text mode (indicated by self.text_mode), any "input" should
+        be a string, and (stdout, stderr) will be strings decoded
+        according to locale encoding, or by "encoding" if set. Text mode
+        is triggered by setting any of text, encoding, errors or
+        universal_newlines.
+        """
+        if timeout is not None:
+            endtime = time.time() + timeout
+        else:
+            endtime = None
+
+        if self.stdin and input is not None and self.text_mode:
+            input = input.encode(self.stdin.encoding)
+
+        stdout = []
+        stderr = []
+        self._stdout_data = None
+        self._stderr_data = None
+
+        read_fds = {}
+        write_fd = -1
+        if self.stdout:
+            read_fds[self.stdout.fileno()] = (self.stdout, stdout)
+        if self.stderr:
+            read_fds[self.stderr.fileno()] = (self.stderr, stderr)
+        if self.stdin:
+            if input:
+                write_fd = self.stdin.fileno()
+            else:
+                self._close_stdin()
+
+        input_offset = 0
+        while read_fds or write_fd != -1:
+            write_fds = [write_fd] if write_fd != -1 else []
+            remaining = self._remaining_time(endtime)
+            if remaining is not None and remaining < 0:
+                self._save_output(stdout, stderr)
+                raise TimeoutExpired(self.args, timeout)
+            ready_read, ready_write, _ = select.select(
+                list(read_fds), write_fds, [], remaining)
+
+            if ready_write:
+                chunk = input[input_offset:input_offset + _PIPE_BUF]
+                try:
+                    input_offset += os.write(write_fd, chunk)
+                except BrokenPipeError:
+                    # The child stopped reading its input
+                    input_offset = len(input)
+                if input_offset >= len(input):
+                    write_fd = -1
+                    self._close_stdin()
+
+            for fd in ready_read:
+                file, chunks = read_fds[fd]
+                data = os.read(fd, 32768)
+                if data:
+                    chunks.append(data)
+                else:
+                    file.close()
+                    del read_fds[fd]
+
+        self._save_output(stdout, stderr)
+        self.wait(timeout=self._remaining_time(endtime))
+
+        return (self._stdout_data, self._stderr_data)
+
+    def _close_stdin(self):
+        try:
+            self.stdin.close()
+        except BrokenPipeError:
+            pass
+
+    def _save_output(self, stdout, stderr):
+        if self.stdout:
+            self._stdout_data = self.stdout._decode(b''.join(stdout))
+        if self.stderr:
+            self._stderr_data = self.stderr._decode(b''.join(stderr))
+
+    def send_signal(self, sig):
+        """Send a signal to the process."""
+        # Skip signalling a process that we know has already died.
+        if self.returncode is None:
+            os.kill(self.pid, sig)
+
+    def terminate(self):
+        """Terminate the process with SIGTERM
+        """
+        self.send_signal(signal.SIGTERM)
+
+    def kill(self):
+        """Kill the process with SIGKILL
+        """
+        self.send_signal(signal.SIGKILL)
@@ -5,7 +5,7 @@ use bitflags::bitflags;
 use num_bigint::BigInt;
 use num_complex::Complex64;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::fmt;
 
 /// Sourcode location.
@@ -34,17 +34,32 @@ impl Location {
 #[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct CodeObject {
     pub instructions: Vec<Instruction>,
-    /// Jump targets.
-    pub label_map: HashMap<Label, usize>,
     pub locations: Vec<Location>,
-    pub arg_names: Vec<String>, // Names of positional arguments
-    pub varargs: Varargs,       // *args or *
+    pub arg_names: Vec<String>,  // Names of positional arguments
+    pub posonlyarg_count: usize, // How many of the positional arguments are positional-only
+    pub varargs: Varargs,        // *args or *
     pub kwonlyarg_names: Vec<String>,
     pub varkeywords: Varargs, // **kwargs or **
     pub source_path: String,
     pub first_line_number: usize,
     pub obj_name: String, // Name of the object that created this code object
     pub is_generator: bool,
+    /// Whether this is the code of a function, whose local variables live in
+    /// the frame rather than in a namespace dict.
+    pub is_optimized: bool,
+    /// Constants loaded by `LoadConst`, which refers to them by index.
+    pub constants: Vec<Constant>,
+    /// Names of the globals, attributes and modules which instructions refer
+    /// to by index.
+    pub names: Vec<String>,
+    /// Names of the local variables stored in the fast local slots of the frame.
+    pub varnames: Vec<String>,
+    /// Names of the local variables which nested functions refer to, and
+    /// which are therefore stored in cells.
+    pub cellvars: Vec<String>,
+    /// Names of the variables of enclosing functions which this code refers
+    /// to, and which it receives as cells in the closure of its function.
+    pub freevars: Vec<String>,
 }
 
 bitflags! {
@@ -53,15 +68,16 @@ bitflags! {
         const HAS_DEFAULTS = 0x01;
         const HAS_KW_ONLY_DEFAULTS = 0x02;
         const HAS_ANNOTATIONS = 0x04;
+        const HAS_CLOSURE = 0x08;
     }
 }
 
+/// The offset of the instruction which is the target of a jump.
 pub type Label = usize;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum NameScope {
     Local,
-    NonLocal,
     Global,
 }
 
@@ -80,35 +96,63 @@ pub enum ConversionFlag {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Instruction {
     Import {
-        name: String,
-        symbols: Vec<String>,
+        name_index: usize,
+        symbol_indices: Vec<usize>,
         level: usize,
     },
     ImportStar {
-        name: String,
+        name_index: usize,
         level: usize,
     },
     LoadName {
-        name: String,
+        index: usize,
         scope: NameScope,
     },
     StoreName {
-        name: String,
+        index: usize,
         scope: NameScope,
     },
     DeleteName {
-        name: String,
+        index: usize,
+        scope: NameScope,
+    },
+    LoadFast {
+        index: usize,
+    },
+    StoreFast {
+        index: usize,
+    },
+    DeleteFast {
+        index: usize,
+    },
+    /// Push the cell of a cell or free variable, to build a closure.
+    LoadClosure {
+        index: usize,
+    },
+    LoadDeref {
+        index: usize,
+    },
+    /// Load a free variable of a class body, unless the class namespace
+    /// has a binding for the name.
+    LoadClassDeref {
+        index: usize,
+    },
+    StoreDeref {
+        index: usize,
+    },
+    DeleteDeref {
+        index: usize,
     },
     StoreSubscript,
     DeleteSubscript,
     StoreAttr {
-        name: String,
+        index: usize,
     },
     DeleteAttr {
-        name: String,
+        index: usize,
     },
     LoadConst {
-        value: Constant,
+        index: usize,
     },
     UnaryOperation {
         op: UnaryOperator,
@@ -118,7 +162,12 @@ pub enum Instruction {
         inplace: bool,
     },
     LoadAttr {
-        name: String,
+        index: usize,
+    },
+    /// Look up a method to be called by `CallMethod`, without binding it to
+    /// the object it is looked up on.
+    LoadMethod {
+        index: usize,
     },
     CompareOperation {
         op: ComparisonOperator,
@@ -147,6 +196,10 @@ pub enum Instruction {
     CallFunction {
         typ: CallType,
     },
+    /// Call the method loaded by `LoadMethod`.
+    CallMethod {
+        typ: CallType,
+    },
     ForIter {
         target: Label,
     },
@@ -160,6 +213,11 @@ pub enum Instruction {
     SetupExcept {
         handler: Label,
     },
+    SetupFinally {
+        handler: Label,
+    },
+    EnterFinally,
+    EndFinally,
     SetupWith {
         end: Label,
     },
@@ -188,6 +246,9 @@ pub enum Instruction {
     BuildMap {
         size: usize,
         unpack: bool,
+        /// Whether the map holds keyword arguments, whose keys must be
+        /// unique strings.
+        for_call: bool,
     },
     BuildSlice {
         size: usize,
@@ -203,6 +264,7 @@ pub enum Instruction {
     },
     PrintExpr,
     LoadBuildClass,
+    SetupAnnotations,
     UnpackSequence {
         size: usize,
     },
@@ -213,7 +275,6 @@ pub enum Instruction {
     Unpack,
     FormatValue {
         conversion: Option<ConversionFlag>,
-        spec: String,
     },
     PopException,
 }
@@ -296,45 +357,74 @@ pub enum BlockType {
 }
 */
 
+/// The parameters accepted by the function a code object belongs to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArgSpec {
+    pub arg_names: Vec<String>,
+    pub posonlyarg_count: usize,
+    pub varargs: Varargs,
+    pub kwonlyarg_names: Vec<String>,
+    pub varkeywords: Varargs,
+}
+
+impl Default for ArgSpec {
+    fn default() -> Self {
+        ArgSpec {
+            arg_names: Vec::new(),
+            posonlyarg_count: 0,
+            varargs: Varargs::None,
+            kwonlyarg_names: Vec::new(),
+            varkeywords: Varargs::None,
+        }
+    }
+}
+
 impl CodeObject {
     pub fn new(
-        arg_names: Vec<String>,
-        varargs: Varargs,
-        kwonlyarg_names: Vec<String>,
-        varkeywords: Varargs,
+        args: ArgSpec,
         source_path: String,
         first_line_number: usize,
         obj_name: String,
     ) -> CodeObject {
         CodeObject {
             instructions: Vec::new(),
-            label_map: HashMap::new(),
             locations: Vec::new(),
-            arg_names,
-            varargs,
-            kwonlyarg_names,
-            varkeywords,
+            arg_names: args.arg_names,
+            posonlyarg_count: args.posonlyarg_count,
+            varargs: args.varargs,
+            kwonlyarg_names: args.kwonlyarg_names,
+            varkeywords: args.varkeywords,
             source_path,
             first_line_number,
             obj_name,
             is_generator: false,
+            is_optimized: false,
+            constants: Vec::new(),
+            names: Vec::new(),
+            varnames: Vec::new(),
+            cellvars: Vec::new(),
+            freevars: Vec::new(),
         }
     }
 
-    pub fn get_constants(&self) -> impl Iterator<Item = &Constant> {
-        self.instructions.iter().filter_map(|x| {
-            if let Instruction::LoadConst { value } = x {
-                Some(value)
-            } else {
-                None
-            }
-        })
+    /// The name of the cell or free variable with index `index`, where cell
+    /// variables come first.
+    pub fn deref_name(&self, index: usize) -> &str {
+        if index < self.cellvars.len() {
+            &self.cellvars[index]
+        } else {
+            &self.freevars[index - self.cellvars.len()]
+        }
     }
 }
 
 impl fmt::Display for CodeObject {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let label_targets: HashSet<&usize> = self.label_map.values().collect();
+        let label_targets: HashSet<Label> = self
+            .instructions
+            .iter()
+            .flat_map(Instruction::label_targets)
+            .collect();
         for (offset, instruction) in self.instructions.iter().enumerate() {
             let arrow = if label_targets.contains(&offset) {
                 ">>"
@@ -342,14 +432,40 @@ impl fmt::Display for CodeObject {
                 "  "
             };
             write!(f, "          {} {:5} ", arrow, offset)?;
-            instruction.fmt_dis(f, &self.label_map)?;
+            instruction.fmt_dis(f, self)?;
         }
         Ok(())
     }
 }
 
 impl Instruction {
-    fn fmt_dis(&self, f: &mut fmt::Formatter, label_map: &HashMap<Label, usize>) -> fmt::Result {
+    /// The offsets of the instructions which this instruction may jump to.
+    pub fn label_targets(&self) -> Vec<Label> {
+        match self {
+            Jump { target } | JumpIf { target } | JumpIfFalse { target } | ForIter { target } => {
+                vec![*target]
+            }
+            SetupLoop { start, end } => vec![*start, *end],
+            SetupExcept { handler } | SetupFinally { handler } => vec![*handler],
+            SetupWith { end } | CleanupWith { end } => vec![*end],
+            _ => vec![],
+        }
+    }
+
+    /// Mutable references to the jump targets of this instruction.
+    pub fn label_targets_mut(&mut self) -> Vec<&mut Label> {
+        match self {
+            Jump { target } | JumpIf { target } | JumpIfFalse { target } | ForIter { target } => {
+                vec![target]
+            }
+            SetupLoop { start, end } => vec![start, end],
+            SetupExcept { handler } | SetupFinally { handler } => vec![handler],
+            SetupWith { end } | CleanupWith { end } => vec![end],
+            _ => vec![],
+        }
+    }
+
+    fn fmt_dis(&self, f: &mut fmt::Formatter, code: &CodeObject) -> fmt::Result {
         macro_rules! w {
             ($variant:ident) => {
                 write!(f, "{:20}\n", stringify!($variant))
@@ -374,22 +490,44 @@ impl Instruction {
 
         match self {
             Import {
-                name,
-                symbols,
+                name_index,
+                symbol_indices,
                 level,
-            } => w!(Import, name, format!("{:?}", symbols), level),
-            ImportStar { name, level } => w!(ImportStar, name, level),
-            LoadName { name, scope } => w!(LoadName, name, format!("{:?}", scope)),
-            StoreName { name, scope } => w!(StoreName, name, format!("{:?}", scope)),
-            DeleteName { name } => w!(DeleteName, name),
+            } => {
+                let symbols: Vec<&String> = symbol_indices
+                    .iter()
+                    .map(|index| &code.names[*index])
+                    .collect();
+                w!(
+                    Import,
+                    code.names[*name_index],
+                    format!("{:?}", symbols),
+                    level
+                )
+            }
+            ImportStar { name_index, level } => w!(ImportStar, code.names[*name_index], level),
+            LoadName { index, scope } => w!(LoadName, code.names[*index], format!("{:?}", scope)),
+            StoreName { index, scope } => w!(StoreName, code.names[*index], format!("{:?}", scope)),
+            DeleteName { index, scope } => {
+                w!(DeleteName, code.names[*index], format!("{:?}", scope))
+            }
+            LoadFast { index } => w!(LoadFast, index, code.varnames[*index]),
+            StoreFast { index } => w!(StoreFast, index, code.varnames[*index]),
+            DeleteFast { index } => w!(DeleteFast, index, code.varnames[*index]),
+            LoadClosure { index } => w!(LoadClosure, index, code.deref_name(*index)),
+            LoadDeref { index } => w!(LoadDeref, index, code.deref_name(*index)),
+            LoadClassDeref { index } => w!(LoadClassDeref, index, code.deref_name(*index)),
+            StoreDeref { index } => w!(StoreDeref, index, code.deref_name(*index)),
+            DeleteDeref { index } => w!(DeleteDeref, index, code.deref_name(*index)),
             StoreSubscript => w!(StoreSubscript),
             DeleteSubscript => w!(DeleteSubscript),
-            StoreAttr { name } => w!(StoreAttr, name),
-            DeleteAttr { name } => w!(DeleteAttr, name),
-            LoadConst { value } => w!(LoadConst, value),
+            StoreAttr { index } => w!(StoreAttr, code.names[*index]),
+            DeleteAttr { index } => w!(DeleteAttr, code.names[*index]),
+            LoadConst { index } => w!(LoadConst, code.constants[*index]),
             UnaryOperation { op } => w!(UnaryOperation, format!("{:?}", op)),
             BinaryOperation { op, inplace } => w!(BinaryOperation, format!("{:?}", op), inplace),
-            LoadAttr { name } => w!(LoadAttr, name),
+            LoadAttr { index } => w!(LoadAttr, code.names[*index]),
+            LoadMethod { index } => w!(LoadMethod, code.names[*index]),
             CompareOperation { op } => w!(CompareOperation, format!("{:?}", op)),
             Pop => w!(Pop),
             Rotate { amount } => w!(Rotate, amount),
@@ -398,17 +536,21 @@ impl Instruction {
             Pass => w!(Pass),
             Continue => w!(Continue),
             Break => w!(Break),
-            Jump { target } => w!(Jump, label_map[target]),
-            JumpIf { target } => w!(JumpIf, label_map[target]),
-            JumpIfFalse { target } => w!(JumpIfFalse, label_map[target]),
+            Jump { target } => w!(Jump, target),
+            JumpIf { target } => w!(JumpIf, target),
+            JumpIfFalse { target } => w!(JumpIfFalse, target),
             MakeFunction { flags } => w!(MakeFunction, format!("{:?}", flags)),
             CallFunction { typ } => w!(CallFunction, format!("{:?}", typ)),
-            ForIter { target } => w!(ForIter, label_map[target]),
+            CallMethod { typ } => w!(CallMethod, format!("{:?}", typ)),
+            ForIter { target } => w!(ForIter, target),
             ReturnValue => w!(ReturnValue),
             YieldValue => w!(YieldValue),
             YieldFrom => w!(YieldFrom),
-            SetupLoop { start, end } => w!(SetupLoop, label_map[start], label_map[end]),
+            SetupLoop { start, end } => w!(SetupLoop, start, end),
             SetupExcept { handler } => w!(SetupExcept, handler),
+            SetupFinally { handler } => w!(SetupFinally, handler),
+            EnterFinally => w!(EnterFinally),
+            EndFinally => w!(EndFinally),
             SetupWith { end } => w!(SetupWith, end),
             CleanupWith { end } => w!(CleanupWith, end),
             PopBlock => w!(PopBlock),
@@ -417,17 +559,22 @@ impl Instruction {
             BuildTuple { size, unpack } => w!(BuildTuple, size, unpack),
             BuildList { size, unpack } => w!(BuildList, size, unpack),
             BuildSet { size, unpack } => w!(BuildSet, size, unpack),
-            BuildMap { size, unpack } => w!(BuildMap, size, unpack),
+            BuildMap {
+                size,
+                unpack,
+                for_call,
+            } => w!(BuildMap, size, unpack, for_call),
             BuildSlice { size } => w!(BuildSlice, size),
             ListAppend { i } => w!(ListAppend, i),
             SetAdd { i } => w!(SetAdd, i),
             MapAdd { i } => w!(MapAdd, i),
             PrintExpr => w!(PrintExpr),
             LoadBuildClass => w!(LoadBuildClass),
+            SetupAnnotations => w!(SetupAnnotations),
             UnpackSequence { size } => w!(UnpackSequence, size),
             UnpackEx { before, after } => w!(UnpackEx, before, after),
             Unpack => w!(Unpack),
-            FormatValue { spec, .. } => w!(FormatValue, spec), // TODO: write conversion
+            FormatValue { conversion } => w!(FormatValue, format!("{:?}", conversion)),
             PopException => w!(PopException),
         }
     }
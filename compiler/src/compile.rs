@@ -5,37 +5,69 @@
 //!   https://github.com/python/cpython/blob/master/Python/compile.c
 //!   https://github.com/micropython/micropython/blob/master/py/compile.c
 
+use crate::encoding;
 use crate::error::{CompileError, CompileErrorType};
-use crate::symboltable::{make_symbol_table, statements_to_symbol_table, SymbolRole, SymbolScope};
+use crate::symboltable::{
+    make_symbol_table, statements_to_symbol_table, SymbolScope, SymbolTable, SymbolTableType,
+};
+use crate::unparse::unparse_expression;
 use num_complex::Complex64;
-use rustpython_bytecode::bytecode::{self, CallType, CodeObject, Instruction, Varargs};
+use rustpython_bytecode::bytecode::{self, ArgSpec, CallType, CodeObject, Instruction, Varargs};
 use rustpython_parser::{ast, parser};
+use std::collections::{HashMap, HashSet};
 
 struct Compiler {
     code_object_stack: Vec<CodeObject>,
-    scope_stack: Vec<SymbolScope>,
+    scope_stack: Vec<SymbolTable>,
     nxt_label: usize,
+    /// Offsets of the instructions which labels are set to, in the code
+    /// object they belong to.
+    label_map: HashMap<Label, usize>,
     source_path: Option<String>,
     current_source_location: ast::Location,
     current_qualified_path: Option<String>,
     in_loop: bool,
     in_function_def: bool,
+    /// Set by `from __future__ import annotations`: annotations are stored as strings.
+    future_annotations: bool,
 }
 
 /// Compile a given sourcecode into a bytecode object.
 pub fn compile(source: &str, mode: &Mode, source_path: String) -> Result<CodeObject, CompileError> {
-    match mode {
-        Mode::Exec => {
-            let ast = parser::parse_program(source)?;
-            compile_program(ast, source_path)
+    let result = match mode {
+        Mode::Exec => parser::parse_program(source)
+            .map_err(CompileError::from)
+            .and_then(|ast| compile_program(ast, source_path.clone())),
+        Mode::Eval => parser::parse_statement(source)
+            .map_err(CompileError::from)
+            .and_then(|statement| compile_statement_eval(statement, source_path.clone())),
+        Mode::Single => parser::parse_program(source)
+            .map_err(CompileError::from)
+            .and_then(|ast| compile_program_single(ast, source_path.clone())),
+    };
+    result.map_err(|mut error| {
+        // Attach the offending line and file so the error can be reported like CPython does:
+        let row = error.location.row();
+        if let Some(statement) = row.checked_sub(1).and_then(|i| source.lines().nth(i)) {
+            error.update_statement_info(statement.to_string());
         }
-        Mode::Eval => {
-            let statement = parser::parse_statement(source)?;
-            compile_statement_eval(statement, source_path)
-        }
-        Mode::Single => {
-            let ast = parser::parse_program(source)?;
-            compile_program_single(ast, source_path)
+        error.update_source_path(&source_path);
+        error
+    })
+}
+
+/// Compile source code given as bytes, decoding it according to its byte
+/// order mark or encoding declaration.
+pub fn compile_bytes(
+    source: &[u8],
+    mode: &Mode,
+    source_path: String,
+) -> Result<CodeObject, CompileError> {
+    match encoding::decode_source(source) {
+        Ok(source) => compile(&source, mode, source_path),
+        Err(mut error) => {
+            error.update_source_path(&source_path);
+            Err(error)
         }
     }
 }
@@ -96,6 +128,13 @@ enum EvalContext {
     Expression,
 }
 
+#[derive(Clone, Copy)]
+enum NameUsage {
+    Load,
+    Store,
+    Delete,
+}
+
 type Label = usize;
 
 impl Compiler {
@@ -104,46 +143,54 @@ impl Compiler {
             code_object_stack: Vec::new(),
             scope_stack: Vec::new(),
             nxt_label: 0,
+            label_map: HashMap::new(),
             source_path: None,
             current_source_location: ast::Location::default(),
             current_qualified_path: None,
             in_loop: false,
             in_function_def: false,
+            future_annotations: false,
         }
     }
 
     fn push_new_code_object(&mut self, obj_name: String) {
         let line_number = self.get_source_line_number();
         self.code_object_stack.push(CodeObject::new(
-            Vec::new(),
-            Varargs::None,
-            Vec::new(),
-            Varargs::None,
+            ArgSpec::default(),
             self.source_path.clone().unwrap(),
             line_number,
             obj_name,
         ));
     }
 
+    /// Finish the code object being compiled, with its jump targets resolved
+    /// from labels to instruction offsets.
     fn pop_code_object(&mut self) -> CodeObject {
         // self.scope_stack.pop().unwrap();
-        self.code_object_stack.pop().unwrap()
+        let mut code = self.code_object_stack.pop().unwrap();
+        for instruction in &mut code.instructions {
+            for label in instruction.label_targets_mut() {
+                *label = self.label_map[label];
+            }
+        }
+        code
     }
 
     fn compile_program(
         &mut self,
         program: &ast::Program,
-        symbol_scope: SymbolScope,
+        symbol_scope: SymbolTable,
     ) -> Result<(), CompileError> {
         let size_before = self.code_object_stack.len();
         self.scope_stack.push(symbol_scope);
+        if find_ann(&program.statements) {
+            self.emit(Instruction::SetupAnnotations);
+        }
         self.compile_statements(&program.statements)?;
         assert!(self.code_object_stack.len() == size_before);
 
         // Emit None at end:
-        self.emit(Instruction::LoadConst {
-            value: bytecode::Constant::None,
-        });
+        self.emit_constant(bytecode::Constant::None);
         self.emit(Instruction::ReturnValue);
         Ok(())
     }
@@ -151,9 +198,12 @@ impl Compiler {
     fn compile_program_single(
         &mut self,
         program: &ast::Program,
-        symbol_scope: SymbolScope,
+        symbol_scope: SymbolTable,
     ) -> Result<(), CompileError> {
         self.scope_stack.push(symbol_scope);
+        if find_ann(&program.statements) {
+            self.emit(Instruction::SetupAnnotations);
+        }
 
         let mut emitted_return = false;
 
@@ -177,9 +227,7 @@ impl Compiler {
         }
 
         if !emitted_return {
-            self.emit(Instruction::LoadConst {
-                value: bytecode::Constant::None,
-            });
+            self.emit_constant(bytecode::Constant::None);
             self.emit(Instruction::ReturnValue);
         }
 
@@ -190,7 +238,7 @@ impl Compiler {
     fn compile_statement_eval(
         &mut self,
         statements: &[ast::LocatedStatement],
-        symbol_table: SymbolScope,
+        symbol_table: SymbolTable,
     ) -> Result<(), CompileError> {
         self.scope_stack.push(symbol_table);
         for statement in statements {
@@ -198,8 +246,10 @@ impl Compiler {
                 self.compile_expression(expression)?;
             } else {
                 return Err(CompileError {
+                    statement: None,
                     error: CompileErrorType::ExpectExpr,
                     location: statement.location.clone(),
+                    source_path: None,
                 });
             }
         }
@@ -217,29 +267,132 @@ impl Compiler {
         Ok(())
     }
 
-    fn scope_for_name(&self, name: &str) -> bytecode::NameScope {
-        let role = self.lookup_name(name);
-        match role {
-            SymbolRole::Global => bytecode::NameScope::Global,
-            SymbolRole::Nonlocal => bytecode::NameScope::NonLocal,
+    /// Emit the instruction which loads, stores or deletes `name`, according
+    /// to its scope in the symbol table.
+    fn compile_name(&mut self, name: &str, usage: NameUsage) {
+        let table = self.scope_stack.last().unwrap();
+        let symbol_scope = table
+            .lookup(name)
+            .map_or(SymbolScope::Unknown, |symbol| symbol.scope);
+        let in_class = table.typ == SymbolTableType::Class;
+        let in_function = table.is_function();
+
+        let scope = match symbol_scope {
+            SymbolScope::Free | SymbolScope::Cell => {
+                let index = self.deref_index(name);
+                self.emit(match usage {
+                    NameUsage::Load if in_class => Instruction::LoadClassDeref { index },
+                    NameUsage::Load => Instruction::LoadDeref { index },
+                    NameUsage::Store => Instruction::StoreDeref { index },
+                    NameUsage::Delete => Instruction::DeleteDeref { index },
+                });
+                return;
+            }
+            SymbolScope::Local if in_function => {
+                let index = self.varname_index(name);
+                self.emit(match usage {
+                    NameUsage::Load => Instruction::LoadFast { index },
+                    NameUsage::Store => Instruction::StoreFast { index },
+                    NameUsage::Delete => Instruction::DeleteFast { index },
+                });
+                return;
+            }
+            SymbolScope::GlobalExplicit => bytecode::NameScope::Global,
+            SymbolScope::GlobalImplicit if in_function => bytecode::NameScope::Global,
             _ => bytecode::NameScope::Local,
+        };
+        let index = self.name_index(name);
+        self.emit(match usage {
+            NameUsage::Load => Instruction::LoadName { index, scope },
+            NameUsage::Store => Instruction::StoreName { index, scope },
+            NameUsage::Delete => Instruction::DeleteName { index, scope },
+        });
+    }
+
+    /// The fast local slot of a local variable of the function being compiled.
+    fn varname_index(&mut self, name: &str) -> usize {
+        let varnames = &mut self.current_code_object().varnames;
+        match varnames.iter().position(|varname| varname == name) {
+            Some(index) => index,
+            None => {
+                varnames.push(name.to_string());
+                varnames.len() - 1
+            }
+        }
+    }
+
+    /// The index of a cell or free variable of the code being compiled.
+    fn deref_index(&mut self, name: &str) -> usize {
+        let code = self.current_code_object();
+        match code.cellvars.iter().position(|cellvar| cellvar == name) {
+            Some(index) => index,
+            None => {
+                code.cellvars.len()
+                    + code
+                        .freevars
+                        .iter()
+                        .position(|freevar| freevar == name)
+                        .unwrap()
+            }
         }
     }
 
+    /// The index of `name` in the names of the code being compiled.
+    fn name_index(&mut self, name: &str) -> usize {
+        let names = &mut self.current_code_object().names;
+        match names.iter().position(|existing| existing == name) {
+            Some(index) => index,
+            None => {
+                names.push(name.to_string());
+                names.len() - 1
+            }
+        }
+    }
+
+    /// Emit the loading of a constant, stored in the constants of the code
+    /// being compiled.
+    fn emit_constant(&mut self, constant: bytecode::Constant) {
+        let constants = &mut self.current_code_object().constants;
+        let index = match constants
+            .iter()
+            .position(|existing| is_same_constant(existing, &constant))
+        {
+            Some(index) => index,
+            None => {
+                constants.push(constant);
+                constants.len() - 1
+            }
+        };
+        self.emit(Instruction::LoadConst { index })
+    }
+
     fn load_name(&mut self, name: &str) {
-        let scope = self.scope_for_name(name);
-        self.emit(Instruction::LoadName {
-            name: name.to_string(),
-            scope,
-        });
+        self.compile_name(name, NameUsage::Load)
     }
 
     fn store_name(&mut self, name: &str) {
-        let scope = self.scope_for_name(name);
-        self.emit(Instruction::StoreName {
-            name: name.to_string(),
-            scope,
+        self.compile_name(name, NameUsage::Store)
+    }
+
+    fn delete_name(&mut self, name: &str) {
+        self.compile_name(name, NameUsage::Delete)
+    }
+
+    /// Build the closure of a function with the given code, which holds the
+    /// cells of its free variables, and return whether there is one.
+    fn compile_closure(&mut self, code: &CodeObject) -> bool {
+        if code.freevars.is_empty() {
+            return false;
+        }
+        for name in &code.freevars {
+            let index = self.deref_index(name);
+            self.emit(Instruction::LoadClosure { index });
+        }
+        self.emit(Instruction::BuildTuple {
+            size: code.freevars.len(),
+            unpack: false,
         });
+        true
     }
 
     fn compile_statement(&mut self, statement: &ast::LocatedStatement) -> Result<(), CompileError> {
@@ -256,19 +409,25 @@ impl Compiler {
                 } in import_parts
                 {
                     let level = *level;
+                    if module == "__future__"
+                        && symbols.iter().any(|symbol| symbol.symbol == "annotations")
+                    {
+                        self.future_annotations = true;
+                    }
+                    let name_index = self.name_index(module);
                     if let Some(alias) = alias {
                         // import module as alias
                         self.emit(Instruction::Import {
-                            name: module.clone(),
-                            symbols: vec![],
+                            name_index,
+                            symbol_indices: vec![],
                             level,
                         });
                         self.store_name(&alias);
                     } else if symbols.is_empty() {
                         // import module
                         self.emit(Instruction::Import {
-                            name: module.clone(),
-                            symbols: vec![],
+                            name_index,
+                            symbol_indices: vec![],
                             level,
                         });
                         self.store_name(&module.clone());
@@ -278,25 +437,22 @@ impl Compiler {
                             .any(|import_symbol| import_symbol.symbol == "*");
                         if import_star {
                             // from module import *
-                            self.emit(Instruction::ImportStar {
-                                name: module.clone(),
-                                level,
-                            });
+                            self.emit(Instruction::ImportStar { name_index, level });
                         } else {
                             // from module import symbol
                             // from module import symbol as alias
-                            let (names, symbols_strings): (Vec<String>, Vec<String>) = symbols
+                            let (names, symbol_indices): (Vec<String>, Vec<usize>) = symbols
                                 .iter()
                                 .map(|ast::ImportSymbol { symbol, alias }| {
                                     (
                                         alias.clone().unwrap_or_else(|| symbol.to_string()),
-                                        symbol.to_string(),
+                                        self.name_index(symbol),
                                     )
                                 })
                                 .unzip();
                             self.emit(Instruction::Import {
-                                name: module.clone(),
-                                symbols: symbols_strings,
+                                name_index,
+                                symbol_indices,
                                 level,
                             });
                             names.iter().rev().for_each(|name| self.store_name(&name));
@@ -391,7 +547,7 @@ impl Compiler {
                 orelse,
             } => self.compile_for(target, iter, body, orelse)?,
             ast::Statement::AsyncFor { .. } => {
-                unimplemented!("async for");
+                return Err(self.unsupported("async for"));
             }
             ast::Statement::Raise { exception, cause } => match exception {
                 Some(value) => {
@@ -424,7 +580,7 @@ impl Compiler {
                 returns,
             } => self.compile_function_def(name, args, body, decorator_list, returns)?,
             ast::Statement::AsyncFunctionDef { .. } => {
-                unimplemented!("async def");
+                return Err(self.unsupported("async def"));
             }
             ast::Statement::ClassDef {
                 name,
@@ -438,8 +594,9 @@ impl Compiler {
 
                 let end_label = self.new_label();
                 self.compile_test(test, Some(end_label), None, EvalContext::Statement)?;
+                let index = self.name_index("AssertionError");
                 self.emit(Instruction::LoadName {
-                    name: String::from("AssertionError"),
+                    index,
                     scope: bytecode::NameScope::Local,
                 });
                 match msg {
@@ -461,8 +618,10 @@ impl Compiler {
             ast::Statement::Break => {
                 if !self.in_loop {
                     return Err(CompileError {
+                        statement: None,
                         error: CompileErrorType::InvalidBreak,
                         location: statement.location.clone(),
+                        source_path: None,
                     });
                 }
                 self.emit(Instruction::Break);
@@ -470,8 +629,10 @@ impl Compiler {
             ast::Statement::Continue => {
                 if !self.in_loop {
                     return Err(CompileError {
+                        statement: None,
                         error: CompileErrorType::InvalidContinue,
                         location: statement.location.clone(),
+                        source_path: None,
                     });
                 }
                 self.emit(Instruction::Continue);
@@ -479,8 +640,10 @@ impl Compiler {
             ast::Statement::Return { value } => {
                 if !self.in_function_def {
                     return Err(CompileError {
+                        statement: None,
                         error: CompileErrorType::InvalidReturn,
                         location: statement.location.clone(),
+                        source_path: None,
                     });
                 }
                 match value {
@@ -488,9 +651,7 @@ impl Compiler {
                         self.compile_expression(v)?;
                     }
                     None => {
-                        self.emit(Instruction::LoadConst {
-                            value: bytecode::Constant::None,
-                        });
+                        self.emit_constant(bytecode::Constant::None);
                     }
                 }
 
@@ -514,6 +675,12 @@ impl Compiler {
                 self.compile_op(op, true);
                 self.compile_store(target)?;
             }
+            ast::Statement::AnnAssign {
+                target,
+                annotation,
+                value,
+                simple,
+            } => self.compile_annotated_assign(target, annotation, value, *simple)?,
             ast::Statement::Delete { targets } => {
                 for target in targets {
                     self.compile_delete(target)?;
@@ -526,18 +693,93 @@ impl Compiler {
         Ok(())
     }
 
+    fn compile_annotated_assign(
+        &mut self,
+        target: &ast::Expression,
+        annotation: &ast::Expression,
+        value: &Option<ast::Expression>,
+        simple: bool,
+    ) -> Result<(), CompileError> {
+        if let Some(value) = value {
+            self.compile_expression(value)?;
+            self.compile_store(target)?;
+        }
+
+        match target {
+            ast::Expression::Identifier { name } if simple => {
+                // Annotations are only evaluated in a module or class.
+                if self.in_function_def {
+                    return Ok(());
+                }
+
+                // Store as dict entry in __annotations__ dict:
+                self.compile_annotation(annotation)?;
+                let index = self.name_index("__annotations__");
+                self.emit(Instruction::LoadName {
+                    index,
+                    scope: bytecode::NameScope::Local,
+                });
+                self.emit_constant(bytecode::Constant::String {
+                    value: name.to_string(),
+                });
+                self.emit(Instruction::StoreSubscript);
+                return Ok(());
+            }
+            ast::Expression::Identifier { .. } => {}
+            // Evaluate the parts of `obj.attr` or `obj[index]`, but store nothing:
+            ast::Expression::Attribute { value: obj, .. } => {
+                if value.is_none() {
+                    self.compile_expression(obj)?;
+                    self.emit(Instruction::Pop);
+                }
+            }
+            ast::Expression::Subscript { a, b } => {
+                if value.is_none() {
+                    self.compile_expression(a)?;
+                    self.emit(Instruction::Pop);
+                    self.compile_expression(b)?;
+                    self.emit(Instruction::Pop);
+                }
+            }
+            _ => {
+                return Err(CompileError {
+                    statement: None,
+                    error: CompileErrorType::SyntaxError(
+                        "illegal target for annotation".to_string(),
+                    ),
+                    location: self.current_source_location.clone(),
+                    source_path: None,
+                });
+            }
+        }
+        if !self.future_annotations && !self.in_function_def {
+            self.compile_expression(annotation)?;
+            self.emit(Instruction::Pop);
+        }
+        Ok(())
+    }
+
+    /// Pushes the value of an annotation, which is its source code when annotations are deferred.
+    fn compile_annotation(&mut self, annotation: &ast::Expression) -> Result<(), CompileError> {
+        if self.future_annotations {
+            self.emit_constant(bytecode::Constant::String {
+                value: unparse_expression(annotation),
+            });
+        } else {
+            self.compile_expression(annotation)?;
+        }
+        Ok(())
+    }
+
     fn compile_delete(&mut self, expression: &ast::Expression) -> Result<(), CompileError> {
         match expression {
             ast::Expression::Identifier { name } => {
-                self.emit(Instruction::DeleteName {
-                    name: name.to_string(),
-                });
+                self.delete_name(name);
             }
             ast::Expression::Attribute { value, name } => {
                 self.compile_expression(value)?;
-                self.emit(Instruction::DeleteAttr {
-                    name: name.to_string(),
-                });
+                let index = self.name_index(name);
+                self.emit(Instruction::DeleteAttr { index });
             }
             ast::Expression::Subscript { a, b } => {
                 self.compile_expression(a)?;
@@ -551,8 +793,10 @@ impl Compiler {
             }
             _ => {
                 return Err(CompileError {
+                    statement: None,
                     error: CompileErrorType::Delete(expression.name()),
                     location: self.current_source_location.clone(),
+                    source_path: None,
                 });
             }
         }
@@ -580,10 +824,8 @@ impl Compiler {
         let mut num_kw_only_defaults = 0;
         for (kw, default) in args.kwonlyargs.iter().zip(&args.kw_defaults) {
             if let Some(default) = default {
-                self.emit(Instruction::LoadConst {
-                    value: bytecode::Constant::String {
-                        value: kw.arg.clone(),
-                    },
+                self.emit_constant(bytecode::Constant::String {
+                    value: kw.arg.clone(),
                 });
                 self.compile_expression(default)?;
                 num_kw_only_defaults += 1;
@@ -593,15 +835,24 @@ impl Compiler {
             self.emit(Instruction::BuildMap {
                 size: num_kw_only_defaults,
                 unpack: false,
+                for_call: false,
             });
         }
 
         let line_number = self.get_source_line_number();
         self.code_object_stack.push(CodeObject::new(
-            args.args.iter().map(|a| a.arg.clone()).collect(),
-            compile_varargs(&args.vararg),
-            args.kwonlyargs.iter().map(|a| a.arg.clone()).collect(),
-            compile_varargs(&args.kwarg),
+            ArgSpec {
+                arg_names: args
+                    .posonlyargs
+                    .iter()
+                    .chain(&args.args)
+                    .map(|a| a.arg.clone())
+                    .collect(),
+                posonlyarg_count: args.posonlyargs.len(),
+                varargs: compile_varargs(&args.vararg),
+                kwonlyarg_names: args.kwonlyargs.iter().map(|a| a.arg.clone()).collect(),
+                varkeywords: compile_varargs(&args.kwarg),
+            },
             self.source_path.clone().unwrap(),
             line_number,
             name.to_string(),
@@ -645,10 +896,46 @@ impl Compiler {
         orelse: &Option<Vec<ast::LocatedStatement>>,
         finalbody: &Option<Vec<ast::LocatedStatement>>,
     ) -> Result<(), CompileError> {
-        let mut handler_label = self.new_label();
         let finally_label = self.new_label();
-        let else_label = self.new_label();
+
+        // Setup a finally block if we have a finally statement.
+        if finalbody.is_some() {
+            self.emit(Instruction::SetupFinally {
+                handler: finally_label,
+            });
+        }
+
         // try:
+        if handlers.is_empty() {
+            self.compile_statements(body)?;
+        } else {
+            self.compile_try_except(body, handlers, orelse)?;
+        }
+
+        // finally:
+        if let Some(statements) = finalbody {
+            // Leaving the try statement normally also runs the finally block.
+            self.emit(Instruction::PopBlock);
+            self.emit(Instruction::EnterFinally);
+
+            self.set_label(finally_label);
+            self.compile_statements(statements)?;
+            self.emit(Instruction::EndFinally);
+        }
+
+        Ok(())
+    }
+
+    fn compile_try_except(
+        &mut self,
+        body: &[ast::LocatedStatement],
+        handlers: &[ast::ExceptHandler],
+        orelse: &Option<Vec<ast::LocatedStatement>>,
+    ) -> Result<(), CompileError> {
+        let mut handler_label = self.new_label();
+        let else_label = self.new_label();
+        let end_label = self.new_label();
+
         self.emit(Instruction::SetupExcept {
             handler: handler_label,
         });
@@ -668,8 +955,9 @@ impl Compiler {
                 self.emit(Instruction::Duplicate);
 
                 // Check exception type:
+                let index = self.name_index("isinstance");
                 self.emit(Instruction::LoadName {
-                    name: String::from("isinstance"),
+                    index,
                     scope: bytecode::NameScope::Local,
                 });
                 self.emit(Instruction::Rotate { amount: 2 });
@@ -699,9 +987,7 @@ impl Compiler {
             // Handler code:
             self.compile_statements(&handler.body)?;
             self.emit(Instruction::PopException);
-            self.emit(Instruction::Jump {
-                target: finally_label,
-            });
+            self.emit(Instruction::Jump { target: end_label });
 
             // Emit a new label for the next handler
             self.set_label(handler_label);
@@ -712,13 +998,7 @@ impl Compiler {
         });
         self.set_label(handler_label);
         // If code flows here, we have an unhandled exception,
-        // emit finally code and raise again!
-        // Duplicate finally code here:
-        // TODO: this bytecode is now duplicate, could this be
-        // improved?
-        if let Some(statements) = finalbody {
-            self.compile_statements(statements)?;
-        }
+        // raise it again!
         self.emit(Instruction::Raise { argc: 0 });
 
         // We successfully ran the try block:
@@ -728,12 +1008,7 @@ impl Compiler {
             self.compile_statements(statements)?;
         }
 
-        // finally:
-        self.set_label(finally_label);
-        if let Some(statements) = finalbody {
-            self.compile_statements(statements)?;
-        }
-        // unimplemented!();
+        self.set_label(end_label);
         Ok(())
     }
 
@@ -765,9 +1040,7 @@ impl Compiler {
         self.compile_statements(new_body)?;
 
         // Emit None at end:
-        self.emit(Instruction::LoadConst {
-            value: bytecode::Constant::None,
-        });
+        self.emit_constant(bytecode::Constant::None);
         self.emit(Instruction::ReturnValue);
         let code = self.pop_code_object();
         self.leave_scope();
@@ -778,24 +1051,28 @@ impl Compiler {
         // Return annotation:
         if let Some(annotation) = returns {
             // key:
-            self.emit(Instruction::LoadConst {
-                value: bytecode::Constant::String {
-                    value: "return".to_string(),
-                },
+            self.emit_constant(bytecode::Constant::String {
+                value: "return".to_string(),
             });
             // value:
-            self.compile_expression(annotation)?;
+            self.compile_annotation(annotation)?;
             num_annotations += 1;
         }
 
-        for arg in args.args.iter() {
+        let mut params: Vec<&ast::Parameter> = args.posonlyargs.iter().chain(&args.args).collect();
+        if let ast::Varargs::Named(vararg) = &args.vararg {
+            params.push(vararg);
+        }
+        params.extend(args.kwonlyargs.iter());
+        if let ast::Varargs::Named(kwarg) = &args.kwarg {
+            params.push(kwarg);
+        }
+        for arg in params {
             if let Some(annotation) = &arg.annotation {
-                self.emit(Instruction::LoadConst {
-                    value: bytecode::Constant::String {
-                        value: arg.arg.to_string(),
-                    },
+                self.emit_constant(bytecode::Constant::String {
+                    value: arg.arg.to_string(),
                 });
-                self.compile_expression(&annotation)?;
+                self.compile_annotation(&annotation)?;
                 num_annotations += 1;
             }
         }
@@ -805,18 +1082,19 @@ impl Compiler {
             self.emit(Instruction::BuildMap {
                 size: num_annotations,
                 unpack: false,
+                for_call: false,
             });
         }
 
-        self.emit(Instruction::LoadConst {
-            value: bytecode::Constant::Code {
-                code: Box::new(code),
-            },
+        if self.compile_closure(&code) {
+            flags |= bytecode::FunctionOpArg::HAS_CLOSURE;
+        }
+
+        self.emit_constant(bytecode::Constant::Code {
+            code: Box::new(code),
         });
-        self.emit(Instruction::LoadConst {
-            value: bytecode::Constant::String {
-                value: qualified_name,
-            },
+        self.emit_constant(bytecode::Constant::String {
+            value: qualified_name,
         });
 
         // Turn code object into function object:
@@ -841,7 +1119,9 @@ impl Compiler {
         decorator_list: &[ast::Expression],
     ) -> Result<(), CompileError> {
         let was_in_loop = self.in_loop;
+        let was_in_function_def = self.in_function_def;
         self.in_loop = false;
+        self.in_function_def = false;
 
         let old_qualified_path = self.current_qualified_path.clone();
         let qualified_name = self.create_qualified_name(name, "");
@@ -851,10 +1131,7 @@ impl Compiler {
         self.emit(Instruction::LoadBuildClass);
         let line_number = self.get_source_line_number();
         self.code_object_stack.push(CodeObject::new(
-            vec![],
-            Varargs::None,
-            vec![],
-            Varargs::None,
+            ArgSpec::default(),
             self.source_path.clone().unwrap(),
             line_number,
             name.to_string(),
@@ -863,76 +1140,61 @@ impl Compiler {
 
         let (new_body, doc_str) = get_doc(body);
 
+        let index = self.name_index("__name__");
         self.emit(Instruction::LoadName {
-            name: "__name__".to_string(),
+            index,
             scope: bytecode::NameScope::Local,
         });
+        let index = self.name_index("__module__");
         self.emit(Instruction::StoreName {
-            name: "__module__".to_string(),
+            index,
             scope: bytecode::NameScope::Local,
         });
+        if find_ann(new_body) {
+            self.emit(Instruction::SetupAnnotations);
+        }
         self.compile_statements(new_body)?;
-        self.emit(Instruction::LoadConst {
-            value: bytecode::Constant::None,
-        });
+
+        // Pass the cell for `__class__` to `__build_class__`, which fills it
+        // in once the class is created:
+        if self.scope_stack.last().unwrap().needs_class_closure {
+            let index = self.deref_index("__class__");
+            self.emit(Instruction::LoadClosure { index });
+            let index = self.name_index("__classcell__");
+            self.emit(Instruction::StoreName {
+                index,
+                scope: bytecode::NameScope::Local,
+            });
+        }
+        self.emit_constant(bytecode::Constant::None);
         self.emit(Instruction::ReturnValue);
 
         let code = self.pop_code_object();
         self.leave_scope();
+        self.in_function_def = was_in_function_def;
 
-        self.emit(Instruction::LoadConst {
-            value: bytecode::Constant::Code {
-                code: Box::new(code),
-            },
+        let mut flags = bytecode::FunctionOpArg::empty();
+        if self.compile_closure(&code) {
+            flags |= bytecode::FunctionOpArg::HAS_CLOSURE;
+        }
+
+        self.emit_constant(bytecode::Constant::Code {
+            code: Box::new(code),
         });
-        self.emit(Instruction::LoadConst {
-            value: bytecode::Constant::String {
-                value: name.to_string(),
-            },
+        self.emit_constant(bytecode::Constant::String {
+            value: name.to_string(),
         });
 
         // Turn code object into function object:
-        self.emit(Instruction::MakeFunction {
-            flags: bytecode::FunctionOpArg::empty(),
-        });
+        self.emit(Instruction::MakeFunction { flags });
 
-        self.emit(Instruction::LoadConst {
-            value: bytecode::Constant::String {
-                value: qualified_name,
-            },
+        self.emit_constant(bytecode::Constant::String {
+            value: qualified_name,
         });
 
-        for base in bases {
-            self.compile_expression(base)?;
-        }
-
-        if !keywords.is_empty() {
-            let mut kwarg_names = vec![];
-            for keyword in keywords {
-                if let Some(name) = &keyword.name {
-                    kwarg_names.push(bytecode::Constant::String {
-                        value: name.to_string(),
-                    });
-                } else {
-                    // This means **kwargs!
-                    panic!("name must be set");
-                }
-                self.compile_expression(&keyword.value)?;
-            }
-
-            self.emit(Instruction::LoadConst {
-                value: bytecode::Constant::Tuple {
-                    elements: kwarg_names,
-                },
-            });
-            self.emit(Instruction::CallFunction {
-                typ: CallType::Keyword(2 + keywords.len() + bases.len()),
-            });
-        } else {
-            self.emit(Instruction::CallFunction {
-                typ: CallType::Positional(2 + bases.len()),
-            });
-        }
+        // Call __build_class__ with the class body function, the name and the bases:
+        let typ = self.compile_call_args(2, bases, keywords)?;
+        self.emit(Instruction::CallFunction { typ });
 
         self.store_docstring(doc_str);
         self.apply_decorators(decorator_list);
@@ -949,16 +1211,13 @@ impl Compiler {
             self.emit(Instruction::Duplicate);
 
             // Doc string value:
-            self.emit(Instruction::LoadConst {
-                value: bytecode::Constant::String {
-                    value: doc_string.to_string(),
-                },
+            self.emit_constant(bytecode::Constant::String {
+                value: doc_string.to_string(),
             });
 
             self.emit(Instruction::Rotate { amount: 2 });
-            self.emit(Instruction::StoreAttr {
-                name: "__doc__".to_string(),
-            });
+            let index = self.name_index("__doc__");
+            self.emit(Instruction::StoreAttr { index });
         }
     }
 
@@ -1092,9 +1351,8 @@ impl Compiler {
             }
             ast::Expression::Attribute { value, name } => {
                 self.compile_expression(value)?;
-                self.emit(Instruction::StoreAttr {
-                    name: name.to_string(),
-                });
+                let index = self.name_index(name);
+                self.emit(Instruction::StoreAttr { index });
             }
             ast::Expression::List { elements } | ast::Expression::Tuple { elements } => {
                 let mut seen_star = false;
@@ -1104,8 +1362,10 @@ impl Compiler {
                     if let ast::Expression::Starred { .. } = element {
                         if seen_star {
                             return Err(CompileError {
+                                statement: None,
                                 error: CompileErrorType::StarArgs,
                                 location: self.current_source_location.clone(),
+                                source_path: None,
                             });
                         } else {
                             seen_star = true;
@@ -1131,10 +1391,22 @@ impl Compiler {
                     }
                 }
             }
+            ast::Expression::Starred { .. } => {
+                return Err(CompileError {
+                    statement: None,
+                    error: CompileErrorType::SyntaxError(
+                        "starred assignment target must be in a list or tuple".to_string(),
+                    ),
+                    location: self.current_source_location.clone(),
+                    source_path: None,
+                });
+            }
             _ => {
                 return Err(CompileError {
+                    statement: None,
                     error: CompileErrorType::Assign(target.name()),
                     location: self.current_source_location.clone(),
+                    source_path: None,
                 });
             }
         }
@@ -1262,9 +1534,8 @@ impl Compiler {
             }
             ast::Expression::Attribute { value, name } => {
                 self.compile_expression(value)?;
-                self.emit(Instruction::LoadAttr {
-                    name: name.to_string(),
-                });
+                let index = self.name_index(name);
+                self.emit(Instruction::LoadAttr { index });
             }
             ast::Expression::Compare { vals, ops } => {
                 self.compile_chained_comparison(vals, ops)?;
@@ -1279,7 +1550,7 @@ impl Compiler {
                         value: Complex64::new(*real, *imag),
                     },
                 };
-                self.emit(Instruction::LoadConst { value: const_value });
+                self.emit_constant(const_value);
             }
             ast::Expression::List { elements } => {
                 let size = elements.len();
@@ -1316,6 +1587,7 @@ impl Compiler {
                             self.emit(Instruction::BuildMap {
                                 size: 1,
                                 unpack: false,
+                                for_call: false,
                             });
                         }
                     } else {
@@ -1326,6 +1598,7 @@ impl Compiler {
                 self.emit(Instruction::BuildMap {
                     size,
                     unpack: has_double_star,
+                    for_call: false,
                 });
             }
             ast::Expression::Slice { elements } => {
@@ -1338,59 +1611,47 @@ impl Compiler {
             ast::Expression::Yield { value } => {
                 if !self.in_function_def {
                     return Err(CompileError {
+                        statement: None,
                         error: CompileErrorType::InvalidYield,
                         location: self.current_source_location.clone(),
+                        source_path: None,
                     });
                 }
                 self.mark_generator();
                 match value {
                     Some(expression) => self.compile_expression(expression)?,
-                    None => self.emit(Instruction::LoadConst {
-                        value: bytecode::Constant::None,
-                    }),
+                    None => self.emit_constant(bytecode::Constant::None),
                 };
                 self.emit(Instruction::YieldValue);
             }
             ast::Expression::Await { .. } => {
-                unimplemented!("await");
+                return Err(self.unsupported("await"));
             }
             ast::Expression::YieldFrom { value } => {
                 self.mark_generator();
                 self.compile_expression(value)?;
                 self.emit(Instruction::GetIter);
-                self.emit(Instruction::LoadConst {
-                    value: bytecode::Constant::None,
-                });
+                self.emit_constant(bytecode::Constant::None);
                 self.emit(Instruction::YieldFrom);
             }
             ast::Expression::True => {
-                self.emit(Instruction::LoadConst {
-                    value: bytecode::Constant::Boolean { value: true },
-                });
+                self.emit_constant(bytecode::Constant::Boolean { value: true });
             }
             ast::Expression::False => {
-                self.emit(Instruction::LoadConst {
-                    value: bytecode::Constant::Boolean { value: false },
-                });
+                self.emit_constant(bytecode::Constant::Boolean { value: false });
             }
             ast::Expression::None => {
-                self.emit(Instruction::LoadConst {
-                    value: bytecode::Constant::None,
-                });
+                self.emit_constant(bytecode::Constant::None);
             }
             ast::Expression::Ellipsis => {
-                self.emit(Instruction::LoadConst {
-                    value: bytecode::Constant::Ellipsis,
-                });
+                self.emit_constant(bytecode::Constant::Ellipsis);
             }
             ast::Expression::String { value } => {
                 self.compile_string(value)?;
             }
             ast::Expression::Bytes { value } => {
-                self.emit(Instruction::LoadConst {
-                    value: bytecode::Constant::Bytes {
-                        value: value.clone(),
-                    },
+                self.emit_constant(bytecode::Constant::Bytes {
+                    value: value.clone(),
                 });
             }
             ast::Expression::Identifier { name } => {
@@ -1399,29 +1660,36 @@ impl Compiler {
             ast::Expression::Lambda { args, body } => {
                 let name = "<lambda>".to_string();
                 // no need to worry about the self.loop_depth because there are no loops in lambda expressions
-                let flags = self.enter_function(&name, args)?;
+                let mut flags = self.enter_function(&name, args)?;
+                let was_in_function_def = self.in_function_def;
+                self.in_function_def = true;
                 self.compile_expression(body)?;
                 self.emit(Instruction::ReturnValue);
                 let code = self.pop_code_object();
                 self.leave_scope();
-                self.emit(Instruction::LoadConst {
-                    value: bytecode::Constant::Code {
-                        code: Box::new(code),
-                    },
-                });
-                self.emit(Instruction::LoadConst {
-                    value: bytecode::Constant::String { value: name },
+                self.in_function_def = was_in_function_def;
+                if self.compile_closure(&code) {
+                    flags |= bytecode::FunctionOpArg::HAS_CLOSURE;
+                }
+                self.emit_constant(bytecode::Constant::Code {
+                    code: Box::new(code),
                 });
+                self.emit_constant(bytecode::Constant::String { value: name });
                 // Turn code object into function object:
                 self.emit(Instruction::MakeFunction { flags });
             }
             ast::Expression::Comprehension { kind, generators } => {
                 self.compile_comprehension(kind, generators)?;
             }
-            ast::Expression::Starred { value } => {
-                self.compile_expression(value)?;
-                self.emit(Instruction::Unpack);
-                panic!("We should not just unpack a starred args, since the size is unknown.");
+            ast::Expression::Starred { .. } => {
+                return Err(CompileError {
+                    statement: None,
+                    error: CompileErrorType::SyntaxError(
+                        "can't use starred expression here".to_string(),
+                    ),
+                    location: self.current_source_location.clone(),
+                    source_path: None,
+                });
             }
             ast::Expression::IfExpression { test, body, orelse } => {
                 let no_label = self.new_label();
@@ -1433,6 +1701,11 @@ impl Compiler {
                 self.compile_expression(orelse)?;
                 self.set_label(end_label);
             }
+            ast::Expression::NamedExpression { left, right } => {
+                self.compile_expression(right)?;
+                self.emit(Instruction::Duplicate);
+                self.compile_store(left)?;
+            }
         }
         Ok(())
     }
@@ -1443,17 +1716,73 @@ impl Compiler {
         args: &[ast::Expression],
         keywords: &[ast::Keyword],
     ) -> Result<(), CompileError> {
-        self.compile_expression(function)?;
-        let count = args.len() + keywords.len();
+        if let ast::Expression::Attribute { value, name } = function {
+            // Method calls skip creating a bound method:
+            self.compile_expression(value)?;
+            let index = self.name_index(name);
+            self.emit(Instruction::LoadMethod { index });
+            let typ = self.compile_call_args(0, args, keywords)?;
+            self.emit(Instruction::CallMethod { typ });
+        } else {
+            self.compile_expression(function)?;
+            let typ = self.compile_call_args(0, args, keywords)?;
+            self.emit(Instruction::CallFunction { typ });
+        }
+        Ok(())
+    }
+
+    /// Compile the arguments of a call, and return how they are passed. The
+    /// callable and `additional_positional` leading positional arguments are
+    /// already on the stack.
+    fn compile_call_args(
+        &mut self,
+        additional_positional: usize,
+        args: &[ast::Expression],
+        keywords: &[ast::Keyword],
+    ) -> Result<CallType, CompileError> {
+        let count = additional_positional + args.len() + keywords.len();
+
+        let mut keyword_names = HashSet::new();
+        for name in keywords.iter().filter_map(|keyword| keyword.name.as_ref()) {
+            if !keyword_names.insert(name) {
+                return Err(CompileError {
+                    statement: None,
+                    error: CompileErrorType::SyntaxError(format!(
+                        "keyword argument repeated: {}",
+                        name
+                    )),
+                    location: self.current_source_location.clone(),
+                    source_path: None,
+                });
+            }
+        }
 
-        // Normal arguments:
-        let must_unpack = self.gather_elements(args)?;
         let has_double_star = keywords.iter().any(|k| k.name.is_none());
+        let has_stars = args.iter().any(|arg| {
+            if let ast::Expression::Starred { .. } = arg {
+                true
+            } else {
+                false
+            }
+        });
 
-        if must_unpack || has_double_star {
-            // Create a tuple with positional args:
+        if has_stars || has_double_star {
+            // Create a tuple with positional args. Any leading arguments
+            // already on the stack are packed up as one of the parts:
+            let mut size = args.len();
+            let must_unpack = if additional_positional > 0 {
+                self.emit(Instruction::BuildTuple {
+                    size: additional_positional,
+                    unpack: false,
+                });
+                size += 1;
+                self.gather_elements_as_tuples(args)?;
+                true
+            } else {
+                self.gather_elements(args)?
+            };
             self.emit(Instruction::BuildTuple {
-                size: args.len(),
+                size,
                 unpack: must_unpack,
             });
 
@@ -1461,16 +1790,15 @@ impl Compiler {
             if !keywords.is_empty() {
                 for keyword in keywords {
                     if let Some(name) = &keyword.name {
-                        self.emit(Instruction::LoadConst {
-                            value: bytecode::Constant::String {
-                                value: name.to_string(),
-                            },
+                        self.emit_constant(bytecode::Constant::String {
+                            value: name.to_string(),
                         });
                         self.compile_expression(&keyword.value)?;
                         if has_double_star {
                             self.emit(Instruction::BuildMap {
                                 size: 1,
                                 unpack: false,
+                                for_call: true,
                             });
                         }
                     } else {
@@ -1482,17 +1810,16 @@ impl Compiler {
                 self.emit(Instruction::BuildMap {
                     size: keywords.len(),
                     unpack: has_double_star,
+                    for_call: true,
                 });
 
-                self.emit(Instruction::CallFunction {
-                    typ: CallType::Ex(true),
-                });
+                Ok(CallType::Ex(true))
             } else {
-                self.emit(Instruction::CallFunction {
-                    typ: CallType::Ex(false),
-                });
+                Ok(CallType::Ex(false))
             }
         } else {
+            self.gather_elements(args)?;
+
             // Keyword arguments:
             if !keywords.is_empty() {
                 let mut kwarg_names = vec![];
@@ -1508,21 +1835,14 @@ impl Compiler {
                     self.compile_expression(&keyword.value)?;
                 }
 
-                self.emit(Instruction::LoadConst {
-                    value: bytecode::Constant::Tuple {
-                        elements: kwarg_names,
-                    },
-                });
-                self.emit(Instruction::CallFunction {
-                    typ: CallType::Keyword(count),
+                self.emit_constant(bytecode::Constant::Tuple {
+                    elements: kwarg_names,
                 });
+                Ok(CallType::Keyword(count))
             } else {
-                self.emit(Instruction::CallFunction {
-                    typ: CallType::Positional(count),
-                });
+                Ok(CallType::Positional(count))
             }
         }
-        Ok(())
     }
 
     // Given a vector of expr / star expr generate code which gives either
@@ -1537,21 +1857,35 @@ impl Compiler {
             }
         });
 
+        if has_stars {
+            self.gather_elements_as_tuples(elements)?;
+        } else {
+            for element in elements {
+                self.compile_expression(element)?;
+            }
+        }
+
+        Ok(has_stars)
+    }
+
+    // Push every element as an iterable: starred values as they are, and
+    // other values wrapped in a one-element tuple.
+    fn gather_elements_as_tuples(
+        &mut self,
+        elements: &[ast::Expression],
+    ) -> Result<(), CompileError> {
         for element in elements {
             if let ast::Expression::Starred { value } = element {
                 self.compile_expression(value)?;
             } else {
                 self.compile_expression(element)?;
-                if has_stars {
-                    self.emit(Instruction::BuildTuple {
-                        size: 1,
-                        unpack: false,
-                    });
-                }
+                self.emit(Instruction::BuildTuple {
+                    size: 1,
+                    unpack: false,
+                });
             }
         }
-
-        Ok(has_stars)
+        Ok(())
     }
 
     fn compile_comprehension(
@@ -1573,14 +1907,15 @@ impl Compiler {
         let line_number = self.get_source_line_number();
         // Create magnificent function <listcomp>:
         self.code_object_stack.push(CodeObject::new(
-            vec![".0".to_string()],
-            Varargs::None,
-            vec![],
-            Varargs::None,
+            ArgSpec {
+                arg_names: vec![".0".to_string()],
+                ..ArgSpec::default()
+            },
             self.source_path.clone().unwrap(),
             line_number,
             name.clone(),
         ));
+        self.enter_scope();
 
         // Create empty object of proper type:
         match kind {
@@ -1601,6 +1936,7 @@ impl Compiler {
                 self.emit(Instruction::BuildMap {
                     size: 0,
                     unpack: false,
+                    for_call: false,
                 });
             }
         }
@@ -1609,10 +1945,7 @@ impl Compiler {
         for generator in generators {
             if loop_labels.is_empty() {
                 // Load iterator onto stack (passed as first argument):
-                self.emit(Instruction::LoadName {
-                    name: String::from(".0"),
-                    scope: bytecode::NameScope::Local,
-                });
+                self.load_name(".0");
             } else {
                 // Evaluate iterated item:
                 self.compile_expression(&generator.iter)?;
@@ -1653,6 +1986,16 @@ impl Compiler {
                 self.emit(Instruction::Pop);
             }
             ast::ComprehensionKind::List { element } => {
+                if let ast::Expression::Starred { .. } = element {
+                    return Err(CompileError {
+                        statement: None,
+                        error: CompileErrorType::SyntaxError(
+                            "iterable unpacking cannot be used in comprehension".to_string(),
+                        ),
+                        location: self.current_source_location.clone(),
+                        source_path: None,
+                    });
+                }
                 self.compile_expression(element)?;
                 self.emit(Instruction::ListAppend {
                     i: 1 + generators.len(),
@@ -1690,23 +2033,23 @@ impl Compiler {
 
         // Fetch code for listcomp function:
         let code = self.pop_code_object();
+        self.leave_scope();
+
+        let mut flags = bytecode::FunctionOpArg::empty();
+        if self.compile_closure(&code) {
+            flags |= bytecode::FunctionOpArg::HAS_CLOSURE;
+        }
 
         // List comprehension code:
-        self.emit(Instruction::LoadConst {
-            value: bytecode::Constant::Code {
-                code: Box::new(code),
-            },
+        self.emit_constant(bytecode::Constant::Code {
+            code: Box::new(code),
         });
 
         // List comprehension function name:
-        self.emit(Instruction::LoadConst {
-            value: bytecode::Constant::String { value: name },
-        });
+        self.emit_constant(bytecode::Constant::String { value: name });
 
         // Turn code object into function object:
-        self.emit(Instruction::MakeFunction {
-            flags: bytecode::FunctionOpArg::empty(),
-        });
+        self.emit(Instruction::MakeFunction { flags });
 
         // Evaluate iterated item:
         self.compile_expression(&generators[0].iter)?;
@@ -1730,10 +2073,8 @@ impl Compiler {
                 self.emit(Instruction::BuildString { size: values.len() })
             }
             ast::StringGroup::Constant { value } => {
-                self.emit(Instruction::LoadConst {
-                    value: bytecode::Constant::String {
-                        value: value.to_string(),
-                    },
+                self.emit_constant(bytecode::Constant::String {
+                    value: value.to_string(),
                 });
             }
             ast::StringGroup::FormattedValue {
@@ -1742,33 +2083,59 @@ impl Compiler {
                 spec,
             } => {
                 self.compile_expression(value)?;
+                match spec {
+                    Some(spec) => self.compile_string(spec)?,
+                    None => self.emit_constant(bytecode::Constant::String {
+                        value: String::new(),
+                    }),
+                }
                 self.emit(Instruction::FormatValue {
                     conversion: conversion.map(compile_conversion_flag),
-                    spec: spec.clone(),
                 });
             }
         }
         Ok(())
     }
 
+    fn unsupported(&self, feature: &'static str) -> CompileError {
+        CompileError {
+            statement: None,
+            error: CompileErrorType::Unsupported(feature),
+            location: self.current_source_location.clone(),
+            source_path: None,
+        }
+    }
+
     // Scope helpers:
+    /// Enter the symbol table of the code object which was just pushed, and
+    /// number its variables.
     fn enter_scope(&mut self) {
         // println!("Enter scope {:?}", self.scope_stack);
         // Enter first subscope!
-        let scope = self.scope_stack.last_mut().unwrap().sub_scopes.remove(0);
-        self.scope_stack.push(scope);
+        let table = self.scope_stack.last_mut().unwrap().sub_tables.remove(0);
+        let is_function = table.is_function();
+        let code = self.current_code_object();
+        code.cellvars = table.cell_names();
+        code.freevars = table.free_names();
+        if is_function {
+            code.is_optimized = true;
+            // The parameters take the first fast local slots:
+            let mut varnames = code.arg_names.clone();
+            varnames.extend(code.kwonlyarg_names.iter().cloned());
+            for varargs in &[&code.varargs, &code.varkeywords] {
+                if let Varargs::Named(name) = varargs {
+                    varnames.push(name.clone());
+                }
+            }
+            code.varnames = varnames;
+        }
+        self.scope_stack.push(table);
     }
 
     fn leave_scope(&mut self) {
         // println!("Leave scope {:?}", self.scope_stack);
-        let scope = self.scope_stack.pop().unwrap();
-        assert!(scope.sub_scopes.is_empty());
-    }
-
-    fn lookup_name(&self, name: &str) -> &SymbolRole {
-        // println!("Looking up {:?}", name);
-        let scope = self.scope_stack.last().unwrap();
-        scope.lookup(name).unwrap()
+        let table = self.scope_stack.pop().unwrap();
+        assert!(table.sub_tables.is_empty());
     }
 
     // Low level helper functions:
@@ -1795,7 +2162,7 @@ impl Compiler {
     fn set_label(&mut self, label: Label) {
         let position = self.current_code_object().instructions.len();
         // assert!(label not in self.label_map)
-        self.current_code_object().label_map.insert(label, position);
+        self.label_map.insert(label, position);
     }
 
     fn set_source_location(&mut self, location: &ast::Location) {
@@ -1834,6 +2201,52 @@ fn get_doc(body: &[ast::LocatedStatement]) -> (&[ast::LocatedStatement], Option<
     (body, None)
 }
 
+/// Whether any statement in the block, outside nested functions and classes, is an
+/// annotated assignment; the scope then needs an `__annotations__` dict.
+fn find_ann(body: &[ast::LocatedStatement]) -> bool {
+    body.iter().any(|statement| match &statement.node {
+        ast::Statement::AnnAssign { .. } => true,
+        ast::Statement::If { body, orelse, .. }
+        | ast::Statement::While { body, orelse, .. }
+        | ast::Statement::For { body, orelse, .. }
+        | ast::Statement::AsyncFor { body, orelse, .. } => {
+            find_ann(body) || orelse.as_ref().map_or(false, |orelse| find_ann(orelse))
+        }
+        ast::Statement::With { body, .. } => find_ann(body),
+        ast::Statement::Try {
+            body,
+            handlers,
+            orelse,
+            finalbody,
+        } => {
+            find_ann(body)
+                || handlers.iter().any(|handler| find_ann(&handler.body))
+                || orelse.as_ref().map_or(false, |orelse| find_ann(orelse))
+                || finalbody
+                    .as_ref()
+                    .map_or(false, |finalbody| find_ann(finalbody))
+        }
+        _ => false,
+    })
+}
+
+/// Whether two constants can share a slot in the constants of a code object.
+/// Floats are compared by their bits, to keep `0.0` and `-0.0` apart.
+fn is_same_constant(a: &bytecode::Constant, b: &bytecode::Constant) -> bool {
+    use bytecode::Constant::*;
+    match (a, b) {
+        (Float { value: a }, Float { value: b }) => a.to_bits() == b.to_bits(),
+        (Complex { value: a }, Complex { value: b }) => {
+            a.re.to_bits() == b.re.to_bits() && a.im.to_bits() == b.im.to_bits()
+        }
+        (Tuple { elements: a }, Tuple { elements: b }) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| is_same_constant(a, b))
+        }
+        (Code { .. }, _) => false,
+        _ => a == b,
+    }
+}
+
 fn compile_location(location: &ast::Location) -> bytecode::Location {
     bytecode::Location::new(location.row(), location.column())
 }
@@ -1873,29 +2286,103 @@ mod tests {
         compiler.pop_code_object()
     }
 
+    #[test]
+    fn test_compile_errors_instead_of_panics() {
+        use super::{compile, compile_bytes, Mode};
+
+        for source in &[
+            "async def f(): pass\n",
+            "def f(a=1, b): pass\n",
+            "f(a=1, b)\n",
+            &format!("x = {}1\n", "-".repeat(1000)),
+            &format!("x = {}1{}\n", "[".repeat(1000), "]".repeat(1000)),
+        ] {
+            assert!(compile(source, &Mode::Exec, "<test>".to_string()).is_err());
+        }
+        assert!(compile_bytes(b"x = '\xff'\n", &Mode::Exec, "<test>".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_compile_uncompiled_annotations() {
+        // Lambdas that are scanned but never compiled must not confuse the scopes
+        let code = compile_exec(
+            "def f(x: (lambda: y)) -> (lambda: z):\n (lambda: 1).a: (lambda: 2)\n return [a for a in x]\n",
+        );
+        assert!(!code.instructions.is_empty());
+    }
+
+    fn code_constants(code: &CodeObject) -> Vec<&CodeObject> {
+        code.constants
+            .iter()
+            .filter_map(|constant| match constant {
+                Code { code } => Some(&**code),
+                _ => Option::None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fast_locals() {
+        let code = compile_exec("def f(a):\n b = a\n c = 1\n del b\n return lambda: c\n");
+        let function = code_constants(&code)[0];
+        // `c` is used by the lambda, so it is stored in a cell:
+        assert_eq!(function.varnames, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(function.cellvars, vec!["c".to_string()]);
+        assert!(function.instructions.contains(&LoadFast { index: 0 }));
+        assert!(function.instructions.contains(&StoreFast { index: 1 }));
+        assert!(function.instructions.contains(&DeleteFast { index: 1 }));
+        assert!(function.instructions.contains(&StoreDeref { index: 0 }));
+    }
+
+    #[test]
+    fn test_closure_cells() {
+        let code = compile_exec(
+            "def f(a):\n def g():\n  nonlocal a\n  a = b\n b = 1\n return g\n\nclass A:\n def h(self):\n  return super()\n",
+        );
+        let constants = code_constants(&code);
+        let (function, class) = (constants[0], constants[1]);
+        assert_eq!(function.cellvars, vec!["a".to_string(), "b".to_string()]);
+        assert!(function.instructions.contains(&LoadClosure { index: 0 }));
+        assert!(function.instructions.contains(&LoadClosure { index: 1 }));
+        let nested = code_constants(function)[0];
+        assert_eq!(nested.freevars, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(
+            nested.instructions[..2],
+            [LoadDeref { index: 1 }, StoreDeref { index: 0 }]
+        );
+
+        // The methods of a class get `__class__` from a cell of the class body:
+        assert_eq!(class.cellvars, vec!["__class__".to_string()]);
+        let method = code_constants(class)[0];
+        assert_eq!(method.freevars, vec!["__class__".to_string()]);
+        let index = method.names.iter().position(|name| name == "super");
+        assert!(method.instructions.contains(&LoadName {
+            index: index.unwrap(),
+            scope: super::bytecode::NameScope::Global,
+        }));
+    }
+
     #[test]
     fn test_if_ors() {
         let code = compile_exec("if True or False or False:\n pass\n");
         assert_eq!(
             vec![
-                LoadConst {
-                    value: Boolean { value: true }
-                },
-                JumpIf { target: 1 },
-                LoadConst {
-                    value: Boolean { value: false }
-                },
-                JumpIf { target: 1 },
-                LoadConst {
-                    value: Boolean { value: false }
-                },
-                JumpIfFalse { target: 0 },
+                LoadConst { index: 0 },
+                JumpIf { target: 6 },
+                LoadConst { index: 1 },
+                JumpIf { target: 6 },
+                LoadConst { index: 1 },
+                JumpIfFalse { target: 7 },
                 Pass,
-                LoadConst { value: None },
+                LoadConst { index: 2 },
                 ReturnValue
             ],
             code.instructions
         );
+        assert_eq!(
+            vec![Boolean { value: true }, Boolean { value: false }, None],
+            code.constants
+        );
     }
 
     #[test]
@@ -1903,20 +2390,14 @@ mod tests {
         let code = compile_exec("if True and False and False:\n pass\n");
         assert_eq!(
             vec![
-                LoadConst {
-                    value: Boolean { value: true }
-                },
-                JumpIfFalse { target: 0 },
-                LoadConst {
-                    value: Boolean { value: false }
-                },
-                JumpIfFalse { target: 0 },
-                LoadConst {
-                    value: Boolean { value: false }
-                },
-                JumpIfFalse { target: 0 },
+                LoadConst { index: 0 },
+                JumpIfFalse { target: 7 },
+                LoadConst { index: 1 },
+                JumpIfFalse { target: 7 },
+                LoadConst { index: 1 },
+                JumpIfFalse { target: 7 },
                 Pass,
-                LoadConst { value: None },
+                LoadConst { index: 2 },
                 ReturnValue
             ],
             code.instructions
@@ -1928,27 +2409,56 @@ mod tests {
         let code = compile_exec("if (True and False) or (False and True):\n pass\n");
         assert_eq!(
             vec![
-                LoadConst {
-                    value: Boolean { value: true }
-                },
-                JumpIfFalse { target: 2 },
-                LoadConst {
-                    value: Boolean { value: false }
-                },
-                JumpIf { target: 1 },
-                LoadConst {
-                    value: Boolean { value: false }
+                LoadConst { index: 0 },
+                JumpIfFalse { target: 4 },
+                LoadConst { index: 1 },
+                JumpIf { target: 8 },
+                LoadConst { index: 1 },
+                JumpIfFalse { target: 9 },
+                LoadConst { index: 0 },
+                JumpIfFalse { target: 9 },
+                Pass,
+                LoadConst { index: 2 },
+                ReturnValue
+            ],
+            code.instructions
+        );
+    }
+
+    #[test]
+    fn test_method_call() {
+        let code = compile_exec("a.b(1)\n");
+        assert_eq!(
+            vec![
+                LoadName {
+                    index: 0,
+                    scope: super::bytecode::NameScope::Local
                 },
-                JumpIfFalse { target: 0 },
-                LoadConst {
-                    value: Boolean { value: true }
+                LoadMethod { index: 1 },
+                LoadConst { index: 0 },
+                CallMethod {
+                    typ: super::CallType::Positional(1)
                 },
-                JumpIfFalse { target: 0 },
-                Pass,
-                LoadConst { value: None },
+                Pop,
+                LoadConst { index: 1 },
                 ReturnValue
             ],
             code.instructions
         );
     }
+
+    #[test]
+    fn test_name_and_constant_tables() {
+        let code = compile_exec("import a\na.b = a.b + 0.0 + 0.0\ndel a.b\n");
+        assert_eq!(vec!["a".to_string(), "b".to_string()], code.names);
+        assert_eq!(vec![Float { value: 0.0 }, None], code.constants);
+        assert!(code.instructions.contains(&LoadAttr { index: 1 }));
+        assert!(code.instructions.contains(&StoreAttr { index: 1 }));
+        assert!(code.instructions.contains(&DeleteAttr { index: 1 }));
+        // Equal constants share a slot, but 0.0 and -0.0 are different:
+        assert!(!super::is_same_constant(
+            &Float { value: 0.0 },
+            &Float { value: -0.0 }
+        ));
+    }
 }
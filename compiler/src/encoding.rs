@@ -0,0 +1,308 @@
+//! Decode Python source files given as bytes.
+//!
+//! A source file is UTF-8 unless it starts with a UTF-8 byte order mark or
+//! declares another encoding in a PEP 263 comment on one of its first two
+//! lines, such as `# -*- coding: latin-1 -*-`.
+//! See also: https://www.python.org/dev/peps/pep-0263/
+
+use crate::error::{CompileError, CompileErrorType};
+use rustpython_parser::ast::Location;
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// The source encodings we know how to decode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Utf8,
+    Latin1,
+    Ascii,
+    Cp1252,
+}
+
+impl Encoding {
+    /// Look up an encoding by the name used in a coding declaration.
+    fn lookup(name: &str) -> Option<Encoding> {
+        let name = name.to_lowercase().replace('_', "-");
+        let has_prefix = |prefix: &str| name == prefix || name.starts_with(&format!("{}-", prefix));
+        if has_prefix("utf-8") || name == "utf8" {
+            Some(Encoding::Utf8)
+        } else if has_prefix("latin-1") || has_prefix("iso-8859-1") || has_prefix("iso-latin-1") {
+            Some(Encoding::Latin1)
+        } else {
+            match name.as_str() {
+                "latin1" | "iso8859-1" | "l1" => Some(Encoding::Latin1),
+                "ascii" | "us-ascii" | "646" => Some(Encoding::Ascii),
+                "cp1252" | "windows-1252" => Some(Encoding::Cp1252),
+                _ => None,
+            }
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "utf-8",
+            Encoding::Latin1 => "iso-8859-1",
+            Encoding::Ascii => "ascii",
+            Encoding::Cp1252 => "cp1252",
+        }
+    }
+
+    /// Decode `source`, or return the offset of the first byte which cannot
+    /// be decoded together with a description of the problem.
+    fn decode(self, source: &[u8]) -> Result<String, (usize, String)> {
+        match self {
+            Encoding::Utf8 => match std::str::from_utf8(source) {
+                Ok(source) => Ok(source.to_string()),
+                Err(err) => {
+                    let position = err.valid_up_to();
+                    let byte = source[position];
+                    let reason = match (err.error_len(), byte) {
+                        (None, _) => "unexpected end of data",
+                        (Some(_), 0xc2..=0xf4) => "invalid continuation byte",
+                        (Some(_), _) => "invalid start byte",
+                    };
+                    Err((
+                        position,
+                        format!(
+                            "'utf-8' codec can't decode byte 0x{:02x} in position {}: {}",
+                            byte, position, reason
+                        ),
+                    ))
+                }
+            },
+            Encoding::Latin1 => Ok(source.iter().map(|&b| b as char).collect()),
+            Encoding::Ascii => source
+                .iter()
+                .enumerate()
+                .map(|(position, &b)| {
+                    if b.is_ascii() {
+                        Ok(b as char)
+                    } else {
+                        Err((
+                            position,
+                            format!(
+                                "'ascii' codec can't decode byte 0x{:02x} in position {}: ordinal not in range(128)",
+                                b, position
+                            ),
+                        ))
+                    }
+                })
+                .collect(),
+            Encoding::Cp1252 => source
+                .iter()
+                .enumerate()
+                .map(|(position, &b)| {
+                    let c = match b {
+                        0x80..=0x9f => CP1252_HIGH[(b - 0x80) as usize],
+                        _ => Some(b as char),
+                    };
+                    c.ok_or_else(|| {
+                        (
+                            position,
+                            format!(
+                                "'charmap' codec can't decode byte 0x{:02x} in position {}: character maps to <undefined>",
+                                b, position
+                            ),
+                        )
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The characters for bytes 0x80 to 0x9f in cp1252, which differ from latin-1.
+const CP1252_HIGH: [Option<char>; 32] = [
+    Some('\u{20ac}'),
+    None,
+    Some('\u{201a}'),
+    Some('\u{0192}'),
+    Some('\u{201e}'),
+    Some('\u{2026}'),
+    Some('\u{2020}'),
+    Some('\u{2021}'),
+    Some('\u{02c6}'),
+    Some('\u{2030}'),
+    Some('\u{0160}'),
+    Some('\u{2039}'),
+    Some('\u{0152}'),
+    None,
+    Some('\u{017d}'),
+    None,
+    None,
+    Some('\u{2018}'),
+    Some('\u{2019}'),
+    Some('\u{201c}'),
+    Some('\u{201d}'),
+    Some('\u{2022}'),
+    Some('\u{2013}'),
+    Some('\u{2014}'),
+    Some('\u{02dc}'),
+    Some('\u{2122}'),
+    Some('\u{0161}'),
+    Some('\u{203a}'),
+    Some('\u{0153}'),
+    None,
+    Some('\u{017e}'),
+    Some('\u{0178}'),
+];
+
+/// Decode Python source code given as bytes into a string, honouring a UTF-8
+/// byte order mark or an encoding declaration.
+pub fn decode_source(source: &[u8]) -> Result<String, CompileError> {
+    let (source, has_bom) = if source.starts_with(UTF8_BOM) {
+        (&source[UTF8_BOM.len()..], true)
+    } else {
+        (source, false)
+    };
+
+    let encoding = match find_coding_declaration(source) {
+        Some((row, name)) => {
+            let error = |message: String| CompileError {
+                statement: None,
+                error: CompileErrorType::SyntaxError(message),
+                location: Location::new(row, 1),
+                source_path: None,
+            };
+            match Encoding::lookup(&name) {
+                Some(Encoding::Utf8) => Encoding::Utf8,
+                Some(encoding) if has_bom => {
+                    return Err(error(format!(
+                        "encoding problem: {} with BOM",
+                        encoding.name()
+                    )));
+                }
+                Some(encoding) => encoding,
+                None => return Err(error(format!("unknown encoding: {}", name))),
+            }
+        }
+        None => Encoding::Utf8,
+    };
+
+    encoding.decode(source).map_err(|(position, message)| {
+        // Report the position of the offending byte:
+        let valid = &source[..position];
+        let row = valid.iter().filter(|&&b| b == b'\n').count() + 1;
+        let column = valid.len() - valid.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1) + 1;
+        CompileError {
+            statement: None,
+            error: CompileErrorType::Encoding(message),
+            location: Location::new(row, column),
+            source_path: None,
+        }
+    })
+}
+
+/// Find a PEP 263 encoding declaration, returning its line number and the
+/// declared encoding name. Only the first two lines are searched, and the
+/// second one only when the first line is blank or a comment.
+fn find_coding_declaration(source: &[u8]) -> Option<(usize, String)> {
+    let mut lines = source.split(|&b| b == b'\n');
+    let first = lines.next()?;
+    if let Some(name) = coding_spec(first) {
+        return Some((1, name));
+    }
+    let first = trim_start(first);
+    if !first.is_empty() && first[0] != b'#' && first != b"\r" {
+        return None;
+    }
+    lines.next().and_then(coding_spec).map(|name| (2, name))
+}
+
+/// Match a line against `^[ \t\f]*#.*?coding[:=][ \t]*([-\w.]+)`.
+fn coding_spec(line: &[u8]) -> Option<String> {
+    let line = trim_start(line);
+    if line.first() != Some(&b'#') {
+        return None;
+    }
+    let mut rest = line;
+    while let Some(start) = find(rest, b"coding") {
+        rest = &rest[start + b"coding".len()..];
+        if let Some(b':') | Some(b'=') = rest.first() {
+            let value = trim_start_tabs(&rest[1..]);
+            let length = value
+                .iter()
+                .take_while(|&&b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'.')
+                .count();
+            if length > 0 {
+                return Some(String::from_utf8_lossy(&value[..length]).into_owned());
+            }
+        }
+    }
+    None
+}
+
+fn trim_start(line: &[u8]) -> &[u8] {
+    let start = line
+        .iter()
+        .position(|&b| b != b' ' && b != b'\t' && b != b'\x0c')
+        .unwrap_or(line.len());
+    &line[start..]
+}
+
+fn trim_start_tabs(value: &[u8]) -> &[u8] {
+    let start = value
+        .iter()
+        .position(|&b| b != b' ' && b != b'\t')
+        .unwrap_or(value.len());
+    &value[start..]
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_source;
+
+    #[test]
+    fn test_decode_utf8() {
+        assert_eq!(
+            decode_source(b"x = '\xc3\xa9'\n").unwrap(),
+            "x = '\u{e9}'\n"
+        );
+        assert_eq!(decode_source(b"\xef\xbb\xbfx = 1\n").unwrap(), "x = 1\n");
+        assert_eq!(
+            decode_source(b"\xef\xbb\xbf# coding: utf-8\nx = 1\n").unwrap(),
+            "# coding: utf-8\nx = 1\n"
+        );
+        assert!(decode_source(b"x = '\xe9'\n").is_err());
+    }
+
+    #[test]
+    fn test_decode_coding_declaration() {
+        assert_eq!(
+            decode_source(b"# -*- coding: latin-1 -*-\nx = '\xe9'\n").unwrap(),
+            "# -*- coding: latin-1 -*-\nx = '\u{e9}'\n"
+        );
+        assert_eq!(
+            decode_source(b"#!/usr/bin/python\n# vim: set fileencoding=iso-8859-1 :\nx = '\xe9'\n")
+                .unwrap(),
+            "#!/usr/bin/python\n# vim: set fileencoding=iso-8859-1 :\nx = '\u{e9}'\n"
+        );
+        assert_eq!(
+            decode_source(b"# coding=cp1252\nx = '\x80'\n").unwrap(),
+            "# coding=cp1252\nx = '\u{20ac}'\n"
+        );
+        // Only a comment on the first two lines declares an encoding:
+        assert!(decode_source(b"x = 1\n# coding: latin-1\nx = '\xe9'\n").is_err());
+        assert!(decode_source(b"x = 1 # coding: latin-1\nx = '\xe9'\n").is_err());
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let error = decode_source(b"# coding: foo\n").unwrap_err();
+        assert_eq!(error.error.to_string(), "unknown encoding: foo");
+        let error = decode_source(b"\xef\xbb\xbf# coding: latin-1\n").unwrap_err();
+        assert_eq!(
+            error.error.to_string(),
+            "encoding problem: iso-8859-1 with BOM"
+        );
+        let error = decode_source(b"# coding: ascii\nx = '\xe9'\n").unwrap_err();
+        assert_eq!(error.location.row(), 2);
+        assert_eq!(error.location.column(), 6);
+    }
+}
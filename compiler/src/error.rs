@@ -1,4 +1,4 @@
-use rustpython_parser::error::ParseError;
+use rustpython_parser::error::{ParseError, ParseErrorType};
 use rustpython_parser::lexer::Location;
 
 use std::error::Error;
@@ -6,15 +6,44 @@ use std::fmt;
 
 #[derive(Debug)]
 pub struct CompileError {
+    /// The source line containing the error, if known.
+    pub statement: Option<String>,
     pub error: CompileErrorType,
     pub location: Location,
+    pub source_path: Option<String>,
+}
+
+impl CompileError {
+    pub fn update_statement_info(&mut self, statement: String) {
+        self.statement = Some(statement);
+    }
+
+    pub fn update_source_path(&mut self, source_path: &str) {
+        self.source_path = Some(source_path.to_string());
+    }
+
+    pub fn is_indentation_error(&self) -> bool {
+        match &self.error {
+            CompileErrorType::Parse(error) => error.is_indentation_error(),
+            _ => false,
+        }
+    }
+
+    pub fn is_tab_error(&self) -> bool {
+        match &self.error {
+            CompileErrorType::Parse(error) => error.is_tab_error(),
+            _ => false,
+        }
+    }
 }
 
 impl From<ParseError> for CompileError {
     fn from(error: ParseError) -> Self {
         CompileError {
-            error: CompileErrorType::Parse(error),
-            location: Default::default(), // TODO: extract location from parse error!
+            statement: None,
+            error: CompileErrorType::Parse(error.error),
+            location: error.location,
+            source_path: None,
         }
     }
 }
@@ -28,7 +57,7 @@ pub enum CompileErrorType {
     /// Expected an expression got a statement
     ExpectExpr,
     /// Parser error
-    Parse(ParseError),
+    Parse(ParseErrorType),
     SyntaxError(String),
     /// Multiple `*` detected
     StarArgs,
@@ -38,22 +67,34 @@ pub enum CompileErrorType {
     InvalidContinue,
     InvalidReturn,
     InvalidYield,
+    /// The source is not correctly encoded.
+    Encoding(String),
+    /// A construct the compiler cannot handle yet.
+    Unsupported(&'static str),
 }
 
-impl fmt::Display for CompileError {
+impl fmt::Display for CompileErrorType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match &self.error {
+        match self {
             CompileErrorType::Assign(target) => write!(f, "can't assign to {}", target),
             CompileErrorType::Delete(target) => write!(f, "can't delete {}", target),
             CompileErrorType::ExpectExpr => write!(f, "Expecting expression, got statement"),
             CompileErrorType::Parse(err) => write!(f, "{}", err),
             CompileErrorType::SyntaxError(err) => write!(f, "{}", err),
-            CompileErrorType::StarArgs => write!(f, "Two starred expressions in assignment"),
+            CompileErrorType::StarArgs => write!(f, "multiple starred expressions in assignment"),
             CompileErrorType::InvalidBreak => write!(f, "'break' outside loop"),
             CompileErrorType::InvalidContinue => write!(f, "'continue' outside loop"),
             CompileErrorType::InvalidReturn => write!(f, "'return' outside function"),
             CompileErrorType::InvalidYield => write!(f, "'yield' outside function"),
-        }?;
+            CompileErrorType::Encoding(err) => write!(f, "(unicode error) {}", err),
+            CompileErrorType::Unsupported(feature) => write!(f, "{} is not supported yet", feature),
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.error)?;
 
         // Print line number:
         write!(f, " at line {:?}", self.location.row())
@@ -5,5 +5,7 @@
 extern crate log;
 
 pub mod compile;
+pub mod encoding;
 pub mod error;
 mod symboltable;
+mod unparse;
@@ -10,48 +10,114 @@ Inspirational file: https://github.com/python/cpython/blob/master/Python/symtabl
 use crate::error::{CompileError, CompileErrorType};
 use rustpython_parser::ast;
 use rustpython_parser::lexer::Location;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-pub fn make_symbol_table(program: &ast::Program) -> Result<SymbolScope, SymbolTableError> {
+pub fn make_symbol_table(program: &ast::Program) -> Result<SymbolTable, SymbolTableError> {
     let mut builder = SymbolTableBuilder::new();
-    builder.enter_scope();
+    builder.enter_scope(SymbolTableType::Module);
     builder.scan_program(program)?;
-    assert_eq!(builder.scopes.len(), 1);
-
-    let symbol_table = builder.scopes.pop().unwrap();
-    analyze_symbol_table(&symbol_table, None)?;
-    Ok(symbol_table)
+    builder.finish()
 }
 
 pub fn statements_to_symbol_table(
     statements: &[ast::LocatedStatement],
-) -> Result<SymbolScope, SymbolTableError> {
+) -> Result<SymbolTable, SymbolTableError> {
     let mut builder = SymbolTableBuilder::new();
-    builder.enter_scope();
+    builder.enter_scope(SymbolTableType::Module);
     builder.scan_statements(statements)?;
-    assert_eq!(builder.scopes.len(), 1);
-
-    let symbol_table = builder.scopes.pop().unwrap();
-    analyze_symbol_table(&symbol_table, None)?;
-    Ok(symbol_table)
+    builder.finish()
 }
 
-#[derive(Debug)]
-pub enum SymbolRole {
+/// How a name is used at one place in the code.
+#[derive(Debug, Clone, Copy)]
+enum SymbolUsage {
     Global,
     Nonlocal,
     Used,
     Assigned,
+    Parameter,
+}
+
+/// The kind of code block a symbol table belongs to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolTableType {
+    Module,
+    Class,
+    Function,
+    Comprehension,
+}
+
+/// Where the value of a name lives, as found by the analysis of the symbol
+/// table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolScope {
+    Unknown,
+    /// A local variable, which is stored in a fast local slot in functions
+    /// and in the namespace dict of modules and class bodies.
+    Local,
+    /// Declared global with a `global` statement.
+    GlobalExplicit,
+    /// Not bound in any enclosing function, so looked up in the globals and
+    /// then the builtins.
+    GlobalImplicit,
+    /// A variable of an enclosing function, received in a cell.
+    Free,
+    /// A local variable which nested functions refer to, stored in a cell.
+    Cell,
+}
+
+/// A single name in a symbol table.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub scope: SymbolScope,
+    /// Where the symbol was first seen, for error reporting.
+    pub location: Location,
+    pub is_param: bool,
+    pub is_referenced: bool,
+    pub is_assigned: bool,
+    pub is_global: bool,
+    pub is_nonlocal: bool,
+    /// Bound in a class body while the methods of the class refer to a
+    /// variable of the same name in an enclosing function, so that the class
+    /// body needs the cell of that variable too.
+    pub is_free_class: bool,
+}
+
+impl Symbol {
+    fn new(name: &str, location: Location) -> Self {
+        Symbol {
+            name: name.to_string(),
+            scope: SymbolScope::Unknown,
+            location,
+            is_param: false,
+            is_referenced: false,
+            is_assigned: false,
+            is_global: false,
+            is_nonlocal: false,
+            is_free_class: false,
+        }
+    }
+
+    fn is_bound(&self) -> bool {
+        self.is_param || self.is_assigned
+    }
 }
 
 /// Captures all symbols in the current scope, and has a list of subscopes in this scope.
-pub struct SymbolScope {
+pub struct SymbolTable {
+    pub typ: SymbolTableType,
+
     /// A set of symbols present on this scope level.
-    pub symbols: HashMap<String, SymbolRole>,
+    pub symbols: HashMap<String, Symbol>,
 
     /// A list of subscopes in the order as found in the
     /// AST nodes.
-    pub sub_scopes: Vec<SymbolScope>,
+    pub sub_tables: Vec<SymbolTable>,
+
+    /// Whether the methods of this class refer to `__class__`, directly or
+    /// through `super()`, so that the class body must create a cell for it.
+    pub needs_class_closure: bool,
 }
 
 #[derive(Debug)]
@@ -63,109 +129,283 @@ pub struct SymbolTableError {
 impl From<SymbolTableError> for CompileError {
     fn from(error: SymbolTableError) -> Self {
         CompileError {
+            statement: None,
             error: CompileErrorType::SyntaxError(error.error),
             location: error.location,
+            source_path: None,
         }
     }
 }
 
 type SymbolTableResult = Result<(), SymbolTableError>;
 
-impl SymbolScope {
-    pub fn new() -> Self {
-        SymbolScope {
+/// How deeply statements and expressions may be nested.
+const MAX_NESTING: usize = 200;
+
+impl SymbolTable {
+    fn new(typ: SymbolTableType) -> Self {
+        SymbolTable {
+            typ,
             symbols: HashMap::new(),
-            sub_scopes: vec![],
+            sub_tables: vec![],
+            needs_class_closure: false,
         }
     }
 
-    pub fn lookup(&self, name: &str) -> Option<&SymbolRole> {
+    pub fn lookup(&self, name: &str) -> Option<&Symbol> {
         self.symbols.get(name)
     }
+
+    /// Whether this is the table of a function or a comprehension, whose
+    /// local variables live in the frame rather than in a namespace dict.
+    pub fn is_function(&self) -> bool {
+        match self.typ {
+            SymbolTableType::Function | SymbolTableType::Comprehension => true,
+            SymbolTableType::Module | SymbolTableType::Class => false,
+        }
+    }
+
+    /// The names of the cell variables, sorted as they are numbered in the
+    /// code object.
+    pub fn cell_names(&self) -> Vec<String> {
+        let mut names = self.names_where(|symbol| symbol.scope == SymbolScope::Cell);
+        if self.needs_class_closure {
+            names.push("__class__".to_string());
+        }
+        names
+    }
+
+    /// The names of the free variables, sorted as they are numbered in the
+    /// code object.
+    pub fn free_names(&self) -> Vec<String> {
+        self.names_where(|symbol| symbol.scope == SymbolScope::Free || symbol.is_free_class)
+    }
+
+    fn names_where<F>(&self, predicate: F) -> Vec<String>
+    where
+        F: Fn(&Symbol) -> bool,
+    {
+        let mut names: Vec<String> = self
+            .symbols
+            .values()
+            .filter(|symbol| predicate(symbol))
+            .map(|symbol| symbol.name.clone())
+            .collect();
+        names.sort();
+        names
+    }
 }
 
-impl std::fmt::Debug for SymbolScope {
+impl std::fmt::Debug for SymbolTable {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(
             f,
-            "SymbolScope({:?} symbols, {:?} sub scopes)",
+            "SymbolTable({:?} symbols, {:?} sub scopes)",
             self.symbols.len(),
-            self.sub_scopes.len()
+            self.sub_tables.len()
         )
     }
 }
 
-/* Perform some sort of analysis on nonlocals, globals etc..
+/* Find the scope of every name, handling nonlocals, globals and the
+  variables shared between nested functions through cells.
   See also: https://github.com/python/cpython/blob/master/Python/symtable.c#L410
 */
-fn analyze_symbol_table(
-    symbol_scope: &SymbolScope,
-    parent_symbol_scope: Option<&SymbolScope>,
+fn analyze_symbol_table(symbol_table: &mut SymbolTable) -> SymbolTableResult {
+    analyze_block(symbol_table, None, &HashSet::new(), &mut HashSet::new())
+}
+
+/// Analyze `table` and its nested tables. `bound` holds the names bound in
+/// the enclosing functions, if any, and `global` the names known to be
+/// global. The free variables of `table` are added to `free`.
+fn analyze_block(
+    table: &mut SymbolTable,
+    bound: Option<&HashSet<String>>,
+    global: &HashSet<String>,
+    free: &mut HashSet<String>,
 ) -> SymbolTableResult {
-    // Analyze sub scopes:
-    for sub_scope in &symbol_scope.sub_scopes {
-        analyze_symbol_table(&sub_scope, Some(symbol_scope))?;
+    let mut bound = bound.cloned();
+    let mut global = global.clone();
+    let mut local = HashSet::new();
+
+    // The names visible in nested scopes:
+    let mut new_bound = HashSet::new();
+    let mut new_global = HashSet::new();
+
+    // A class namespace does not affect the names visible in its methods:
+    if table.typ == SymbolTableType::Class {
+        new_global.extend(global.iter().cloned());
+        if let Some(bound) = &bound {
+            new_bound.extend(bound.iter().cloned());
+        }
+    }
+
+    for symbol in table.symbols.values_mut() {
+        analyze_name(symbol, bound.as_mut(), &mut local, free, &mut global)?;
+    }
+
+    if table.typ == SymbolTableType::Class {
+        new_bound.insert("__class__".to_string());
+    } else {
+        if table.is_function() {
+            new_bound.extend(local);
+        }
+        if let Some(bound) = &bound {
+            new_bound.extend(bound.iter().cloned());
+        }
+        new_global.extend(global);
     }
 
-    // Analyze symbols:
-    for (symbol_name, symbol_role) in &symbol_scope.symbols {
-        analyze_symbol(symbol_name, symbol_role, parent_symbol_scope)?;
+    // The free variables of the nested scopes:
+    let mut new_free = HashSet::new();
+    for sub_table in &mut table.sub_tables {
+        analyze_block(sub_table, Some(&new_bound), &new_global, &mut new_free)?;
     }
 
+    if table.is_function() {
+        // Local variables used by nested scopes are stored in cells:
+        for symbol in table.symbols.values_mut() {
+            if symbol.scope == SymbolScope::Local && new_free.remove(&symbol.name) {
+                symbol.scope = SymbolScope::Cell;
+            }
+        }
+    } else if table.typ == SymbolTableType::Class && new_free.remove("__class__") {
+        table.needs_class_closure = true;
+    }
+
+    // Pass the free variables of nested scopes on to the enclosing scopes:
+    for name in &new_free {
+        if let Some(symbol) = table.symbols.get_mut(name) {
+            if table.typ == SymbolTableType::Class && (symbol.is_bound() || symbol.is_global) {
+                symbol.is_free_class = true;
+            }
+        } else if bound.as_ref().map_or(true, |bound| bound.contains(name)) {
+            let mut symbol = Symbol::new(name, Default::default());
+            symbol.scope = SymbolScope::Free;
+            table.symbols.insert(name.clone(), symbol);
+        }
+    }
+    free.extend(new_free);
     Ok(())
 }
 
-#[allow(clippy::single_match)]
-fn analyze_symbol(
-    symbol_name: &str,
-    symbol_role: &SymbolRole,
-    parent_symbol_scope: Option<&SymbolScope>,
+fn analyze_name(
+    symbol: &mut Symbol,
+    bound: Option<&mut HashSet<String>>,
+    local: &mut HashSet<String>,
+    free: &mut HashSet<String>,
+    global: &mut HashSet<String>,
 ) -> SymbolTableResult {
-    match symbol_role {
-        SymbolRole::Nonlocal => {
-            // check if name is defined in parent scope!
-            if let Some(parent_symbol_scope) = parent_symbol_scope {
-                if !parent_symbol_scope.symbols.contains_key(symbol_name) {
-                    return Err(SymbolTableError {
-                        error: format!("no binding for nonlocal '{}' found", symbol_name),
-                        location: Default::default(),
-                    });
-                }
-            } else {
-                return Err(SymbolTableError {
-                    error: format!(
-                        "nonlocal {} defined at place without an enclosing scope",
-                        symbol_name
-                    ),
-                    location: Default::default(),
-                });
+    let location = symbol.location.clone();
+    let error = |message: String| SymbolTableError {
+        error: message,
+        location: location.clone(),
+    };
+    if symbol.is_global {
+        if symbol.is_nonlocal {
+            return Err(error(format!(
+                "name '{}' is nonlocal and global",
+                symbol.name
+            )));
+        }
+        symbol.scope = SymbolScope::GlobalExplicit;
+        global.insert(symbol.name.clone());
+        if let Some(bound) = bound {
+            bound.remove(&symbol.name);
+        }
+    } else if symbol.is_nonlocal {
+        match bound {
+            None => {
+                return Err(error(
+                    "nonlocal declaration not allowed at module level".to_string(),
+                ))
             }
+            Some(bound) if !bound.contains(&symbol.name) => {
+                return Err(error(format!(
+                    "no binding for nonlocal '{}' found",
+                    symbol.name
+                )))
+            }
+            Some(_) => {}
         }
-        // TODO: add more checks for globals
-        _ => {}
+        symbol.scope = SymbolScope::Free;
+        free.insert(symbol.name.clone());
+    } else if symbol.is_bound() {
+        symbol.scope = SymbolScope::Local;
+        local.insert(symbol.name.clone());
+        global.remove(&symbol.name);
+    } else if bound.map_or(false, |bound| bound.contains(&symbol.name)) {
+        // Bound in an enclosing function:
+        symbol.scope = SymbolScope::Free;
+        free.insert(symbol.name.clone());
+    } else {
+        symbol.scope = SymbolScope::GlobalImplicit;
     }
     Ok(())
 }
 
 pub struct SymbolTableBuilder {
     // Scope stack.
-    pub scopes: Vec<SymbolScope>,
+    tables: Vec<SymbolTable>,
+
+    // Set by `from __future__ import annotations`: annotations become strings.
+    future_annotations: bool,
+
+    // Iteration variables of the comprehensions being scanned, innermost last.
+    comprehension_targets: Vec<Vec<String>>,
+
+    // Whether the iterable of a comprehension is being scanned.
+    in_comprehension_iter: bool,
+
+    // Location of the statement being scanned.
+    current_location: Location,
+
+    // Depth of the statements and expressions being scanned.
+    nesting: usize,
 }
 
 impl SymbolTableBuilder {
     pub fn new() -> Self {
-        SymbolTableBuilder { scopes: vec![] }
+        SymbolTableBuilder {
+            tables: vec![],
+            future_annotations: false,
+            comprehension_targets: vec![],
+            in_comprehension_iter: false,
+            current_location: Default::default(),
+            nesting: 0,
+        }
+    }
+
+    fn finish(mut self) -> Result<SymbolTable, SymbolTableError> {
+        assert_eq!(self.tables.len(), 1);
+        let mut symbol_table = self.tables.pop().unwrap();
+        analyze_symbol_table(&mut symbol_table)?;
+        Ok(symbol_table)
     }
 
-    pub fn enter_scope(&mut self) {
-        let scope = SymbolScope::new();
-        self.scopes.push(scope);
+    pub fn enter_scope(&mut self, typ: SymbolTableType) {
+        let table = SymbolTable::new(typ);
+        self.tables.push(table);
     }
 
     fn leave_scope(&mut self) {
         // Pop scope and add to subscopes of parent scope.
-        let scope = self.scopes.pop().unwrap();
-        self.scopes.last_mut().unwrap().sub_scopes.push(scope);
+        let table = self.tables.pop().unwrap();
+        self.tables.last_mut().unwrap().sub_tables.push(table);
+    }
+
+    /// Scan the code of a nested function or class, which starts with no
+    /// enclosing comprehensions.
+    fn scan_nested<F>(&mut self, scan: F) -> SymbolTableResult
+    where
+        F: FnOnce(&mut Self) -> SymbolTableResult,
+    {
+        let targets = std::mem::replace(&mut self.comprehension_targets, vec![]);
+        let was_in_comprehension_iter = std::mem::replace(&mut self.in_comprehension_iter, false);
+        scan(self)?;
+        self.in_comprehension_iter = was_in_comprehension_iter;
+        self.comprehension_targets = targets;
+        Ok(())
     }
 
     pub fn scan_program(&mut self, program: &ast::Program) -> SymbolTableResult {
@@ -188,7 +428,7 @@ impl SymbolTableBuilder {
     }
 
     fn scan_parameter(&mut self, parameter: &ast::Parameter) -> SymbolTableResult {
-        self.register_name(&parameter.arg, SymbolRole::Assigned)
+        self.register_name(&parameter.arg, SymbolUsage::Parameter)
     }
 
     fn scan_parameters_annotations(&mut self, parameters: &[ast::Parameter]) -> SymbolTableResult {
@@ -200,21 +440,32 @@ impl SymbolTableBuilder {
 
     fn scan_parameter_annotation(&mut self, parameter: &ast::Parameter) -> SymbolTableResult {
         if let Some(annotation) = &parameter.annotation {
-            self.scan_expression(&annotation)?;
+            self.scan_annotation(&annotation)?;
         }
         Ok(())
     }
 
+    fn scan_annotation(&mut self, annotation: &ast::Expression) -> SymbolTableResult {
+        // Annotations kept as strings are never evaluated, so they use no names.
+        if self.future_annotations {
+            Ok(())
+        } else {
+            self.scan_expression(annotation)
+        }
+    }
+
     fn scan_statement(&mut self, statement: &ast::LocatedStatement) -> SymbolTableResult {
+        self.current_location = statement.location.clone();
+        self.enter_nesting()?;
         match &statement.node {
             ast::Statement::Global { names } => {
                 for name in names {
-                    self.register_name(name, SymbolRole::Global)?;
+                    self.register_name(name, SymbolUsage::Global)?;
                 }
             }
             ast::Statement::Nonlocal { names } => {
                 for name in names {
-                    self.register_name(name, SymbolRole::Nonlocal)?;
+                    self.register_name(name, SymbolUsage::Nonlocal)?;
                 }
             }
             ast::Statement::FunctionDef {
@@ -232,15 +483,24 @@ impl SymbolTableBuilder {
                 returns,
             } => {
                 self.scan_expressions(decorator_list)?;
-                self.register_name(name, SymbolRole::Assigned)?;
-
+                self.register_name(name, SymbolUsage::Assigned)?;
                 self.enter_function(args)?;
+                self.scan_nested(|builder| builder.scan_statements(body))?;
+                self.leave_scope();
 
-                self.scan_statements(body)?;
+                // Annotations are evaluated in the outer scope, after the body is compiled:
                 if let Some(expression) = returns {
-                    self.scan_expression(expression)?;
+                    self.scan_annotation(expression)?;
+                }
+                self.scan_parameters_annotations(&args.posonlyargs)?;
+                self.scan_parameters_annotations(&args.args)?;
+                if let ast::Varargs::Named(name) = &args.vararg {
+                    self.scan_parameter_annotation(name)?;
+                }
+                self.scan_parameters_annotations(&args.kwonlyargs)?;
+                if let ast::Varargs::Named(name) = &args.kwarg {
+                    self.scan_parameter_annotation(name)?;
                 }
-                self.leave_scope();
             }
             ast::Statement::ClassDef {
                 name,
@@ -249,15 +509,18 @@ impl SymbolTableBuilder {
                 keywords,
                 decorator_list,
             } => {
-                self.register_name(name, SymbolRole::Assigned)?;
-                self.enter_scope();
-                self.scan_statements(body)?;
+                // In the order of compilation, which the compiler relies on
+                // to match nested scopes with their symbol tables:
+                self.scan_expressions(decorator_list)?;
+                self.register_name(name, SymbolUsage::Assigned)?;
+                self.enter_scope(SymbolTableType::Class);
+                self.scan_nested(|builder| builder.scan_statements(body))?;
                 self.leave_scope();
+                self.current_location = statement.location.clone();
                 self.scan_expressions(bases)?;
                 for keyword in keywords {
                     self.scan_expression(&keyword.value)?;
                 }
-                self.scan_expressions(decorator_list)?;
             }
             ast::Statement::Expression { expression } => self.scan_expression(expression)?,
             ast::Statement::If { test, body, orelse } => {
@@ -279,7 +542,7 @@ impl SymbolTableBuilder {
                 body,
                 orelse,
             } => {
-                self.scan_expression(target)?;
+                self.scan_target(target)?;
                 self.scan_expression(iter)?;
                 self.scan_statements(body)?;
                 if let Some(code) = orelse {
@@ -298,21 +561,29 @@ impl SymbolTableBuilder {
             }
             ast::Statement::Import { import_parts } => {
                 for part in import_parts {
+                    if part.module == "__future__"
+                        && part
+                            .symbols
+                            .iter()
+                            .any(|symbol| symbol.symbol == "annotations")
+                    {
+                        self.future_annotations = true;
+                    }
                     if let Some(alias) = &part.alias {
                         // `import mymodule as myalias`
-                        self.register_name(alias, SymbolRole::Assigned)?;
+                        self.register_name(alias, SymbolUsage::Assigned)?;
                     } else {
                         if part.symbols.is_empty() {
                             // `import module`
-                            self.register_name(&part.module, SymbolRole::Assigned)?;
+                            self.register_name(&part.module, SymbolUsage::Assigned)?;
                         } else {
                             // `from mymodule import myimport`
                             for symbol in &part.symbols {
                                 if let Some(alias) = &symbol.alias {
                                     // `from mymodule import myimportname as myalias`
-                                    self.register_name(alias, SymbolRole::Assigned)?;
+                                    self.register_name(alias, SymbolUsage::Assigned)?;
                                 } else {
-                                    self.register_name(&symbol.symbol, SymbolRole::Assigned)?;
+                                    self.register_name(&symbol.symbol, SymbolUsage::Assigned)?;
                                 }
                             }
                         }
@@ -331,21 +602,40 @@ impl SymbolTableBuilder {
                 }
             }
             ast::Statement::Delete { targets } => {
-                self.scan_expressions(targets)?;
+                for target in targets {
+                    self.scan_target(target)?;
+                }
             }
             ast::Statement::Assign { targets, value } => {
-                self.scan_expressions(targets)?;
+                for target in targets {
+                    self.scan_target(target)?;
+                }
                 self.scan_expression(value)?;
             }
             ast::Statement::AugAssign { target, value, .. } => {
-                self.scan_expression(target)?;
+                self.scan_target(target)?;
                 self.scan_expression(value)?;
             }
+            ast::Statement::AnnAssign {
+                target,
+                annotation,
+                value,
+                ..
+            } => {
+                self.scan_target(target)?;
+                // Annotations are only evaluated in a module or class.
+                if !self.tables.last().unwrap().is_function() {
+                    self.scan_annotation(annotation)?;
+                }
+                if let Some(value) = value {
+                    self.scan_expression(value)?;
+                }
+            }
             ast::Statement::With { items, body } => {
                 for item in items {
                     self.scan_expression(&item.context_expr)?;
                     if let Some(expression) = &item.optional_vars {
-                        self.scan_expression(expression)?;
+                        self.scan_target(expression)?;
                     }
                 }
                 self.scan_statements(body)?;
@@ -362,7 +652,7 @@ impl SymbolTableBuilder {
                         self.scan_expression(expression)?;
                     }
                     if let Some(name) = &handler.name {
-                        self.register_name(name, SymbolRole::Assigned)?;
+                        self.register_name(name, SymbolUsage::Assigned)?;
                     }
                     self.scan_statements(&handler.body)?;
                 }
@@ -382,9 +672,25 @@ impl SymbolTableBuilder {
                 }
             }
         }
+        self.nesting -= 1;
         Ok(())
     }
 
+    /// Scan the target of an assignment, which binds the names in it.
+    fn scan_target(&mut self, target: &ast::Expression) -> SymbolTableResult {
+        match target {
+            ast::Expression::Identifier { name } => self.register_name(name, SymbolUsage::Assigned),
+            ast::Expression::Tuple { elements } | ast::Expression::List { elements } => {
+                for element in elements {
+                    self.scan_target(element)?;
+                }
+                Ok(())
+            }
+            ast::Expression::Starred { value } => self.scan_target(value),
+            _ => self.scan_expression(target),
+        }
+    }
+
     fn scan_expressions(&mut self, expressions: &[ast::Expression]) -> SymbolTableResult {
         for expression in expressions {
             self.scan_expression(expression)?;
@@ -393,6 +699,7 @@ impl SymbolTableBuilder {
     }
 
     fn scan_expression(&mut self, expression: &ast::Expression) -> SymbolTableResult {
+        self.enter_nesting()?;
         match expression {
             ast::Expression::Binop { a, b, .. } => {
                 self.scan_expression(a)?;
@@ -452,24 +759,14 @@ impl SymbolTableBuilder {
                 self.scan_expressions(elements)?;
             }
             ast::Expression::Comprehension { kind, generators } => {
-                match **kind {
-                    ast::ComprehensionKind::GeneratorExpression { ref element }
-                    | ast::ComprehensionKind::List { ref element }
-                    | ast::ComprehensionKind::Set { ref element } => {
-                        self.scan_expression(element)?;
-                    }
-                    ast::ComprehensionKind::Dict { ref key, ref value } => {
-                        self.scan_expression(&key)?;
-                        self.scan_expression(&value)?;
-                    }
-                }
-
-                for generator in generators {
-                    self.scan_expression(&generator.target)?;
-                    self.scan_expression(&generator.iter)?;
-                    for if_expr in &generator.ifs {
-                        self.scan_expression(if_expr)?;
-                    }
+                self.scan_comprehension(kind, generators)?;
+            }
+            ast::Expression::NamedExpression { left, right } => {
+                self.scan_expression(right)?;
+                if let ast::Expression::Identifier { name } = &**left {
+                    self.scan_named_expression_target(name)?;
+                } else {
+                    self.scan_expression(left)?;
                 }
             }
             ast::Expression::Call {
@@ -487,11 +784,15 @@ impl SymbolTableBuilder {
                 self.scan_string_group(value)?;
             }
             ast::Expression::Identifier { name } => {
-                self.register_name(name, SymbolRole::Used)?;
+                self.register_name(name, SymbolUsage::Used)?;
+                // `super()` finds the class of a method in the `__class__` cell:
+                if name == "super" && self.tables.last().unwrap().is_function() {
+                    self.register_name("__class__", SymbolUsage::Used)?;
+                }
             }
             ast::Expression::Lambda { args, body } => {
                 self.enter_function(args)?;
-                self.scan_expression(body)?;
+                self.scan_nested(|builder| builder.scan_expression(body))?;
                 self.leave_scope();
             }
             ast::Expression::IfExpression { test, body, orelse } => {
@@ -500,6 +801,132 @@ impl SymbolTableBuilder {
                 self.scan_expression(orelse)?;
             }
         }
+        self.nesting -= 1;
+        Ok(())
+    }
+
+    /// Scan a comprehension, which runs as a function of its own. The
+    /// iterable of the first generator is evaluated in the enclosing scope
+    /// and passed to the function as its `.0` parameter.
+    fn scan_comprehension(
+        &mut self,
+        kind: &ast::ComprehensionKind,
+        generators: &[ast::Comprehension],
+    ) -> SymbolTableResult {
+        let mut targets = vec![];
+        for generator in generators {
+            collect_target_names(&generator.target, &mut targets);
+        }
+        self.comprehension_targets.push(targets);
+        let was_in_comprehension_iter = std::mem::replace(&mut self.in_comprehension_iter, false);
+
+        self.enter_scope(SymbolTableType::Comprehension);
+        self.register_name(".0", SymbolUsage::Parameter)?;
+        for (index, generator) in generators.iter().enumerate() {
+            if index > 0 {
+                self.in_comprehension_iter = true;
+                self.scan_expression(&generator.iter)?;
+                self.in_comprehension_iter = false;
+            }
+            self.scan_target(&generator.target)?;
+            self.scan_expressions(&generator.ifs)?;
+        }
+        match kind {
+            ast::ComprehensionKind::GeneratorExpression { element }
+            | ast::ComprehensionKind::List { element }
+            | ast::ComprehensionKind::Set { element } => {
+                self.scan_expression(element)?;
+            }
+            ast::ComprehensionKind::Dict { key, value } => {
+                self.scan_expression(value)?;
+                self.scan_expression(key)?;
+            }
+        }
+        self.leave_scope();
+        self.comprehension_targets.pop();
+
+        self.in_comprehension_iter = true;
+        self.scan_expression(&generators[0].iter)?;
+        self.in_comprehension_iter = was_in_comprehension_iter;
+        Ok(())
+    }
+
+    /// Register the target of an assignment expression. In a comprehension
+    /// it binds the name in the enclosing function or module instead.
+    fn scan_named_expression_target(&mut self, name: &str) -> SymbolTableResult {
+        let error = |message: String, location: &Location| SymbolTableError {
+            error: message,
+            location: location.clone(),
+        };
+        if self.in_comprehension_iter {
+            return Err(error(
+                "assignment expression cannot be used in a comprehension iterable expression"
+                    .to_string(),
+                &self.current_location,
+            ));
+        }
+        if self.tables.last().unwrap().typ != SymbolTableType::Comprehension {
+            return self.register_name(name, SymbolUsage::Assigned);
+        }
+
+        if self
+            .comprehension_targets
+            .iter()
+            .any(|targets| targets.iter().any(|target| target == name))
+        {
+            return Err(error(
+                format!(
+                    "assignment expression cannot rebind comprehension iteration variable '{}'",
+                    name
+                ),
+                &self.current_location,
+            ));
+        }
+        let enclosing = self
+            .tables
+            .iter()
+            .rposition(|table| table.typ != SymbolTableType::Comprehension)
+            .unwrap();
+        let in_function = match self.tables[enclosing].typ {
+            SymbolTableType::Class => {
+                return Err(error(
+                    "assignment expression within a comprehension cannot be used in a class body"
+                        .to_string(),
+                    &self.current_location,
+                ));
+            }
+            typ => typ == SymbolTableType::Function,
+        };
+        self.register_name_in(enclosing, name, SymbolUsage::Assigned)?;
+
+        // The comprehension refers to the variable as if it was declared
+        // nonlocal or global, without the checks of such declarations:
+        let location = self.current_location.clone();
+        let symbol = self
+            .tables
+            .last_mut()
+            .unwrap()
+            .symbols
+            .entry(name.to_string())
+            .or_insert_with(|| Symbol::new(name, location));
+        if in_function {
+            symbol.is_nonlocal = true;
+        } else {
+            symbol.is_global = true;
+        }
+        Ok(())
+    }
+
+    /// The compiler recurses along with the symbol table, so deeply nested
+    /// code is rejected here before it can overflow the stack.
+    fn enter_nesting(&mut self) -> SymbolTableResult {
+        self.nesting += 1;
+        if self.nesting > MAX_NESTING {
+            return Err(SymbolTableError {
+                error: "too many nested statements and expressions".to_string(),
+                location: self.current_location.clone(),
+            });
+        }
         Ok(())
     }
 
@@ -512,19 +939,10 @@ impl SymbolTableBuilder {
             }
         }
 
-        // Annotations are scanned in outer scope:
-        self.scan_parameters_annotations(&args.args)?;
-        self.scan_parameters_annotations(&args.kwonlyargs)?;
-        if let ast::Varargs::Named(name) = &args.vararg {
-            self.scan_parameter_annotation(name)?;
-        }
-        if let ast::Varargs::Named(name) = &args.kwarg {
-            self.scan_parameter_annotation(name)?;
-        }
-
-        self.enter_scope();
+        self.enter_scope(SymbolTableType::Function);
 
         // Fill scope with parameter names:
+        self.scan_parameters(&args.posonlyargs)?;
         self.scan_parameters(&args.args)?;
         self.scan_parameters(&args.kwonlyargs)?;
         if let ast::Varargs::Named(name) = &args.vararg {
@@ -539,8 +957,11 @@ impl SymbolTableBuilder {
     fn scan_string_group(&mut self, group: &ast::StringGroup) -> SymbolTableResult {
         match group {
             ast::StringGroup::Constant { .. } => {}
-            ast::StringGroup::FormattedValue { value, .. } => {
+            ast::StringGroup::FormattedValue { value, spec, .. } => {
                 self.scan_expression(value)?;
+                if let Some(spec) = spec {
+                    self.scan_string_group(spec)?;
+                }
             }
             ast::StringGroup::Joined { values } => {
                 for subgroup in values {
@@ -551,46 +972,81 @@ impl SymbolTableBuilder {
         Ok(())
     }
 
-    #[allow(clippy::single_match)]
-    fn register_name(&mut self, name: &str, role: SymbolRole) -> SymbolTableResult {
-        let scope_depth = self.scopes.len();
-        let current_scope = self.scopes.last_mut().unwrap();
-        let location = Default::default();
-        if current_scope.symbols.contains_key(name) {
-            // Role already set..
-            match role {
-                SymbolRole::Global => {
-                    return Err(SymbolTableError {
-                        error: format!("name '{}' is used prior to global declaration", name),
-                        location,
-                    })
-                }
-                SymbolRole::Nonlocal => {
-                    return Err(SymbolTableError {
-                        error: format!("name '{}' is used prior to nonlocal declaration", name),
-                        location,
-                    })
-                }
-                _ => {
-                    // Ok?
+    fn register_name(&mut self, name: &str, usage: SymbolUsage) -> SymbolTableResult {
+        self.register_name_in(self.tables.len() - 1, name, usage)
+    }
+
+    fn register_name_in(
+        &mut self,
+        table_index: usize,
+        name: &str,
+        usage: SymbolUsage,
+    ) -> SymbolTableResult {
+        let location = self.current_location.clone();
+        let table = &mut self.tables[table_index];
+        let symbol = table
+            .symbols
+            .entry(name.to_string())
+            .or_insert_with(|| Symbol::new(name, location.clone()));
+
+        let declaration_error = |declaration: &str, symbol: &Symbol| {
+            let message = if symbol.is_param {
+                format!("name '{}' is parameter and {}", name, declaration)
+            } else if symbol.is_referenced {
+                format!(
+                    "name '{}' is used prior to {} declaration",
+                    name, declaration
+                )
+            } else {
+                format!(
+                    "name '{}' is assigned to before {} declaration",
+                    name, declaration
+                )
+            };
+            Err(SymbolTableError {
+                error: message,
+                location: location.clone(),
+            })
+        };
+        match usage {
+            SymbolUsage::Global => {
+                if symbol.is_bound() || symbol.is_referenced {
+                    return declaration_error("global", symbol);
                 }
+                symbol.is_global = true;
             }
-        } else {
-            match role {
-                SymbolRole::Nonlocal => {
-                    if scope_depth < 2 {
-                        return Err(SymbolTableError {
-                            error: format!("cannot define nonlocal '{}' at top level.", name),
-                            location,
-                        });
-                    }
+            SymbolUsage::Nonlocal => {
+                if symbol.is_bound() || symbol.is_referenced {
+                    return declaration_error("nonlocal", symbol);
                 }
-                _ => {
-                    // Ok!
+                symbol.is_nonlocal = true;
+            }
+            SymbolUsage::Used => symbol.is_referenced = true,
+            SymbolUsage::Assigned => symbol.is_assigned = true,
+            SymbolUsage::Parameter => {
+                if symbol.is_param {
+                    return Err(SymbolTableError {
+                        error: format!("duplicate argument '{}' in function definition", name),
+                        location,
+                    });
                 }
+                symbol.is_param = true;
             }
-            current_scope.symbols.insert(name.to_string(), role);
         }
         Ok(())
     }
 }
+
+/// Collect the names bound by a comprehension target such as `(a, *b)`.
+fn collect_target_names(target: &ast::Expression, names: &mut Vec<String>) {
+    match target {
+        ast::Expression::Identifier { name } => names.push(name.clone()),
+        ast::Expression::Tuple { elements } | ast::Expression::List { elements } => {
+            for element in elements {
+                collect_target_names(element, names);
+            }
+        }
+        ast::Expression::Starred { value } => collect_target_names(value, names),
+        _ => {}
+    }
+}
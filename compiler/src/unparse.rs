@@ -0,0 +1,574 @@
+//! Turn an expression back into source code.
+//!
+//! Used to store annotations as strings under `from __future__ import annotations`.
+//!
+//! Inspirational code:
+//!   https://github.com/python/cpython/blob/master/Python/ast_unparse.c
+
+use rustpython_parser::ast;
+
+// Operator precedence levels, loosest binding first:
+const PR_TUPLE: u8 = 0;
+const PR_TEST: u8 = 1;
+const PR_OR: u8 = 2;
+const PR_AND: u8 = 3;
+const PR_NOT: u8 = 4;
+const PR_CMP: u8 = 5;
+const PR_BOR: u8 = 6;
+const PR_BXOR: u8 = 7;
+const PR_BAND: u8 = 8;
+const PR_SHIFT: u8 = 9;
+const PR_ARITH: u8 = 10;
+const PR_TERM: u8 = 11;
+const PR_FACTOR: u8 = 12;
+const PR_POWER: u8 = 13;
+const PR_AWAIT: u8 = 14;
+const PR_ATOM: u8 = 15;
+
+/// Returns the source code of an expression, as CPython's `ast_unparse.c` would write it.
+pub fn unparse_expression(expression: &ast::Expression) -> String {
+    let mut unparser = Unparser {
+        source: String::new(),
+    };
+    unparser.expression(expression, PR_TEST);
+    unparser.source
+}
+
+struct Unparser {
+    source: String,
+}
+
+impl Unparser {
+    fn push(&mut self, s: &str) {
+        self.source.push_str(s);
+    }
+
+    fn push_if(&mut self, condition: bool, s: &str) {
+        if condition {
+            self.push(s);
+        }
+    }
+
+    fn expression(&mut self, expression: &ast::Expression, level: u8) {
+        match expression {
+            ast::Expression::BoolOp { a, op, b } => {
+                let (op, pr) = match op {
+                    ast::BooleanOperator::And => (" and ", PR_AND),
+                    ast::BooleanOperator::Or => (" or ", PR_OR),
+                };
+                self.push_if(level > pr, "(");
+                self.expression(a, pr + 1);
+                self.push(op);
+                self.expression(b, pr + 1);
+                self.push_if(level > pr, ")");
+            }
+            ast::Expression::Binop { a, op, b } => {
+                let (op, pr) = binary_operator(op);
+                // Only the power operator is right associative
+                let (left, right) = if pr == PR_POWER {
+                    (pr + 1, pr)
+                } else {
+                    (pr, pr + 1)
+                };
+                self.push_if(level > pr, "(");
+                self.expression(a, left);
+                self.push(op);
+                self.expression(b, right);
+                self.push_if(level > pr, ")");
+            }
+            ast::Expression::Unop { op, a } => {
+                let (op, pr) = match op {
+                    ast::UnaryOperator::Not => ("not ", PR_NOT),
+                    ast::UnaryOperator::Inv => ("~", PR_FACTOR),
+                    ast::UnaryOperator::Neg => ("-", PR_FACTOR),
+                    ast::UnaryOperator::Pos => ("+", PR_FACTOR),
+                };
+                self.push_if(level > pr, "(");
+                self.push(op);
+                self.expression(a, pr);
+                self.push_if(level > pr, ")");
+            }
+            ast::Expression::Compare { vals, ops } => {
+                self.push_if(level > PR_CMP, "(");
+                self.expression(&vals[0], PR_CMP + 1);
+                for (op, val) in ops.iter().zip(vals[1..].iter()) {
+                    self.push(comparison(op));
+                    self.expression(val, PR_CMP + 1);
+                }
+                self.push_if(level > PR_CMP, ")");
+            }
+            ast::Expression::Subscript { a, b } => {
+                self.expression(a, PR_ATOM);
+                self.push("[");
+                self.expression(b, PR_TUPLE);
+                self.push("]");
+            }
+            ast::Expression::Slice { elements } => {
+                if let Some(lower) = elements.first() {
+                    self.optional(lower);
+                }
+                self.push(":");
+                if let Some(upper) = elements.get(1) {
+                    self.optional(upper);
+                }
+                if let Some(step) = elements.get(2) {
+                    if *step != ast::Expression::None {
+                        self.push(":");
+                        self.expression(step, PR_TEST);
+                    }
+                }
+            }
+            ast::Expression::Attribute { value, name } => {
+                self.expression(value, PR_ATOM);
+                // An integer needs a space before the dot: `1 .real`
+                if let ast::Expression::Number {
+                    value: ast::Number::Integer { .. },
+                } = **value
+                {
+                    self.push(" ");
+                }
+                self.push(".");
+                self.push(name);
+            }
+            ast::Expression::Call {
+                function,
+                args,
+                keywords,
+            } => {
+                self.expression(function, PR_ATOM);
+                self.push("(");
+                let mut first = true;
+                for arg in args {
+                    self.push_if(!first, ", ");
+                    first = false;
+                    self.expression(arg, PR_TEST);
+                }
+                for keyword in keywords {
+                    self.push_if(!first, ", ");
+                    first = false;
+                    self.keyword(keyword);
+                }
+                self.push(")");
+            }
+            ast::Expression::Await { value } => {
+                self.push_if(level > PR_AWAIT, "(");
+                self.push("await ");
+                self.expression(value, PR_ATOM);
+                self.push_if(level > PR_AWAIT, ")");
+            }
+            ast::Expression::Yield { value } => {
+                self.push("(yield");
+                if let Some(value) = value {
+                    self.push(" ");
+                    self.expression(value, PR_TEST);
+                }
+                self.push(")");
+            }
+            ast::Expression::YieldFrom { value } => {
+                self.push("(yield from ");
+                self.expression(value, PR_TEST);
+                self.push(")");
+            }
+            ast::Expression::Number { value } => self.number(value),
+            ast::Expression::String { value } => self.string_group(value),
+            ast::Expression::Bytes { value } => {
+                self.push("b");
+                self.push(&quote_bytes(value));
+            }
+            ast::Expression::Identifier { name } => self.push(name),
+            ast::Expression::List { elements } => {
+                self.push("[");
+                self.expressions(elements);
+                self.push("]");
+            }
+            ast::Expression::Tuple { elements } => {
+                if elements.is_empty() {
+                    self.push("()");
+                } else {
+                    self.push_if(level > PR_TUPLE, "(");
+                    self.expressions(elements);
+                    self.push_if(elements.len() == 1, ",");
+                    self.push_if(level > PR_TUPLE, ")");
+                }
+            }
+            ast::Expression::Set { elements } => {
+                self.push("{");
+                self.expressions(elements);
+                self.push("}");
+            }
+            ast::Expression::Dict { elements } => {
+                self.push("{");
+                for (i, (key, value)) in elements.iter().enumerate() {
+                    self.push_if(i > 0, ", ");
+                    match key {
+                        Some(key) => {
+                            self.expression(key, PR_TEST);
+                            self.push(": ");
+                            self.expression(value, PR_TEST);
+                        }
+                        None => {
+                            self.push("**");
+                            self.expression(value, PR_BOR);
+                        }
+                    }
+                }
+                self.push("}");
+            }
+            ast::Expression::Comprehension { kind, generators } => match **kind {
+                ast::ComprehensionKind::GeneratorExpression { ref element } => {
+                    self.push("(");
+                    self.expression(element, PR_TEST);
+                    self.comprehension_generators(generators);
+                    self.push(")");
+                }
+                ast::ComprehensionKind::List { ref element } => {
+                    self.push("[");
+                    self.expression(element, PR_TEST);
+                    self.comprehension_generators(generators);
+                    self.push("]");
+                }
+                ast::ComprehensionKind::Set { ref element } => {
+                    self.push("{");
+                    self.expression(element, PR_TEST);
+                    self.comprehension_generators(generators);
+                    self.push("}");
+                }
+                ast::ComprehensionKind::Dict { ref key, ref value } => {
+                    self.push("{");
+                    self.expression(key, PR_TEST);
+                    self.push(": ");
+                    self.expression(value, PR_TEST);
+                    self.comprehension_generators(generators);
+                    self.push("}");
+                }
+            },
+            ast::Expression::Starred { value } => {
+                self.push("*");
+                self.expression(value, PR_BOR);
+            }
+            ast::Expression::Lambda { args, body } => {
+                self.push_if(level > PR_TEST, "(");
+                self.push("lambda");
+                if !is_empty(args) {
+                    self.push(" ");
+                    self.parameters(args);
+                }
+                self.push(": ");
+                self.expression(body, PR_TEST);
+                self.push_if(level > PR_TEST, ")");
+            }
+            ast::Expression::IfExpression { test, body, orelse } => {
+                self.push_if(level > PR_TEST, "(");
+                self.expression(body, PR_TEST + 1);
+                self.push(" if ");
+                self.expression(test, PR_TEST + 1);
+                self.push(" else ");
+                self.expression(orelse, PR_TEST);
+                self.push_if(level > PR_TEST, ")");
+            }
+            ast::Expression::NamedExpression { left, right } => {
+                self.push_if(level > PR_TUPLE, "(");
+                self.expression(left, PR_ATOM);
+                self.push(" := ");
+                self.expression(right, PR_ATOM);
+                self.push_if(level > PR_TUPLE, ")");
+            }
+            ast::Expression::True => self.push("True"),
+            ast::Expression::False => self.push("False"),
+            ast::Expression::None => self.push("None"),
+            ast::Expression::Ellipsis => self.push("..."),
+        }
+    }
+
+    fn expressions(&mut self, expressions: &[ast::Expression]) {
+        for (i, expression) in expressions.iter().enumerate() {
+            self.push_if(i > 0, ", ");
+            self.expression(expression, PR_TEST);
+        }
+    }
+
+    /// Slice bounds are `None` when left out.
+    fn optional(&mut self, expression: &ast::Expression) {
+        if *expression != ast::Expression::None {
+            self.expression(expression, PR_TEST);
+        }
+    }
+
+    fn keyword(&mut self, keyword: &ast::Keyword) {
+        match &keyword.name {
+            Some(name) => {
+                self.push(name);
+                self.push("=");
+            }
+            None => self.push("**"),
+        }
+        self.expression(&keyword.value, PR_TEST);
+    }
+
+    fn comprehension_generators(&mut self, generators: &[ast::Comprehension]) {
+        for generator in generators {
+            self.push(" for ");
+            self.expression(&generator.target, PR_TUPLE);
+            self.push(" in ");
+            self.expression(&generator.iter, PR_TEST + 1);
+            for if_expr in &generator.ifs {
+                self.push(" if ");
+                self.expression(if_expr, PR_TEST + 1);
+            }
+        }
+    }
+
+    fn parameters(&mut self, args: &ast::Parameters) {
+        let mut first = true;
+        let positional_count = args.posonlyargs.len() + args.args.len();
+        let defaults_start = positional_count - args.defaults.len();
+        for (i, arg) in args.posonlyargs.iter().chain(&args.args).enumerate() {
+            self.push_if(!first, ", ");
+            first = false;
+            self.push(&arg.arg);
+            if i >= defaults_start {
+                self.push("=");
+                self.expression(&args.defaults[i - defaults_start], PR_TEST);
+            }
+            if i + 1 == args.posonlyargs.len() {
+                self.push(", /");
+            }
+        }
+        match &args.vararg {
+            ast::Varargs::Named(arg) => {
+                self.push_if(!first, ", ");
+                first = false;
+                self.push("*");
+                self.push(&arg.arg);
+            }
+            ast::Varargs::Unnamed => {
+                self.push_if(!first, ", ");
+                first = false;
+                self.push("*");
+            }
+            ast::Varargs::None => {}
+        }
+        for (arg, default) in args.kwonlyargs.iter().zip(args.kw_defaults.iter()) {
+            self.push_if(!first, ", ");
+            first = false;
+            self.push(&arg.arg);
+            if let Some(default) = default {
+                self.push("=");
+                self.expression(default, PR_TEST);
+            }
+        }
+        if let ast::Varargs::Named(arg) = &args.kwarg {
+            self.push_if(!first, ", ");
+            self.push("**");
+            self.push(&arg.arg);
+        }
+    }
+
+    fn number(&mut self, number: &ast::Number) {
+        match number {
+            ast::Number::Integer { value } => self.push(&value.to_string()),
+            ast::Number::Float { value } => self.push(&float_repr(*value)),
+            ast::Number::Complex { real, imag } => {
+                if *real == 0.0 {
+                    self.push(&float_repr(*imag));
+                    self.push("j");
+                } else {
+                    self.push("(");
+                    self.push(&float_repr(*real));
+                    self.push("+");
+                    self.push(&float_repr(*imag));
+                    self.push("j)");
+                }
+            }
+        }
+    }
+
+    fn string_group(&mut self, group: &ast::StringGroup) {
+        match group {
+            ast::StringGroup::Constant { value } => self.push(&quote(value)),
+            _ => {
+                let mut body = String::new();
+                fstring_body(group, &mut body);
+                self.push("f");
+                self.push(&quote(&body));
+            }
+        }
+    }
+}
+
+fn fstring_body(group: &ast::StringGroup, body: &mut String) {
+    match group {
+        ast::StringGroup::Constant { value } => {
+            body.push_str(&value.replace("{", "{{").replace("}", "}}"));
+        }
+        ast::StringGroup::FormattedValue {
+            value,
+            conversion,
+            spec,
+        } => {
+            let mut unparser = Unparser {
+                source: String::new(),
+            };
+            unparser.expression(value, PR_TEST + 1);
+            body.push('{');
+            // A space keeps a leading brace from being read as an escaped one
+            if unparser.source.starts_with('{') {
+                body.push(' ');
+            }
+            body.push_str(&unparser.source);
+            match conversion {
+                Some(ast::ConversionFlag::Str) => body.push_str("!s"),
+                Some(ast::ConversionFlag::Repr) => body.push_str("!r"),
+                Some(ast::ConversionFlag::Ascii) => body.push_str("!a"),
+                None => {}
+            }
+            if let Some(spec) = spec {
+                body.push(':');
+                fstring_body(spec, body);
+            }
+            body.push('}');
+        }
+        ast::StringGroup::Joined { values } => {
+            for value in values {
+                fstring_body(value, body);
+            }
+        }
+    }
+}
+
+fn is_empty(args: &ast::Parameters) -> bool {
+    args.posonlyargs.is_empty()
+        && args.args.is_empty()
+        && args.kwonlyargs.is_empty()
+        && match (&args.vararg, &args.kwarg) {
+            (ast::Varargs::None, ast::Varargs::None) => true,
+            _ => false,
+        }
+}
+
+fn binary_operator(op: &ast::Operator) -> (&'static str, u8) {
+    match op {
+        ast::Operator::Add => (" + ", PR_ARITH),
+        ast::Operator::Sub => (" - ", PR_ARITH),
+        ast::Operator::Mult => (" * ", PR_TERM),
+        ast::Operator::MatMult => (" @ ", PR_TERM),
+        ast::Operator::Div => (" / ", PR_TERM),
+        ast::Operator::Mod => (" % ", PR_TERM),
+        ast::Operator::FloorDiv => (" // ", PR_TERM),
+        ast::Operator::Pow => (" ** ", PR_POWER),
+        ast::Operator::LShift => (" << ", PR_SHIFT),
+        ast::Operator::RShift => (" >> ", PR_SHIFT),
+        ast::Operator::BitOr => (" | ", PR_BOR),
+        ast::Operator::BitXor => (" ^ ", PR_BXOR),
+        ast::Operator::BitAnd => (" & ", PR_BAND),
+    }
+}
+
+fn comparison(op: &ast::Comparison) -> &'static str {
+    match op {
+        ast::Comparison::Equal => " == ",
+        ast::Comparison::NotEqual => " != ",
+        ast::Comparison::Less => " < ",
+        ast::Comparison::LessOrEqual => " <= ",
+        ast::Comparison::Greater => " > ",
+        ast::Comparison::GreaterOrEqual => " >= ",
+        ast::Comparison::In => " in ",
+        ast::Comparison::NotIn => " not in ",
+        ast::Comparison::Is => " is ",
+        ast::Comparison::IsNot => " is not ",
+    }
+}
+
+/// Like `repr(float)`; infinity is written as an overflowing literal.
+fn float_repr(value: f64) -> String {
+    if value.is_infinite() {
+        "1e309".to_string()
+    } else if value.fract() == 0.0 && value.abs() < 1e16 {
+        format!("{:.1}", value)
+    } else {
+        let repr = format!("{:?}", value);
+        match repr.find('e') {
+            Some(pos) if !repr[pos + 1..].starts_with('-') => {
+                format!("{}e+{}", &repr[..pos], &repr[pos + 1..])
+            }
+            _ => repr,
+        }
+    }
+}
+
+/// Like `repr(str)`: single quotes unless the string contains only single quotes.
+fn quote(value: &str) -> String {
+    quote_chars(value.chars(), false)
+}
+
+/// Like `repr(bytes)`, without the `b` prefix.
+fn quote_bytes(value: &[u8]) -> String {
+    quote_chars(value.iter().map(|b| *b as char), true)
+}
+
+fn quote_chars<I: Iterator<Item = char> + Clone>(chars: I, bytes: bool) -> String {
+    let has_single = chars.clone().any(|c| c == '\'');
+    let has_double = chars.clone().any(|c| c == '"');
+    let quote = if has_single && !has_double { '"' } else { '\'' };
+    let mut repr = String::new();
+    repr.push(quote);
+    for c in chars {
+        match c {
+            '\\' => repr.push_str("\\\\"),
+            '\n' => repr.push_str("\\n"),
+            '\r' => repr.push_str("\\r"),
+            '\t' => repr.push_str("\\t"),
+            c if c == quote => {
+                repr.push('\\');
+                repr.push(c);
+            }
+            c if (c as u32) < 0x20 || (c as u32 >= 0x7f && (bytes || (c as u32) < 0xa0)) => {
+                repr.push_str(&format!("\\x{:02x}", c as u32));
+            }
+            c => repr.push(c),
+        }
+    }
+    repr.push(quote);
+    repr
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unparse_expression;
+    use rustpython_parser::ast;
+    use rustpython_parser::parser;
+
+    fn unparse(source: &str) -> String {
+        let mut program = parser::parse_program(&format!("{}\n", source)).unwrap();
+        match program.statements.pop().unwrap().node {
+            ast::Statement::Expression { expression } => unparse_expression(&expression),
+            statement => panic!("Expected an expression, got {:?}", statement),
+        }
+    }
+
+    #[test]
+    fn test_unparse_annotations() {
+        assert_eq!(unparse("int"), "int");
+        assert_eq!(unparse("Dict[str, List[int]]"), "Dict[str, List[int]]");
+        assert_eq!(
+            unparse("typing.Optional['Node']"),
+            "typing.Optional['Node']"
+        );
+        assert_eq!(unparse("Callable[[int], None]"), "Callable[[int], None]");
+        assert_eq!(unparse("(a or b) and not c"), "(a or b) and not c");
+        assert_eq!(unparse("-(1 + 2) * 3 ** -x"), "-(1 + 2) * 3 ** (-x)");
+        assert_eq!(unparse("a[1:2, ::3]"), "a[1:2, ::3]");
+        assert_eq!(
+            unparse("f(x, *args, key=1, **kwargs)"),
+            "f(x, *args, key=1, **kwargs)"
+        );
+        assert_eq!(unparse("lambda x, y=1: (x, y)"), "lambda x, y=1: (x, y)");
+        assert_eq!(
+            unparse("lambda a, b=1, /, c=2, *, d: a"),
+            "lambda a, b=1, /, c=2, *, d: a"
+        );
+        assert_eq!(unparse("f(y := g(x), z)"), "f((y := g(x)), z)");
+        assert_eq!(unparse("[x for x in y if x]"), "[x for x in y if x]");
+        assert_eq!(unparse("{'a': 1.5, **d}"), "{'a': 1.5, **d}");
+    }
+}
@@ -0,0 +1,15 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+
+use rustpython_compiler::compile;
+
+fuzz_target!(|data: &[u8]| {
+    for mode in &[
+        compile::Mode::Exec,
+        compile::Mode::Eval,
+        compile::Mode::Single,
+    ] {
+        let _ = compile::compile_bytes(data, mode, "<fuzz>".to_string());
+    }
+});
@@ -78,6 +78,14 @@ pub enum Statement {
         op: Operator,
         value: Box<Expression>,
     },
+    AnnAssign {
+        target: Box<Expression>,
+        annotation: Box<Expression>,
+        value: Option<Expression>,
+        /// Whether the target is a name outside of parentheses, whose
+        /// annotation is stored in `__annotations__`.
+        simple: bool,
+    },
     Expression {
         expression: Expression,
     },
@@ -237,6 +245,11 @@ pub enum Expression {
         body: Box<Expression>,
         orelse: Box<Expression>,
     },
+    /// An assignment expression, such as `(x := 1)`.
+    NamedExpression {
+        left: Box<Expression>,
+        right: Box<Expression>,
+    },
     True,
     False,
     None,
@@ -283,6 +296,7 @@ impl Expression {
             Identifier { .. } => "named expression",
             Lambda { .. } => "lambda",
             IfExpression { .. } => "conditional expression",
+            NamedExpression { .. } => "named expression",
             True | False | None => "keyword",
             Ellipsis => "ellipsis",
         }
@@ -295,6 +309,7 @@ impl Expression {
  */
 #[derive(Debug, PartialEq, Default)]
 pub struct Parameters {
+    pub posonlyargs: Vec<Parameter>,
     pub args: Vec<Parameter>,
     pub kwonlyargs: Vec<Parameter>,
     pub vararg: Varargs, // Optionally we handle optionally named '*args' or '*'
@@ -408,7 +423,7 @@ pub enum StringGroup {
     FormattedValue {
         value: Box<Expression>,
         conversion: Option<ConversionFlag>,
-        spec: String,
+        spec: Option<Box<StringGroup>>,
     },
     Joined {
         values: Vec<StringGroup>,
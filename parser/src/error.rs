@@ -3,44 +3,65 @@
 extern crate lalrpop_util;
 use self::lalrpop_util::ParseError as InnerError;
 
-use crate::lexer::{LexicalError, Location};
+use crate::lexer::{LexicalError, LexicalErrorType, Location};
 use crate::token::Tok;
 
 use std::error::Error;
 use std::fmt;
 
-// A token of type `Tok` was observed, with a span given by the two Location values
-type TokSpan = (Location, Tok, Location);
-
 /// Represents an error during parsing
 #[derive(Debug, PartialEq)]
-pub enum ParseError {
+pub struct ParseError {
+    pub error: ParseErrorType,
+    pub location: Location,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParseErrorType {
     /// Parser encountered an unexpected end of input
-    EOF(Option<Location>),
+    EOF,
     /// Parser encountered an extra token
-    ExtraToken(TokSpan),
+    ExtraToken(Tok),
     /// Parser encountered an invalid token
-    InvalidToken(Location),
+    InvalidToken,
     /// Parser encountered an unexpected token
-    UnrecognizedToken(TokSpan, Vec<String>),
-    /// Maps to `User` type from `lalrpop-util`
-    Other,
+    UnrecognizedToken(Tok, Vec<String>),
+    /// The lexer rejected the source
+    Lexical(LexicalErrorType),
 }
 
-/// Convert `lalrpop_util::ParseError` to our internal type
-impl From<InnerError<Location, Tok, LexicalError>> for ParseError {
-    fn from(err: InnerError<Location, Tok, LexicalError>) -> Self {
+impl ParseError {
+    /// Convert `lalrpop_util::ParseError` to our internal type. Lalrpop
+    /// doesn't know where the input ended, so an unexpected end of file is
+    /// reported at `eof_location`.
+    pub(crate) fn from_lalrpop(
+        err: InnerError<Location, Tok, LexicalError>,
+        eof_location: Location,
+    ) -> Self {
         match err {
-            // TODO: Are there cases where this isn't an EOF?
-            InnerError::InvalidToken { location } => ParseError::EOF(Some(location)),
-            InnerError::ExtraToken { token } => ParseError::ExtraToken(token),
-            // Inner field is a unit-like enum `LexicalError::StringError` with no useful info
-            InnerError::User { .. } => ParseError::Other,
+            InnerError::InvalidToken { location } => ParseError {
+                error: ParseErrorType::InvalidToken,
+                location,
+            },
+            InnerError::ExtraToken { token } => ParseError {
+                error: ParseErrorType::ExtraToken(token.1),
+                location: token.0,
+            },
+            InnerError::User { error } => ParseError {
+                error: ParseErrorType::Lexical(error.error),
+                location: error.location,
+            },
             InnerError::UnrecognizedToken { token, expected } => {
                 match token {
-                    Some(tok) => ParseError::UnrecognizedToken(tok, expected),
+                    Some(tok) => ParseError {
+                        error: ParseErrorType::UnrecognizedToken(tok.1, expected),
+                        location: tok.0,
+                    },
                     // EOF was observed when it was unexpected
-                    None => ParseError::EOF(None),
+                    None => ParseError {
+                        error: ParseErrorType::EOF,
+                        location: eof_location,
+                    },
                 }
             }
         }
@@ -49,25 +70,85 @@ impl From<InnerError<Location, Tok, LexicalError>> for ParseError {
 
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match *self {
-            ParseError::EOF(ref location) => {
-                if let Some(l) = location {
-                    write!(f, "Got unexpected EOF at: {:?}", l)
-                } else {
-                    write!(f, "Got unexpected EOF")
-                }
+        write!(f, "{} at {}", self.error, self.location)
+    }
+}
+
+impl ParseErrorType {
+    /// Whether this error should be raised as an `IndentationError`.
+    pub fn is_indentation_error(&self) -> bool {
+        match self {
+            ParseErrorType::Lexical(LexicalErrorType::IndentationError) => true,
+            ParseErrorType::UnrecognizedToken(Tok::Indent, _)
+            | ParseErrorType::UnrecognizedToken(Tok::Dedent, _) => true,
+            ParseErrorType::UnrecognizedToken(_, expected) => expects_indent(expected),
+            _ => false,
+        }
+    }
+
+    /// Whether this error should be raised as a `TabError`.
+    pub fn is_tab_error(&self) -> bool {
+        match self {
+            ParseErrorType::Lexical(LexicalErrorType::TabError) => true,
+            _ => false,
+        }
+    }
+}
+
+fn expects_indent(expected: &[String]) -> bool {
+    expected.len() == 1 && expected[0] == "indent"
+}
+
+impl fmt::Display for ParseErrorType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorType::EOF => write!(f, "unexpected EOF while parsing"),
+            ParseErrorType::ExtraToken(ref tok) => write!(f, "Got extraneous token: {:?}", tok),
+            ParseErrorType::InvalidToken => write!(f, "Got invalid token"),
+            ParseErrorType::UnrecognizedToken(Tok::Indent, _) => write!(f, "unexpected indent"),
+            ParseErrorType::UnrecognizedToken(Tok::Dedent, _) => write!(f, "unexpected unindent"),
+            ParseErrorType::UnrecognizedToken(_, ref expected) if expects_indent(expected) => {
+                write!(f, "expected an indented block")
             }
-            ParseError::ExtraToken(ref t_span) => {
-                write!(f, "Got extraneous token: {:?} at: {:?}", t_span.1, t_span.0)
+            ParseErrorType::UnrecognizedToken(..) => write!(f, "invalid syntax"),
+            ParseErrorType::Lexical(ref error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// Represents an error in the expressions of an f-string
+#[derive(Debug, PartialEq)]
+pub enum FStringErrorType {
+    UnclosedLbrace,
+    UnopenedRbrace,
+    InvalidExpression(Box<ParseErrorType>),
+    InvalidConversionFlag,
+    EmptyExpression,
+    MismatchedDelimiter,
+    ExpressionNestedTooDeeply,
+    ExpressionCannotInclude(char),
+}
+
+impl fmt::Display for FStringErrorType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FStringErrorType::UnclosedLbrace => write!(f, "f-string: expecting '}}'"),
+            FStringErrorType::UnopenedRbrace => write!(f, "f-string: single '}}' is not allowed"),
+            FStringErrorType::InvalidExpression(error) => write!(f, "f-string: {}", error),
+            FStringErrorType::InvalidConversionFlag => write!(
+                f,
+                "f-string: invalid conversion character: expected 's', 'r', or 'a'"
+            ),
+            FStringErrorType::EmptyExpression => {
+                write!(f, "f-string: empty expression not allowed")
             }
-            ParseError::InvalidToken(ref location) => {
-                write!(f, "Got invalid token at: {:?}", location)
+            FStringErrorType::MismatchedDelimiter => write!(f, "f-string: mismatched parenthesis"),
+            FStringErrorType::ExpressionNestedTooDeeply => {
+                write!(f, "f-string: expressions nested too deeply")
             }
-            ParseError::UnrecognizedToken(ref t_span, _) => {
-                write!(f, "Got unexpected token: {:?} at {:?}", t_span.1, t_span.0)
+            FStringErrorType::ExpressionCannotInclude(c) => {
+                write!(f, "f-string expression part cannot include '{}'", c)
             }
-            // This is user defined, it probably means a more useful error should have been given upstream.
-            ParseError::Other => write!(f, "Got unsupported token(s)"),
         }
     }
 }
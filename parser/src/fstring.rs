@@ -2,37 +2,13 @@ use std::iter;
 use std::mem;
 use std::str;
 
-use lalrpop_util::ParseError as LalrpopError;
-
 use crate::ast::{ConversionFlag, StringGroup};
-use crate::lexer::{LexicalError, LexicalErrorType, Location, Tok};
+use crate::error::FStringErrorType;
 use crate::parser::parse_expression;
 
-use self::FStringError::*;
+use self::FStringErrorType::*;
 use self::StringGroup::*;
 
-// TODO: consolidate these with ParseError
-#[derive(Debug, PartialEq)]
-pub enum FStringError {
-    UnclosedLbrace,
-    UnopenedRbrace,
-    InvalidExpression,
-    InvalidConversionFlag,
-    EmptyExpression,
-    MismatchedDelimiter,
-}
-
-impl From<FStringError> for LalrpopError<Location, Tok, LexicalError> {
-    fn from(_err: FStringError) -> Self {
-        lalrpop_util::ParseError::User {
-            error: LexicalError {
-                error: LexicalErrorType::StringError,
-                location: Default::default(),
-            },
-        }
-    }
-}
-
 struct FStringParser<'a> {
     chars: iter::Peekable<str::Chars<'a>>,
 }
@@ -44,37 +20,64 @@ impl<'a> FStringParser<'a> {
         }
     }
 
-    fn parse_formatted_value(&mut self) -> Result<StringGroup, FStringError> {
+    /// Parse a replacement field after its opening brace, up to and
+    /// including the closing brace. A self-documenting field (`{x=}`)
+    /// expands into its source text followed by the formatted value.
+    fn parse_formatted_value(&mut self, nested: u8) -> Result<Vec<StringGroup>, FStringErrorType> {
+        if nested > 1 {
+            return Err(ExpressionNestedTooDeeply);
+        }
+
         let mut expression = String::new();
-        let mut spec = String::new();
+        let mut spec = None;
         let mut delims = Vec::new();
         let mut conversion = None;
+        let mut debug_text = None;
 
         while let Some(ch) = self.chars.next() {
             match ch {
+                // `!=`, `==`, `<=` and `>=` are operators, not field syntax
+                '!' | '=' | '<' | '>' if self.chars.peek() == Some(&'=') => {
+                    expression.push(ch);
+                    expression.push('=');
+                    self.chars.next();
+                }
                 '!' if delims.is_empty() => {
                     conversion = Some(match self.chars.next() {
                         Some('s') => ConversionFlag::Str,
                         Some('a') => ConversionFlag::Ascii,
                         Some('r') => ConversionFlag::Repr,
-                        Some(_) => {
-                            return Err(InvalidConversionFlag);
-                        }
-                        None => {
-                            break;
-                        }
-                    })
+                        Some(_) => return Err(InvalidConversionFlag),
+                        None => return Err(UnclosedLbrace),
+                    });
+                    match self.chars.peek() {
+                        Some('}') | Some(':') => {}
+                        Some(_) => return Err(InvalidConversionFlag),
+                        None => return Err(UnclosedLbrace),
+                    }
                 }
-                ':' if delims.is_empty() => {
+                '=' if delims.is_empty() && debug_text.is_none() => {
+                    let mut trailing = String::new();
                     while let Some(&next) = self.chars.peek() {
-                        if next != '}' {
-                            spec.push(next);
-                            self.chars.next();
-                        } else {
+                        if !next.is_whitespace() {
                             break;
                         }
+                        trailing.push(next);
+                        self.chars.next();
+                    }
+                    match self.chars.peek() {
+                        Some('}') | Some('!') | Some(':') => {
+                            debug_text = Some(format!("{}={}", expression, trailing));
+                        }
+                        _ => {
+                            expression.push('=');
+                            expression.push_str(&trailing);
+                        }
                     }
                 }
+                ':' if delims.is_empty() => {
+                    spec = Some(Box::new(self.parse_spec(nested)?));
+                }
                 '(' | '{' | '[' => {
                     expression.push(ch);
                     delims.push(ch);
@@ -98,26 +101,42 @@ impl<'a> FStringParser<'a> {
                     expression.push(ch);
                 }
                 '}' => {
-                    if expression.is_empty() {
+                    if expression.trim().is_empty() {
                         return Err(EmptyExpression);
                     }
-                    return Ok(FormattedValue {
-                        value: Box::new(
-                            parse_expression(expression.trim()).map_err(|_| InvalidExpression)?,
-                        ),
+                    // Parenthesize so that the expression may span lines
+                    let value = parse_expression(&format!("({})", expression))
+                        .map_err(|err| InvalidExpression(Box::new(err.error)))?;
+
+                    let mut values = vec![];
+                    if let Some(text) = debug_text {
+                        values.push(Constant { value: text });
+                        if conversion.is_none() && spec.is_none() {
+                            conversion = Some(ConversionFlag::Repr);
+                        }
+                    }
+                    values.push(FormattedValue {
+                        value: Box::new(value),
                         conversion,
                         spec,
                     });
+                    return Ok(values);
                 }
                 '"' | '\'' => {
                     expression.push(ch);
-                    while let Some(next) = self.chars.next() {
-                        expression.push(next);
-                        if next == ch {
-                            break;
+                    loop {
+                        match self.chars.next() {
+                            Some(next) => {
+                                expression.push(next);
+                                if next == ch {
+                                    break;
+                                }
+                            }
+                            None => return Err(UnclosedLbrace),
                         }
                     }
                 }
+                '#' => return Err(ExpressionCannotInclude('#')),
                 _ => {
                     expression.push(ch);
                 }
@@ -127,7 +146,39 @@ impl<'a> FStringParser<'a> {
         Err(UnclosedLbrace)
     }
 
-    fn parse(mut self) -> Result<StringGroup, FStringError> {
+    /// Parse a format spec up to (but not including) the brace closing its
+    /// field. The spec may itself contain replacement fields.
+    fn parse_spec(&mut self, nested: u8) -> Result<StringGroup, FStringErrorType> {
+        let mut content = String::new();
+        let mut values = vec![];
+
+        while let Some(&ch) = self.chars.peek() {
+            match ch {
+                '{' => {
+                    self.chars.next();
+                    if !content.is_empty() {
+                        values.push(Constant {
+                            value: mem::replace(&mut content, String::new()),
+                        });
+                    }
+                    values.extend(self.parse_formatted_value(nested + 1)?);
+                }
+                '}' => break,
+                _ => {
+                    self.chars.next();
+                    content.push(ch);
+                }
+            }
+        }
+
+        if !content.is_empty() {
+            values.push(Constant { value: content })
+        }
+
+        Ok(join(values))
+    }
+
+    fn parse(mut self) -> Result<StringGroup, FStringErrorType> {
         let mut content = String::new();
         let mut values = vec![];
 
@@ -144,7 +195,7 @@ impl<'a> FStringParser<'a> {
                             });
                         }
 
-                        values.push(self.parse_formatted_value()?);
+                        values.extend(self.parse_formatted_value(0)?);
                     }
                 }
                 '}' => {
@@ -165,17 +216,21 @@ impl<'a> FStringParser<'a> {
             values.push(Constant { value: content })
         }
 
-        Ok(match values.len() {
-            0 => Constant {
-                value: String::new(),
-            },
-            1 => values.into_iter().next().unwrap(),
-            _ => Joined { values },
-        })
+        Ok(join(values))
+    }
+}
+
+fn join(values: Vec<StringGroup>) -> StringGroup {
+    match values.len() {
+        0 => Constant {
+            value: String::new(),
+        },
+        1 => values.into_iter().next().unwrap(),
+        _ => Joined { values },
     }
 }
 
-pub fn parse_fstring(source: &str) -> Result<StringGroup, FStringError> {
+pub fn parse_fstring(source: &str) -> Result<StringGroup, FStringErrorType> {
     FStringParser::new(source).parse()
 }
 
@@ -203,12 +258,12 @@ mod tests {
                     FormattedValue {
                         value: Box::new(mk_ident("a")),
                         conversion: None,
-                        spec: String::new(),
+                        spec: None,
                     },
                     FormattedValue {
                         value: Box::new(mk_ident("b")),
                         conversion: None,
-                        spec: String::new(),
+                        spec: None,
                     },
                     Constant {
                         value: "{foo}".to_owned()
@@ -218,6 +273,53 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_fstring_nested_spec() {
+        let source = String::from("{foo:{spec}}");
+        let parse_ast = parse_fstring(&source).unwrap();
+
+        assert_eq!(
+            parse_ast,
+            FormattedValue {
+                value: Box::new(mk_ident("foo")),
+                conversion: None,
+                spec: Some(Box::new(FormattedValue {
+                    value: Box::new(mk_ident("spec")),
+                    conversion: None,
+                    spec: None,
+                })),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_fstring_self_documenting() {
+        let source = String::from("{a = }");
+        let parse_ast = parse_fstring(&source).unwrap();
+
+        assert_eq!(
+            parse_ast,
+            Joined {
+                values: vec![
+                    Constant {
+                        value: "a = ".to_owned()
+                    },
+                    FormattedValue {
+                        value: Box::new(mk_ident("a")),
+                        conversion: Some(ConversionFlag::Repr),
+                        spec: None,
+                    },
+                ]
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_fstring_not_equals() {
+        let source = String::from("{a != b}");
+        assert!(parse_fstring(&source).is_ok());
+    }
+
     #[test]
     fn test_parse_empty_fstring() {
         assert_eq!(
@@ -232,6 +334,15 @@ mod tests {
     fn test_parse_invalid_fstring() {
         assert_eq!(parse_fstring("{"), Err(UnclosedLbrace));
         assert_eq!(parse_fstring("}"), Err(UnopenedRbrace));
-        assert_eq!(parse_fstring("{class}"), Err(InvalidExpression));
+        assert_eq!(parse_fstring("{}"), Err(EmptyExpression));
+        assert_eq!(parse_fstring("{a!x}"), Err(InvalidConversionFlag));
+        assert_eq!(parse_fstring("{a!r x}"), Err(InvalidConversionFlag));
+        assert_eq!(parse_fstring("{a:{b:{c}}}"), Err(ExpressionNestedTooDeeply));
+        assert_eq!(parse_fstring("{a # b}"), Err(ExpressionCannotInclude('#')));
+        assert_eq!(parse_fstring("{a)}"), Err(MismatchedDelimiter));
+        match parse_fstring("{class}") {
+            Err(InvalidExpression(_)) => {}
+            other => panic!("Expected an invalid expression, got {:?}", other),
+        }
     }
 }
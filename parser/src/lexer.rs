@@ -5,11 +5,13 @@ extern crate unic_emoji_char;
 extern crate unicode_xid;
 
 pub use super::token::Tok;
+use crate::error::FStringErrorType;
 use num_bigint::BigInt;
 use num_traits::Num;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt;
 use std::str::FromStr;
 use unic_emoji_char::is_emoji_presentation;
 use unicode_xid::UnicodeXID;
@@ -59,27 +61,90 @@ pub struct Lexer<T: Iterator<Item = char>> {
     keywords: HashMap<String, Tok>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct LexicalError {
     pub error: LexicalErrorType,
     pub location: Location,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum LexicalErrorType {
     StringError,
     UnicodeError,
     NestingError,
-    UnrecognizedToken { tok: char },
+    /// A dedent that doesn't match any outer indentation level.
+    IndentationError,
+    /// Indentation that mixes tabs and spaces ambiguously.
+    TabError,
+    /// A string literal without its closing quote.
+    UnterminatedString,
+    UnrecognizedToken {
+        tok: char,
+    },
+    FStringError(FStringErrorType),
+    /// A parameter without a default follows one with a default.
+    DefaultArgumentError,
+    /// A positional argument follows a keyword argument in a call.
+    PositionalArgumentError,
+    /// An iterable unpacking follows a dict unpacking in a call.
+    UnpackedArgumentError,
+    /// An annotated assignment to a tuple or a list, naming which of the two.
+    MultipleAnnotationTargets(&'static str),
+    /// An annotated assignment to something other than a name, attribute or subscript.
+    IllegalAnnotationTarget,
     OtherError(String),
 }
 
+impl fmt::Display for LexicalErrorType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LexicalErrorType::StringError => write!(f, "Got unexpected string"),
+            LexicalErrorType::UnicodeError => write!(f, "Got unexpected unicode"),
+            LexicalErrorType::NestingError => write!(f, "Got unexpected nesting"),
+            LexicalErrorType::IndentationError => {
+                write!(f, "unindent does not match any outer indentation level")
+            }
+            LexicalErrorType::TabError => {
+                write!(f, "inconsistent use of tabs and spaces in indentation")
+            }
+            LexicalErrorType::UnterminatedString => write!(f, "unterminated string literal"),
+            LexicalErrorType::UnrecognizedToken { tok } => {
+                write!(f, "Got unexpected token {}", tok)
+            }
+            LexicalErrorType::FStringError(error) => write!(f, "{}", error),
+            LexicalErrorType::DefaultArgumentError => {
+                write!(f, "non-default argument follows default argument")
+            }
+            LexicalErrorType::PositionalArgumentError => {
+                write!(f, "positional argument follows keyword argument")
+            }
+            LexicalErrorType::UnpackedArgumentError => write!(
+                f,
+                "iterable argument unpacking follows keyword argument unpacking"
+            ),
+            LexicalErrorType::MultipleAnnotationTargets(kind) => {
+                write!(f, "only single target (not {}) can be annotated", kind)
+            }
+            LexicalErrorType::IllegalAnnotationTarget => {
+                write!(f, "illegal target for annotation")
+            }
+            LexicalErrorType::OtherError(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct Location {
     row: usize,
     column: usize,
 }
 
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {} column {}", self.row, self.column)
+    }
+}
+
 impl Location {
     pub fn new(row: usize, column: usize) -> Self {
         Location { row, column }
@@ -316,7 +381,7 @@ where
 
             // Check if we have a string:
             if self.chr0 == Some('"') || self.chr0 == Some('\'') {
-                return self.lex_string(saw_b, saw_r, saw_u, saw_f);
+                return self.lex_string(start_pos, saw_b, saw_r, saw_u, saw_f);
             }
         }
 
@@ -359,17 +424,15 @@ where
     }
 
     fn lex_number_radix(&mut self, start_pos: Location, radix: u32) -> LexResult {
-        let mut value_text = String::new();
-
-        loop {
-            if let Some(c) = self.take_number(radix) {
-                value_text.push(c);
-            } else if self.chr0 == Some('_') {
-                self.next_char();
-            } else {
-                break;
-            }
+        // An underscore may separate the prefix from the digits, as in 0x_ff
+        if self.chr0 == Some('_') {
+            self.next_char();
+        }
+        let value_text = self.radix_run(radix);
+        if value_text.is_empty() {
+            return Err(self.invalid_number(radix));
         }
+        self.check_number_end(radix)?;
 
         let end_pos = self.get_pos();
         let value = BigInt::from_str_radix(&value_text, radix).map_err(|e| LexicalError {
@@ -381,26 +444,21 @@ where
 
     fn lex_normal_number(&mut self) -> LexResult {
         let start_pos = self.get_pos();
-
-        let mut value_text = String::new();
+        let starts_with_zero = self.chr0 == Some('0');
 
         // Normal number:
-        while let Some(c) = self.take_number(10) {
-            value_text.push(c);
-        }
+        let mut value_text = self.radix_run(10);
 
         // If float:
-        if self.chr0 == Some('.') || self.chr0 == Some('e') {
+        if self.chr0 == Some('.') || self.at_exponent() {
             // Take '.':
             if self.chr0 == Some('.') {
                 value_text.push(self.next_char().unwrap());
-                while let Some(c) = self.take_number(10) {
-                    value_text.push(c);
-                }
+                value_text.push_str(&self.radix_run(10));
             }
 
             // 1e6 for example:
-            if self.chr0 == Some('e') {
+            if self.at_exponent() {
                 value_text.push(self.next_char().unwrap());
 
                 // Optional +/-
@@ -408,15 +466,19 @@ where
                     value_text.push(self.next_char().unwrap());
                 }
 
-                while let Some(c) = self.take_number(10) {
-                    value_text.push(c);
+                let exponent = self.radix_run(10);
+                if exponent.is_empty() {
+                    return Err(self.invalid_number(10));
                 }
+                value_text.push_str(&exponent);
             }
 
-            let value = f64::from_str(&value_text).unwrap();
+            // Out of range exponents give infinity or zero, just like CPython.
+            let value = f64::from_str(&value_text).map_err(|_| self.invalid_number(10))?;
             // Parse trailing 'j':
-            if self.chr0 == Some('j') {
+            if self.at_imaginary() {
                 self.next_char();
+                self.check_number_end(10)?;
                 let end_pos = self.get_pos();
                 Ok((
                     start_pos,
@@ -427,24 +489,93 @@ where
                     end_pos,
                 ))
             } else {
+                self.check_number_end(10)?;
                 let end_pos = self.get_pos();
                 Ok((start_pos, Tok::Float { value }, end_pos))
             }
         } else {
             // Parse trailing 'j':
-            if self.chr0 == Some('j') {
+            if self.at_imaginary() {
                 self.next_char();
+                self.check_number_end(10)?;
                 let end_pos = self.get_pos();
-                let imag = f64::from_str(&value_text).unwrap();
+                let imag = f64::from_str(&value_text).map_err(|_| self.invalid_number(10))?;
                 Ok((start_pos, Tok::Complex { real: 0.0, imag }, end_pos))
             } else {
+                self.check_number_end(10)?;
+                if starts_with_zero && value_text.chars().any(|c| c != '0') {
+                    return Err(LexicalError {
+                        error: LexicalErrorType::OtherError(
+                            "leading zeros in decimal integer literals are not permitted; \
+                             use an 0o prefix for octal integers"
+                                .to_string(),
+                        ),
+                        location: start_pos,
+                    });
+                }
                 let end_pos = self.get_pos();
-                let value = value_text.parse::<BigInt>().unwrap();
+                let value = value_text
+                    .parse::<BigInt>()
+                    .map_err(|_| self.invalid_number(10))?;
                 Ok((start_pos, Tok::Int { value }, end_pos))
             }
         }
     }
 
+    /// Consume a run of digits in the given radix, dropping the single
+    /// underscores that may group them.
+    fn radix_run(&mut self, radix: u32) -> String {
+        let mut value_text = String::new();
+        loop {
+            if let Some(c) = self.take_number(radix) {
+                value_text.push(c);
+            } else if self.chr0 == Some('_') && is_digit_of_radix(self.chr1, radix) {
+                self.next_char();
+            } else {
+                break;
+            }
+        }
+        value_text
+    }
+
+    fn at_exponent(&self) -> bool {
+        match self.chr0 {
+            Some('e') | Some('E') => match self.chr1 {
+                Some('+') | Some('-') | Some('0'..='9') => true,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn at_imaginary(&self) -> bool {
+        self.chr0 == Some('j') || self.chr0 == Some('J')
+    }
+
+    /// Reject digits and underscores directly following a number literal,
+    /// such as in `0b12` or `1__0`.
+    fn check_number_end(&self, radix: u32) -> Result<(), LexicalError> {
+        match self.chr0 {
+            Some(c @ '0'..='9') if radix < 10 => Err(LexicalError {
+                error: LexicalErrorType::OtherError(format!(
+                    "invalid digit '{}' in {} literal",
+                    c,
+                    radix_name(radix)
+                )),
+                location: self.get_pos(),
+            }),
+            Some('0'..='9') | Some('_') => Err(self.invalid_number(radix)),
+            _ => Ok(()),
+        }
+    }
+
+    fn invalid_number(&self, radix: u32) -> LexicalError {
+        LexicalError {
+            error: LexicalErrorType::OtherError(format!("invalid {} literal", radix_name(radix))),
+            location: self.get_pos(),
+        }
+    }
+
     fn lex_comment(&mut self) {
         // Skip everything until end of line
         self.next_char();
@@ -479,8 +610,10 @@ where
         }
     }
 
+    /// Lex a string literal whose token, including any prefix, starts at `start_pos`.
     fn lex_string(
         &mut self,
+        start_pos: Location,
         is_bytes: bool,
         is_raw: bool,
         _is_unicode: bool,
@@ -488,7 +621,6 @@ where
     ) -> LexResult {
         let quote_char = self.next_char().unwrap();
         let mut string_content = String::new();
-        let start_pos = self.get_pos();
 
         // If the next two characters are also the quote character, then we have a triple-quoted
         // string; consume those two characters and ensure that we require a triple-quote to close
@@ -512,8 +644,8 @@ where
                             string_content.push(c)
                         } else {
                             return Err(LexicalError {
-                                error: LexicalErrorType::StringError,
-                                location: self.get_pos(),
+                                error: LexicalErrorType::UnterminatedString,
+                                location: start_pos,
                             });
                         }
                     } else {
@@ -546,8 +678,8 @@ where
                             }
                             None => {
                                 return Err(LexicalError {
-                                    error: LexicalErrorType::StringError,
-                                    location: self.get_pos(),
+                                    error: LexicalErrorType::UnterminatedString,
+                                    location: start_pos,
                                 });
                             }
                         }
@@ -572,8 +704,8 @@ where
                         if c == '\n' {
                             if !triple_quoted {
                                 return Err(LexicalError {
-                                    error: LexicalErrorType::StringError,
-                                    location: self.get_pos(),
+                                    error: LexicalErrorType::UnterminatedString,
+                                    location: start_pos,
                                 });
                             }
                             self.new_line();
@@ -583,8 +715,8 @@ where
                 }
                 None => {
                     return Err(LexicalError {
-                        error: LexicalErrorType::StringError,
-                        location: self.get_pos(),
+                        error: LexicalErrorType::UnterminatedString,
+                        location: start_pos,
                     });
                 }
             }
@@ -594,7 +726,7 @@ where
         let tok = if is_bytes {
             if string_content.is_ascii() {
                 Tok::Bytes {
-                    value: lex_byte(string_content)?,
+                    value: lex_byte(string_content, start_pos.clone())?,
                 }
             } else {
                 return Err(LexicalError {
@@ -631,27 +763,7 @@ where
     }
 
     fn take_number(&mut self, radix: u32) -> Option<char> {
-        let take_char = match radix {
-            2 => match self.chr0 {
-                Some('0'..='1') => true,
-                _ => false,
-            },
-            8 => match self.chr0 {
-                Some('0'..='7') => true,
-                _ => false,
-            },
-            10 => match self.chr0 {
-                Some('0'..='9') => true,
-                _ => false,
-            },
-            16 => match self.chr0 {
-                Some('0'..='9') | Some('a'..='f') | Some('A'..='F') => true,
-                _ => false,
-            },
-            x => unimplemented!("Radix not implemented: {}", x),
-        };
-
-        if take_char {
+        if is_digit_of_radix(self.chr0, radix) {
             Some(self.next_char().unwrap())
         } else {
             None
@@ -704,9 +816,7 @@ where
                         // This is technically stricter than python3 but spaces before
                         // tabs is even more insane than mixing spaces and tabs.
                         return Err(LexicalError {
-                            error: LexicalErrorType::OtherError(
-                                "Tabs not allowed as part of indentation after spaces".to_string(),
-                            ),
+                            error: LexicalErrorType::TabError,
                             location: self.get_pos(),
                         });
                     }
@@ -777,7 +887,7 @@ where
                                     }
                                     None => {
                                         return Err(LexicalError {
-                                            error: LexicalErrorType::OtherError("inconsistent use of tabs and spaces in indentation".to_string()),
+                                            error: LexicalErrorType::TabError,
                                             location: self.get_pos(),
                                         });
                                     }
@@ -788,11 +898,8 @@ where
                             }
 
                             if indentation_level != *self.indentation_stack.last().unwrap() {
-                                // TODO: handle wrong indentations
                                 return Err(LexicalError {
-                                    error: LexicalErrorType::OtherError(
-                                        "Non matching indentation levels!".to_string(),
-                                    ),
+                                    error: LexicalErrorType::IndentationError,
                                     location: self.get_pos(),
                                 });
                             }
@@ -801,10 +908,7 @@ where
                         }
                         None => {
                             return Err(LexicalError {
-                                error: LexicalErrorType::OtherError(
-                                    "inconsistent use of tabs and spaces in indentation"
-                                        .to_string(),
-                                ),
+                                error: LexicalErrorType::TabError,
                                 location: self.get_pos(),
                             });
                         }
@@ -835,11 +939,9 @@ where
                             self.lex_comment();
                             continue;
                         }
-                        '"' => {
-                            return self.lex_string(false, false, false, false);
-                        }
-                        '\'' => {
-                            return self.lex_string(false, false, false, false);
+                        '"' | '\'' => {
+                            let start_pos = self.get_pos();
+                            return self.lex_string(start_pos, false, false, false, false);
                         }
                         '=' => {
                             let tok_start = self.get_pos();
@@ -1072,7 +1174,16 @@ where
                             return result;
                         }
                         ':' => {
-                            return self.eat_single_char(Tok::Colon);
+                            let tok_start = self.get_pos();
+                            self.next_char();
+                            if let Some('=') = self.chr0 {
+                                self.next_char();
+                                let tok_end = self.get_pos();
+                                return Ok((tok_start, Tok::ColonEqual, tok_end));
+                            } else {
+                                let tok_end = self.get_pos();
+                                return Ok((tok_start, Tok::Colon, tok_end));
+                            }
                         }
                         ';' => {
                             return self.eat_single_char(Tok::Semi);
@@ -1178,10 +1289,11 @@ where
                             continue;
                         }
                         _ => {
+                            let location = self.get_pos();
                             let c = self.next_char();
                             return Err(LexicalError {
                                 error: LexicalErrorType::UnrecognizedToken { tok: c.unwrap() },
-                                location: self.get_pos(),
+                                location,
                             });
                         } // Ignore all the rest..
                     }
@@ -1231,7 +1343,7 @@ where
     }
 }
 
-fn lex_byte(s: String) -> Result<Vec<u8>, LexicalError> {
+fn lex_byte(s: String, location: Location) -> Result<Vec<u8>, LexicalError> {
     let mut res = vec![];
     let mut escape = false; //flag if previous was \
     let mut hex_on = false; // hex mode on or off
@@ -1252,7 +1364,7 @@ fn lex_byte(s: String) -> Result<Vec<u8>, LexicalError> {
             } else {
                 return Err(LexicalError {
                     error: LexicalErrorType::StringError,
-                    location: Default::default(),
+                    location,
                 });
             }
         } else {
@@ -1282,6 +1394,22 @@ fn lex_byte(s: String) -> Result<Vec<u8>, LexicalError> {
     Ok(res)
 }
 
+fn is_digit_of_radix(c: Option<char>, radix: u32) -> bool {
+    match c {
+        Some(c) => c.is_digit(radix),
+        None => false,
+    }
+}
+
+fn radix_name(radix: u32) -> &'static str {
+    match radix {
+        2 => "binary",
+        8 => "octal",
+        16 => "hexadecimal",
+        _ => "decimal",
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{make_tokenizer, NewlineHandler, Tok};
@@ -1359,6 +1487,50 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_numbers_with_underscores() {
+        let source = String::from("1_000_000 0x_FF 0b1_0 1_0.5e1_0 .5j 1E400 1J");
+        let tokens = lex_source(&source);
+        assert_eq!(
+            tokens,
+            vec![
+                Tok::Int {
+                    value: BigInt::from(1_000_000),
+                },
+                Tok::Int {
+                    value: BigInt::from(255),
+                },
+                Tok::Int {
+                    value: BigInt::from(2),
+                },
+                Tok::Float { value: 10.5e10 },
+                Tok::Complex {
+                    real: 0.0,
+                    imag: 0.5,
+                },
+                Tok::Float {
+                    value: std::f64::INFINITY,
+                },
+                Tok::Complex {
+                    real: 0.0,
+                    imag: 1.0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_numbers() {
+        for source in &["0777", "0b2", "0o8", "0x", "0xg", "1__0", "1_", "1.5e+"] {
+            let mut lexer = make_tokenizer(source);
+            assert!(
+                lexer.next().unwrap().is_err(),
+                "{} should not be a valid number",
+                source
+            );
+        }
+    }
+
     macro_rules! test_line_comment {
         ($($name:ident: $eol:expr,)*) => {
             $(
@@ -1604,6 +1776,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_colon_equal() {
+        let source = String::from("(x := a[1:2]):=");
+        let tokens = lex_source(&source);
+        assert_eq!(
+            tokens,
+            vec![
+                Tok::Lpar,
+                Tok::Name {
+                    name: String::from("x"),
+                },
+                Tok::ColonEqual,
+                Tok::Name {
+                    name: String::from("a"),
+                },
+                Tok::Lsqb,
+                Tok::Int {
+                    value: BigInt::from(1),
+                },
+                Tok::Colon,
+                Tok::Int {
+                    value: BigInt::from(2),
+                },
+                Tok::Rsqb,
+                Tok::Rpar,
+                Tok::ColonEqual,
+            ]
+        );
+    }
+
     #[test]
     fn test_string() {
         let source = String::from(r#""double" 'single' 'can\'t' "\\\"" '\t\r\n' '\g' r'raw\''"#);
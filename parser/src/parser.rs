@@ -16,10 +16,16 @@ macro_rules! do_lalr_parsing {
     ($input: expr, $pat: ident, $tok: ident) => {{
         let lxr = lexer::make_tokenizer($input);
         let marker_token = (Default::default(), token::Tok::$tok, Default::default());
-        let tokenizer = iter::once(Ok(marker_token)).chain(lxr);
+        // Remember where the last token ended, to locate an unexpected EOF:
+        let mut eof_location = Default::default();
+        let tokenizer = iter::once(Ok(marker_token)).chain(lxr).inspect(|token| {
+            if let Ok((_, _, end)) = token {
+                eof_location = end.clone();
+            }
+        });
 
         match python::TopParser::new().parse(tokenizer) {
-            Err(err) => Err(ParseError::from(err)),
+            Err(err) => Err(ParseError::from_lalrpop(err, eof_location)),
             Ok(top) => {
                 if let ast::Top::$pat(x) = top {
                     Ok(x)
@@ -71,6 +77,8 @@ mod tests {
     use super::parse_expression;
     use super::parse_program;
     use super::parse_statement;
+    use crate::error::ParseErrorType;
+    use crate::lexer::{LexicalErrorType, Location};
     use num_bigint::BigInt;
 
     #[test]
@@ -244,6 +252,7 @@ mod tests {
                 node: ast::Statement::Expression {
                     expression: ast::Expression::Lambda {
                         args: ast::Parameters {
+                            posonlyargs: vec![],
                             args: vec![
                                 ast::Parameter {
                                     arg: String::from("x"),
@@ -339,6 +348,7 @@ mod tests {
                             node: ast::Statement::FunctionDef {
                                 name: String::from("__init__"),
                                 args: ast::Parameters {
+                                    posonlyargs: vec![],
                                     args: vec![ast::Parameter {
                                         arg: String::from("self"),
                                         annotation: None,
@@ -362,6 +372,7 @@ mod tests {
                             node: ast::Statement::FunctionDef {
                                 name: String::from("method_with_default"),
                                 args: ast::Parameters {
+                                    posonlyargs: vec![],
                                     args: vec![
                                         ast::Parameter {
                                             arg: String::from("self"),
@@ -491,4 +502,22 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_parse_error_location() {
+        let err = parse_program("a = 1\nb = a +* 2\n").unwrap_err();
+        assert_eq!(err.error.to_string(), "invalid syntax");
+        assert_eq!(err.location, Location::new(2, 8));
+
+        let err = parse_program("x = (1 +\n").unwrap_err();
+        assert_eq!(err.error, ParseErrorType::EOF);
+        assert_eq!(err.location, Location::new(1, 9));
+
+        let err = parse_program("if x:\n    y\n  z\n").unwrap_err();
+        assert_eq!(
+            err.error,
+            ParseErrorType::Lexical(LexicalErrorType::IndentationError)
+        );
+        assert_eq!(err.location.row(), 3);
+    }
 }
@@ -64,6 +64,7 @@ pub enum Tok {
     AtEqual,
     Rarrow,
     Ellipsis,
+    ColonEqual, // ':='
 
     // Keywords (alphabetically):
     False,
@@ -8,14 +8,14 @@ extern crate rustpython_vm;
 extern crate rustyline;
 
 use clap::{App, Arg};
-use rustpython_compiler::{compile, error::CompileError, error::CompileErrorType};
-use rustpython_parser::error::ParseError;
+use rustpython_compiler::{compile, encoding, error::CompileError, error::CompileErrorType};
+use rustpython_parser::error::ParseErrorType;
 use rustpython_vm::{
     frame::Scope,
     import,
-    obj::objstr,
+    obj::{objstr, objtype},
     print_exception,
-    pyobject::{ItemProtocol, PyResult},
+    pyobject::{IdProtocol, ItemProtocol, PyObjectRef, PyResult, TryFromObject},
     util, VirtualMachine,
 };
 
@@ -56,6 +56,10 @@ fn main() {
     let res = import::init_importlib(&vm);
     handle_exception(&vm, res);
 
+    // Importing the signal module makes Ctrl-C raise KeyboardInterrupt
+    let res = vm.import("signal", &vm.ctx.new_tuple(vec![]), 0);
+    handle_exception(&vm, res);
+
     // Figure out if a -c option was given:
     let result = if let Some(command) = matches.value_of("c") {
         run_command(&vm, command.to_string())
@@ -85,11 +89,33 @@ fn _run_string(vm: &VirtualMachine, source: &str, source_path: String) -> PyResu
 
 fn handle_exception(vm: &VirtualMachine, result: PyResult) {
     if let Err(err) = result {
+        if objtype::isinstance(&err, &vm.ctx.exceptions.system_exit) {
+            std::process::exit(system_exit_code(vm, &err));
+        }
         print_exception(vm, &err);
         std::process::exit(1);
     }
 }
 
+/// The process exit status for an uncaught SystemExit: its `code` when that
+/// is an integer, 0 for None, and otherwise 1 after printing the code.
+fn system_exit_code(vm: &VirtualMachine, exc: &PyObjectRef) -> i32 {
+    let code = vm
+        .get_attribute(exc.clone(), "code")
+        .unwrap_or_else(|_| vm.get_none());
+    if code.is(&vm.get_none()) {
+        0
+    } else if let Ok(code) = i32::try_from_object(vm, code.clone()) {
+        code
+    } else {
+        match vm.to_str(&code) {
+            Ok(code) => eprintln!("{}", code.as_str()),
+            Err(_) => eprintln!("<exit code str() failed>"),
+        }
+        1
+    }
+}
+
 fn run_command(vm: &VirtualMachine, mut source: String) -> PyResult {
     debug!("Running command {}", source);
 
@@ -134,7 +160,14 @@ fn run_script(vm: &VirtualMachine, script_file: &str) -> PyResult {
     vm.call_method(&sys_path, "insert", vec![vm.new_int(0), vm.new_str(dir)])?;
 
     match util::read_file(&file_path) {
-        Ok(source) => _run_string(vm, &source, file_path.to_str().unwrap().to_string()),
+        Ok(source) => {
+            let source_path = file_path.to_str().unwrap().to_string();
+            let source = encoding::decode_source(&source).map_err(|mut err| {
+                err.update_source_path(&source_path);
+                vm.new_syntax_error(&err)
+            })?;
+            _run_string(vm, &source, source_path)
+        }
         Err(err) => {
             error!(
                 "Failed reading file '{}': {:?}",
@@ -166,7 +199,7 @@ fn shell_exec(vm: &VirtualMachine, source: &str, scope: Scope) -> Result<(), Com
                 Ok(value) => {
                     // Save non-None values as "_"
 
-                    use rustpython_vm::pyobject::{IdProtocol, IntoPyObject};
+                    use rustpython_vm::pyobject::IntoPyObject;
 
                     if !value.is(&vm.get_none()) {
                         let key = objstr::PyString::from("_").into_pyobject(vm);
@@ -175,7 +208,11 @@ fn shell_exec(vm: &VirtualMachine, source: &str, scope: Scope) -> Result<(), Com
                 }
 
                 Err(err) => {
-                    print_exception(vm, &err);
+                    if objtype::isinstance(&err, &vm.ctx.exceptions.system_exit) {
+                        handle_exception(vm, Err(err));
+                    } else {
+                        print_exception(vm, &err);
+                    }
                 }
             }
 
@@ -184,7 +221,7 @@ fn shell_exec(vm: &VirtualMachine, source: &str, scope: Scope) -> Result<(), Com
         // Don't inject syntax errors for line continuation
         Err(
             err @ CompileError {
-                error: CompileErrorType::Parse(ParseError::EOF(_)),
+                error: CompileErrorType::Parse(ParseErrorType::EOF),
                 ..
             },
         ) => Err(err),
@@ -261,7 +298,7 @@ fn run_shell(vm: &VirtualMachine) -> PyResult {
 
                 match shell_exec(vm, &input, vars.clone()) {
                     Err(CompileError {
-                        error: CompileErrorType::Parse(ParseError::EOF(_)),
+                        error: CompileErrorType::Parse(ParseErrorType::EOF),
                         ..
                     }) => {
                         continuing = true;
@@ -273,8 +310,10 @@ fn run_shell(vm: &VirtualMachine) -> PyResult {
                 }
             }
             Err(ReadlineError::Interrupted) => {
-                // TODO: Raise a real KeyboardInterrupt exception
-                println!("^C");
+                // The terminal is in raw mode while a line is read, so Ctrl-C
+                // doesn't raise KeyboardInterrupt here; just drop the input
+                println!("\nKeyboardInterrupt");
+                input = String::new();
                 continuing = false;
                 continue;
             }
@@ -28,3 +28,12 @@ assert 'Lt' in str(n.body[0].value.ops[0])
 assert 'Gt' in str(n.body[0].value.ops[1])
 assert n.body[0].value.comparators[0].n == 4
 assert n.body[0].value.comparators[1].n == 5
+
+n = ast.parse("(x := 1)\n").body[0].value
+assert type(n) is ast.NamedExpr
+assert n.target.id == 'x'
+assert n.value.n == 1
+
+f = ast.parse("def f(a, /, b): pass\n").body[0]
+assert [a.arg for a in f.args.posonlyargs] == ['a']
+assert [a.arg for a in f.args.args] == ['b']
@@ -0,0 +1,138 @@
+import builtins
+from testutils import assertRaises
+
+
+class A:
+    def f(self):
+        return 'A.f'
+
+
+class B(A):
+    pass
+
+
+b = B()
+
+
+def call_f():
+    return b.f()
+
+
+assert call_f() == 'A.f'
+
+# Changing a class is seen through its subclasses:
+A.f = lambda self: 'new A.f'
+assert call_f() == 'new A.f'
+B.f = lambda self: 'B.f'
+assert call_f() == 'B.f'
+del B.f
+assert call_f() == 'new A.f'
+del A.f
+with assertRaises(AttributeError):
+    call_f()
+with assertRaises(AttributeError):
+    del A.f
+
+# An attribute of the instance shadows a method of the class:
+A.f = lambda self: 'A.f'
+b.f = lambda: 'instance f'
+assert call_f() == 'instance f'
+del b.f
+assert call_f() == 'A.f'
+
+
+class C:
+    def __getattribute__(self, name):
+        return lambda: 'getattribute ' + name
+
+
+class D:
+    def __getattr__(self, name):
+        return lambda: 'getattr ' + name
+
+    def method(self):
+        return 'method'
+
+
+def call_g(obj):
+    return obj.g()
+
+
+assert call_g(C()) == 'getattribute g'
+assert call_g(D()) == 'getattr g'
+assert D().method() == 'method'
+
+
+class E:
+    @staticmethod
+    def g():
+        return 'static'
+
+    @classmethod
+    def h(cls):
+        return cls
+
+    @property
+    def p(self):
+        return lambda: 'property'
+
+    def __init__(self):
+        self.i = lambda: 'instance'
+
+    def k(self):
+        return 'E.k'
+
+
+e = E()
+assert call_g(e) == 'static'
+assert e.h() is E
+assert E.h() is E
+assert e.p() == 'property'
+assert e.i() == 'instance'
+
+
+class F(E):
+    def g(self):
+        return super().k() + ' via super'
+
+
+assert call_g(F()) == 'E.k via super'
+
+# Methods of builtin types and modules:
+assert [1, 2].index(2) == 1
+assert 'a,b'.split(',') == ['a', 'b']
+assert (5).__add__(1) == 6
+assert builtins.len([1]) == 1
+
+# Calls with keyword and starred arguments:
+assert 'x{}{y}'.format(1, y=2) == 'x12'
+assert 'x{}{}'.format(*[1, 2]) == 'x12'
+assert 'x{y}'.format(**{'y': 3}) == 'x3'
+
+# Globals and builtins:
+def get_len():
+    return len
+
+
+assert get_len() is builtins.len
+len = 'global'
+assert get_len() == 'global'
+del len
+assert get_len() is builtins.len
+builtins.cached_builtin = 1
+
+
+def get_cached_builtin():
+    return cached_builtin
+
+
+assert get_cached_builtin() == 1
+builtins.cached_builtin = 2
+assert get_cached_builtin() == 2
+del builtins.cached_builtin
+with assertRaises(NameError):
+    get_cached_builtin()
+
+code = compile('x', '<test>', 'eval')
+assert eval(code, {'x': 1}) == 1
+assert eval(code, {'x': 2}) == 2
@@ -1,3 +1,5 @@
+from testutils import assertRaises
+
 assert True
 assert not False
 
@@ -77,3 +79,22 @@ assert False.__xor__(0) is not False
 assert False.__xor__(False) is False
 assert False.__rxor__(0) is not False
 assert False.__rxor__(False) is False
+
+# Without __bool__, truthiness falls back to __len__
+class Sized:
+    def __init__(self, length):
+        self.length = length
+
+    def __len__(self):
+        return self.length
+
+assert not Sized(0)
+assert Sized(2)
+assert bool(Sized(0)) is False
+
+class BadLen:
+    def __len__(self):
+        return 'x'
+
+with assertRaises(TypeError):
+    bool(BadLen())
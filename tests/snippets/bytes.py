@@ -597,3 +597,8 @@ assert a * -1 == b''
 assert a * 1 == b'abcd'
 assert a * 3 == b'abcdabcdabcd'
 assert 3 * a == b'abcdabcdabcd'
+
+# concatenation
+assert b'ab' + b'cd' == b'abcd'
+assert type(b'ab' + b'cd') is bytes
+assert type(b'ab' + bytearray(b'cd')) is bytes
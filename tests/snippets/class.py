@@ -155,3 +155,18 @@ assert T4.t1.__doc__ == "t1"
 
 cm = classmethod(lambda cls: cls)
 assert cm.__func__(int) is int
+
+
+class Meta(type):
+    pass
+
+class Base:
+    pass
+
+bases = [Base]
+kwargs = {'metaclass': Meta}
+class T5(*bases, **kwargs):
+    pass
+
+assert type(T5) is Meta
+assert issubclass(T5, Base)
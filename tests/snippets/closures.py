@@ -0,0 +1,211 @@
+from testutils import assertRaises
+
+
+def make_counter():
+    count = 0
+
+    def increment():
+        nonlocal count
+        count += 1
+        return count
+
+    return increment
+
+
+counter = make_counter()
+assert counter() == 1
+assert counter() == 2
+assert make_counter()() == 1
+
+# Closures see the current value of a variable, not the one at creation:
+def late_binding():
+    functions = []
+    for i in range(3):
+        functions.append(lambda: i)
+    return [f() for f in functions]
+
+
+assert late_binding() == [2, 2, 2]
+
+
+def default_binding():
+    return [f() for f in [lambda i=i: i for i in range(3)]]
+
+
+assert default_binding() == [0, 1, 2]
+
+# The cells of a closure:
+def outer(a):
+    b = 'b'
+
+    def inner():
+        return a, b
+
+    return inner
+
+
+inner = outer('a')
+assert inner() == ('a', 'b')
+assert type(inner.__closure__) is tuple
+assert [cell.cell_contents for cell in inner.__closure__] == ['a', 'b']
+assert type(inner.__closure__[0]).__name__ == 'cell'
+assert outer.__closure__ is None
+assert inner.__code__.co_freevars == ('a', 'b')
+assert inner.__code__.co_cellvars == ()
+assert outer.__code__.co_cellvars == ('a', 'b')
+assert outer.__code__.co_freevars == ()
+assert outer.__code__.co_varnames == ('a', 'inner')
+
+# Functions sharing a variable share its cell:
+def shared():
+    value = 1
+
+    def get():
+        return value
+
+    def set(new):
+        nonlocal value
+        value = new
+
+    return get, set
+
+
+get, set = shared()
+set(5)
+assert get() == 5
+assert get.__closure__[0] is set.__closure__[0]
+
+# Nested functions pass free variables through:
+def level1():
+    x = 1
+
+    def level2():
+        def level3():
+            return x
+
+        return level3
+
+    return level2
+
+
+level3 = level1()()
+assert level3() == 1
+assert level1().__code__.co_freevars == ('x',)
+
+
+def free_in_locals():
+    a = 1
+
+    def inner():
+        b = a
+        return locals()
+
+    return inner()
+
+
+assert free_in_locals() == {'a': 1, 'b': 1}
+
+
+def deleted_free_variable():
+    x = 1
+
+    def inner():
+        return x
+
+    del x
+    return inner
+
+
+with assertRaises(NameError):
+    deleted_free_variable()()
+
+
+def unbound_cell():
+    def inner():
+        return x
+
+    y = x
+    x = 1
+
+
+with assertRaises(UnboundLocalError):
+    unbound_cell()
+
+# Class bodies see the variables of enclosing functions, but do not share
+# their own with their methods:
+def class_scopes():
+    x = 'function'
+    y = 'function'
+
+    class A:
+        x = 'class'
+        from_class = x
+        from_function = y
+
+        def method(self):
+            return x
+
+    return A
+
+
+A = class_scopes()
+assert A.from_class == 'class'
+assert A.from_function == 'function'
+assert A().method() == 'function'
+assert A.x == 'class'
+
+
+class B:
+    def get_class(self):
+        return __class__
+
+    def get_super(self):
+        return super()
+
+
+assert B().get_class() is B
+assert isinstance(B().get_super(), super)
+assert '__classcell__' not in B.__dict__
+
+
+# Builtins and globals are found from nested functions:
+def uses_builtins():
+    def inner():
+        return len([1, 2])
+
+    return inner()
+
+
+assert uses_builtins() == 2
+
+code = deleted_free_variable().__code__
+with assertRaises(TypeError):
+    exec(code)
+
+with assertRaises(SyntaxError):
+    exec('nonlocal x')
+
+with assertRaises(SyntaxError):
+    exec("""
+def f():
+    x = 1
+    def g():
+        print(x)
+        nonlocal x
+""")
+
+with assertRaises(SyntaxError):
+    exec("""
+def f():
+    x = 1
+    def g():
+        global x
+        nonlocal x
+""")
+
+with assertRaises(SyntaxError):
+    exec("""
+def f():
+    def g():
+        nonlocal x
+""")
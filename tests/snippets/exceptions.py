@@ -23,3 +23,135 @@ class A:
 exc = KeyError(A())
 assert str(exc) == 'repr'
 assert repr(exc) == 'KeyError(repr,)'
+
+# Hierarchy
+assert issubclass(KeyError, LookupError)
+assert issubclass(IndexError, LookupError)
+assert issubclass(LookupError, Exception)
+assert issubclass(UnboundLocalError, NameError)
+assert issubclass(RecursionError, RuntimeError)
+assert issubclass(TabError, IndentationError)
+assert issubclass(IndentationError, SyntaxError)
+assert issubclass(UnicodeDecodeError, UnicodeError)
+assert issubclass(UnicodeEncodeError, UnicodeError)
+assert issubclass(UnicodeTranslateError, UnicodeError)
+assert issubclass(UnicodeError, ValueError)
+assert issubclass(FloatingPointError, ArithmeticError)
+assert issubclass(BufferError, Exception)
+assert issubclass(StopAsyncIteration, Exception)
+for exc_type in [SystemExit, KeyboardInterrupt, GeneratorExit]:
+    assert issubclass(exc_type, BaseException)
+    assert not issubclass(exc_type, Exception)
+for exc_type in [BrokenPipeError, ConnectionAbortedError, ConnectionRefusedError,
+                 ConnectionResetError]:
+    assert issubclass(exc_type, ConnectionError)
+for exc_type in [BlockingIOError, ChildProcessError, ConnectionError, FileExistsError,
+                 FileNotFoundError, InterruptedError, IsADirectoryError,
+                 NotADirectoryError, PermissionError, ProcessLookupError, TimeoutError]:
+    assert issubclass(exc_type, OSError)
+assert IOError is OSError
+assert EnvironmentError is OSError
+
+# args and str()
+exc = ValueError('message')
+assert exc.args == ('message',)
+assert str(exc) == 'message'
+assert str(ValueError(1, 2)) == '(1, 2)'
+assert str(BaseException('base')) == 'base'
+
+class MyError(Exception):
+    def __init__(self, a, b):
+        self.a = a
+
+exc = MyError(1, 2)
+assert exc.args == (1, 2)
+assert exc.a == 1
+
+# Chaining attributes
+exc = ValueError()
+assert exc.__cause__ is None
+assert exc.__context__ is None
+assert exc.__suppress_context__ is False
+
+try:
+    try:
+        raise KeyError('inner')
+    except KeyError as e:
+        raise ValueError('outer') from e
+except ValueError as e:
+    assert isinstance(e.__cause__, KeyError)
+    assert e.__suppress_context__ is True
+
+exc = ValueError('x')
+assert exc.with_traceback(None) is exc
+
+# SystemExit
+assert SystemExit().code is None
+assert SystemExit(3).code == 3
+assert SystemExit(1, 2).code == (1, 2)
+
+try:
+    exit(4)
+except SystemExit as e:
+    assert e.code == 4
+else:
+    assert False, "exit() didn't raise SystemExit"
+
+assert StopIteration(5).value == 5
+assert StopIteration().value is None
+
+# OSError
+exc = OSError(2, 'No such file or directory')
+assert type(exc) is FileNotFoundError
+assert exc.errno == 2
+assert exc.strerror == 'No such file or directory'
+assert exc.filename is None
+assert exc.args == (2, 'No such file or directory')
+assert str(exc) == '[Errno 2] No such file or directory'
+
+exc = OSError(13, 'Permission denied', 'secret.txt')
+assert type(exc) is PermissionError
+assert exc.filename == 'secret.txt'
+assert exc.args == (13, 'Permission denied')
+assert str(exc) == "[Errno 13] Permission denied: 'secret.txt'"
+
+exc = OSError(1, 'msg', 'a', None, 'b')
+assert str(exc) == "[Errno 1] msg: 'a' -> 'b'"
+assert exc.filename2 == 'b'
+
+exc = OSError('just a message')
+assert type(exc) is OSError
+assert exc.errno is None
+assert str(exc) == 'just a message'
+
+# Subclasses aren't remapped
+assert type(FileExistsError(2, 'x')) is FileExistsError
+
+try:
+    open('DOES_NOT_EXIST')
+except FileNotFoundError as e:
+    assert e.errno == 2
+else:
+    assert False, "open() didn't raise FileNotFoundError"
+
+# SyntaxError
+exc = SyntaxError('invalid syntax', ('test.py', 3, 7, 'x = = 1\n'))
+assert exc.msg == 'invalid syntax'
+assert exc.filename == 'test.py'
+assert exc.lineno == 3
+assert exc.offset == 7
+assert exc.text == 'x = = 1\n'
+assert str(exc) == 'invalid syntax (test.py, line 3)'
+
+# UnicodeDecodeError
+try:
+    b'abc\xff'.decode('utf-8')
+except UnicodeDecodeError as e:
+    assert e.encoding == 'utf-8'
+    assert e.object == b'abc\xff'
+    assert e.start == 3
+    assert e.end == 4
+    assert e.reason == 'invalid start byte'
+    assert str(e) == "'utf-8' codec can't decode byte 0xff in position 3: invalid start byte"
+else:
+    assert False, "decode() didn't raise UnicodeDecodeError"
@@ -19,7 +19,12 @@ assert f"{{{(lambda x: f'hello, {x}')('world}')}" == '{hello, world}'
 # Normally `!` cannot appear outside of delimiters in the expression but
 # cpython makes an exception for `!=`, so we should too.
 
-# assert f'{1 != 2}' == 'True'
+assert f'{1 != 2}' == 'True'
+assert f'{1 == 2}' == 'False'
+assert f'{1 <= 2}' == 'True'
+assert f'{ {"a": 1}["a"] }' == '1'
+assert f'{(lambda: 5)()}' == '5'
+assert f'{[x * 2 for x in range(3)]}' == '[0, 2, 4]'
 
 
 # conversion flags
@@ -39,3 +44,61 @@ v = Value()
 assert f'{v}' == 'foo'
 assert f'{v!r}' == 'bar'
 assert f'{v!s}' == 'baz'
+assert f'{v!a}' == 'bar'
+assert f'{"é"!a}' == "'\\xe9'"
+assert f'{"é"!r}' == "'é'"
+assert ascii('\u20ac\U0001f600') == "'\\u20ac\\U0001f600'"
+
+
+# format specs with nested replacement fields
+
+width = 6
+fill = '*'
+value = 42
+assert f'{value:{fill}>{width}}' == '****42'
+assert f'{value:>{width}x}' == '    2a'
+assert f'{value:{"+"}}' == '+42'
+assert f'{7:{"<"}{3}}' == '7  '
+
+
+# self-documenting expressions
+
+x = 'hi'
+assert f'{x=}' == "x='hi'"
+assert f'{x = }' == "x = 'hi'"
+assert f'{x=!s}' == 'x=hi'
+assert f'{width=:>4}' == 'width=   6'
+assert f'{1+1=}' == '1+1=2'
+
+
+# multi-line triple-quoted f-strings
+
+assert f'''{
+    x
+}''' == 'hi'
+assert f'''a
+{x}
+b''' == 'a\nhi\nb'
+
+
+def fstring_error(source):
+    try:
+        compile(source, 'test.py', 'exec')
+    except SyntaxError as ex:
+        return ex
+    raise AssertionError("Must throw syntax error")
+
+assert fstring_error("f'{'").msg == "f-string: expecting '}'"
+assert fstring_error("f'}'").msg == "f-string: single '}' is not allowed"
+assert fstring_error("f'{}'").msg == "f-string: empty expression not allowed"
+assert fstring_error("f'{x!z}'").msg.startswith("f-string: invalid conversion character")
+assert fstring_error("f'{x:{y:{z}}}'").msg == "f-string: expressions nested too deeply"
+assert fstring_error("f'{x # y}'").msg == "f-string expression part cannot include '#'"
+assert fstring_error("a = 1\nb = f'{)}'\n").lineno == 2
+
+
+def nested_spec_in_function():
+    fill = '-'
+    return f'{1:{fill}>3}'
+
+assert nested_spec_in_function() == '--1'
@@ -95,3 +95,26 @@ assert kwargs == [('a', 1), ('b', 2)]
 
 kwargs = func(a=1, b=2, c=3)
 assert kwargs == [('a', 1), ('b', 2), ('c', 3)]
+
+
+# Positional-only parameters
+def posonly(a, b=2, /, c=3, *, d=4):
+    return (a, b, c, d)
+
+assert posonly(1) == (1, 2, 3, 4)
+assert posonly(1, 5, 6, d=7) == (1, 5, 6, 7)
+assert posonly(1, c=8) == (1, 2, 8, 4)
+assert posonly.__defaults__ == (2, 3)
+assert posonly.__code__.co_argcount == 3
+assert posonly.__code__.co_posonlyargcount == 2
+
+with assertRaises(TypeError):
+    posonly(a=1)
+
+with assertRaises(TypeError):
+    posonly(1, b=2)
+
+def posonly_kwargs(a, /, **kwargs):
+    return a, kwargs
+
+assert posonly_kwargs(1, a=2) == (1, {'a': 2})
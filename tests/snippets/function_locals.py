@@ -0,0 +1,176 @@
+import sys
+
+from testutils import assertRaises
+
+x = 'global'
+
+
+def assigned_later():
+    y = x
+    x = 'local'
+    return y
+
+
+with assertRaises(UnboundLocalError):
+    assigned_later()
+
+
+def deleted():
+    a = 1
+    del a
+    return a
+
+
+with assertRaises(UnboundLocalError):
+    deleted()
+
+
+def delete_unbound():
+    del a
+    a = 1
+
+
+with assertRaises(UnboundLocalError):
+    delete_unbound()
+
+
+def read_global():
+    return x
+
+
+assert read_global() == 'global'
+
+
+def arguments(a, b=2, *args, c, d=4, **kwargs):
+    e = a + b + c + d
+    return locals()
+
+
+assert arguments(1, c=3) == {'a': 1, 'b': 2, 'c': 3, 'd': 4, 'e': 10, 'args': (), 'kwargs': {}}
+assert arguments(1, 2, 5, c=3, f=6)['args'] == (5,)
+assert arguments(1, 2, 5, c=3, f=6)['kwargs'] == {'f': 6}
+
+
+def locals_snapshot():
+    a = 1
+    first = locals()
+    b = 2
+    del a
+    second = locals()
+    return first, second
+
+
+first, second = locals_snapshot()
+assert 'a' not in second
+assert second['b'] == 2
+
+
+def frame_locals():
+    a = 1
+    return sys._getframe().f_locals
+
+
+assert frame_locals()['a'] == 1
+
+
+def closures():
+    counter = 0
+    unused = 5
+
+    def increment():
+        nonlocal counter
+        counter += 1
+
+    increment()
+    increment()
+
+    squares = [i * i for i in range(counter + 2)]
+    offset = 10
+    shifted = [value + offset for value in squares]
+    if (total := sum(shifted)) > 0:
+        pass
+    found = [last for item in squares if (last := item) > 0]
+    return counter, squares, shifted, total, last, unused
+
+
+assert closures() == (2, [0, 1, 4, 9], [10, 11, 14, 19], 54, 9, 5)
+
+
+def class_in_function():
+    value = 42
+
+    class A:
+        attribute = value
+
+        def method(self):
+            return value
+
+    return A.attribute, A().method()
+
+
+assert class_in_function() == (42, 42)
+
+
+def eval_in_function():
+    a = 1
+    b = 2
+    return eval('a + b')
+
+
+assert eval_in_function() == 3
+
+
+def loop():
+    total = 0
+    for i in range(100):
+        total += i
+    return total, i
+
+
+assert loop() == (4950, 99)
+
+
+def generator(n):
+    for i in range(n):
+        doubled = i * 2
+        yield doubled
+
+
+assert list(generator(3)) == [0, 2, 4]
+
+
+class Base:
+    def greet(self):
+        return 'base'
+
+
+class Derived(Base):
+    def greet(self):
+        greeting = super().greet()
+        return greeting + ' derived'
+
+
+assert Derived().greet() == 'base derived'
+
+
+class Context:
+    def __enter__(self):
+        return 'entered'
+
+    def __exit__(self, *args):
+        pass
+
+
+def unpacking():
+    a, (b, *c) = 1, (2, 3, 4)
+    with Context() as f:
+        pass
+    try:
+        raise ValueError
+    except ValueError as error:
+        caught = error
+    import sys as system
+    return a, b, c, f, type(caught), system is sys
+
+
+assert unpacking() == (1, 2, [3, 4], 'entered', ValueError, True)
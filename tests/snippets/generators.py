@@ -64,3 +64,33 @@ assert next(g) == 1
 
 with assertRaises(KeyError):
     assert g.throw(KeyError, KeyError(), None) == 2
+
+
+def returns_value():
+    yield 1
+    return 42
+
+def delegate():
+    r = yield from returns_value()
+    yield r
+    r = yield from [2, 3]
+    yield r
+
+assert list(delegate()) == [1, 42, 2, 3, None]
+
+g = returns_value()
+next(g)
+with assertRaises(StopIteration):
+    next(g)
+
+try:
+    next(g)
+except StopIteration as e:
+    assert e.value is None
+g = returns_value()
+next(g)
+try:
+    next(g)
+except StopIteration as e:
+    assert e.value == 42
+    assert e.args == (42,)
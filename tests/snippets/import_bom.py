@@ -0,0 +1,3 @@
+﻿# This is used by source_encoding.py; the two should be modified in concert
+
+CAFE = "café"
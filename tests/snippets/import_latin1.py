@@ -0,0 +1,4 @@
+# -*- coding: latin-1 -*-
+# This is used by source_encoding.py; the two should be modified in concert
+
+CAFE = "caf"
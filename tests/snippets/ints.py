@@ -139,3 +139,33 @@ class F(float):
         return 3
 
 assert int(F(1.2)) == 3
+
+# small ints are shared
+small_ints = [-5, 3, 100, 256]
+assert int("100") is small_ints[2]
+assert int("-5") is small_ints[0]
+assert sum([128, 128]) is small_ints[3]
+assert small_ints[3] - 1 + 1 is small_ints[3]
+assert int(I(100)) is small_ints[1]
+assert int("1000") is not int("1000")
+
+# results which overflow a machine word
+int_max = 9223372036854775807
+int_min = -9223372036854775808
+assert int_max + 1 == 9223372036854775808
+assert int_min - 1 == -9223372036854775809
+assert 1 - int_min == 9223372036854775809
+assert int_min // -1 == 9223372036854775808
+assert -int_min == 9223372036854775808
+assert abs(int_min) == 9223372036854775808
+assert 3037000500 * 3037000500 == 9223372037000250000
+assert 1 << 63 == 9223372036854775808
+assert -1 << 63 == int_min
+assert 3 ** 40 == 12157665459056928801
+assert (int_max + 1) - 1 == int_max
+assert (1 << 100) >> 99 == 2
+assert -1 >> 100 == -1
+assert 7 // -2 == -4
+assert (2 ** 53 + 1) / 1 == 9007199254740992.0
+assert int_max & (int_max + 1) == 0
+assert hash(int_max + 1) == hash(1 << 63)
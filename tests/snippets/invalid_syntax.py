@@ -16,3 +16,72 @@ else:
 
 with assertRaises(SyntaxError):
     compile('0xX', 'test.py', 'exec')
+
+
+def compile_error(source):
+    try:
+        compile(source, 'test.py', 'exec')
+    except SyntaxError as ex:
+        return ex
+    raise AssertionError("Must throw syntax error")
+
+
+ex = compile_error('a = 1\nb = a +* 2\n')
+assert type(ex) is SyntaxError
+assert ex.filename == 'test.py'
+assert ex.lineno == 2
+assert ex.offset == 8
+assert ex.text.rstrip('\n') == 'b = a +* 2'
+
+ex = compile_error('for i in range(3):\n    pass\nbreak\n')
+assert ex.lineno == 3
+
+ex = compile_error('x = 1\n  y = 2\n')
+assert type(ex) is IndentationError
+assert ex.lineno == 2
+
+ex = compile_error('def f():\nreturn 1\n')
+assert type(ex) is IndentationError
+assert ex.lineno == 2
+
+ex = compile_error('if x:\n    y = 2\n  z = 3\n')
+assert type(ex) is IndentationError
+assert ex.lineno == 3
+
+ex = compile_error('if x:\n\ty = 2\n        z = 3\n')
+assert type(ex) is TabError
+assert ex.lineno == 3
+
+ex = compile_error('def f(a=1, b):\n    pass\n')
+assert ex.msg == 'non-default argument follows default argument'
+assert ex.offset == 12
+
+ex = compile_error('f(a=1, b)\n')
+assert ex.msg == 'positional argument follows keyword argument'
+assert ex.offset == 9
+
+ex = compile_error('f(**a, *b)\n')
+assert ex.msg == 'iterable argument unpacking follows keyword argument unpacking'
+assert ex.offset == 8
+
+ex = compile_error('x = $\n')
+assert ex.offset == 5
+
+ex = compile_error('x = "abc\n')
+assert ex.msg.startswith('unterminated string literal')
+assert ex.offset == 5
+
+ex = compile_error('x = b"abc')
+assert ex.msg.startswith('unterminated string literal')
+assert ex.offset == 5
+
+# Deeply nested code is rejected instead of overflowing the stack
+for source in ['x = ' + '-' * 5000 + '1\n', 'x = ' + '[' * 5000 + ']' * 5000 + '\n']:
+    try:
+        compile(source, 'test.py', 'exec')
+    except (SyntaxError, RecursionError, MemoryError):
+        pass
+    else:
+        raise AssertionError("Must reject deeply nested code")
+
+compile_error(b"x = '\xff'\n")
@@ -18,6 +18,7 @@ y = x
 x *= 2
 assert y is x
 assert x == [1, 2, 3] * 2
+assert_raises(MemoryError, lambda: x * 2**62)
 
 # index()
 assert ['a', 'b', 'c'].index('b') == 1
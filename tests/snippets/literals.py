@@ -6,3 +6,27 @@ assert 0O777 == 511
 assert 0xcafebabe == 3405691582
 assert 0Xcafebabe == 3405691582
 assert 0xCAFEBABE == 3405691582
+assert 1_000_000 == 1000000
+assert 0x_FF == 255
+assert 0b_1_0 == 2
+assert 0o_17 == 15
+assert 000 == 0
+
+# Float and imaginary literals
+assert 1_0.5 == 10.5
+assert 1e1_0 == 10000000000.0
+assert 1E3 == 1000.0
+assert 1e400 == float('inf')
+assert 1e-400 == 0.0
+assert .5j == 0.5j
+assert 1J == 1j
+assert 1_0j == 10j
+
+# Invalid literals
+for source in ['0777', '0b2', '0o8', '0x', '0xg', '1__0', '1_', '1.5e+', '0b_']:
+    try:
+        compile(source, 'test.py', 'eval')
+    except SyntaxError:
+        pass
+    else:
+        raise AssertionError('{} should be an invalid literal'.format(source))
@@ -0,0 +1,46 @@
+from testutils import assertRaises
+
+if (n := 10) > 5:
+    assert n == 10
+
+items = [1, 2, 3]
+while (item := items.pop()) > 1:
+    pass
+assert item == 1
+assert items == []
+
+assert [y := 2, y ** 2] == [2, 4]
+assert (lambda: (z := 5) + z)() == 10
+
+def call(value):
+    return value
+
+assert call(w := 7) == 7
+assert w == 7
+
+# The target of an assignment expression in a comprehension binds in the
+# enclosing scope.
+values = [total := 0] + [total := total + v for v in range(4)]
+assert values == [0, 0, 1, 3, 6]
+assert total == 6
+
+def first_even(numbers):
+    if any((found := x) % 2 == 0 for x in numbers):
+        return found
+
+assert first_even([1, 3, 4, 5]) == 4
+
+def last_square():
+    [last := i * i for i in range(5)]
+    return last
+
+assert last_square() == 16
+
+with assertRaises(SyntaxError):
+    compile("x := 1", "<test>", "exec")
+
+with assertRaises(SyntaxError):
+    compile("[i := 0 for i in range(3)]", "<test>", "exec")
+
+with assertRaises(SyntaxError):
+    compile("class C:\n    [j := 0 for i in range(3)]\n", "<test>", "exec")
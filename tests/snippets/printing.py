@@ -14,3 +14,7 @@ except:
 buf = io.StringIO()
 print('hello, world', file=buf)
 assert buf.getvalue() == 'hello, world\n', buf.getvalue()
+
+buf = io.StringIO()
+print(1, 2.5, None, [3], sep=',', file=buf)
+assert buf.getvalue() == '1,2.5,None,[3]\n', buf.getvalue()
@@ -0,0 +1,50 @@
+import import_latin1
+import import_bom
+
+assert import_latin1.CAFE == 'caf\xe9'
+assert import_bom.CAFE == 'caf\xe9'
+
+
+def run(source):
+    namespace = {}
+    exec(compile(source, 'test.py', 'exec'), namespace)
+    return namespace['x']
+
+
+# Source without a declaration is UTF-8, optionally with a byte order mark:
+assert run(b"x = 'caf\xc3\xa9'") == 'caf\xe9'
+assert run(b"\xef\xbb\xbfx = 'caf\xc3\xa9'") == 'caf\xe9'
+assert run(b"\xef\xbb\xbf# coding: utf-8\nx = 'caf\xc3\xa9'") == 'caf\xe9'
+
+# Encoding declarations on the first or second line:
+assert run(b"# -*- coding: latin-1 -*-\nx = 'caf\xe9'") == 'caf\xe9'
+assert run(b"# coding=iso-8859-1\nx = 'caf\xe9'") == 'caf\xe9'
+assert run(b"#!/usr/bin/env python\n# vim: set fileencoding=latin_1 :\nx = 'caf\xe9'") == 'caf\xe9'
+assert run(b"# coding: cp1252\nx = '\x80'") == '€'
+assert run(b"# coding: ascii\nx = 'cafe'") == 'cafe'
+
+# A declaration in a string source is ignored:
+assert run("# coding: latin-1\nx = 'caf\xe9'") == 'caf\xe9'
+
+# exec and eval accept bytes as well:
+namespace = {}
+exec(b"# coding: latin-1\nx = 'caf\xe9'", namespace)
+assert namespace['x'] == 'caf\xe9'
+assert eval(b"# coding: latin-1\n'caf\xe9'") == 'caf\xe9'
+
+
+def assert_syntax_error(source, message):
+    try:
+        compile(source, 'test.py', 'exec')
+    except SyntaxError as ex:
+        assert message in ex.msg, ex.msg
+    else:
+        raise AssertionError('{!r} should not compile'.format(source))
+
+
+assert_syntax_error(b"# coding: unknown-encoding\nx = 1", 'unknown encoding: unknown-encoding')
+assert_syntax_error(b"\xef\xbb\xbf# coding: latin-1\nx = 1", 'encoding problem')
+assert_syntax_error(b"# coding: ascii\nx = 'caf\xe9'", "'ascii' codec can't decode byte 0xe9")
+# The declaration has to be a comment on one of the first two lines:
+assert_syntax_error(b"x = 1\n\n# coding: latin-1\nx = 'caf\xe9'", "can't decode")
+assert_syntax_error(b"x = 1\n# coding: latin-1\nx = 'caf\xe9'", "can't decode")
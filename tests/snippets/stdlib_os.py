@@ -1,4 +1,5 @@
 import os
+import subprocess
 import time
 import stat
 
@@ -13,6 +14,28 @@ assert_raises(FileNotFoundError, lambda: os.open('DOES_NOT_EXIST', os.O_RDONLY))
 assert_raises(FileNotFoundError, lambda: os.open('DOES_NOT_EXIST', os.O_WRONLY))
 assert_raises(FileNotFoundError, lambda: os.rename('DOES_NOT_EXIST', 'DOES_NOT_EXIST 2'))
 
+# Errors of calls on paths report the paths
+try:
+    open('DOES_NOT_EXIST')
+except FileNotFoundError as e:
+    assert e.filename == 'DOES_NOT_EXIST'
+    assert e.filename2 is None
+    assert str(e) == "[Errno 2] No such file or directory: 'DOES_NOT_EXIST'"
+else:
+    assert False, 'open() of a missing file did not fail'
+
+try:
+    os.rename('DOES_NOT_EXIST', 'DOES_NOT_EXIST 2')
+except FileNotFoundError as e:
+    assert (e.filename, e.filename2) == ('DOES_NOT_EXIST', 'DOES_NOT_EXIST 2')
+    assert str(e) == "[Errno 2] No such file or directory: 'DOES_NOT_EXIST' -> 'DOES_NOT_EXIST 2'"
+
+for func in [os.stat, os.listdir, os.rmdir, os.remove]:
+    try:
+        func('DOES_NOT_EXIST')
+    except FileNotFoundError as e:
+        assert e.filename == 'DOES_NOT_EXIST', func
+
 try:
 	os.open('DOES_NOT_EXIST', 0)
 except OSError as err:
@@ -247,7 +270,150 @@ with TestWithTempDir() as tmpdir:
 		assert os.getcwd() == os.path.realpath(tmpdir)
 		os.path.exists(FILE_NAME)
 
+	# stat_result is a tuple as well
+	stat_res = os.stat(fname)
+	assert stat_res[stat.ST_SIZE] == stat_res.st_size
+	assert stat_res[stat.ST_MODE] == stat_res.st_mode
+	assert stat_res.st_mtime_ns // 1000000000 == int(stat_res.st_mtime)
+	assert isinstance(stat_res.st_atime_ns, int)
+	mode, ino, dev, nlink, uid, gid, size = tuple(stat_res)[:7]
+	assert size == stat_res.st_size
+
+	# link, rename, replace
+	link_name = os.path.join(tmpdir, "hardlink")
+	os.link(fname, link_name)
+	assert os.stat(link_name).st_size == os.stat(fname).st_size
+	renamed = os.path.join(tmpdir, "renamed")
+	os.rename(link_name, renamed)
+	assert not os.path.exists(link_name)
+	fd = os.open(link_name, os.O_WRONLY | os.O_CREAT)
+	os.close(fd)
+	os.replace(link_name, renamed)
+	assert os.stat(renamed).st_size == 0
+
+	# truncate, lseek
+	os.truncate(renamed, 4)
+	assert os.stat(renamed).st_size == 4
+	fd = os.open(renamed, os.O_RDWR)
+	os.ftruncate(fd, 10)
+	assert os.lseek(fd, 0, os.SEEK_END) == 10
+	assert os.lseek(fd, -3, os.SEEK_CUR) == 7
+	assert os.lseek(fd, 2, os.SEEK_SET) == 2
+	os.close(fd)
+
+	# access
+	assert os.access(fname, os.F_OK)
+	assert os.access(fname, os.R_OK)
+	assert not os.access(os.path.join(tmpdir, "NO_SUCH_FILE"), os.F_OK)
+
+	# chmod
+	os.chmod(renamed, 0o444)
+	if os.name != "nt":
+		assert stat.S_IMODE(os.stat(renamed).st_mode) == 0o444
+	os.chmod(renamed, 0o644)
+	if os.name != "nt":
+		assert stat.S_IMODE(os.stat(renamed).st_mode) == 0o644
+
+	if os.name != "nt":
+		assert os.readlink(symlink_file) == fname
+		# A target that isn't valid UTF-8 still reads back as a str
+		undecodable_link = os.path.join(tmpdir, "undecodable")
+		subprocess.check_call(["ln", "-s", b"\xff", undecodable_link])
+		assert isinstance(os.readlink(undecodable_link), str)
+		os.remove(undecodable_link)
+
+		# dir_fd may be unsupported, but raises rather than crashing
+		dir_fd_calls = [lambda: os.stat(fname, dir_fd=0), lambda: os.readlink(symlink_file, dir_fd=0)]
+		dir_fd_calls.append(lambda: os.chmod(renamed, 0o644, dir_fd=0))
+		for call in dir_fd_calls:
+			try:
+				call()
+			except NotImplementedError:
+				pass
+
+		os.utime(renamed, (1000000000, 2000000000))
+		assert os.stat(renamed).st_mtime == 2000000000
+		assert os.stat(renamed).st_atime == 1000000000
+		os.utime(renamed, ns=(1500000000123456789, 1500000000987654321))
+		assert os.stat(renamed).st_mtime_ns == 1500000000987654321
+		os.utime(renamed)
+		assert abs(os.stat(renamed).st_mtime - time.time()) < 60
+		assert_raises(ValueError, lambda: os.utime(renamed, (1, 1), ns=(1, 1)))
+
+		os.chown(renamed, -1, -1)
+		os.chown(renamed, os.getuid(), os.getgid())
+
+	# walk
+	walk_root = os.path.join(tmpdir, "walk")
+	os.mkdir(walk_root)
+	os.mkdir(os.path.join(walk_root, "sub"))
+	fd = os.open(os.path.join(walk_root, "sub", "leaf"), os.O_WRONLY | os.O_CREAT)
+	os.close(fd)
+	walked = list(os.walk(walk_root))
+	assert walked == [(walk_root, ["sub"], []), (os.path.join(walk_root, "sub"), [], ["leaf"])]
+	walked = list(os.walk(walk_root, topdown=False))
+	assert walked[-1] == (walk_root, ["sub"], [])
+	assert list(os.walk(os.path.join(tmpdir, "NO_SUCH_DIR"))) == []
+
+	with os.scandir(walk_root) as it:
+		assert [entry.name for entry in it] == ["sub"]
+
+assert len(os.urandom(16)) == 16
+assert os.urandom(16) != os.urandom(16)
+assert os.cpu_count() is None or os.cpu_count() >= 1
+
+if os.name == "posix":
+	assert os.getuid() >= 0
+	assert os.geteuid() >= 0
+	assert os.getgid() >= 0
+	assert os.getegid() >= 0
+
+	old_mask = os.umask(0o22)
+	assert os.umask(old_mask) == 0o22
+
+	fd = os.open('README.md', os.O_RDONLY)
+	assert not os.isatty(fd)
+	fd2 = os.dup(fd)
+	assert fd2 != fd
+	assert not os.get_inheritable(fd2)
+	# The duplicate shares the file offset
+	head = os.read(fd2, 4)
+	assert len(head) == 4
+	assert os.read(fd, 4) != head
+	os.lseek(fd, 0, os.SEEK_SET)
+	assert os.read(fd2, 4) == head
+	os.close(fd2)
+	os.close(fd)
+	assert_raises(OSError, lambda: os.get_terminal_size(fd))
+
 # supports
 assert isinstance(os.supports_fd, set)
 assert isinstance(os.supports_dir_fd, set)
 assert isinstance(os.supports_follow_symlinks, set)
+
+assert os.getpid() > 0
+
+if os.name == "posix":
+	assert os.getppid() > 0
+	assert os.getppid() != os.getpid()
+
+	r, w = os.pipe()
+	assert not os.get_inheritable(r)
+	assert not os.get_inheritable(w)
+	assert os.write(w, b"ping") == 4
+	assert os.read(r, 100) == b"ping"
+	os.close(w)
+	assert os.read(r, 100) == b""
+	os.close(r)
+
+	pid = os.fork()
+	if pid == 0:
+		os._exit(3)
+	wpid, status = os.waitpid(pid, 0)
+	assert wpid == pid
+	assert os.WIFEXITED(status)
+	assert not os.WIFSIGNALED(status)
+	assert os.WEXITSTATUS(status) == 3
+
+	assert isinstance(os.strerror(2), str)
+	assert_raises(OSError, lambda: os.kill(pid, 0))
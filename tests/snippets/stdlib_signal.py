@@ -0,0 +1,112 @@
+import os
+import select
+import signal
+import sys
+import time
+from testutils import assertRaises
+
+assert signal.getsignal(signal.SIGINT) is signal.default_int_handler
+
+with assertRaises(KeyboardInterrupt):
+    signal.default_int_handler(signal.SIGINT, None)
+
+assert issubclass(KeyboardInterrupt, BaseException)
+assert not issubclass(KeyboardInterrupt, Exception)
+
+with assertRaises(ValueError):
+    signal.signal(0, signal.SIG_DFL)
+
+with assertRaises(ValueError):
+    signal.getsignal(-1)
+
+with assertRaises(TypeError):
+    signal.signal(signal.SIGTERM, 42)
+
+old = signal.signal(signal.SIGTERM, signal.SIG_IGN)
+assert old == signal.SIG_DFL
+assert signal.getsignal(signal.SIGTERM) == signal.SIG_IGN
+assert signal.signal(signal.SIGTERM, signal.SIG_DFL) == signal.SIG_IGN
+
+if os.name == "posix":
+    received = []
+
+    def handler(signum, frame):
+        received.append(signum)
+
+    old = signal.signal(signal.SIGUSR1, handler)
+    assert old == signal.SIG_DFL
+    assert signal.getsignal(signal.SIGUSR1) is handler
+
+    os.kill(os.getpid(), signal.SIGUSR1)
+    # The handler runs between bytecode instructions
+    for _ in range(10):
+        pass
+    assert received == [signal.SIGUSR1]
+
+    # Handlers can raise, and the exception can be caught
+    with assertRaises(KeyboardInterrupt):
+        os.kill(os.getpid(), signal.SIGINT)
+        for _ in range(10):
+            pass
+
+    def raising_handler(signum, frame):
+        raise ValueError(signum)
+
+    signal.signal(signal.SIGUSR2, raising_handler)
+    try:
+        os.kill(os.getpid(), signal.SIGUSR2)
+        for _ in range(10):
+            pass
+    except ValueError as e:
+        assert e.args == (signal.SIGUSR2,)
+    else:
+        assert False, "handler didn't raise"
+    signal.signal(signal.SIGUSR2, signal.SIG_DFL)
+
+    # Ignored signals don't kill the process
+    signal.signal(signal.SIGUSR1, signal.SIG_IGN)
+    os.kill(os.getpid(), signal.SIGUSR1)
+    signal.signal(signal.SIGUSR1, signal.SIG_DFL)
+
+    # alarm() delivers SIGALRM, and interrupts blocking calls
+    signal.signal(signal.SIGALRM, handler)
+    received = []
+    assert signal.alarm(1) == 0
+    assert signal.alarm(1) > 0
+    r, w = os.pipe()
+    with assertRaises(KeyboardInterrupt):
+        signal.signal(signal.SIGALRM, signal.default_int_handler)
+        os.read(r, 1)
+    os.close(r)
+    os.close(w)
+
+    signal.signal(signal.SIGALRM, handler)
+    assert signal.setitimer(signal.ITIMER_REAL, 0.05) == (0.0, 0.0)
+    value, interval = signal.getitimer(signal.ITIMER_REAL)
+    assert 0 < value <= 0.05
+    assert interval == 0.0
+    deadline = time.time() + 5
+    while not received and time.time() < deadline:
+        pass
+    assert received == [signal.SIGALRM]
+    assert signal.getitimer(signal.ITIMER_REAL) == (0.0, 0.0)
+
+    # An interrupted select() only waits for what is left of its timeout
+    received = []
+    r, w = os.pipe()
+    start = time.time()
+    signal.setitimer(signal.ITIMER_REAL, 0.5)
+    assert select.select([r], [], [], 0.6) == ([], [], [])
+    assert received == [signal.SIGALRM]
+    assert time.time() - start < 1.0
+    os.close(r)
+    os.close(w)
+    signal.signal(signal.SIGALRM, signal.SIG_DFL)
+
+if sys.platform.startswith('linux'):
+    # Real-time signals go up to SIGRTMAX, which is 64
+    assert signal.NSIG == 65
+    assert signal.signal(64, signal.SIG_IGN) in (signal.SIG_DFL, None)
+    signal.signal(64, signal.SIG_DFL)
+    with assertRaises(ValueError):
+        signal.signal(65, signal.SIG_IGN)
@@ -0,0 +1,81 @@
+import os
+import time
+
+from testutils import assert_raises
+
+if os.name == "posix":
+    import subprocess
+
+    p = subprocess.Popen(["echo", "test"])
+    assert p.pid > 0
+    assert p.wait() == 0
+    assert p.returncode == 0
+
+    p = subprocess.Popen(["sleep", "2"])
+    assert p.poll() is None
+    assert_raises(subprocess.TimeoutExpired, lambda: p.wait(timeout=0.1))
+    p.terminate()
+    assert p.wait() == -15
+
+    p = subprocess.Popen(["sleep", "2"])
+    p.kill()
+    assert p.wait() == -9
+
+    p = subprocess.Popen(["echo", "test"], stdout=subprocess.PIPE)
+    assert p.stdout.read() == b"test\n"
+    p.wait()
+    p.stdout.close()
+
+    result = subprocess.run(["cat"], input=b"x" * 100000, capture_output=True)
+    assert result.returncode == 0
+    assert result.stdout == b"x" * 100000
+    assert result.stderr == b""
+
+    result = subprocess.run("echo out; echo err >&2; exit 3", shell=True,
+                            capture_output=True, text=True)
+    assert result.returncode == 3
+    assert result.stdout == "out\n"
+    assert result.stderr == "err\n"
+
+    result = subprocess.run(["sh", "-c", "echo out; echo err >&2"],
+                            stdout=subprocess.PIPE, stderr=subprocess.STDOUT)
+    assert result.stdout == b"out\nerr\n"
+
+    assert subprocess.check_output(["pwd"], cwd="/") == b"/\n"
+    assert subprocess.check_output("echo $FOO", shell=True,
+                                   env={"FOO": "bar"}) == b"bar\n"
+    assert subprocess.run(["cat"], stdin=subprocess.DEVNULL,
+                          stdout=subprocess.PIPE).stdout == b""
+
+    assert subprocess.call(["true"]) == 0
+    assert subprocess.call(["false"]) == 1
+    assert_raises(subprocess.CalledProcessError,
+                  lambda: subprocess.check_call(["false"]))
+    try:
+        subprocess.run(["sh", "-c", "exit 5"], check=True)
+    except subprocess.CalledProcessError as err:
+        assert err.returncode == 5
+    else:
+        assert False, "CalledProcessError not raised"
+
+    assert_raises(FileNotFoundError,
+                  lambda: subprocess.run(["/does/not/exist"]))
+    assert_raises(FileNotFoundError,
+                  lambda: subprocess.run(["ls"], cwd="/does/not/exist"))
+
+    assert_raises(subprocess.TimeoutExpired,
+                  lambda: subprocess.run(["sleep", "2"], timeout=0.1))
+
+    assert subprocess.getstatusoutput("echo a; exit 2") == (2, "a")
+    assert subprocess.getoutput("echo hello") == "hello"
+
+    # Descriptors are not leaked into the child unless passed explicitly
+    r, w = os.pipe()
+    result = subprocess.run(["sh", "-c", "echo x >&%d" % w],
+                            stderr=subprocess.DEVNULL)
+    assert result.returncode != 0
+    result = subprocess.run(["sh", "-c", "echo x >&%d" % w], pass_fds=[w])
+    assert result.returncode == 0
+    os.close(w)
+    assert os.read(r, 10) == b"x\n"
+    os.close(r)
@@ -1,3 +1,4 @@
+from testutils import assertRaises
 import sys
 
 print(sys.argv)
@@ -20,3 +21,20 @@ assert isinstance(sys.flags, tuple)
 assert type(sys.flags).__name__ == "flags"
 assert type(sys.flags.optimize) is int
 assert sys.flags[3] == sys.flags.optimize
+
+# string interning
+interned = 'interned'
+name = ''.join(['inter', 'ned'])
+assert name is not interned
+assert sys.intern(name) is interned
+text = sys.intern(''.join(['not an ', 'identifier']))
+assert sys.intern(''.join(['not an identi', 'fier'])) is text
+assert sys.intern(text) is text
+
+class S(str):
+    pass
+
+with assertRaises(TypeError):
+    sys.intern(S('interned'))
+with assertRaises(TypeError):
+    sys.intern(1)
@@ -1,6 +1,8 @@
 
 import hashlib
 
+from testutils import assert_raises
+
 # print(hashlib.md5)
 h = hashlib.md5()
 h.update(b'a')
@@ -31,3 +33,49 @@ assert h.hexdigest() == 'a0e1ad0c123c9c65e8ef850db2ce4b5cef2c35b06527c615b015435
 h.update(b'bla')
 print(h.hexdigest())
 assert h.hexdigest() == '25738bfe4cc104131e1b45bece4dfd4e7e1d6f0dffda1211e996e9d5d3b66e81'
+
+h = hashlib.sha256(b'a')
+assert h.hexdigest() == 'ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb'
+assert h.block_size == 64
+assert hashlib.sha512().block_size == 128
+
+h2 = h.copy()
+h2.update(b'b')
+assert h.hexdigest() == 'ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb'
+assert h2.hexdigest() == hashlib.sha256(b'ab').hexdigest()
+
+h = hashlib.shake_128()
+assert h.name == 'shake_128'
+assert hashlib.new('shake_256').name == 'shake_256'
+assert not hasattr(hashlib, 'shake128')
+assert h.hexdigest(16) == '7f9c2ba4e88f827d616045507605853e'
+assert len(h.digest(100)) == 100
+assert_raises(TypeError, lambda: h.digest())
+assert_raises(ValueError, lambda: h.digest(2**62))
+assert_raises(ValueError, lambda: h.hexdigest(2**29))
+assert hashlib.shake_256().hexdigest(32) == '46b9dd2b0ba88d13233b3feb743eeb243fcd52ea62b81b82b50c27646ed5762f'
+
+assert hashlib.blake2b().hexdigest() == hashlib.new('blake2b').hexdigest()
+h = hashlib.blake2b(digest_size=32)
+assert h.digest_size == 32
+assert h.hexdigest() == '0e5751c026e543b2e8ab2eb06099daa1d1e5df47778f7787faab45cdf12fe3a8'
+assert hashlib.blake2s(b'x', key=b'k').digest() != hashlib.blake2s(b'x').digest()
+assert hashlib.blake2s(b'x', salt=b's').digest() != hashlib.blake2s(b'x', person=b's').digest()
+assert_raises(ValueError, lambda: hashlib.blake2s(digest_size=33))
+assert_raises(ValueError, lambda: hashlib.blake2b(salt=b'x' * 17))
+
+# RFC 6070 and RFC 7914 test vectors
+assert hashlib.pbkdf2_hmac('sha1', b'password', b'salt', 2).hex() == 'ea6c014dc72d6f8ccd1ed92ace1d41f0d8de8957'
+assert hashlib.pbkdf2_hmac('sha256', b'password', b'salt', 1, 32).hex() == '120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b'
+assert len(hashlib.pbkdf2_hmac('sha256', b'password', b'salt', 1, 80)) == 80
+assert_raises(ValueError, lambda: hashlib.pbkdf2_hmac('foo', b'password', b'salt', 1))
+
+assert hashlib.scrypt(b'', salt=b'', n=16, r=1, p=1, dklen=64).hex() == (
+    '77d6576238657b203b19ca42c18a0497f16b4844e3074ae8dfdffa3fede21442'
+    'fcd0069ded0948f8326a753a0fc81f17e8d3e0fb2e0d3628cf35e20c38d18906')
+assert_raises(ValueError, lambda: hashlib.scrypt(b'', salt=b'', n=15, r=1, p=1))
+assert_raises(OverflowError, lambda: hashlib.pbkdf2_hmac('sha256', b'', b'', 1, 2**62))
+assert_raises(OverflowError, lambda: hashlib.pbkdf2_hmac('sha256', b'', b'', 2**40))
+assert_raises(ValueError, lambda: hashlib.scrypt(b'', salt=b'', n=16, r=1, p=1, dklen=2**62))
+assert_raises(ValueError, lambda: hashlib.scrypt(b'', salt=b'', n=16, r=1, p=1, maxmem=2**62))
+assert_raises(ValueError, lambda: hashlib.scrypt(b'', salt=b'', n=2**20, r=8, p=1))
@@ -0,0 +1,35 @@
+import hashlib
+import hmac
+
+from testutils import assert_raises
+
+msg = b'The quick brown fox jumps over the lazy dog'
+
+h = hmac.new(b'key', msg, 'md5')
+assert h.hexdigest() == '80070713463e7749b90c2dc24911e275'
+assert h.name == 'hmac-md5'
+assert h.digest_size == 16
+assert h.block_size == 64
+
+h = hmac.new(b'key', digestmod=hashlib.sha256)
+h.update(msg)
+assert h.hexdigest() == 'f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8'
+assert hmac.digest(b'key', msg, 'sha256') == h.digest()
+
+h2 = h.copy()
+h2.update(b'!')
+assert h.hexdigest() == 'f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8'
+assert h2.digest() == hmac.new(b'key', msg + b'!', 'sha256').digest()
+
+# Keys longer than the block size are hashed first
+long_key = b'k' * 100
+assert hmac.new(long_key, msg, 'sha1').digest() == hmac.new(hashlib.sha1(long_key).digest(), msg, 'sha1').digest()
+
+assert_raises(TypeError, lambda: hmac.new(b'key', msg))
+assert_raises(ValueError, lambda: hmac.new(b'key', msg, 'shake_128'))
+
+assert hmac.compare_digest(b'abc', b'abc')
+assert not hmac.compare_digest(b'abc', b'abd')
+assert not hmac.compare_digest(b'abc', b'ab')
+assert hmac.compare_digest('abc', 'abc')
+assert_raises(TypeError, lambda: hmac.compare_digest('abc', b'abc'))
@@ -220,3 +220,244 @@ try:
     raise NameError
 except NameError as ex:
     assert ex.__context__ == None
+
+
+# The finally block runs on every way out of the try statement
+def return_in_try(l):
+    try:
+        l.append(1)
+        return 'try'
+    finally:
+        l.append(2)
+
+l = []
+assert return_in_try(l) == 'try'
+assert l == [1, 2]
+
+
+def return_in_except(l):
+    try:
+        1/0
+    except ZeroDivisionError:
+        l.append(1)
+        return 'except'
+    else:
+        l.append('else')
+    finally:
+        l.append(2)
+
+l = []
+assert return_in_except(l) == 'except'
+assert l == [1, 2]
+
+
+def return_in_finally():
+    try:
+        return 'try'
+    finally:
+        return 'finally'
+
+assert return_in_finally() == 'finally'
+
+
+def return_in_finally_swallows_exception():
+    try:
+        1/0
+    finally:
+        return 'finally'
+
+assert return_in_finally_swallows_exception() == 'finally'
+
+
+l = []
+for i in range(5):
+    try:
+        if i == 1:
+            continue
+        if i == 3:
+            break
+        l.append(i)
+    finally:
+        l.append('f%d' % i)
+l.append('done')
+assert l == [0, 'f0', 'f1', 2, 'f2', 'f3', 'done']
+
+
+l = []
+with assertRaises(NameError):
+    try:
+        l.append(1)
+    except ZeroDivisionError:
+        l.append('except')
+    else:
+        l.append(2)
+        raise NameError
+    finally:
+        l.append(3)
+assert l == [1, 2, 3]
+
+
+# Exceptions raised in a handler still run the finally block
+l = []
+with assertRaises(KeyError):
+    try:
+        1/0
+    except ZeroDivisionError:
+        {}['missing']
+    finally:
+        l.append('finally')
+assert l == ['finally']
+
+
+# Nested finally blocks run from the inside out
+def nested(l):
+    for i in range(3):
+        try:
+            try:
+                return i
+            finally:
+                l.append('inner')
+        finally:
+            l.append('outer')
+
+l = []
+assert nested(l) == 0
+assert l == ['inner', 'outer']
+
+
+# A break in finally discards the pending exception
+for i in range(3):
+    try:
+        1/0
+    finally:
+        break
+assert i == 0
+
+
+def finally_in_generator(l):
+    try:
+        yield 1
+        yield 2
+    finally:
+        l.append('finally')
+
+l = []
+assert list(finally_in_generator(l)) == [1, 2]
+assert l == ['finally']
+
+
+# Exception chaining
+try:
+    try:
+        raise ValueError('first')
+    except ValueError:
+        raise KeyError('second')
+except KeyError as ex:
+    assert type(ex.__context__) == ValueError
+    assert ex.__cause__ is None
+    assert not ex.__suppress_context__
+
+# Errors raised by native code are chained too
+try:
+    try:
+        1/0
+    except ZeroDivisionError:
+        {}['missing']
+except KeyError as ex:
+    assert type(ex.__context__) == ZeroDivisionError
+
+try:
+    try:
+        raise ValueError
+    except ValueError as ex:
+        raise KeyError from ex
+except KeyError as ex:
+    assert type(ex.__cause__) == ValueError
+    assert ex.__context__ is ex.__cause__
+    assert ex.__suppress_context__
+
+try:
+    try:
+        raise ValueError
+    except ValueError:
+        raise KeyError from None
+except KeyError as ex:
+    assert ex.__cause__ is None
+    assert type(ex.__context__) == ValueError
+    assert ex.__suppress_context__
+
+# A bare raise keeps the original context
+try:
+    try:
+        raise ValueError
+    except ValueError:
+        try:
+            raise KeyError
+        except KeyError:
+            pass
+        raise
+except ValueError as ex:
+    assert ex.__context__ is None
+
+# The context is the exception being handled where the error occurs
+def raise_in_handler():
+    try:
+        raise ZeroDivisionError
+    except ZeroDivisionError:
+        raise KeyError
+
+try:
+    try:
+        raise ValueError
+    except ValueError:
+        raise_in_handler()
+except KeyError as ex:
+    assert type(ex.__context__) == ZeroDivisionError
+    assert type(ex.__context__.__context__) == ValueError
+
+# Raising an exception from its own context chain doesn't create a cycle
+try:
+    try:
+        raise ValueError
+    except ValueError as first:
+        try:
+            raise KeyError
+        except KeyError:
+            raise first
+except ValueError as ex:
+    assert type(ex.__context__) == KeyError
+    assert ex.__context__.__context__ is None
+
+# An exception raised in a finally block is chained to the one propagating
+try:
+    try:
+        raise ValueError
+    finally:
+        raise KeyError
+except KeyError as ex:
+    assert type(ex.__context__) == ValueError
+
+# Once the finally block is done with it, the propagating exception is no longer the context
+def return_in_finally():
+    try:
+        raise ValueError
+    finally:
+        return 1
+
+assert return_in_finally() == 1
+try:
+    raise KeyError
+except KeyError as ex:
+    assert ex.__context__ is None
+
+try:
+    try:
+        try:
+            raise ValueError
+        finally:
+            pass
+    except ValueError:
+        pass
+    raise KeyError
+except KeyError as ex:
+    assert ex.__context__ is None
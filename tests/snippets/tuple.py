@@ -1,3 +1,5 @@
+from testutils import assert_raises
+
 assert (1,2) == (1,2)
 
 x = (1,2)
@@ -12,6 +14,7 @@ assert x * 3 == (1, 2, 1, 2, 1, 2)
 assert 3 * x == (1, 2, 1, 2, 1, 2)
 assert x * 0 == ()
 assert x * -1 == ()  # integers less than zero treated as 0
+assert_raises(MemoryError, lambda: x * 2**62)
 
 assert y < x, "tuple __lt__ failed"
 assert x > y, "tuple __gt__ failed"
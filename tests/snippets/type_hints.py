@@ -10,3 +10,89 @@ print(curry.__annotations__)
 assert curry.__annotations__['foo'] is int
 assert curry.__annotations__['return'] is float
 assert curry.__annotations__['bla'] is int
+
+# Variable annotations
+x: int = 5
+y: 'List[int]'
+assert x == 5
+assert __annotations__['x'] is int
+assert __annotations__['y'] == 'List[int]'
+assert 'y' not in globals()
+
+class Point:
+    x: int
+    y: int = 0
+    label: str = 'origin'
+
+    def __init__(self, x):
+        self.x: int = x
+        z: undefined_name = 1
+
+assert Point.__annotations__ == {'x': int, 'y': int, 'label': str}
+assert Point.y == 0
+assert not hasattr(Point, 'x')
+p = Point(3)
+assert p.x == 3
+assert 'x' not in Point.__init__.__annotations__
+
+class NoAnnotations:
+    pass
+
+assert '__annotations__' not in NoAnnotations.__dict__
+
+def f():
+    a: int
+    b: undefined_name = 2
+    return b
+
+assert f() == 2
+
+# Attribute and subscript targets evaluate the annotation but don't store it
+d = {}
+d['key']: int = 1
+assert d == {'key': 1}
+p.y: str
+assert 'p' not in __annotations__
+
+if True:
+    nested: float = 1.5
+assert __annotations__['nested'] is float
+
+# A parenthesized name is assigned, but its annotation isn't stored
+(paren): int = 1
+assert paren == 1
+assert 'paren' not in __annotations__
+
+# Only a single name, attribute or subscript can be annotated
+for source, message in [
+    ("(a, b): int", "only single target (not tuple) can be annotated"),
+    ("[a, b]: int", "only single target (not list) can be annotated"),
+    ("f(): int", "illegal target for annotation"),
+    ("1: int = 2", "illegal target for annotation"),
+]:
+    try:
+        compile(source, "<annotation>", "exec")
+    except SyntaxError as e:
+        assert e.msg == message, e.msg
+    else:
+        assert False, source
+
+# Deferred evaluation
+namespace = {}
+exec("""from __future__ import annotations
+class C:
+    attr: Dict[str, List[int]] = 1
+    other: 'Node'
+def g(a: undefined, *, b: Optional[int] = None) -> a.b[1:2]:
+    pass
+""", namespace)
+C = namespace['C']
+assert C.__annotations__ == {'attr': 'Dict[str, List[int]]', 'other': "'Node'"}
+assert C.attr == 1
+g = namespace['g']
+assert g.__annotations__ == {'a': 'undefined', 'b': 'Optional[int]', 'return': 'a.b[1:2]'}
+
+def h(a, *args: int, b: str, **kwargs: float) -> 'h':
+    pass
+
+assert h.__annotations__ == {'args': int, 'b': str, 'kwargs': float, 'return': 'h'}
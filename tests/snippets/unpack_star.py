@@ -0,0 +1,95 @@
+from testutils import assertRaises
+
+# Starred assignment targets (PEP 3132)
+*a, b = [1, 2, 3]
+assert a == [1, 2]
+assert b == 3
+
+(a, *b), c = [1, 2, 3], 4
+assert a == 1
+assert b == [2, 3]
+assert c == 4
+
+[a, *b] = 'xyz'
+assert b == ['y', 'z']
+
+*a, = range(2)
+assert a == [0, 1]
+
+for (a, *b), c in [((1, 2, 3), 4)]:
+    assert a == 1
+    assert b == [2, 3]
+    assert c == 4
+
+assert [b for a, *b in [(1, 2), (3, 4, 5)]] == [[2], [4, 5]]
+
+with assertRaises(ValueError):
+    a, *b, c = [1]
+
+with assertRaises(ValueError):
+    a, b = [1, 2, 3]
+
+with assertRaises(ValueError):
+    a, b, c = [1, 2]
+
+for source in ['*a, *b = [1]', '*a = [1]', '[*x for x in y]', 'f(x=1, x=2)', '(*a)']:
+    with assertRaises(SyntaxError):
+        compile(source, '<test>', 'exec')
+
+# Unpacking in displays (PEP 448)
+assert [*range(2), *'ab'] == [0, 1, 'a', 'b']
+assert (*range(2), 5) == (0, 1, 5)
+assert {*range(2), 5} == {0, 1, 5}
+assert {**{'a': 1}, 'b': 2, **{'c': 3}} == {'a': 1, 'b': 2, 'c': 3}
+assert {'a': 0, **{'a': 1}} == {'a': 1}
+
+x = *[1], 2
+assert x == (1, 2)
+
+def g():
+    return *[1], 2
+
+assert g() == (1, 2)
+
+def gen():
+    yield *[1], 2
+
+assert next(gen()) == (1, 2)
+
+items = []
+for x in *[1], 2:
+    items.append(x)
+assert items == [1, 2]
+
+
+class Mapping:
+    def keys(self):
+        return ['k']
+
+    def __getitem__(self, key):
+        return key * 2
+
+
+assert {**Mapping()} == {'k': 'kk'}
+
+with assertRaises(TypeError):
+    {**[1]}
+
+# Multiple unpacking in calls
+def f(*args, **kwargs):
+    return args, kwargs
+
+assert f(*[1], 2, *(3,), **{'x': 1}, y=2, **{'z': 3}) == ((1, 2, 3), {'x': 1, 'y': 2, 'z': 3})
+assert f(**Mapping()) == ((), {'k': 'kk'})
+
+with assertRaises(TypeError):
+    f(**{'x': 1}, x=2)
+
+with assertRaises(TypeError):
+    f(**{'x': 1}, **{'x': 2})
+
+with assertRaises(TypeError):
+    f(**[1])
+
+with assertRaises(TypeError):
+    f(**{1: 2})
@@ -5,13 +5,13 @@
 use std::cell::Cell;
 use std::char;
 use std::io::{self, Write};
-use std::str;
 
 use num_bigint::Sign;
 use num_traits::{Signed, ToPrimitive, Zero};
 
 use crate::obj::objbool;
 use crate::obj::objbytes::PyBytesRef;
+use crate::obj::objcell::PyCell;
 use crate::obj::objcode::PyCodeRef;
 use crate::obj::objdict::PyDictRef;
 use crate::obj::objint::{self, PyIntRef};
@@ -58,10 +58,22 @@ fn builtin_any(iterable: PyIterable<bool>, vm: &VirtualMachine) -> PyResult<bool
     Ok(false)
 }
 
-// builtin_ascii
+pub fn builtin_ascii(obj: PyObjectRef, vm: &VirtualMachine) -> PyResult<String> {
+    let repr = vm.to_repr(&obj)?;
+    let mut ascii = String::with_capacity(repr.value.len());
+    for c in repr.value.chars() {
+        match c as u32 {
+            0..=0x7f => ascii.push(c),
+            n @ 0x80..=0xff => ascii.push_str(&format!("\\x{:02x}", n)),
+            n @ 0x100..=0xffff => ascii.push_str(&format!("\\u{:04x}", n)),
+            n => ascii.push_str(&format!("\\U{:08x}", n)),
+        }
+    }
+    Ok(ascii)
+}
 
 fn builtin_bin(x: PyIntRef, _vm: &VirtualMachine) -> String {
-    let x = x.as_bigint();
+    let x = &*x.as_bigint();
     if x.is_negative() {
         format!("-0b{:b}", x.abs())
     } else {
@@ -101,15 +113,6 @@ struct CompileArgs {
 
 #[cfg(feature = "rustpython_compiler")]
 fn builtin_compile(args: CompileArgs, vm: &VirtualMachine) -> PyResult<PyCodeRef> {
-    // TODO: compile::compile should probably get bytes
-    let source = match args.source {
-        Either::A(string) => string.value.to_string(),
-        Either::B(bytes) => str::from_utf8(&bytes).unwrap().to_string(),
-    };
-
-    // TODO: fix this newline bug:
-    let source = format!("{}\n", source);
-
     let mode = {
         let mode = &args.mode.value;
         if mode == "exec" {
@@ -125,8 +128,17 @@ fn builtin_compile(args: CompileArgs, vm: &VirtualMachine) -> PyResult<PyCodeRef
         }
     };
 
-    vm.compile(&source, &mode, args.filename.value.to_string())
-        .map_err(|err| vm.new_syntax_error(&err))
+    let source_path = args.filename.value.to_string();
+    // TODO: fix this newline bug:
+    match args.source {
+        Either::A(string) => vm.compile(&format!("{}\n", string.value), &mode, source_path),
+        Either::B(bytes) => {
+            let mut source = bytes.to_vec();
+            source.push(b'\n');
+            vm.compile_bytes(&source, &mode, source_path)
+        }
+    }
+    .map_err(|err| vm.new_syntax_error(&err))
 }
 
 fn builtin_delattr(obj: PyObjectRef, attr: PyStringRef, vm: &VirtualMachine) -> PyResult<()> {
@@ -169,6 +181,7 @@ fn builtin_eval(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
 
     // Determine code object:
     let code_obj = if let Ok(code_obj) = PyCodeRef::try_from_object(vm, source.clone()) {
+        check_no_free_variables(&code_obj, "eval", vm)?;
         code_obj
     } else if objtype::isinstance(source, &vm.ctx.str_type()) {
         let mode = compile::Mode::Eval;
@@ -177,8 +190,16 @@ fn builtin_eval(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
         let source = format!("{}\n", source);
         vm.compile(&source, &mode, "<string>".to_string())
             .map_err(|err| vm.new_syntax_error(&err))?
+    } else if let Ok(bytes) = PyBytesRef::try_from_object(vm, source.clone()) {
+        let mode = compile::Mode::Eval;
+        let mut source = bytes.to_vec();
+        source.push(b'\n');
+        vm.compile_bytes(&source, &mode, "<string>".to_string())
+            .map_err(|err| vm.new_syntax_error(&err))?
     } else {
-        return Err(vm.new_type_error("code argument must be str or code object".to_string()));
+        return Err(
+            vm.new_type_error("eval() arg 1 must be a string, bytes or code object".to_string())
+        );
     };
 
     // Run the source:
@@ -206,16 +227,38 @@ fn builtin_exec(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
         let source = format!("{}\n", source);
         vm.compile(&source, &mode, "<string>".to_string())
             .map_err(|err| vm.new_syntax_error(&err))?
+    } else if let Ok(bytes) = PyBytesRef::try_from_object(vm, source.clone()) {
+        let mode = compile::Mode::Exec;
+        let mut source = bytes.to_vec();
+        source.push(b'\n');
+        vm.compile_bytes(&source, &mode, "<string>".to_string())
+            .map_err(|err| vm.new_syntax_error(&err))?
     } else if let Ok(code_obj) = PyCodeRef::try_from_object(vm, source.clone()) {
+        check_no_free_variables(&code_obj, "exec", vm)?;
         code_obj
     } else {
-        return Err(vm.new_type_error("source argument must be str or code object".to_string()));
+        return Err(
+            vm.new_type_error("exec() arg 1 must be a string, bytes or code object".to_string())
+        );
     };
 
     // Run the code:
     vm.run_code_obj(code_obj, scope)
 }
 
+/// The code of a nested function cannot run without the cells of its closure.
+#[cfg(feature = "rustpython_compiler")]
+fn check_no_free_variables(code: &PyCodeRef, function: &str, vm: &VirtualMachine) -> PyResult<()> {
+    if code.code.freevars.is_empty() {
+        Ok(())
+    } else {
+        Err(vm.new_type_error(format!(
+            "code object passed to {}() may not contain free variables",
+            function
+        )))
+    }
+}
+
 fn make_scope(
     vm: &VirtualMachine,
     globals: Option<&PyObjectRef>,
@@ -247,7 +290,9 @@ fn make_scope(
             if globals.is_some() {
                 None
             } else {
-                current_scope.get_only_locals()
+                current_scope
+                    .get_only_locals()
+                    .map(|_| vm.current_frame().unwrap().get_locals(vm))
             }
         }
     };
@@ -334,7 +379,7 @@ fn builtin_hash(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
 fn builtin_hex(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
     arg_check!(vm, args, required = [(number, Some(vm.ctx.int_type()))]);
 
-    let n = objint::get_value(number);
+    let n = &*objint::get_value(number);
     let s = if n.is_negative() {
         format!("-0x{:x}", n.abs())
     } else {
@@ -524,7 +569,7 @@ fn builtin_next(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
 fn builtin_oct(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
     arg_check!(vm, args, required = [(number, Some(vm.ctx.int_type()))]);
 
-    let n = objint::get_value(number);
+    let n = &*objint::get_value(number);
     let s = if n.is_negative() {
         format!("-0o{:o}", n.abs())
     } else {
@@ -627,7 +672,8 @@ trait Printer {
 
 impl Printer for &'_ PyObjectRef {
     fn write(&mut self, vm: &VirtualMachine, obj: PyObjectRef) -> PyResult<()> {
-        vm.call_method(self, "write", vec![obj])?;
+        let s = vm.to_str(&obj)?;
+        vm.call_method(self, "write", vec![s.into_object()])?;
         Ok(())
     }
 
@@ -650,20 +696,30 @@ impl Printer for std::io::StdoutLock<'_> {
     }
 }
 
-pub fn builtin_exit(exit_code_arg: OptionalArg<PyObjectRef>, vm: &VirtualMachine) -> PyResult<()> {
-    if let OptionalArg::Present(exit_code_obj) = exit_code_arg {
-        match i32::try_from_object(&vm, exit_code_obj.clone()) {
-            Ok(code) => std::process::exit(code),
-            _ => println!("{}", vm.to_str(&exit_code_obj)?.as_str()),
-        }
+/// Raises SystemExit; the interpreter exits when it isn't caught.
+pub fn builtin_exit(exit_code_arg: OptionalArg<PyObjectRef>, vm: &VirtualMachine) -> PyResult {
+    let args: Vec<_> = exit_code_arg.into_option().into_iter().collect();
+    let system_exit = vm.ctx.exceptions.system_exit.clone().into_object();
+    Err(vm.invoke(system_exit, args)?)
+}
+
+/// The `sys.stdout` installed by the host, if any, which `print` writes to
+/// instead of the process stdout.
+fn sys_stdout(vm: &VirtualMachine) -> Option<PyObjectRef> {
+    let dict = vm.sys_module.dict.as_ref()?;
+    if !dict.contains_key("stdout", vm) {
+        return None;
     }
-    std::process::exit(0);
+    dict.get_item("stdout", vm)
+        .ok()
+        .filter(|stdout| !stdout.is(&vm.get_none()))
 }
 
 pub fn builtin_print(objects: Args, options: PrintOptions, vm: &VirtualMachine) -> PyResult<()> {
     let stdout = io::stdout();
+    let file = options.file.clone().or_else(|| sys_stdout(vm));
 
-    let mut printer: Box<dyn Printer> = if let Some(file) = &options.file {
+    let mut printer: Box<dyn Printer> = if let Some(file) = &file {
         Box::new(file)
     } else {
         Box::new(stdout.lock())
@@ -739,7 +795,7 @@ fn builtin_round(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
     } else {
         // without a parameter, the result type is coerced to int
         let rounded = &vm.call_method(number, "__round__", vec![])?;
-        Ok(vm.ctx.new_int(objint::get_value(rounded).clone()))
+        Ok(vm.ctx.new_int(objint::get_value(rounded).into_owned()))
     }
 }
 
@@ -805,6 +861,7 @@ pub fn make_module(vm: &VirtualMachine, module: PyObjectRef) {
         "abs" => ctx.new_rustfunc(builtin_abs),
         "all" => ctx.new_rustfunc(builtin_all),
         "any" => ctx.new_rustfunc(builtin_any),
+        "ascii" => ctx.new_rustfunc(builtin_ascii),
         "bin" => ctx.new_rustfunc(builtin_bin),
         "bool" => ctx.bool_type(),
         "bytearray" => ctx.bytearray_type(),
@@ -873,27 +930,59 @@ pub fn make_module(vm: &VirtualMachine, module: PyObjectRef) {
         // Exceptions:
         "BaseException" => ctx.exceptions.base_exception_type.clone(),
         "Exception" => ctx.exceptions.exception_type.clone(),
+        "GeneratorExit" => ctx.exceptions.generator_exit.clone(),
+        "KeyboardInterrupt" => ctx.exceptions.keyboard_interrupt.clone(),
+        "SystemExit" => ctx.exceptions.system_exit.clone(),
         "ArithmeticError" => ctx.exceptions.arithmetic_error.clone(),
         "AssertionError" => ctx.exceptions.assertion_error.clone(),
         "AttributeError" => ctx.exceptions.attribute_error.clone(),
-        "NameError" => ctx.exceptions.name_error.clone(),
+        "BufferError" => ctx.exceptions.buffer_error.clone(),
+        "EOFError" => ctx.exceptions.eof_error.clone(),
+        "FloatingPointError" => ctx.exceptions.floating_point_error.clone(),
         "OverflowError" => ctx.exceptions.overflow_error.clone(),
-        "RuntimeError" => ctx.exceptions.runtime_error.clone(),
+        "ZeroDivisionError" => ctx.exceptions.zero_division_error.clone(),
+        "ImportError" => ctx.exceptions.import_error.clone(),
+        "ModuleNotFoundError" => ctx.exceptions.module_not_found_error.clone(),
+        "LookupError" => ctx.exceptions.lookup_error.clone(),
+        "IndexError" => ctx.exceptions.index_error.clone(),
+        "KeyError" => ctx.exceptions.key_error.clone(),
+        "MemoryError" => ctx.exceptions.memory_error.clone(),
+        "NameError" => ctx.exceptions.name_error.clone(),
+        "UnboundLocalError" => ctx.exceptions.unbound_local_error.clone(),
+        "OSError" => ctx.exceptions.os_error.clone(),
+        "EnvironmentError" => ctx.exceptions.os_error.clone(),
+        "IOError" => ctx.exceptions.os_error.clone(),
+        "BlockingIOError" => ctx.exceptions.blocking_io_error.clone(),
+        "ChildProcessError" => ctx.exceptions.child_process_error.clone(),
+        "ConnectionError" => ctx.exceptions.connection_error.clone(),
+        "BrokenPipeError" => ctx.exceptions.broken_pipe_error.clone(),
+        "ConnectionAbortedError" => ctx.exceptions.connection_aborted_error.clone(),
+        "ConnectionRefusedError" => ctx.exceptions.connection_refused_error.clone(),
+        "ConnectionResetError" => ctx.exceptions.connection_reset_error.clone(),
+        "FileExistsError" => ctx.exceptions.file_exists_error.clone(),
+        "FileNotFoundError" => ctx.exceptions.file_not_found_error.clone(),
+        "InterruptedError" => ctx.exceptions.interrupted_error.clone(),
+        "IsADirectoryError" => ctx.exceptions.is_a_directory_error.clone(),
+        "NotADirectoryError" => ctx.exceptions.not_a_directory_error.clone(),
+        "PermissionError" => ctx.exceptions.permission_error.clone(),
+        "ProcessLookupError" => ctx.exceptions.process_lookup_error.clone(),
+        "TimeoutError" => ctx.exceptions.timeout_error.clone(),
         "ReferenceError" => ctx.exceptions.reference_error.clone(),
-        "SyntaxError" =>  ctx.exceptions.syntax_error.clone(),
+        "RuntimeError" => ctx.exceptions.runtime_error.clone(),
         "NotImplementedError" => ctx.exceptions.not_implemented_error.clone(),
+        "RecursionError" => ctx.exceptions.recursion_error.clone(),
+        "StopAsyncIteration" => ctx.exceptions.stop_async_iteration.clone(),
+        "StopIteration" => ctx.exceptions.stop_iteration.clone(),
+        "SyntaxError" =>  ctx.exceptions.syntax_error.clone(),
+        "IndentationError" => ctx.exceptions.indentation_error.clone(),
+        "TabError" => ctx.exceptions.tab_error.clone(),
+        "SystemError" => ctx.exceptions.system_error.clone(),
         "TypeError" => ctx.exceptions.type_error.clone(),
         "ValueError" => ctx.exceptions.value_error.clone(),
-        "IndexError" => ctx.exceptions.index_error.clone(),
-        "ImportError" => ctx.exceptions.import_error.clone(),
-        "FileNotFoundError" => ctx.exceptions.file_not_found_error.clone(),
-        "FileExistsError" => ctx.exceptions.file_exists_error.clone(),
-        "StopIteration" => ctx.exceptions.stop_iteration.clone(),
-        "ZeroDivisionError" => ctx.exceptions.zero_division_error.clone(),
-        "KeyError" => ctx.exceptions.key_error.clone(),
-        "OSError" => ctx.exceptions.os_error.clone(),
-        "ModuleNotFoundError" => ctx.exceptions.module_not_found_error.clone(),
-        "EOFError" => ctx.exceptions.eof_error.clone(),
+        "UnicodeError" => ctx.exceptions.unicode_error.clone(),
+        "UnicodeDecodeError" => ctx.exceptions.unicode_decode_error.clone(),
+        "UnicodeEncodeError" => ctx.exceptions.unicode_encode_error.clone(),
+        "UnicodeTranslateError" => ctx.exceptions.unicode_translate_error.clone(),
 
         // Warnings
         "Warning" => ctx.exceptions.warning.clone(),
@@ -942,18 +1031,27 @@ pub fn builtin_build_class_(
 
     let namespace: PyDictRef = TryFromObject::try_from_object(vm, namespace)?;
 
-    let cells = vm.ctx.new_dict();
-
-    vm.invoke_with_locals(function, cells.clone(), namespace.clone())?;
+    vm.invoke_with_locals(function, namespace.clone())?;
 
     namespace.set_item("__name__", name_obj.clone(), vm)?;
     namespace.set_item("__qualname__", qualified_name.into_object(), vm)?;
 
+    // The class body stores the cell which its methods use for `__class__`:
+    let class_cell = match namespace.get_item_option("__classcell__", vm)? {
+        Some(cell) => {
+            namespace.del_item("__classcell__", vm)?;
+            cell.downcast::<PyCell>().ok()
+        }
+        None => None,
+    };
+
     let class = vm.call_method(
         metaclass.as_object(),
         "__call__",
         vec![name_obj, bases, namespace.into_object()],
     )?;
-    cells.set_item("__class__", class.clone(), vm)?;
+    if let Some(cell) = class_cell {
+        cell.set(Some(class.clone()));
+    }
     Ok(class)
 }
@@ -8,6 +8,7 @@ use crate::vm::VirtualMachine;
 /// And: http://code.activestate.com/recipes/578375/
 use std::collections::{hash_map::DefaultHasher, HashMap};
 use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 /// hash value of an object returned by __hash__
 type HashValue = pyhash::PyHash;
@@ -21,6 +22,7 @@ pub struct Dict<T = PyObjectRef> {
     size: usize,
     indices: HashMap<HashIndex, EntryIndex>,
     entries: Vec<Option<DictEntry<T>>>,
+    version: u64,
 }
 
 impl<T> Default for Dict<T> {
@@ -29,10 +31,13 @@ impl<T> Default for Dict<T> {
             size: 0,
             indices: HashMap::new(),
             entries: Vec::new(),
+            version: 0,
         }
     }
 }
 
+static NEXT_VERSION: AtomicU64 = AtomicU64::new(1);
+
 #[derive(Clone)]
 struct DictEntry<T> {
     hash: HashValue,
@@ -63,11 +68,23 @@ impl<T: Clone> Dict<T> {
         self.entries.push(Some(entry));
         self.indices.insert(hash_index, entry_index);
         self.size += 1;
+        self.modified();
     }
 
     fn unchecked_delete(&mut self, entry_index: EntryIndex) {
         self.entries[entry_index] = None;
         self.size -= 1;
+        self.modified();
+    }
+
+    fn modified(&mut self) {
+        self.version = NEXT_VERSION.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Identifies the contents of the dict: it changes with every change to
+    /// the dict, and is never shared with a dict holding other contents.
+    pub fn version(&self) -> u64 {
+        self.version
     }
 
     /// Store a key
@@ -77,6 +94,7 @@ impl<T: Clone> Dict<T> {
                 // Update existing key
                 if let Some(ref mut entry) = self.entries[index] {
                     entry.value = value;
+                    self.modified();
                     Ok(())
                 } else {
                     panic!("Lookup returned invalid index into entries!");
@@ -121,7 +139,8 @@ impl<T: Clone> Dict<T> {
     pub fn clear(&mut self) {
         self.entries.clear();
         self.indices.clear();
-        self.size = 0
+        self.size = 0;
+        self.modified();
     }
 
     /// Delete a key
@@ -1,51 +1,384 @@
 use crate::function::PyFuncArgs;
+use crate::obj::objbool;
+use crate::obj::objint::PyInt;
 use crate::obj::objsequence;
+use crate::obj::objstr;
 use crate::obj::objtuple::{PyTuple, PyTupleRef};
 use crate::obj::objtype;
 use crate::obj::objtype::PyClassRef;
-use crate::pyobject::{create_type, IdProtocol, PyContext, PyObjectRef, PyResult, TypeProtocol};
+use crate::pyobject::{
+    create_type, IdProtocol, PyContext, PyObjectRef, PyResult, TryFromObject, TypeProtocol,
+};
 use crate::vm::VirtualMachine;
+use num_traits::ToPrimitive;
+use std::collections::HashSet;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, Write};
+
+fn exception_new(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
+    if args.args.is_empty() {
+        return Err(vm.new_type_error("BaseException.__new__(): not enough arguments".to_string()));
+    }
+    let cls = PyClassRef::try_from_object(vm, args.args[0].clone())?;
+    let exc = vm.ctx.new_instance(cls, Some(vm.ctx.new_dict()));
+    // Set here rather than in __init__, so that subclasses overriding
+    // __init__ without calling the base class still get them
+    vm.set_attr(&exc, "args", vm.ctx.new_tuple(args.args[1..].to_vec()))?;
+    vm.set_attr(&exc, "__traceback__", vm.ctx.new_list(vec![]))?;
+    Ok(exc)
+}
 
 fn exception_init(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
     let exc_self = args.args[0].clone();
     let exc_args = vm.ctx.new_tuple(args.args[1..].to_vec());
     vm.set_attr(&exc_self, "args", exc_args)?;
+    Ok(vm.get_none())
+}
+
+fn exception_with_traceback(
+    exc: PyObjectRef,
+    traceback: PyObjectRef,
+    vm: &VirtualMachine,
+) -> PyResult {
+    // Tracebacks are lists of (filename, lineno, name) tuples
+    let traceback = if vm.get_none().is(&traceback) {
+        vm.ctx.new_list(vec![])
+    } else {
+        traceback
+    };
+    vm.set_attr(&exc, "__traceback__", traceback)?;
+    Ok(exc)
+}
+
+/// The positional arguments the exception was created with.
+fn exception_args(exc: &PyObjectRef, vm: &VirtualMachine) -> PyResult<Vec<PyObjectRef>> {
+    let args = vm.get_attribute(exc.clone(), "args")?;
+    let elements = objsequence::get_elements_tuple(&args).to_vec();
+    Ok(elements)
+}
+
+/// The first positional argument, or None.
+fn first_arg(exc: &PyObjectRef, vm: &VirtualMachine) -> PyResult {
+    Ok(exception_args(exc, vm)?
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| vm.get_none()))
+}
+
+fn system_exit_init(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
+    exception_init(vm, args.clone())?;
+    let code = match args.args.len() {
+        1 => vm.get_none(),
+        2 => args.args[1].clone(),
+        _ => vm.ctx.new_tuple(args.args[1..].to_vec()),
+    };
+    vm.set_attr(&args.args[0], "code", code)?;
+    Ok(vm.get_none())
+}
+
+fn stop_iteration_init(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
+    exception_init(vm, args.clone())?;
+    let value = first_arg(&args.args[0], vm)?;
+    vm.set_attr(&args.args[0], "value", value)?;
+    Ok(vm.get_none())
+}
 
-    let traceback = vm.ctx.new_list(Vec::new());
-    vm.set_attr(&exc_self, "__traceback__", traceback)?;
+fn import_error_init(vm: &VirtualMachine, mut args: PyFuncArgs) -> PyResult {
+    let name = args.take_keyword("name").unwrap_or_else(|| vm.get_none());
+    let path = args.take_keyword("path").unwrap_or_else(|| vm.get_none());
+    exception_init(vm, args.clone())?;
+    let msg = first_arg(&args.args[0], vm)?;
+    vm.set_attr(&args.args[0], "msg", msg)?;
+    vm.set_attr(&args.args[0], "name", name)?;
+    vm.set_attr(&args.args[0], "path", path)?;
     Ok(vm.get_none())
 }
 
+/// The subclass of OSError that corresponds to an errno value, if any.
+#[cfg(not(target_arch = "wasm32"))]
+fn errno_exception_type(errno: i32, zoo: &ExceptionZoo) -> Option<&PyClassRef> {
+    #[cfg(unix)]
+    {
+        if errno == libc::ESHUTDOWN {
+            return Some(&zoo.broken_pipe_error);
+        }
+    }
+    let types: &[(&[i32], &PyClassRef)] = &[
+        (
+            &[
+                libc::EAGAIN,
+                libc::EALREADY,
+                libc::EWOULDBLOCK,
+                libc::EINPROGRESS,
+            ],
+            &zoo.blocking_io_error,
+        ),
+        (&[libc::ECHILD], &zoo.child_process_error),
+        (&[libc::EPIPE], &zoo.broken_pipe_error),
+        (&[libc::ECONNABORTED], &zoo.connection_aborted_error),
+        (&[libc::ECONNREFUSED], &zoo.connection_refused_error),
+        (&[libc::ECONNRESET], &zoo.connection_reset_error),
+        (&[libc::EEXIST], &zoo.file_exists_error),
+        (&[libc::ENOENT], &zoo.file_not_found_error),
+        (&[libc::EISDIR], &zoo.is_a_directory_error),
+        (&[libc::ENOTDIR], &zoo.not_a_directory_error),
+        (&[libc::EINTR], &zoo.interrupted_error),
+        (&[libc::EACCES, libc::EPERM], &zoo.permission_error),
+        (&[libc::ESRCH], &zoo.process_lookup_error),
+        (&[libc::ETIMEDOUT], &zoo.timeout_error),
+    ];
+    types
+        .iter()
+        .find(|(errnos, _)| errnos.contains(&errno))
+        .map(|(_, exc_type)| *exc_type)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn errno_exception_type(_errno: i32, _zoo: &ExceptionZoo) -> Option<&PyClassRef> {
+    None
+}
+
+/// OSError(errno, strerror) creates the subclass matching `errno`, for
+/// example FileNotFoundError for ENOENT.
+fn os_error_new(vm: &VirtualMachine, mut args: PyFuncArgs) -> PyResult {
+    if args.args.len() >= 3 && args.args[0].is(&vm.ctx.exceptions.os_error) {
+        if let Some(errno) = args.args[1].payload::<PyInt>() {
+            let errno = errno.as_bigint().to_i32();
+            if let Some(exc_type) =
+                errno.and_then(|errno| errno_exception_type(errno, &vm.ctx.exceptions))
+            {
+                args.args[0] = exc_type.clone().into_object();
+            }
+        }
+    }
+    exception_new(vm, args)
+}
+
+fn os_error_init(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
+    let exc = &args.args[0];
+    let exc_args = &args.args[1..];
+    let none = vm.get_none();
+    let (errno, strerror, filename, filename2) = if exc_args.len() >= 2 && exc_args.len() <= 5 {
+        let arg = |i: usize| exc_args.get(i).cloned().unwrap_or_else(|| none.clone());
+        (arg(0), arg(1), arg(2), arg(4))
+    } else {
+        (none.clone(), none.clone(), none.clone(), none.clone())
+    };
+    // Like CPython, `args` only keeps errno and strerror when a filename is given
+    let kept_args = if exc_args.len() >= 3 && exc_args.len() <= 5 {
+        &exc_args[..2]
+    } else {
+        exc_args
+    };
+    vm.set_attr(exc, "args", vm.ctx.new_tuple(kept_args.to_vec()))?;
+    vm.set_attr(exc, "errno", errno)?;
+    vm.set_attr(exc, "strerror", strerror)?;
+    vm.set_attr(exc, "filename", filename)?;
+    vm.set_attr(exc, "filename2", filename2)?;
+    Ok(vm.get_none())
+}
+
+fn os_error_str(exc: PyObjectRef, vm: &VirtualMachine) -> PyResult<String> {
+    let none = vm.get_none();
+    let errno = vm.get_attribute(exc.clone(), "errno")?;
+    let strerror = vm.get_attribute(exc.clone(), "strerror")?;
+    let filename = vm.get_attribute(exc.clone(), "filename")?;
+    let filename2 = vm.get_attribute(exc.clone(), "filename2")?;
+    if !filename.is(&none) {
+        let mut s = format!(
+            "[Errno {}] {}: {}",
+            vm.to_str(&errno)?.as_str(),
+            vm.to_str(&strerror)?.as_str(),
+            vm.to_repr(&filename)?.as_str()
+        );
+        if !filename2.is(&none) {
+            s.push_str(&format!(" -> {}", vm.to_repr(&filename2)?.as_str()));
+        }
+        Ok(s)
+    } else if !errno.is(&none) && !strerror.is(&none) {
+        Ok(format!(
+            "[Errno {}] {}",
+            vm.to_str(&errno)?.as_str(),
+            vm.to_str(&strerror)?.as_str()
+        ))
+    } else {
+        exception_str_inner(&exc, vm)
+    }
+}
+
+fn syntax_error_init(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
+    exception_init(vm, args.clone())?;
+    let exc = &args.args[0];
+    let none = vm.get_none();
+    let msg = args.args.get(1).cloned().unwrap_or_else(|| none.clone());
+    vm.set_attr(exc, "msg", msg)?;
+    let details = match args.args.get(2) {
+        Some(details) if args.args.len() == 3 => {
+            let details = PyTupleRef::try_from_object(vm, details.clone())?;
+            if details.elements.len() != 4 {
+                return Err(vm.new_index_error("tuple index out of range".to_string()));
+            }
+            details.elements.clone()
+        }
+        _ => vec![none; 4],
+    };
+    for (name, value) in ["filename", "lineno", "offset", "text"].iter().zip(details) {
+        vm.set_attr(exc, *name, value)?;
+    }
+    Ok(vm.get_none())
+}
+
+fn syntax_error_str(exc: PyObjectRef, vm: &VirtualMachine) -> PyResult<String> {
+    let none = vm.get_none();
+    let msg = vm.get_attribute(exc.clone(), "msg")?;
+    let msg = vm.to_str(&msg)?.as_str().to_string();
+    let filename = vm.get_attribute(exc.clone(), "filename")?;
+    let lineno = vm.get_attribute(exc.clone(), "lineno")?;
+    let lineno = if lineno.payload::<PyInt>().is_some() {
+        Some(vm.to_str(&lineno)?.as_str().to_string())
+    } else {
+        None
+    };
+    let filename = if filename.is(&none) {
+        None
+    } else {
+        let filename = vm.to_str(&filename)?.as_str().to_string();
+        Some(
+            std::path::Path::new(&filename)
+                .file_name()
+                .map_or(filename.clone(), |name| name.to_string_lossy().into_owned()),
+        )
+    };
+    Ok(match (filename, lineno) {
+        (Some(filename), Some(lineno)) => format!("{} ({}, line {})", msg, filename, lineno),
+        (Some(filename), None) => format!("{} ({})", msg, filename),
+        (None, Some(lineno)) => format!("{} (line {})", msg, lineno),
+        (None, None) => msg,
+    })
+}
+
+/// UnicodeDecodeError(encoding, object, start, end, reason) and
+/// UnicodeEncodeError(encoding, object, start, end, reason)
+fn unicode_error_init(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
+    exception_init(vm, args.clone())?;
+    let exc = &args.args[0];
+    let exc_args = &args.args[1..];
+    if exc_args.len() != 5 {
+        return Err(vm.new_type_error(format!(
+            "function takes exactly 5 arguments ({} given)",
+            exc_args.len()
+        )));
+    }
+    for (name, value) in ["encoding", "object", "start", "end", "reason"]
+        .iter()
+        .zip(exc_args)
+    {
+        vm.set_attr(exc, *name, value.clone())?;
+    }
+    Ok(vm.get_none())
+}
+
+fn unicode_error_str(exc: PyObjectRef, vm: &VirtualMachine) -> PyResult<String> {
+    let is_decode = objtype::isinstance(&exc, &vm.ctx.exceptions.unicode_decode_error);
+    let attr = |name: &str| vm.get_attribute(exc.clone(), name);
+    let encoding = vm.to_str(&attr("encoding")?)?.as_str().to_string();
+    let object = attr("object")?;
+    let start = usize::try_from_object(vm, attr("start")?)?;
+    let end = usize::try_from_object(vm, attr("end")?)?;
+    let reason = vm.to_str(&attr("reason")?)?.as_str().to_string();
+    let item = vm.call_method(&object, "__getitem__", vec![vm.ctx.new_int(start)])?;
+    if end == start + 1 {
+        let item = if is_decode {
+            format!("byte 0x{:02x}", u8::try_from_object(vm, item)?)
+        } else {
+            format!("character {}", vm.to_repr(&item)?.as_str())
+        };
+        Ok(format!(
+            "'{}' codec can't {} {} in position {}: {}",
+            encoding,
+            if is_decode { "decode" } else { "encode" },
+            item,
+            start,
+            reason
+        ))
+    } else {
+        Ok(format!(
+            "'{}' codec can't {} {} in position {}-{}: {}",
+            encoding,
+            if is_decode { "decode" } else { "encode" },
+            if is_decode { "bytes" } else { "characters" },
+            start,
+            end - 1,
+            reason
+        ))
+    }
+}
+
 /// Print exception chain
 pub fn print_exception(vm: &VirtualMachine, exc: &PyObjectRef) {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let _ = write_exception(&mut stdout, vm, exc);
+}
+
+/// Write exception chain, formatted the way `print_exception` prints it
+pub fn write_exception<W: Write>(
+    output: &mut W,
+    vm: &VirtualMachine,
+    exc: &PyObjectRef,
+) -> io::Result<()> {
+    let mut seen = HashSet::new();
+    write_exception_chain(output, vm, exc, &mut seen)
+}
+
+/// Write the causes and contexts of an exception before the exception itself,
+/// skipping exceptions that were already written to cope with cycles.
+fn write_exception_chain<W: Write>(
+    output: &mut W,
+    vm: &VirtualMachine,
+    exc: &PyObjectRef,
+    seen: &mut HashSet<usize>,
+) -> io::Result<()> {
+    seen.insert(exc.get_id());
     let mut had_cause = false;
     if let Ok(cause) = vm.get_attribute(exc.clone(), "__cause__") {
         if !vm.get_none().is(&cause) {
             had_cause = true;
-            print_exception(vm, &cause);
-            println!("\nThe above exception was the direct cause of the following exception:\n");
+            if !seen.contains(&cause.get_id()) {
+                write_exception_chain(output, vm, &cause, seen)?;
+                writeln!(
+                    output,
+                    "\nThe above exception was the direct cause of the following exception:\n"
+                )?;
+            }
         }
     }
-    if !had_cause {
+    let suppress_context = vm
+        .get_attribute(exc.clone(), "__suppress_context__")
+        .map_or(false, |suppress| {
+            objbool::boolval(vm, suppress).unwrap_or(false)
+        });
+    if !had_cause && !suppress_context {
         if let Ok(context) = vm.get_attribute(exc.clone(), "__context__") {
-            if !vm.get_none().is(&context) {
-                print_exception(vm, &context);
-                println!("\nDuring handling of the above exception, another exception occurred:\n");
+            if !vm.get_none().is(&context) && !seen.contains(&context.get_id()) {
+                write_exception_chain(output, vm, &context, seen)?;
+                writeln!(
+                    output,
+                    "\nDuring handling of the above exception, another exception occurred:\n"
+                )?;
             }
         }
     }
-    print_exception_inner(vm, exc)
+    write_exception_inner(output, vm, exc)
 }
 
-fn print_source_line(filename: String, lineno: usize) {
+fn write_source_line<W: Write>(output: &mut W, filename: String, lineno: usize) -> io::Result<()> {
     // TODO: use io.open() method instead, when available, according to https://github.com/python/cpython/blob/master/Python/traceback.c#L393
     // TODO: support different encodings
     let file = match File::open(filename) {
         Ok(file) => file,
         Err(_) => {
-            return;
+            return Ok(());
         }
     };
     let file = BufReader::new(file);
@@ -54,15 +387,20 @@ fn print_source_line(filename: String, lineno: usize) {
         if i + 1 == lineno {
             if let Ok(line) = line {
                 // Indented with 4 spaces
-                println!("    {}", line.trim_start());
+                writeln!(output, "    {}", line.trim_start())?;
             }
-            return;
+            return Ok(());
         }
     }
+    Ok(())
 }
 
-/// Print exception occurrence location from traceback element
-fn print_traceback_entry(vm: &VirtualMachine, tb_entry: &PyObjectRef) {
+/// Write exception occurrence location from traceback element
+fn write_traceback_entry<W: Write>(
+    output: &mut W,
+    vm: &VirtualMachine,
+    tb_entry: &PyObjectRef,
+) -> io::Result<()> {
     if objtype::isinstance(&tb_entry, &vm.ctx.tuple_type()) {
         let location_attrs = objsequence::get_elements_tuple(&tb_entry);
         let filename = if let Ok(x) = vm.to_str(&location_attrs[0]) {
@@ -83,45 +421,91 @@ fn print_traceback_entry(vm: &VirtualMachine, tb_entry: &PyObjectRef) {
             "<error>".to_string()
         };
 
-        println!(
+        writeln!(
+            output,
             r##"  File "{}", line {}, in {}"##,
             filename, lineno, obj_name
-        );
-        print_source_line(filename, lineno.parse().unwrap());
+        )?;
+        write_source_line(output, filename, lineno.parse().unwrap())
+    } else {
+        writeln!(output, "  File ??")
+    }
+}
+
+/// Write the file and line of a SyntaxError with a caret under the offending
+/// column, the way CPython does, and return its bare message.
+fn write_syntax_error_location<W: Write>(
+    output: &mut W,
+    vm: &VirtualMachine,
+    exc: &PyObjectRef,
+) -> Option<String> {
+    let lineno = vm.get_attribute(exc.clone(), "lineno").ok()?;
+    let lineno = usize::try_from_object(vm, lineno).ok()?;
+    let filename = vm.get_attribute(exc.clone(), "filename").ok()?;
+    let filename = if vm.get_none().is(&filename) {
+        "<string>".to_string()
     } else {
-        println!("  File ??");
-        return;
+        vm.to_str(&filename).ok()?.as_str().to_string()
+    };
+    let msg = vm.get_attribute(exc.clone(), "msg").ok()?;
+    let msg = vm.to_str(&msg).ok()?.as_str().to_string();
+
+    writeln!(output, r##"  File "{}", line {}"##, filename, lineno).ok()?;
+    let text = vm.get_attribute(exc.clone(), "text").ok()?;
+    if objtype::isinstance(&text, &vm.ctx.str_type()) {
+        let text = objstr::get_value(&text);
+        let line = text.trim_end();
+        let trimmed = line.trim_start();
+        writeln!(output, "    {}", trimmed).ok()?;
+        let offset = vm
+            .get_attribute(exc.clone(), "offset")
+            .ok()
+            .and_then(|offset| usize::try_from_object(vm, offset).ok());
+        if let Some(offset) = offset {
+            // The offset counts from 1 and includes the stripped indentation
+            let indent = line.chars().count() - trimmed.chars().count();
+            let column = offset.saturating_sub(1 + indent);
+            writeln!(output, "    {}^", " ".repeat(column)).ok()?;
+        }
     }
+    Some(msg)
 }
 
-/// Print exception with traceback
-pub fn print_exception_inner(vm: &VirtualMachine, exc: &PyObjectRef) {
+/// Write exception with traceback
+pub fn write_exception_inner<W: Write>(
+    output: &mut W,
+    vm: &VirtualMachine,
+    exc: &PyObjectRef,
+) -> io::Result<()> {
     if let Ok(tb) = vm.get_attribute(exc.clone(), "__traceback__") {
-        println!("Traceback (most recent call last):");
         if objtype::isinstance(&tb, &vm.ctx.list_type()) {
             let mut tb_entries = objsequence::get_elements_list(&tb).to_vec();
+            // Errors raised while compiling the main program have no frames
+            if !tb_entries.is_empty() {
+                writeln!(output, "Traceback (most recent call last):")?;
+            }
             tb_entries.reverse();
 
             for exc_location in tb_entries.iter() {
-                print_traceback_entry(vm, exc_location);
+                write_traceback_entry(output, vm, exc_location)?;
             }
+        } else {
+            writeln!(output, "Traceback (most recent call last):")?;
         }
     } else {
-        println!("No traceback set on exception");
+        writeln!(output, "No traceback set on exception")?;
     }
 
-    let varargs = vm
-        .get_attribute(exc.clone(), "args")
-        .unwrap()
-        .downcast::<PyTuple>()
-        .expect("'args' must be a tuple");
-    let args_repr = exception_args_as_string(vm, varargs);
-
     let exc_name = exc.class().name.clone();
-    match args_repr.len() {
-        0 => println!("{}", exc_name),
-        1 => println!("{}: {}", exc_name, args_repr[0]),
-        _ => println!("{}: ({})", exc_name, args_repr.join(", ")),
+    if objtype::isinstance(exc, &vm.ctx.exceptions.syntax_error) {
+        if let Some(msg) = write_syntax_error_location(output, vm, exc) {
+            return writeln!(output, "{}: {}", exc_name, msg);
+        }
+    }
+    match vm.to_str(exc) {
+        Ok(ref msg) if msg.as_str().is_empty() => writeln!(output, "{}", exc_name),
+        Ok(msg) => writeln!(output, "{}: {}", exc_name, msg.as_str()),
+        Err(_) => writeln!(output, "{}: <exception str() failed>", exc_name),
     }
 }
 
@@ -149,11 +533,37 @@ fn exception_args_as_string(vm: &VirtualMachine, varargs: PyTupleRef) -> Vec<Str
     }
 }
 
+fn exception_str_inner(exc: &PyObjectRef, vm: &VirtualMachine) -> PyResult<String> {
+    let args = vm
+        .get_attribute(exc.clone(), "args")?
+        .downcast::<PyTuple>()
+        .expect("'args' must be a tuple");
+    match args.elements.len() {
+        0 => Ok("".to_string()),
+        1 => Ok(vm.to_str(&args.elements[0])?.as_str().to_string()),
+        _ => Ok(format!(
+            "({})",
+            exception_args_as_string(vm, args).join(", ")
+        )),
+    }
+}
+
 fn exception_str(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
     arg_check!(
         vm,
         args,
-        required = [(exc, Some(vm.ctx.exceptions.exception_type.clone()))]
+        required = [(exc, Some(vm.ctx.exceptions.base_exception_type.clone()))]
+    );
+    Ok(vm.new_str(exception_str_inner(exc, vm)?))
+}
+
+/// KeyError shows the repr of the missing key, so that str(KeyError(''))
+/// isn't empty.
+fn key_error_str(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
+    arg_check!(
+        vm,
+        args,
+        required = [(exc, Some(vm.ctx.exceptions.key_error.clone()))]
     );
     let args = vm
         .get_attribute(exc.clone(), "args")
@@ -173,7 +583,7 @@ fn exception_repr(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
     arg_check!(
         vm,
         args,
-        required = [(exc, Some(vm.ctx.exceptions.exception_type.clone()))]
+        required = [(exc, Some(vm.ctx.exceptions.base_exception_type.clone()))]
     );
     let args = vm
         .get_attribute(exc.clone(), "args")
@@ -197,23 +607,53 @@ pub struct ExceptionZoo {
     pub assertion_error: PyClassRef,
     pub attribute_error: PyClassRef,
     pub base_exception_type: PyClassRef,
+    pub blocking_io_error: PyClassRef,
+    pub broken_pipe_error: PyClassRef,
+    pub buffer_error: PyClassRef,
+    pub child_process_error: PyClassRef,
+    pub connection_aborted_error: PyClassRef,
+    pub connection_error: PyClassRef,
+    pub connection_refused_error: PyClassRef,
+    pub connection_reset_error: PyClassRef,
     pub exception_type: PyClassRef,
     pub file_not_found_error: PyClassRef,
     pub file_exists_error: PyClassRef,
+    pub floating_point_error: PyClassRef,
+    pub generator_exit: PyClassRef,
     pub import_error: PyClassRef,
+    pub indentation_error: PyClassRef,
     pub index_error: PyClassRef,
+    pub interrupted_error: PyClassRef,
+    pub is_a_directory_error: PyClassRef,
     pub key_error: PyClassRef,
+    pub keyboard_interrupt: PyClassRef,
+    pub lookup_error: PyClassRef,
+    pub memory_error: PyClassRef,
     pub module_not_found_error: PyClassRef,
     pub name_error: PyClassRef,
+    pub not_a_directory_error: PyClassRef,
     pub not_implemented_error: PyClassRef,
     pub os_error: PyClassRef,
     pub overflow_error: PyClassRef,
     pub permission_error: PyClassRef,
+    pub process_lookup_error: PyClassRef,
+    pub recursion_error: PyClassRef,
     pub reference_error: PyClassRef,
+    pub resource_limit_error: PyClassRef,
     pub runtime_error: PyClassRef,
+    pub stop_async_iteration: PyClassRef,
     pub stop_iteration: PyClassRef,
     pub syntax_error: PyClassRef,
+    pub system_error: PyClassRef,
+    pub system_exit: PyClassRef,
+    pub tab_error: PyClassRef,
+    pub timeout_error: PyClassRef,
     pub type_error: PyClassRef,
+    pub unbound_local_error: PyClassRef,
+    pub unicode_decode_error: PyClassRef,
+    pub unicode_encode_error: PyClassRef,
+    pub unicode_error: PyClassRef,
+    pub unicode_translate_error: PyClassRef,
     pub value_error: PyClassRef,
     pub zero_division_error: PyClassRef,
     pub eof_error: PyClassRef,
@@ -236,28 +676,63 @@ impl ExceptionZoo {
         // Sorted By Hierarchy then alphabetized.
         let base_exception_type = create_type("BaseException", &type_type, &object_type);
         let exception_type = create_type("Exception", &type_type, &base_exception_type);
+        let generator_exit = create_type("GeneratorExit", &type_type, &base_exception_type);
+        let keyboard_interrupt = create_type("KeyboardInterrupt", &type_type, &base_exception_type);
+        let resource_limit_error =
+            create_type("ResourceLimitError", &type_type, &base_exception_type);
+        let system_exit = create_type("SystemExit", &type_type, &base_exception_type);
         let arithmetic_error = create_type("ArithmeticError", &type_type, &exception_type);
         let assertion_error = create_type("AssertionError", &type_type, &exception_type);
         let attribute_error = create_type("AttributeError", &type_type, &exception_type);
+        let buffer_error = create_type("BufferError", &type_type, &exception_type);
+        let eof_error = create_type("EOFError", &type_type, &exception_type);
         let import_error = create_type("ImportError", &type_type, &exception_type);
-        let index_error = create_type("IndexError", &type_type, &exception_type);
-        let key_error = create_type("KeyError", &type_type, &exception_type);
+        let lookup_error = create_type("LookupError", &type_type, &exception_type);
+        let memory_error = create_type("MemoryError", &type_type, &exception_type);
         let name_error = create_type("NameError", &type_type, &exception_type);
         let os_error = create_type("OSError", &type_type, &exception_type);
-        let runtime_error = create_type("RuntimeError", &type_type, &exception_type);
         let reference_error = create_type("ReferenceError", &type_type, &exception_type);
+        let runtime_error = create_type("RuntimeError", &type_type, &exception_type);
+        let stop_async_iteration = create_type("StopAsyncIteration", &type_type, &exception_type);
         let stop_iteration = create_type("StopIteration", &type_type, &exception_type);
         let syntax_error = create_type("SyntaxError", &type_type, &exception_type);
+        let system_error = create_type("SystemError", &type_type, &exception_type);
         let type_error = create_type("TypeError", &type_type, &exception_type);
         let value_error = create_type("ValueError", &type_type, &exception_type);
+        let floating_point_error = create_type("FloatingPointError", &type_type, &arithmetic_error);
         let overflow_error = create_type("OverflowError", &type_type, &arithmetic_error);
         let zero_division_error = create_type("ZeroDivisionError", &type_type, &arithmetic_error);
         let module_not_found_error = create_type("ModuleNotFoundError", &type_type, &import_error);
-        let not_implemented_error = create_type("NotImplementedError", &type_type, &runtime_error);
+        let index_error = create_type("IndexError", &type_type, &lookup_error);
+        let key_error = create_type("KeyError", &type_type, &lookup_error);
+        let unbound_local_error = create_type("UnboundLocalError", &type_type, &name_error);
+        let blocking_io_error = create_type("BlockingIOError", &type_type, &os_error);
+        let child_process_error = create_type("ChildProcessError", &type_type, &os_error);
+        let connection_error = create_type("ConnectionError", &type_type, &os_error);
+        let file_exists_error = create_type("FileExistsError", &type_type, &os_error);
         let file_not_found_error = create_type("FileNotFoundError", &type_type, &os_error);
+        let interrupted_error = create_type("InterruptedError", &type_type, &os_error);
+        let is_a_directory_error = create_type("IsADirectoryError", &type_type, &os_error);
+        let not_a_directory_error = create_type("NotADirectoryError", &type_type, &os_error);
         let permission_error = create_type("PermissionError", &type_type, &os_error);
-        let file_exists_error = create_type("FileExistsError", &type_type, &os_error);
-        let eof_error = create_type("EOFError", &type_type, &exception_type);
+        let process_lookup_error = create_type("ProcessLookupError", &type_type, &os_error);
+        let timeout_error = create_type("TimeoutError", &type_type, &os_error);
+        let broken_pipe_error = create_type("BrokenPipeError", &type_type, &connection_error);
+        let connection_aborted_error =
+            create_type("ConnectionAbortedError", &type_type, &connection_error);
+        let connection_refused_error =
+            create_type("ConnectionRefusedError", &type_type, &connection_error);
+        let connection_reset_error =
+            create_type("ConnectionResetError", &type_type, &connection_error);
+        let not_implemented_error = create_type("NotImplementedError", &type_type, &runtime_error);
+        let recursion_error = create_type("RecursionError", &type_type, &runtime_error);
+        let indentation_error = create_type("IndentationError", &type_type, &syntax_error);
+        let tab_error = create_type("TabError", &type_type, &indentation_error);
+        let unicode_error = create_type("UnicodeError", &type_type, &value_error);
+        let unicode_decode_error = create_type("UnicodeDecodeError", &type_type, &unicode_error);
+        let unicode_encode_error = create_type("UnicodeEncodeError", &type_type, &unicode_error);
+        let unicode_translate_error =
+            create_type("UnicodeTranslateError", &type_type, &unicode_error);
 
         let warning = create_type("Warning", &type_type, &exception_type);
         let bytes_warning = create_type("BytesWarning", &type_type, &warning);
@@ -277,22 +752,51 @@ impl ExceptionZoo {
             assertion_error,
             attribute_error,
             base_exception_type,
+            blocking_io_error,
+            broken_pipe_error,
+            buffer_error,
+            child_process_error,
+            connection_aborted_error,
+            connection_error,
+            connection_refused_error,
+            connection_reset_error,
             exception_type,
             file_not_found_error,
             file_exists_error,
+            floating_point_error,
+            generator_exit,
             import_error,
+            indentation_error,
             index_error,
+            interrupted_error,
+            is_a_directory_error,
             key_error,
+            keyboard_interrupt,
+            lookup_error,
+            memory_error,
             module_not_found_error,
             name_error,
+            not_a_directory_error,
             not_implemented_error,
             os_error,
             overflow_error,
             permission_error,
+            process_lookup_error,
+            recursion_error,
             runtime_error,
+            stop_async_iteration,
             stop_iteration,
             syntax_error,
+            system_error,
+            system_exit,
+            tab_error,
+            timeout_error,
             type_error,
+            unbound_local_error,
+            unicode_decode_error,
+            unicode_encode_error,
+            unicode_error,
+            unicode_translate_error,
             value_error,
             zero_division_error,
             eof_error,
@@ -307,6 +811,7 @@ impl ExceptionZoo {
             resource_warning,
             runtime_warning,
             reference_error,
+            resource_limit_error,
             user_warning,
         }
     }
@@ -315,12 +820,66 @@ impl ExceptionZoo {
 pub fn init(context: &PyContext) {
     let base_exception_type = &context.exceptions.base_exception_type;
     extend_class!(context, base_exception_type, {
-        "__init__" => context.new_rustfunc(exception_init)
-    });
-
-    let exception_type = &context.exceptions.exception_type;
-    extend_class!(context, exception_type, {
+        "__new__" => context.new_rustfunc(exception_new),
+        "__init__" => context.new_rustfunc(exception_init),
         "__str__" => context.new_rustfunc(exception_str),
         "__repr__" => context.new_rustfunc(exception_repr),
+        "with_traceback" => context.new_rustfunc(exception_with_traceback),
+        "__cause__" => context.none(),
+        "__context__" => context.none(),
+        "__suppress_context__" => context.new_bool(false),
+    });
+
+    // The class attributes are defaults for subclasses whose __init__
+    // doesn't call the base class
+    extend_class!(context, &context.exceptions.system_exit, {
+        "__init__" => context.new_rustfunc(system_exit_init),
+        "code" => context.none(),
+    });
+
+    extend_class!(context, &context.exceptions.stop_iteration, {
+        "__init__" => context.new_rustfunc(stop_iteration_init),
+        "value" => context.none(),
+    });
+
+    extend_class!(context, &context.exceptions.import_error, {
+        "__init__" => context.new_rustfunc(import_error_init),
+        "msg" => context.none(),
+        "name" => context.none(),
+        "path" => context.none(),
     });
+
+    extend_class!(context, &context.exceptions.key_error, {
+        "__str__" => context.new_rustfunc(key_error_str),
+    });
+
+    extend_class!(context, &context.exceptions.os_error, {
+        "__new__" => context.new_rustfunc(os_error_new),
+        "__init__" => context.new_rustfunc(os_error_init),
+        "__str__" => context.new_rustfunc(os_error_str),
+        "errno" => context.none(),
+        "strerror" => context.none(),
+        "filename" => context.none(),
+        "filename2" => context.none(),
+    });
+
+    extend_class!(context, &context.exceptions.syntax_error, {
+        "__init__" => context.new_rustfunc(syntax_error_init),
+        "__str__" => context.new_rustfunc(syntax_error_str),
+        "msg" => context.none(),
+        "filename" => context.none(),
+        "lineno" => context.none(),
+        "offset" => context.none(),
+        "text" => context.none(),
+    });
+
+    for unicode_error in &[
+        &context.exceptions.unicode_decode_error,
+        &context.exceptions.unicode_encode_error,
+    ] {
+        extend_class!(context, unicode_error, {
+            "__init__" => context.new_rustfunc(unicode_error_init),
+            "__str__" => context.new_rustfunc(unicode_error_str),
+        });
+    }
 }
@@ -1,21 +1,23 @@
 use std::cell::RefCell;
 use std::fmt;
-use std::rc::Rc;
 
 use crate::builtins;
 use crate::bytecode;
 use crate::function::PyFuncArgs;
 use crate::obj::objbool;
-use crate::obj::objcode::PyCodeRef;
+use crate::obj::objcell::{PyCell, PyCellRef};
+use crate::obj::objcode::{NameCache, PyCodeRef};
 use crate::obj::objdict::{PyDict, PyDictRef};
+use crate::obj::objint::PyInt;
 use crate::obj::objiter;
 use crate::obj::objlist;
 use crate::obj::objslice::PySlice;
 use crate::obj::objstr;
-use crate::obj::objstr::PyString;
-use crate::obj::objtuple::PyTuple;
+use crate::obj::objstr::{PyString, PyStringRef};
+use crate::obj::objtuple::{PyTuple, PyTupleRef};
 use crate::obj::objtype;
 use crate::obj::objtype::PyClassRef;
+use crate::obj::objweakref::PyWeak;
 use crate::pyobject::{
     IdProtocol, ItemProtocol, PyContext, PyObjectRef, PyRef, PyResult, PyValue, TryFromObject,
     TypeProtocol,
@@ -24,60 +26,13 @@ use crate::vm::VirtualMachine;
 use indexmap::IndexMap;
 use itertools::Itertools;
 
-/*
- * So a scope is a linked list of scopes.
- * When a name is looked up, it is check in its scope.
- */
-#[derive(Debug)]
-struct RcListNode<T> {
-    elem: T,
-    next: Option<Rc<RcListNode<T>>>,
-}
-
-#[derive(Debug, Clone)]
-struct RcList<T> {
-    head: Option<Rc<RcListNode<T>>>,
-}
-
-struct Iter<'a, T: 'a> {
-    next: Option<&'a RcListNode<T>>,
-}
-
-impl<T> RcList<T> {
-    pub fn new() -> Self {
-        RcList { head: None }
-    }
-
-    pub fn insert(self, elem: T) -> Self {
-        RcList {
-            head: Some(Rc::new(RcListNode {
-                elem,
-                next: self.head,
-            })),
-        }
-    }
-
-    pub fn iter(&self) -> Iter<T> {
-        Iter {
-            next: self.head.as_ref().map(|node| &**node),
-        }
-    }
-}
-
-impl<'a, T> Iterator for Iter<'a, T> {
-    type Item = &'a T;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        self.next.map(|node| {
-            self.next = node.next.as_ref().map(|node| &**node);
-            &node.elem
-        })
-    }
-}
-
+/// The namespaces in which the names of a frame are looked up. Module code
+/// uses its globals as locals, while functions and class bodies have their own
+/// locals dict. The variables of enclosing functions are not part of the scope,
+/// as they are passed to nested functions in cells.
 #[derive(Clone)]
 pub struct Scope {
-    locals: RcList<PyDictRef>,
+    locals: Option<PyDictRef>,
     pub globals: PyDictRef,
 }
 
@@ -90,10 +45,6 @@ impl fmt::Debug for Scope {
 
 impl Scope {
     pub fn new(locals: Option<PyDictRef>, globals: PyDictRef) -> Scope {
-        let locals = match locals {
-            Some(dict) => RcList::new().insert(dict),
-            None => RcList::new(),
-        };
         Scope { locals, globals }
     }
 
@@ -112,19 +63,19 @@ impl Scope {
     }
 
     pub fn get_locals(&self) -> PyDictRef {
-        match self.locals.iter().next() {
+        match &self.locals {
             Some(dict) => dict.clone(),
             None => self.globals.clone(),
         }
     }
 
     pub fn get_only_locals(&self) -> Option<PyDictRef> {
-        self.locals.iter().next().cloned()
+        self.locals.clone()
     }
 
     pub fn new_child_scope_with_locals(&self, locals: PyDictRef) -> Scope {
         Scope {
-            locals: self.locals.clone().insert(locals),
+            locals: Some(locals),
             globals: self.globals.clone(),
         }
     }
@@ -138,59 +89,47 @@ pub trait NameProtocol {
     fn load_name(&self, vm: &VirtualMachine, name: &str) -> Option<PyObjectRef>;
     fn store_name(&self, vm: &VirtualMachine, name: &str, value: PyObjectRef);
     fn delete_name(&self, vm: &VirtualMachine, name: &str) -> PyResult;
-    fn load_cell(&self, vm: &VirtualMachine, name: &str) -> Option<PyObjectRef>;
-    fn store_cell(&self, vm: &VirtualMachine, name: &str, value: PyObjectRef);
     fn load_global(&self, vm: &VirtualMachine, name: &str) -> Option<PyObjectRef>;
     fn store_global(&self, vm: &VirtualMachine, name: &str, value: PyObjectRef);
+    fn delete_global(&self, vm: &VirtualMachine, name: &str) -> PyResult;
 }
 
 impl NameProtocol for Scope {
     fn load_name(&self, vm: &VirtualMachine, name: &str) -> Option<PyObjectRef> {
-        for dict in self.locals.iter() {
-            if let Some(value) = dict.get_item_option(name, vm).unwrap() {
+        if let Some(dict) = &self.locals {
+            if let Some(value) = dict.get_item_option(vm.ctx.intern_str(name), vm).unwrap() {
                 return Some(value);
             }
         }
-
-        if let Some(value) = self.globals.get_item_option(name, vm).unwrap() {
-            return Some(value);
-        }
-
-        vm.get_attribute(vm.builtins.clone(), name).ok()
-    }
-
-    fn load_cell(&self, vm: &VirtualMachine, name: &str) -> Option<PyObjectRef> {
-        for dict in self.locals.iter().skip(1) {
-            if let Some(value) = dict.get_item_option(name, vm).unwrap() {
-                return Some(value);
-            }
-        }
-        None
-    }
-
-    fn store_cell(&self, vm: &VirtualMachine, name: &str, value: PyObjectRef) {
-        self.locals
-            .iter()
-            .nth(1)
-            .expect("no outer scope for non-local")
-            .set_item(name, value, vm)
-            .unwrap();
+        self.load_global(vm, name)
     }
 
     fn store_name(&self, vm: &VirtualMachine, key: &str, value: PyObjectRef) {
-        self.get_locals().set_item(key, value, vm).unwrap();
+        self.get_locals()
+            .set_item(vm.ctx.intern_str(key), value, vm)
+            .unwrap();
     }
 
     fn delete_name(&self, vm: &VirtualMachine, key: &str) -> PyResult {
-        self.get_locals().del_item(key, vm)
+        self.get_locals().del_item(vm.ctx.intern_str(key), vm)
     }
 
     fn load_global(&self, vm: &VirtualMachine, name: &str) -> Option<PyObjectRef> {
-        self.globals.get_item_option(name, vm).unwrap()
+        let name = vm.ctx.intern_str(name);
+        if let Some(value) = self.globals.get_item_option(name.clone(), vm).unwrap() {
+            return Some(value);
+        }
+        vm.get_attribute(vm.builtins.clone(), name).ok()
     }
 
     fn store_global(&self, vm: &VirtualMachine, name: &str, value: PyObjectRef) {
-        self.globals.set_item(name, value, vm).unwrap();
+        self.globals
+            .set_item(vm.ctx.intern_str(name), value, vm)
+            .unwrap();
+    }
+
+    fn delete_global(&self, vm: &VirtualMachine, name: &str) -> PyResult {
+        self.globals.del_item(vm.ctx.intern_str(name), vm)
     }
 }
 
@@ -211,6 +150,13 @@ enum BlockType {
     TryExcept {
         handler: bytecode::Label,
     },
+    Finally {
+        handler: bytecode::Label,
+    },
+    /// Active finally sequence, which resumes `reason` when it ends.
+    FinallyHandler {
+        reason: Option<UnwindReason>,
+    },
     With {
         end: bytecode::Label,
         context_manager: PyObjectRef,
@@ -218,15 +164,33 @@ enum BlockType {
     ExceptHandler,
 }
 
+/// The reason why we might be unwinding the block stack.
+#[derive(Clone, Debug)]
+enum UnwindReason {
+    /// We are returning a value from a return statement.
+    Returning { value: PyObjectRef },
+    /// We hit an exception, so unwind to the nearest handler.
+    Raising { exception: PyObjectRef },
+    /// We hit a break statement.
+    Break,
+    /// We hit a continue statement.
+    Continue,
+}
+
 pub type FrameRef = PyRef<Frame>;
 
 pub struct Frame {
-    pub code: bytecode::CodeObject,
+    pub code: PyCodeRef,
     // We need 1 stack per frame
     stack: RefCell<Vec<PyObjectRef>>, // The main data frame of the stack machine
     blocks: RefCell<Vec<Block>>,      // Block frames, for controlling loops and exceptions
     pub scope: Scope,                 // Variables
-    pub lasti: RefCell<usize>,        // index of last instruction ran
+    /// Values of the local variables named by `code.varnames`.
+    fastlocals: RefCell<Vec<Option<PyObjectRef>>>,
+    /// Cells of the variables named by `code.cellvars` followed by those
+    /// named by `code.freevars`, which come from the closure.
+    cells: Vec<PyCellRef>,
+    pub lasti: RefCell<usize>, // index of last instruction ran
 }
 
 impl PyValue for Frame {
@@ -245,7 +209,12 @@ pub enum ExecutionResult {
 pub type FrameResult = Result<Option<ExecutionResult>, PyObjectRef>;
 
 impl Frame {
-    pub fn new(code: PyCodeRef, scope: Scope) -> Frame {
+    pub fn new(
+        code: PyCodeRef,
+        scope: Scope,
+        closure: Option<&PyTupleRef>,
+        vm: &VirtualMachine,
+    ) -> Frame {
         //populate the globals and locals
         //TODO: This is wrong, check https://github.com/nedbat/byterun/blob/31e6c4a8212c35b5157919abff43a7daa0f377c6/byterun/pyvm2.py#L95
         /*
@@ -257,17 +226,127 @@ impl Frame {
         // let locals = globals;
         // locals.extend(callargs);
 
+        let new_cell = || PyCell::default().into_ref(vm);
+        let mut cells: Vec<PyCellRef> = code.cellvars.iter().map(|_| new_cell()).collect();
+        let closure = closure.map_or(&[] as &[PyObjectRef], |closure| &closure.elements);
+        for index in 0..code.freevars.len() {
+            let cell = closure
+                .get(index)
+                .and_then(|cell| cell.clone().downcast().ok())
+                .unwrap_or_else(new_cell);
+            cells.push(cell);
+        }
+
+        let fastlocals = RefCell::new(vec![None; code.varnames.len()]);
         Frame {
-            code: code.code.clone(),
+            code,
             stack: RefCell::new(vec![]),
             blocks: RefCell::new(vec![]),
             // save the callargs as locals
             // globals: locals.clone(),
             scope,
+            fastlocals,
+            cells,
             lasti: RefCell::new(0),
         }
     }
 
+    fn fast_index(&self, name: &str) -> Option<usize> {
+        self.code
+            .varnames
+            .iter()
+            .position(|varname| varname == name)
+    }
+
+    fn cell_index(&self, name: &str) -> Option<usize> {
+        self.code
+            .cellvars
+            .iter()
+            .position(|cellvar| cellvar == name)
+    }
+
+    /// Bind a local variable, such as an argument of the function being called.
+    pub fn store_local(&self, vm: &VirtualMachine, name: &str, value: PyObjectRef) {
+        if let Some(index) = self.cell_index(name) {
+            self.cells[index].set(Some(value));
+            return;
+        }
+        match self.fast_index(name) {
+            Some(index) => self.fastlocals.borrow_mut()[index] = Some(value),
+            None => self.scope.store_name(vm, name, value),
+        }
+    }
+
+    /// Look up a variable bound in the local scope of this frame.
+    pub fn load_local(&self, vm: &VirtualMachine, name: &str) -> Option<PyObjectRef> {
+        if let Some(index) = self.cell_index(name) {
+            return self.cells[index].get();
+        }
+        match self.fast_index(name) {
+            Some(index) => self.fastlocals.borrow()[index].clone(),
+            None => self.scope.get_locals().get_item_option(name, vm).unwrap(),
+        }
+    }
+
+    /// Look up a free variable, such as `__class__` in methods.
+    pub fn load_free(&self, name: &str) -> Option<Option<PyObjectRef>> {
+        self.code
+            .freevars
+            .iter()
+            .position(|freevar| freevar == name)
+            .map(|index| self.cells[self.code.cellvars.len() + index].get())
+    }
+
+    /// The local variables as a dict, with the current values of the fast
+    /// locals and, in functions, of the cell and free variables copied into it.
+    pub fn get_locals(&self, vm: &VirtualMachine) -> PyDictRef {
+        let locals = self.scope.get_locals();
+        let fastlocals = self.fastlocals.borrow();
+        let mut variables: Vec<(&String, Option<PyObjectRef>)> = self
+            .code
+            .varnames
+            .iter()
+            .zip(fastlocals.iter().cloned())
+            .collect();
+        if self.code.is_optimized {
+            let derefs = self.code.cellvars.iter().chain(&self.code.freevars);
+            variables.extend(derefs.zip(self.cells.iter().map(|cell| cell.get())));
+        }
+        for (name, value) in variables {
+            match value {
+                Some(value) => {
+                    locals.set_item(name, value, vm).unwrap();
+                }
+                None => {
+                    if locals.contains_key(name, vm) {
+                        locals.del_item(name, vm).unwrap();
+                    }
+                }
+            }
+        }
+        locals
+    }
+
+    fn unbound_local_error(&self, vm: &VirtualMachine, name: &str) -> PyObjectRef {
+        vm.new_exception(
+            vm.ctx.exceptions.unbound_local_error.clone(),
+            format!("local variable '{}' referenced before assignment", name),
+        )
+    }
+
+    /// The error for a cell or free variable which has no value.
+    fn unbound_deref_error(&self, vm: &VirtualMachine, index: usize) -> PyObjectRef {
+        let name = self.code.deref_name(index);
+        if index < self.code.cellvars.len() {
+            self.unbound_local_error(vm, name)
+        } else {
+            vm.new_name_error(format!(
+                "free variable '{}' referenced before assignment in enclosing scope",
+                name
+            ))
+        }
+    }
+
     pub fn run(&self, vm: &VirtualMachine) -> Result<ExecutionResult, PyObjectRef> {
         let filename = &self.code.source_path.to_string();
 
@@ -277,7 +356,14 @@ impl Frame {
         // Execute until return or exception:
         loop {
             let lineno = self.get_lineno();
-            let result = self.execute_instruction(vm);
+            // Signal handlers and the periodic callback run between
+            // instructions, and whatever they or an exceeded resource limit
+            // raise is handled as if the next instruction raised it
+            let result = vm
+                .check_signals()
+                .and_then(|()| vm.limits.check(vm))
+                .and_then(|()| vm.check_periodic_callback())
+                .and_then(|()| self.execute_instruction(vm));
             match result {
                 Ok(None) => {}
                 Ok(Some(value)) => {
@@ -292,19 +378,37 @@ impl Frame {
                         &exception,
                         &vm.ctx.exceptions.base_exception_type
                     ));
-                    let traceback = vm
+                    // Start a new traceback if user code replaced it with something else
+                    let traceback = match vm
                         .get_attribute(exception.clone(), "__traceback__")
-                        .unwrap();
+                        .ok()
+                        .and_then(|traceback| traceback.downcast::<objlist::PyList>().ok())
+                    {
+                        Some(traceback) => traceback,
+                        None => {
+                            let traceback = vm.ctx.new_list(vec![]);
+                            vm.set_attr(&exception, "__traceback__", traceback.clone())?;
+                            traceback.downcast().unwrap()
+                        }
+                    };
+                    // An exception without traceback entries was raised from native
+                    // code called by this frame, so this is where it occurred.
+                    if traceback.elements.borrow().is_empty() {
+                        vm.contextualize_exception(&exception)?;
+                    }
                     trace!("Adding to traceback: {:?} {:?}", traceback, lineno);
                     let raise_location = vm.ctx.new_tuple(vec![
                         vm.ctx.new_str(filename.clone()),
                         vm.ctx.new_int(lineno.row()),
                         vm.ctx.new_str(run_obj_name.clone()),
                     ]);
-                    objlist::PyListRef::try_from_object(vm, traceback)?.append(raise_location, vm);
-                    match self.unwind_exception(vm, exception) {
-                        None => {}
-                        Some(exception) => {
+                    traceback.elements.borrow_mut().push(raise_location);
+                    match self.unwind_blocks(vm, UnwindReason::Raising { exception }) {
+                        Ok(None) => {}
+                        Ok(Some(value)) => {
+                            break Ok(value);
+                        }
+                        Err(exception) => {
                             // TODO: append line number to traceback?
                             // traceback.append();
                             break Err(exception);
@@ -320,9 +424,10 @@ impl Frame {
         vm: &VirtualMachine,
         exception: PyObjectRef,
     ) -> Result<ExecutionResult, PyObjectRef> {
-        match self.unwind_exception(vm, exception) {
-            None => self.run(vm),
-            Some(exception) => Err(exception),
+        match self.unwind_blocks(vm, UnwindReason::Raising { exception }) {
+            Ok(None) => self.run(vm),
+            Ok(Some(result)) => Ok(result),
+            Err(exception) => Err(exception),
         }
     }
 
@@ -348,29 +453,86 @@ impl Frame {
         }
 
         match &instruction {
-            bytecode::Instruction::LoadConst { ref value } => {
-                let obj = vm.ctx.unwrap_constant(value);
-                self.push_value(obj);
+            bytecode::Instruction::LoadConst { index } => {
+                self.push_value(self.code.consts[*index].clone());
                 Ok(None)
             }
             bytecode::Instruction::Import {
-                ref name,
-                ref symbols,
-                ref level,
-            } => self.import(vm, name, symbols, level),
-            bytecode::Instruction::ImportStar {
-                ref name,
-                ref level,
-            } => self.import_star(vm, name, level),
-            bytecode::Instruction::LoadName {
-                ref name,
-                ref scope,
-            } => self.load_name(vm, name, scope),
-            bytecode::Instruction::StoreName {
-                ref name,
-                ref scope,
-            } => self.store_name(vm, name, scope),
-            bytecode::Instruction::DeleteName { ref name } => self.delete_name(vm, name),
+                name_index,
+                ref symbol_indices,
+                level,
+            } => self.import(vm, &self.code.names[*name_index], symbol_indices, *level),
+            bytecode::Instruction::ImportStar { name_index, level } => {
+                self.import_star(vm, &self.code.names[*name_index], *level)
+            }
+            bytecode::Instruction::LoadName { index, ref scope } => {
+                if *scope == bytecode::NameScope::Global || self.scope.locals.is_none() {
+                    self.load_global(vm, *index)
+                } else {
+                    self.load_name(vm, &self.code.names[*index], scope)
+                }
+            }
+            bytecode::Instruction::StoreName { index, ref scope } => {
+                self.store_name(vm, &self.code.names[*index], scope)
+            }
+            bytecode::Instruction::DeleteName { index, ref scope } => {
+                self.delete_name(vm, &self.code.names[*index], scope)
+            }
+            bytecode::Instruction::LoadFast { index } => {
+                let value = self.fastlocals.borrow()[*index].clone();
+                match value {
+                    Some(value) => {
+                        self.push_value(value);
+                        Ok(None)
+                    }
+                    None => Err(self.unbound_local_error(vm, &self.code.varnames[*index])),
+                }
+            }
+            bytecode::Instruction::StoreFast { index } => {
+                let value = self.pop_value();
+                self.fastlocals.borrow_mut()[*index] = Some(value);
+                Ok(None)
+            }
+            bytecode::Instruction::DeleteFast { index } => {
+                match self.fastlocals.borrow_mut()[*index].take() {
+                    Some(_) => Ok(None),
+                    None => Err(self.unbound_local_error(vm, &self.code.varnames[*index])),
+                }
+            }
+            bytecode::Instruction::LoadClosure { index } => {
+                self.push_value(self.cells[*index].clone().into_object());
+                Ok(None)
+            }
+            bytecode::Instruction::LoadDeref { index } => match self.cells[*index].get() {
+                Some(value) => {
+                    self.push_value(value);
+                    Ok(None)
+                }
+                None => Err(self.unbound_deref_error(vm, *index)),
+            },
+            bytecode::Instruction::LoadClassDeref { index } => {
+                let name = self.code.deref_name(*index);
+                let value = match self.scope.get_locals().get_item_option(name, vm)? {
+                    Some(value) => value,
+                    None => self.cells[*index]
+                        .get()
+                        .ok_or_else(|| self.unbound_deref_error(vm, *index))?,
+                };
+                self.push_value(value);
+                Ok(None)
+            }
+            bytecode::Instruction::StoreDeref { index } => {
+                let value = self.pop_value();
+                self.cells[*index].set(Some(value));
+                Ok(None)
+            }
+            bytecode::Instruction::DeleteDeref { index } => match self.cells[*index].get() {
+                Some(_) => {
+                    self.cells[*index].set(None);
+                    Ok(None)
+                }
+                None => Err(self.unbound_deref_error(vm, *index)),
+            },
             bytecode::Instruction::StoreSubscript => self.execute_store_subscript(vm),
             bytecode::Instruction::DeleteSubscript => self.execute_delete_subscript(vm),
             bytecode::Instruction::Pop => {
@@ -439,20 +601,35 @@ impl Frame {
                 self.push_value(list_obj);
                 Ok(None)
             }
-            bytecode::Instruction::BuildMap { size, unpack } => {
+            bytecode::Instruction::BuildMap {
+                size,
+                unpack,
+                for_call,
+            } => {
                 let map_obj = vm.ctx.new_dict();
                 if *unpack {
                     for obj in self.pop_multiple(*size) {
-                        // Take all key-value pairs from the dict:
-                        let dict: PyDictRef =
-                            obj.downcast().expect("Need a dictionary to build a map.");
-                        for (key, value) in dict {
-                            map_obj.set_item(key, value, vm).unwrap();
+                        // Take all key-value pairs from the mapping:
+                        for (key, value) in self.mapping_items(vm, obj, *for_call)? {
+                            if *for_call {
+                                if !objtype::isinstance(&key, &vm.ctx.str_type()) {
+                                    return Err(
+                                        vm.new_type_error("keywords must be strings".to_string())
+                                    );
+                                }
+                                if map_obj.contains_key(key.clone(), vm) {
+                                    return Err(vm.new_type_error(format!(
+                                        "got multiple values for keyword argument '{}'",
+                                        objstr::get_value(&key)
+                                    )));
+                                }
+                            }
+                            map_obj.set_item(key, value, vm)?;
                         }
                     }
                 } else {
                     for (key, value) in self.pop_multiple(2 * size).into_iter().tuples() {
-                        map_obj.set_item(key, value, vm).unwrap();
+                        map_obj.set_item(key, value, vm)?;
                     }
                 }
 
@@ -482,7 +659,7 @@ impl Frame {
             bytecode::Instruction::ListAppend { i } => {
                 let list_obj = self.nth_value(*i);
                 let item = self.pop_value();
-                objlist::PyListRef::try_from_object(vm, list_obj)?.append(item, vm);
+                objlist::PyListRef::try_from_object(vm, list_obj)?.append(item, vm)?;
                 Ok(None)
             }
             bytecode::Instruction::SetAdd { i } => {
@@ -501,18 +678,21 @@ impl Frame {
             bytecode::Instruction::BinaryOperation { ref op, inplace } => {
                 self.execute_binop(vm, op, *inplace)
             }
-            bytecode::Instruction::LoadAttr { ref name } => self.load_attr(vm, name),
-            bytecode::Instruction::StoreAttr { ref name } => self.store_attr(vm, name),
-            bytecode::Instruction::DeleteAttr { ref name } => self.delete_attr(vm, name),
+            bytecode::Instruction::LoadAttr { index } => {
+                self.load_attr(vm, &self.code.interned_names[*index])
+            }
+            bytecode::Instruction::LoadMethod { index } => self.load_method(vm, *index),
+            bytecode::Instruction::StoreAttr { index } => {
+                self.store_attr(vm, &self.code.interned_names[*index])
+            }
+            bytecode::Instruction::DeleteAttr { index } => {
+                self.delete_attr(vm, &self.code.interned_names[*index])
+            }
             bytecode::Instruction::UnaryOperation { ref op } => self.execute_unop(vm, op),
             bytecode::Instruction::CompareOperation { ref op } => self.execute_compare(vm, op),
             bytecode::Instruction::ReturnValue => {
                 let value = self.pop_value();
-                if let Some(exc) = self.unwind_blocks(vm) {
-                    Err(exc)
-                } else {
-                    Ok(Some(ExecutionResult::Return(value)))
-                }
+                self.unwind_blocks(vm, UnwindReason::Returning { value })
             }
             bytecode::Instruction::YieldValue => {
                 let value = self.pop_value();
@@ -523,19 +703,20 @@ impl Frame {
                 self.pop_value();
 
                 let top_of_stack = self.last_value();
-                let next_obj = objiter::get_next_object(vm, &top_of_stack)?;
-
-                match next_obj {
-                    Some(value) => {
+                match objiter::call_next(vm, &top_of_stack) {
+                    Ok(value) => {
                         // Set back program counter:
                         *self.lasti.borrow_mut() -= 1;
                         Ok(Some(ExecutionResult::Yield(value)))
                     }
-                    None => {
-                        // Pop iterator from stack:
+                    Err(err) if objtype::isinstance(&err, &vm.ctx.exceptions.stop_iteration) => {
+                        // Replace the iterator with the value of the `yield from` expression:
+                        let value = vm.get_attribute(err, "value")?;
                         self.pop_value();
+                        self.push_value(value);
                         Ok(None)
                     }
+                    Err(err) => Err(err),
                 }
             }
             bytecode::Instruction::SetupLoop { start, end } => {
@@ -549,6 +730,29 @@ impl Frame {
                 self.push_block(BlockType::TryExcept { handler: *handler });
                 Ok(None)
             }
+            bytecode::Instruction::SetupFinally { handler } => {
+                self.push_block(BlockType::Finally { handler: *handler });
+                Ok(None)
+            }
+            bytecode::Instruction::EnterFinally => {
+                self.push_block(BlockType::FinallyHandler { reason: None });
+                Ok(None)
+            }
+            bytecode::Instruction::EndFinally => {
+                // Resume whatever brought us into the finally block:
+                let block = self.pop_block().unwrap();
+                if let BlockType::FinallyHandler { reason } = block.typ {
+                    if let Some(UnwindReason::Raising { .. }) = reason {
+                        vm.pop_exception();
+                    }
+                    match reason {
+                        Some(reason) => self.unwind_blocks(vm, reason),
+                        None => Ok(None),
+                    }
+                } else {
+                    unreachable!("Block stack is incorrect, expected a finally handler block");
+                }
+            }
             bytecode::Instruction::SetupWith { end } => {
                 let context_manager = self.pop_value();
                 // Call enter:
@@ -621,6 +825,16 @@ impl Frame {
                     .downcast()
                     .expect("Second to top value on the stack must be a code object");
 
+                let closure = if flags.contains(bytecode::FunctionOpArg::HAS_CLOSURE) {
+                    Some(
+                        self.pop_value()
+                            .downcast::<PyTuple>()
+                            .expect("Stack value for closure expected to be a tuple"),
+                    )
+                } else {
+                    None
+                };
+
                 let annotations = if flags.contains(bytecode::FunctionOpArg::HAS_ANNOTATIONS) {
                     self.pop_value()
                 } else {
@@ -650,10 +864,12 @@ impl Frame {
 
                 // pop argc arguments
                 // argument: name, args, globals
-                let scope = self.scope.clone();
-                let func_obj = vm
-                    .ctx
-                    .new_function(code_obj, scope, defaults, kw_only_defaults);
+                // Functions only need the globals, as they get the variables
+                // of enclosing functions from their closure:
+                let scope = Scope::new(None, self.scope.globals.clone());
+                let func_obj =
+                    vm.ctx
+                        .new_function(code_obj, scope, defaults, kw_only_defaults, closure);
 
                 let name = qualified_name.value.split('.').next_back().unwrap();
                 vm.set_attr(&func_obj, "__name__", vm.new_str(name.to_string()))?;
@@ -670,44 +886,18 @@ impl Frame {
                 Ok(None)
             }
             bytecode::Instruction::CallFunction { typ } => {
-                let args = match typ {
-                    bytecode::CallType::Positional(count) => {
-                        let args: Vec<PyObjectRef> = self.pop_multiple(*count);
-                        PyFuncArgs {
-                            args,
-                            kwargs: IndexMap::new(),
-                        }
-                    }
-                    bytecode::CallType::Keyword(count) => {
-                        let kwarg_names = self.pop_value();
-                        let args: Vec<PyObjectRef> = self.pop_multiple(*count);
-
-                        let kwarg_names = vm
-                            .extract_elements(&kwarg_names)?
-                            .iter()
-                            .map(|pyobj| objstr::get_value(pyobj))
-                            .collect();
-                        PyFuncArgs::new(args, kwarg_names)
-                    }
-                    bytecode::CallType::Ex(has_kwargs) => {
-                        let kwargs = if *has_kwargs {
-                            let kw_dict: PyDictRef =
-                                self.pop_value().downcast().expect("Kwargs must be a dict.");
-                            kw_dict
-                                .into_iter()
-                                .map(|elem| (objstr::get_value(&elem.0), elem.1))
-                                .collect()
-                        } else {
-                            IndexMap::new()
-                        };
-                        let args = self.pop_value();
-                        let args = vm.extract_elements(&args)?;
-                        PyFuncArgs { args, kwargs }
-                    }
-                };
-
-                // Call function:
+                let args = self.pop_call_args(vm, typ)?;
+                let func_ref = self.pop_value();
+                let value = vm.invoke(func_ref, args)?;
+                self.push_value(value);
+                Ok(None)
+            }
+            bytecode::Instruction::CallMethod { typ } => {
+                let args = self.pop_call_args(vm, typ)?;
                 let func_ref = self.pop_value();
+                let is_method = self.pop_value().is(&vm.ctx.true_value);
+                let target = self.pop_value();
+                let args = if is_method { args.insert(target) } else { args };
                 let value = vm.invoke(func_ref, args)?;
                 self.push_value(value);
                 Ok(None)
@@ -736,62 +926,40 @@ impl Frame {
 
             bytecode::Instruction::Raise { argc } => {
                 let cause = match argc {
-                    2 => self.get_exception(vm, true)?,
-                    _ => vm.get_none(),
+                    2 => Some(self.get_exception(vm, true)?),
+                    _ => None,
                 };
                 let exception = match argc {
-                    0 => match vm.pop_exception() {
-                        Some(exc) => exc,
-                        None => {
-                            return Err(vm.new_exception(
+                    // A bare raise re-raises the exception being handled, as is.
+                    0 => {
+                        return Err(match vm.current_exception() {
+                            Some(exc) => exc,
+                            None => vm.new_exception(
                                 vm.ctx.exceptions.runtime_error.clone(),
                                 "No active exception to reraise".to_string(),
-                            ));
-                        }
-                    },
+                            ),
+                        });
+                    }
                     1 | 2 => self.get_exception(vm, false)?,
                     3 => panic!("Not implemented!"),
                     _ => panic!("Invalid parameter for RAISE_VARARGS, must be between 0 to 3"),
                 };
-                let context = match argc {
-                    0 => vm.get_none(), // We have already got the exception,
-                    _ => match vm.pop_exception() {
-                        Some(exc) => exc,
-                        None => vm.get_none(),
-                    },
-                };
-                info!(
-                    "Exception raised: {:?} with cause: {:?} and context: {:?}",
-                    exception, cause, context
-                );
-                vm.set_attr(&exception, vm.new_str("__cause__".to_string()), cause)?;
-                vm.set_attr(&exception, vm.new_str("__context__".to_string()), context)?;
+                info!("Exception raised: {:?} with cause: {:?}", exception, cause);
+                if let Some(cause) = cause {
+                    // `raise ... from ...` hides the context when printed
+                    vm.set_attr(&exception, "__cause__", cause)?;
+                    vm.set_attr(&exception, "__suppress_context__", vm.new_bool(true))?;
+                }
+                vm.contextualize_exception(&exception)?;
                 Err(exception)
             }
 
-            bytecode::Instruction::Break => {
-                let block = self.unwind_loop(vm);
-                if let BlockType::Loop { end, .. } = block.typ {
-                    self.pop_block();
-                    self.jump(end);
-                } else {
-                    unreachable!()
-                }
-                Ok(None)
-            }
+            bytecode::Instruction::Break => self.unwind_blocks(vm, UnwindReason::Break),
             bytecode::Instruction::Pass => {
                 // Ah, this is nice, just relax!
                 Ok(None)
             }
-            bytecode::Instruction::Continue => {
-                let block = self.unwind_loop(vm);
-                if let BlockType::Loop { start, .. } = block.typ {
-                    self.jump(start);
-                } else {
-                    unreachable!();
-                }
-                Ok(None)
-            }
+            bytecode::Instruction::Continue => self.unwind_blocks(vm, UnwindReason::Continue),
             bytecode::Instruction::PrintExpr => {
                 let expr = self.pop_value();
                 if !expr.is(&vm.get_none()) {
@@ -807,11 +975,25 @@ impl Frame {
                 self.push_value(vm.ctx.new_rustfunc(builtins::builtin_build_class_));
                 Ok(None)
             }
+            bytecode::Instruction::SetupAnnotations => {
+                let locals = self.scope.get_locals();
+                if !locals.contains_key("__annotations__", vm) {
+                    locals.set_item("__annotations__", vm.ctx.new_dict().into_object(), vm)?;
+                }
+                Ok(None)
+            }
             bytecode::Instruction::UnpackSequence { size } => {
                 let value = self.pop_value();
                 let elements = vm.extract_elements(&value)?;
-                if elements.len() != *size {
-                    Err(vm.new_value_error("Wrong number of values to unpack".to_string()))
+                if elements.len() > *size {
+                    Err(vm
+                        .new_value_error(format!("too many values to unpack (expected {})", size)))
+                } else if elements.len() < *size {
+                    Err(vm.new_value_error(format!(
+                        "not enough values to unpack (expected {}, got {})",
+                        size,
+                        elements.len()
+                    )))
                 } else {
                     for element in elements.into_iter().rev() {
                         self.push_value(element);
@@ -825,7 +1007,7 @@ impl Frame {
                 let min_expected = *before + *after;
                 if elements.len() < min_expected {
                     Err(vm.new_value_error(format!(
-                        "Not enough values to unpack (expected at least {}, got {}",
+                        "not enough values to unpack (expected at least {}, got {})",
                         min_expected,
                         elements.len()
                     )))
@@ -862,16 +1044,16 @@ impl Frame {
                 }
                 Ok(None)
             }
-            bytecode::Instruction::FormatValue { conversion, spec } => {
+            bytecode::Instruction::FormatValue { conversion } => {
                 use bytecode::ConversionFlag::*;
+                let spec = self.pop_value();
                 let value = match conversion {
                     Some(Str) => vm.to_str(&self.pop_value())?.into_object(),
                     Some(Repr) => vm.to_repr(&self.pop_value())?.into_object(),
-                    Some(Ascii) => self.pop_value(), // TODO
+                    Some(Ascii) => vm.new_str(builtins::builtin_ascii(self.pop_value(), vm)?),
                     None => self.pop_value(),
                 };
 
-                let spec = vm.new_str(spec.clone());
                 let formatted = vm.call_method(&value, "__format__", vec![spec])?;
                 self.push_value(formatted);
                 Ok(None)
@@ -909,18 +1091,56 @@ impl Frame {
         }
     }
 
+    /// The key-value pairs of a `**` unpacked mapping, which is either a dict
+    /// or an object with `keys()` and `__getitem__`.
+    fn mapping_items(
+        &self,
+        vm: &VirtualMachine,
+        obj: PyObjectRef,
+        for_call: bool,
+    ) -> PyResult<Vec<(PyObjectRef, PyObjectRef)>> {
+        if let Ok(dict) = obj.clone().downcast::<PyDict>() {
+            return Ok(dict.into_iter().collect());
+        }
+        let keys = match vm.get_method(obj.clone(), "keys") {
+            Some(keys) => vm.invoke(keys?, vec![])?,
+            None if for_call => {
+                return Err(vm.new_type_error(format!(
+                    "argument after ** must be a mapping, not {}",
+                    obj.class().name
+                )));
+            }
+            None => {
+                return Err(
+                    vm.new_type_error(format!("'{}' object is not a mapping", obj.class().name))
+                );
+            }
+        };
+        vm.extract_elements(&keys)?
+            .into_iter()
+            .map(|key| {
+                let value = obj.get_item(key.clone(), vm)?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+
     fn import(
         &self,
         vm: &VirtualMachine,
         module: &str,
-        symbols: &Vec<String>,
-        level: &usize,
+        symbol_indices: &[usize],
+        level: usize,
     ) -> FrameResult {
+        let symbols: Vec<&String> = symbol_indices
+            .iter()
+            .map(|index| &self.code.names[*index])
+            .collect();
         let from_list = symbols
             .iter()
             .map(|symbol| vm.ctx.new_str(symbol.to_string()))
             .collect();
-        let module = vm.import(module, &vm.ctx.new_tuple(from_list), *level)?;
+        let module = vm.import(module, &vm.ctx.new_tuple(from_list), level)?;
 
         if symbols.is_empty() {
             self.push_value(module);
@@ -935,8 +1155,8 @@ impl Frame {
         Ok(None)
     }
 
-    fn import_star(&self, vm: &VirtualMachine, module: &str, level: &usize) -> FrameResult {
-        let module = vm.import(module, &vm.ctx.new_tuple(vec![]), *level)?;
+    fn import_star(&self, vm: &VirtualMachine, module: &str, level: usize) -> FrameResult {
+        let module = vm.import(module, &vm.ctx.new_tuple(vec![]), level)?;
 
         // Grab all the names from the module and put them in the context
         if let Some(dict) = &module.dict {
@@ -947,103 +1167,98 @@ impl Frame {
         Ok(None)
     }
 
-    // Unwind all blocks:
-    fn unwind_blocks(&self, vm: &VirtualMachine) -> Option<PyObjectRef> {
-        while let Some(block) = self.pop_block() {
+    /// Unwind the block stack for the given reason, until a block handles it.
+    ///
+    /// Finally blocks always run, and resume the unwinding once they end.
+    /// Returns the result of the frame when no block is left to handle it.
+    fn unwind_blocks(&self, vm: &VirtualMachine, mut reason: UnwindReason) -> FrameResult {
+        while let Some(block) = self.current_block() {
             match block.typ {
-                BlockType::Loop { .. } => {}
-                BlockType::TryExcept { .. } => {
-                    // TODO: execute finally handler
-                }
-                BlockType::With {
-                    context_manager, ..
-                } => {
-                    match self.call_context_manager_exit_no_exception(vm, &context_manager) {
-                        Ok(..) => {}
-                        Err(exc) => {
-                            // __exit__ went wrong,
-                            return Some(exc);
-                        }
+                BlockType::Loop { start, end } => match &reason {
+                    UnwindReason::Break => {
+                        self.pop_block();
+                        self.jump(end);
+                        return Ok(None);
                     }
-                }
-                BlockType::ExceptHandler => {
-                    vm.pop_exception();
-                }
-            }
-        }
-
-        None
-    }
-
-    fn unwind_loop(&self, vm: &VirtualMachine) -> Block {
-        loop {
-            let block = self.current_block().expect("not in a loop");
-            match block.typ {
-                BlockType::Loop { .. } => break block,
-                BlockType::TryExcept { .. } => {
-                    // TODO: execute finally handler
-                }
-                BlockType::With {
-                    context_manager, ..
-                } => match self.call_context_manager_exit_no_exception(vm, &context_manager) {
-                    Ok(..) => {}
-                    Err(exc) => {
-                        panic!("Exception in with __exit__ {:?}", exc);
+                    UnwindReason::Continue => {
+                        self.jump(start);
+                        return Ok(None);
+                    }
+                    _ => {
+                        self.pop_block();
                     }
                 },
-                BlockType::ExceptHandler => {
-                    vm.pop_exception();
-                }
-            }
-
-            self.pop_block();
-        }
-    }
-
-    fn unwind_exception(&self, vm: &VirtualMachine, exc: PyObjectRef) -> Option<PyObjectRef> {
-        // unwind block stack on exception and find any handlers:
-        while let Some(block) = self.pop_block() {
-            match block.typ {
                 BlockType::TryExcept { handler } => {
-                    self.push_block(BlockType::ExceptHandler {});
-                    self.push_value(exc.clone());
-                    vm.push_exception(exc);
+                    self.pop_block();
+                    if let UnwindReason::Raising { exception } = &reason {
+                        self.push_block(BlockType::ExceptHandler {});
+                        self.push_value(exception.clone());
+                        vm.push_exception(exception.clone());
+                        self.jump(handler);
+                        return Ok(None);
+                    }
+                }
+                BlockType::Finally { handler } => {
+                    self.pop_block();
+                    // Exceptions raised by the finally body are chained to the pending one.
+                    if let UnwindReason::Raising { exception } = &reason {
+                        vm.push_exception(exception.clone());
+                    }
+                    self.push_block(BlockType::FinallyHandler {
+                        reason: Some(reason),
+                    });
                     self.jump(handler);
-                    return None;
+                    return Ok(None);
+                }
+                BlockType::FinallyHandler {
+                    reason: finally_reason,
+                } => {
+                    // Leaving a finally block early discards whatever it was going to resume.
+                    self.pop_block();
+                    if let Some(UnwindReason::Raising { .. }) = finally_reason {
+                        vm.pop_exception();
+                    }
                 }
                 BlockType::With {
                     end,
                     context_manager,
                 } => {
-                    match self.call_context_manager_exit(vm, &context_manager, exc.clone()) {
-                        Ok(exit_result_obj) => {
-                            match objbool::boolval(vm, exit_result_obj) {
-                                // If __exit__ method returned True, suppress the exception and continue execution.
-                                Ok(suppress_exception) => {
-                                    if suppress_exception {
-                                        self.jump(end);
-                                        return None;
-                                    } else {
-                                        // go on with the stack unwinding.
-                                    }
-                                }
-                                Err(exit_exc) => {
-                                    return Some(exit_exc);
-                                }
-                            }
+                    self.pop_block();
+                    let exit_result = match &reason {
+                        UnwindReason::Raising { exception } => self
+                            .call_context_manager_exit(vm, &context_manager, exception.clone())
+                            .and_then(|exit_result| objbool::boolval(vm, exit_result)),
+                        _ => self
+                            .call_context_manager_exit_no_exception(vm, &context_manager)
+                            .map(|_| false),
+                    };
+                    match exit_result {
+                        // If __exit__ method returned True, suppress the exception and continue execution.
+                        Ok(true) => {
+                            self.jump(end);
+                            return Ok(None);
                         }
-                        Err(exit_exc) => {
-                            // TODO: what about original exception?
-                            return Some(exit_exc);
+                        Ok(false) => {}
+                        // __exit__ went wrong, so unwind with its exception instead.
+                        Err(exception) => {
+                            reason = UnwindReason::Raising { exception };
                         }
                     }
                 }
-                BlockType::Loop { .. } => {}
-                // Exception was already popped on Raised.
-                BlockType::ExceptHandler => {}
+                BlockType::ExceptHandler => {
+                    self.pop_block();
+                    vm.pop_exception();
+                }
+            }
+        }
+
+        match reason {
+            UnwindReason::Raising { exception } => Err(exception),
+            UnwindReason::Returning { value } => Ok(Some(ExecutionResult::Return(value))),
+            UnwindReason::Break | UnwindReason::Continue => {
+                panic!("Internal error: break or continue must occur within a loop block.")
             }
         }
-        Some(exc)
     }
 
     fn call_context_manager_exit_no_exception(
@@ -1085,9 +1300,6 @@ impl Frame {
             bytecode::NameScope::Global => {
                 self.scope.store_global(vm, name, obj);
             }
-            bytecode::NameScope::NonLocal => {
-                self.scope.store_cell(vm, name, obj);
-            }
             bytecode::NameScope::Local => {
                 self.scope.store_name(vm, name, obj);
             }
@@ -1095,8 +1307,17 @@ impl Frame {
         Ok(None)
     }
 
-    fn delete_name(&self, vm: &VirtualMachine, name: &str) -> FrameResult {
-        match self.scope.delete_name(vm, name) {
+    fn delete_name(
+        &self,
+        vm: &VirtualMachine,
+        name: &str,
+        name_scope: &bytecode::NameScope,
+    ) -> FrameResult {
+        let result = match name_scope {
+            bytecode::NameScope::Global => self.scope.delete_global(vm, name),
+            bytecode::NameScope::Local => self.scope.delete_name(vm, name),
+        };
+        match result {
             Ok(_) => Ok(None),
             Err(_) => Err(vm.new_name_error(format!("name '{}' is not defined", name))),
         }
@@ -1110,7 +1331,6 @@ impl Frame {
     ) -> FrameResult {
         let optional_value = match name_scope {
             bytecode::NameScope::Global => self.scope.load_global(vm, name),
-            bytecode::NameScope::NonLocal => self.scope.load_cell(vm, name),
             bytecode::NameScope::Local => self.scope.load_name(&vm, name),
         };
 
@@ -1141,9 +1361,8 @@ impl Frame {
     }
 
     fn jump(&self, label: bytecode::Label) {
-        let target_pc = self.code.label_map[&label];
-        trace!("jump from {:?} to {:?}", self.lasti, target_pc);
-        *self.lasti.borrow_mut() = target_pc;
+        trace!("jump from {:?} to {:?}", self.lasti, label);
+        *self.lasti.borrow_mut() = label;
     }
 
     fn execute_binop(
@@ -1154,6 +1373,10 @@ impl Frame {
     ) -> FrameResult {
         let b_ref = self.pop_value();
         let a_ref = self.pop_value();
+        if let Some(value) = self.execute_int_binop(vm, op, &a_ref, &b_ref) {
+            self.push_value(value?);
+            return Ok(None);
+        }
         let value = match *op {
             bytecode::BinaryOperator::Subtract if inplace => vm._isub(a_ref, b_ref),
             bytecode::BinaryOperator::Subtract => vm._sub(a_ref, b_ref),
@@ -1190,6 +1413,45 @@ impl Frame {
         Ok(None)
     }
 
+    /// Apply a binary operator to two objects of exactly type `int` without
+    /// looking up its methods, which are assumed to be those of `int`, and
+    /// without the in-place variants, which `int` does not have.
+    fn execute_int_binop(
+        &self,
+        vm: &VirtualMachine,
+        op: &bytecode::BinaryOperator,
+        a: &PyObjectRef,
+        b: &PyObjectRef,
+    ) -> Option<PyResult> {
+        if !(a.class().is(&vm.ctx.int_type) && b.class().is(&vm.ctx.int_type)) {
+            return None;
+        }
+        let a = a.payload::<PyInt>()?;
+        let b = b.clone();
+        let value = match *op {
+            bytecode::BinaryOperator::Add => Ok(a.add(b, vm)),
+            bytecode::BinaryOperator::Subtract => Ok(a.sub(b, vm)),
+            bytecode::BinaryOperator::Multiply => Ok(a.mul(b, vm)),
+            bytecode::BinaryOperator::Divide => a.truediv(b, vm),
+            bytecode::BinaryOperator::FloorDivide => a.floordiv(b, vm),
+            bytecode::BinaryOperator::Modulo => a.mod_(b, vm),
+            bytecode::BinaryOperator::Power => a.pow(b, vm),
+            bytecode::BinaryOperator::Lshift => a.lshift(b, vm),
+            bytecode::BinaryOperator::Rshift => a.rshift(b, vm),
+            bytecode::BinaryOperator::Xor => Ok(a.xor(b, vm)),
+            bytecode::BinaryOperator::Or => Ok(a.or(b, vm)),
+            bytecode::BinaryOperator::And => Ok(a.and(b, vm)),
+            bytecode::BinaryOperator::MatrixMultiply | bytecode::BinaryOperator::Subscript => {
+                return None;
+            }
+        };
+        match value {
+            // Leave the operands to the reflected method and error reporting
+            Ok(ref value) if value.is(&vm.ctx.not_implemented) => None,
+            value => Some(value),
+        }
+    }
+
     fn execute_unop(&self, vm: &VirtualMachine, op: &bytecode::UnaryOperator) -> FrameResult {
         let a = self.pop_value();
         let value = match *op {
@@ -1237,6 +1499,23 @@ impl Frame {
     ) -> FrameResult {
         let b = self.pop_value();
         let a = self.pop_value();
+        if a.class().is(&vm.ctx.int_type) && b.class().is(&vm.ctx.int_type) {
+            // Compare ints without looking up their methods, like `execute_int_binop`
+            let int = a.payload::<PyInt>().unwrap();
+            let value = match *op {
+                bytecode::ComparisonOperator::Equal => Some(int.eq(b.clone(), vm)),
+                bytecode::ComparisonOperator::NotEqual => Some(int.ne(b.clone(), vm)),
+                bytecode::ComparisonOperator::Less => Some(int.lt(b.clone(), vm)),
+                bytecode::ComparisonOperator::LessOrEqual => Some(int.le(b.clone(), vm)),
+                bytecode::ComparisonOperator::Greater => Some(int.gt(b.clone(), vm)),
+                bytecode::ComparisonOperator::GreaterOrEqual => Some(int.ge(b.clone(), vm)),
+                _ => None,
+            };
+            if let Some(value) = value {
+                self.push_value(value);
+                return Ok(None);
+            }
+        }
         let value = match *op {
             bytecode::ComparisonOperator::Equal => vm._eq(a, b)?,
             bytecode::ComparisonOperator::NotEqual => vm._ne(a, b)?,
@@ -1254,24 +1533,184 @@ impl Frame {
         Ok(None)
     }
 
-    fn load_attr(&self, vm: &VirtualMachine, attr_name: &str) -> FrameResult {
+    /// Pop the arguments of a call, passed as described by `typ`.
+    fn pop_call_args(&self, vm: &VirtualMachine, typ: &bytecode::CallType) -> PyResult<PyFuncArgs> {
+        let args = match typ {
+            bytecode::CallType::Positional(count) => {
+                let args: Vec<PyObjectRef> = self.pop_multiple(*count);
+                PyFuncArgs {
+                    args,
+                    kwargs: IndexMap::new(),
+                }
+            }
+            bytecode::CallType::Keyword(count) => {
+                let kwarg_names = self.pop_value();
+                let args: Vec<PyObjectRef> = self.pop_multiple(*count);
+
+                let kwarg_names = vm
+                    .extract_elements(&kwarg_names)?
+                    .iter()
+                    .map(|pyobj| objstr::get_value(pyobj))
+                    .collect();
+                PyFuncArgs::new(args, kwarg_names)
+            }
+            bytecode::CallType::Ex(has_kwargs) => {
+                let kwargs = if *has_kwargs {
+                    let kw_dict: PyDictRef =
+                        self.pop_value().downcast().expect("Kwargs must be a dict.");
+                    kw_dict
+                        .into_iter()
+                        .map(|elem| (objstr::get_value(&elem.0), elem.1))
+                        .collect()
+                } else {
+                    IndexMap::new()
+                };
+                let args = self.pop_value();
+                let args = vm.extract_elements(&args)?;
+                PyFuncArgs { args, kwargs }
+            }
+        };
+        Ok(args)
+    }
+
+    /// Load a global or builtin variable, from the cache of the name as long
+    /// as neither the globals nor the builtins changed.
+    fn load_global(&self, vm: &VirtualMachine, index: usize) -> FrameResult {
+        let globals_version = self.scope.globals.version();
+        let builtins = vm.builtins.dict.as_ref();
+        let builtins_version = builtins.map_or(0, |builtins| builtins.version());
+        if let NameCache::Global {
+            globals_version: cached_globals_version,
+            builtins_version: cached_builtins_version,
+            value,
+        } = &self.code.name_caches.borrow()[index]
+        {
+            if *cached_globals_version == globals_version
+                && *cached_builtins_version == builtins_version
+            {
+                if let Some(value) = value.upgrade() {
+                    self.push_value(value);
+                    return Ok(None);
+                }
+            }
+        }
+
+        let name = &self.code.interned_names[index];
+        let value = match self.scope.globals.get_item_option(name.clone(), vm)? {
+            Some(value) => Some(value),
+            None => match builtins {
+                Some(builtins) => builtins.get_item_option(name.clone(), vm)?,
+                None => None,
+            },
+        };
+        match value {
+            Some(value) => {
+                self.code.name_caches.borrow_mut()[index] = NameCache::Global {
+                    globals_version,
+                    builtins_version,
+                    value: PyWeak::downgrade(&value),
+                };
+                self.push_value(value);
+                Ok(None)
+            }
+            None => self.load_name(vm, name.as_str(), &bytecode::NameScope::Global),
+        }
+    }
+
+    /// Push the method of an object for `CallMethod`: the object, `True` and
+    /// the function found on its type when calling it would bind it to the
+    /// object, and otherwise `None`, `False` and the attribute.
+    fn load_method(&self, vm: &VirtualMachine, index: usize) -> FrameResult {
+        let obj = self.pop_value();
+        let name = &self.code.interned_names[index];
+        let cls = obj.class();
+        let type_version = cls.version_tag.get();
+        let cached = match &self.code.name_caches.borrow()[index] {
+            NameCache::Method {
+                type_version: cached_type_version,
+                generic_getattribute,
+                attribute,
+                is_function,
+            } if *cached_type_version == type_version => match attribute {
+                Some(attribute) => attribute
+                    .upgrade()
+                    .map(|attribute| (*generic_getattribute, Some(attribute), *is_function)),
+                None => Some((*generic_getattribute, None, *is_function)),
+            },
+            _ => None,
+        };
+        let (generic_getattribute, attribute, is_function) = match cached {
+            Some(cached) => cached,
+            None => {
+                let generic_getattribute = objtype::class_get_attr(&cls, "__getattribute__")
+                    .map_or(false, |getattribute| {
+                        objtype::class_get_attr(&vm.ctx.object(), "__getattribute__")
+                            .map_or(false, |generic| getattribute.is(&generic))
+                    });
+                let attribute = objtype::class_get_attr(&cls, name.as_str());
+                let is_function = attribute.as_ref().map_or(false, |attribute| {
+                    let attribute_class = attribute.class();
+                    attribute_class.is(&vm.ctx.function_type())
+                        || attribute_class.is(&vm.ctx.builtin_function_or_method_type())
+                });
+                self.code.name_caches.borrow_mut()[index] = NameCache::Method {
+                    type_version,
+                    generic_getattribute,
+                    attribute: attribute.as_ref().map(PyWeak::downgrade),
+                    is_function,
+                };
+                (generic_getattribute, attribute, is_function)
+            }
+        };
+
+        // Without data descriptors on the type, an attribute of the object
+        // itself takes precedence:
+        if generic_getattribute && (attribute.is_none() || is_function) {
+            let instance_attribute = match &obj.dict {
+                Some(dict) => dict.get_item_option(name.clone(), vm)?,
+                None => None,
+            };
+            match (instance_attribute, attribute) {
+                (Some(instance_attribute), _) => {
+                    self.push_value(vm.get_none());
+                    self.push_value(vm.new_bool(false));
+                    self.push_value(instance_attribute);
+                    return Ok(None);
+                }
+                (None, Some(function)) => {
+                    self.push_value(obj);
+                    self.push_value(vm.new_bool(true));
+                    self.push_value(function);
+                    return Ok(None);
+                }
+                (None, None) => {}
+            }
+        }
+
+        let attribute = vm.get_attribute(obj, name.clone())?;
+        self.push_value(vm.get_none());
+        self.push_value(vm.new_bool(false));
+        self.push_value(attribute);
+        Ok(None)
+    }
+
+    fn load_attr(&self, vm: &VirtualMachine, attr_name: &PyStringRef) -> FrameResult {
         let parent = self.pop_value();
-        let obj = vm.get_attribute(parent, attr_name)?;
+        let obj = vm.get_attribute(parent, attr_name.clone())?;
         self.push_value(obj);
         Ok(None)
     }
 
-    fn store_attr(&self, vm: &VirtualMachine, attr_name: &str) -> FrameResult {
+    fn store_attr(&self, vm: &VirtualMachine, attr_name: &PyStringRef) -> FrameResult {
         let parent = self.pop_value();
         let value = self.pop_value();
-        vm.set_attr(&parent, vm.new_str(attr_name.to_string()), value)?;
+        vm.set_attr(&parent, attr_name.clone(), value)?;
         Ok(None)
     }
 
-    fn delete_attr(&self, vm: &VirtualMachine, attr_name: &str) -> FrameResult {
+    fn delete_attr(&self, vm: &VirtualMachine, attr_name: &PyStringRef) -> FrameResult {
         let parent = self.pop_value();
-        let name = vm.ctx.new_str(attr_name.to_string());
-        vm.del_attr(&parent, name)?;
+        vm.del_attr(&parent, attr_name.clone().into_object())?;
         Ok(None)
     }
 
@@ -1369,6 +1808,17 @@ impl fmt::Debug for Frame {
         let local_str = dict
             .into_iter()
             .map(|elem| format!("\n  {:?} = {:?}", elem.0, elem.1))
+            .chain(
+                self.code
+                    .varnames
+                    .iter()
+                    .zip(self.fastlocals.borrow().iter())
+                    .filter_map(|(name, value)| {
+                        value
+                            .as_ref()
+                            .map(|value| format!("\n  {:?} = {:?}", name, value))
+                    }),
+            )
             .collect::<String>();
         write!(
             f,
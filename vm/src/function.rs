@@ -452,6 +452,50 @@ tuple_from_py_func_args!(A, B, C, D);
 tuple_from_py_func_args!(A, B, C, D, E);
 tuple_from_py_func_args!(A, B, C, D, E, F);
 
+/// Implemented by values that can be passed as the arguments of a call from
+/// rust code, such as a tuple of rust values converted with `IntoPyObject`.
+pub trait IntoFuncArgs {
+    fn into_func_args(self, vm: &VirtualMachine) -> PyResult<PyFuncArgs>;
+}
+
+impl IntoFuncArgs for PyFuncArgs {
+    fn into_func_args(self, _vm: &VirtualMachine) -> PyResult<PyFuncArgs> {
+        Ok(self)
+    }
+}
+
+impl IntoFuncArgs for Vec<PyObjectRef> {
+    fn into_func_args(self, _vm: &VirtualMachine) -> PyResult<PyFuncArgs> {
+        Ok(self.into())
+    }
+}
+
+impl IntoFuncArgs for () {
+    fn into_func_args(self, _vm: &VirtualMachine) -> PyResult<PyFuncArgs> {
+        Ok(PyFuncArgs::default())
+    }
+}
+
+macro_rules! tuple_into_func_args {
+    ($(($n:tt, $T:ident)),+) => {
+        impl<$($T),+> IntoFuncArgs for ($($T,)+)
+        where
+            $($T: IntoPyObject),+
+        {
+            fn into_func_args(self, vm: &VirtualMachine) -> PyResult<PyFuncArgs> {
+                Ok(vec![$(self.$n.into_pyobject(vm)?),+].into())
+            }
+        }
+    };
+}
+
+tuple_into_func_args!((0, A));
+tuple_into_func_args!((0, A), (1, B));
+tuple_into_func_args!((0, A), (1, B), (2, C));
+tuple_into_func_args!((0, A), (1, B), (2, C), (3, D));
+tuple_into_func_args!((0, A), (1, B), (2, C), (3, D), (4, E));
+tuple_into_func_args!((0, A), (1, B), (2, C), (3, D), (4, E), (5, F));
+
 /// A built-in Python function.
 pub type PyNativeFunc = Box<dyn Fn(&VirtualMachine, PyFuncArgs) -> PyResult + 'static>;
 
@@ -11,7 +11,7 @@ use crate::pyobject::{ItemProtocol, PyResult, PyValue};
 use crate::util;
 use crate::vm::VirtualMachine;
 #[cfg(feature = "rustpython_compiler")]
-use rustpython_compiler::compile;
+use rustpython_compiler::{compile, encoding};
 
 pub fn init_importlib(vm: &VirtualMachine) -> PyResult {
     let importlib = import_frozen(vm, "_frozen_importlib")?;
@@ -66,13 +66,13 @@ pub fn import_module(vm: &VirtualMachine, current_path: PathBuf, module_name: &s
             .map_err(|e| vm.new_exception(notfound_error.clone(), e))?;
         let source = util::read_file(file_path.as_path())
             .map_err(|e| vm.new_exception(import_error.clone(), e.to_string()))?;
+        let file_path = file_path.to_str().unwrap().to_string();
+        let source = encoding::decode_source(&source).map_err(|mut err| {
+            err.update_source_path(&file_path);
+            vm.new_syntax_error(&err)
+        })?;
 
-        import_file(
-            vm,
-            module_name,
-            file_path.to_str().unwrap().to_string(),
-            source,
-        )
+        import_file(vm, module_name, file_path, source)
     } else {
         let notfound_error = &vm.ctx.exceptions.module_not_found_error;
         Err(vm.new_exception(notfound_error.clone(), module_name.to_string()))
@@ -110,7 +110,7 @@ pub fn import_codeobj(
 
     // Execute main code in module:
     vm.run_code_obj(
-        objcode::PyCode::new(code_obj).into_ref(vm),
+        objcode::PyCode::new(code_obj, &vm.ctx).into_ref(vm),
         Scope::with_builtins(None, attrs, vm),
     )?;
     Ok(module)
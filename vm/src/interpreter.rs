@@ -0,0 +1,721 @@
+//! A high level interface for embedding the interpreter in a rust program.
+//!
+//! ```
+//! use rustpython_vm::interpreter::Interpreter;
+//!
+//! let interp = Interpreter::builder().argv(vec!["embedded"]).build().unwrap();
+//! interp.run_string("def add(a, b):\n    return a + b").unwrap();
+//! let sum: i32 = interp.call("add", (40, 2)).unwrap();
+//! assert_eq!(sum, 42);
+//!
+//! let err = interp.eval::<i32>("1 / 0").unwrap_err();
+//! assert_eq!(err.type_name(), "ZeroDivisionError");
+//! ```
+
+use std::cell::RefCell;
+use std::error;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use rustpython_compiler::{compile, encoding};
+
+use crate::bytecode::CodeObject;
+use crate::exceptions::write_exception;
+use crate::frame::Scope;
+use crate::function::IntoFuncArgs;
+use crate::import;
+use crate::obj::objcode::PyCodeRef;
+use crate::obj::objstr::PyStringRef;
+use crate::pyobject::{
+    IntoPyObject, ItemProtocol, PyObjectRef, PyResult, TryFromObject, TypeProtocol,
+};
+use crate::sandbox::{Access, ResourceLimits};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::stdlib::os;
+use crate::util;
+use crate::vm::{VirtualMachine, VirtualMachineBuilder};
+
+/// The native modules the import machinery needs to start, which are kept
+/// whatever modules are enabled.
+const IMPORTLIB_MODULES: &[&str] = &[
+    "_imp",
+    "_io",
+    "_os",
+    "_thread",
+    "_warnings",
+    "_weakref",
+    "marshal",
+];
+
+/// A Python exception which escaped to the host, with its traceback formatted
+/// the way the interpreter prints it.
+pub struct PythonError {
+    exception: PyObjectRef,
+    type_name: String,
+    message: String,
+    traceback: String,
+}
+
+impl PythonError {
+    pub fn new(vm: &VirtualMachine, exception: PyObjectRef) -> PythonError {
+        let type_name = exception.class().name.clone();
+        let message = match vm.to_str(&exception) {
+            Ok(message) => message.as_str().to_string(),
+            Err(_) => "<exception str() failed>".to_string(),
+        };
+        let mut traceback = Vec::new();
+        write_exception(&mut traceback, vm, &exception).unwrap();
+        PythonError {
+            exception,
+            type_name,
+            message,
+            traceback: String::from_utf8_lossy(&traceback).into_owned(),
+        }
+    }
+
+    /// The exception object itself.
+    pub fn exception(&self) -> &PyObjectRef {
+        &self.exception
+    }
+
+    /// The name of the class of the exception, such as `ValueError`.
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+
+    /// The `str()` of the exception.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The traceback, causes and message of the exception.
+    pub fn traceback(&self) -> &str {
+        &self.traceback
+    }
+
+    pub fn into_exception(self) -> PyObjectRef {
+        self.exception
+    }
+}
+
+impl fmt::Display for PythonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.traceback.trim_end())
+    }
+}
+
+impl fmt::Debug for PythonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PythonError")
+            .field("type_name", &self.type_name)
+            .field("message", &self.message)
+            .finish()
+    }
+}
+
+impl error::Error for PythonError {}
+
+/// Configures an `Interpreter` before it starts.
+#[derive(Default)]
+pub struct InterpreterBuilder {
+    path: Option<Vec<String>>,
+    argv: Option<Vec<String>>,
+    vm: VirtualMachineBuilder,
+    stdout: Option<Box<dyn Write>>,
+    stderr: Option<Box<dyn Write>>,
+    limits: ResourceLimits,
+}
+
+impl InterpreterBuilder {
+    /// Set `sys.path`, instead of the paths from the `RUSTPYTHONPATH` and
+    /// `PYTHONPATH` environment variables.
+    pub fn path<I, S>(mut self, path: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.path = Some(path.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set `sys.argv`, instead of the arguments of the process.
+    pub fn argv<I, S>(mut self, argv: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.argv = Some(argv.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Only enable the given native modules of those registered so far,
+    /// besides the ones the import machinery needs. All of the modules of the
+    /// standard library are enabled by default.
+    pub fn stdlib_modules<I, S>(mut self, modules: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let modules: Vec<String> = modules.into_iter().map(Into::into).collect();
+        let disabled: Vec<String> = self
+            .vm
+            .native_module_names()
+            .filter(|name| !IMPORTLIB_MODULES.contains(name) && !modules.iter().any(|m| m == name))
+            .map(ToOwned::to_owned)
+            .collect();
+        for name in disabled {
+            self.vm = self.vm.disable_module(&name);
+        }
+        self
+    }
+
+    /// Register a module implemented in rust, as with
+    /// `VirtualMachineBuilder::native_module`.
+    pub fn native_module<F>(mut self, name: &str, init: F) -> Self
+    where
+        F: Fn(&VirtualMachine) -> PyObjectRef + 'static,
+    {
+        self.vm = self.vm.native_module(name, init);
+        self
+    }
+
+    /// Register a frozen module, as with `VirtualMachineBuilder::frozen_module`.
+    pub fn frozen_module(mut self, name: &str, code: CodeObject) -> Self {
+        self.vm = self.vm.frozen_module(name, code);
+        self
+    }
+
+    /// Remove a native or frozen module, as with
+    /// `VirtualMachineBuilder::disable_module`.
+    pub fn disable_module(mut self, name: &str) -> Self {
+        self.vm = self.vm.disable_module(name);
+        self
+    }
+
+    /// Decide which accesses to files, the network and processes the code may
+    /// make, as with `VirtualMachineBuilder::access_policy`. Importing modules
+    /// from `sys.path` needs read access to it.
+    pub fn access_policy<F>(mut self, policy: F) -> Self
+    where
+        F: Fn(&VirtualMachine, Access) -> bool + 'static,
+    {
+        self.vm = self.vm.access_policy(policy);
+        self
+    }
+
+    /// Restrict the interpreter for running untrusted code, as with
+    /// `VirtualMachineBuilder::sandboxed`.
+    pub fn sandboxed(mut self) -> Self {
+        self.vm = self.vm.sandboxed();
+        self
+    }
+
+    /// Limit the resources used by each run of code, which do not apply to
+    /// starting the interpreter.
+    pub fn resource_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Send what is written to `sys.stdout`, including by `print`, to `stdout`
+    /// instead of the stdout of the process.
+    pub fn stdout<W: Write + 'static>(mut self, stdout: W) -> Self {
+        self.stdout = Some(Box::new(stdout));
+        self
+    }
+
+    /// Send what is written to `sys.stderr` to `stderr` instead of the stderr
+    /// of the process.
+    pub fn stderr<W: Write + 'static>(mut self, stderr: W) -> Self {
+        self.stderr = Some(Box::new(stderr));
+        self
+    }
+
+    pub fn build(self) -> Result<Interpreter, PythonError> {
+        let InterpreterBuilder {
+            path,
+            argv,
+            vm,
+            stdout,
+            stderr,
+            limits,
+        } = self;
+        let vm = vm.build();
+        let stdout = stdout.unwrap_or_else(|| Box::new(io::stdout()));
+        let stderr = stderr.unwrap_or_else(|| Box::new(io::stderr()));
+        match init(&vm, path, argv, stdout, stderr) {
+            Ok(scope) => {
+                vm.set_resource_limits(limits);
+                Ok(Interpreter { vm, scope })
+            }
+            Err(exc) => Err(PythonError::new(&vm, exc)),
+        }
+    }
+}
+
+/// Configure the sys module and importlib, and create the `__main__` module
+/// that code runs in.
+fn init(
+    vm: &VirtualMachine,
+    path: Option<Vec<String>>,
+    argv: Option<Vec<String>>,
+    stdout: Box<dyn Write>,
+    stderr: Box<dyn Write>,
+) -> PyResult<Scope> {
+    let new_str_list = |strings: Vec<String>| {
+        vm.ctx
+            .new_list(strings.into_iter().map(|s| vm.new_str(s)).collect())
+    };
+    if let Some(path) = path {
+        vm.set_attr(&vm.sys_module, "path", new_str_list(path))?;
+    }
+    if let Some(argv) = argv {
+        vm.set_attr(&vm.sys_module, "argv", new_str_list(argv))?;
+    }
+    vm.set_attr(&vm.sys_module, "stdout", new_output_file(vm, stdout))?;
+    vm.set_attr(&vm.sys_module, "stderr", new_output_file(vm, stderr))?;
+
+    import::init_importlib(vm)?;
+
+    let globals = vm.ctx.new_dict();
+    globals.set_item("__name__", vm.new_str("__main__".to_string()), vm)?;
+    let main_module = vm.ctx.new_module("__main__", globals.clone());
+    let sys_modules = vm.get_attribute(vm.sys_module.clone(), "modules")?;
+    sys_modules.set_item("__main__", main_module, vm)?;
+    Ok(Scope::with_builtins(None, globals, vm))
+}
+
+/// A file object for `sys.stdout` or `sys.stderr` which writes to `sink`.
+fn new_output_file(vm: &VirtualMachine, sink: Box<dyn Write>) -> PyObjectRef {
+    let sink = Rc::new(RefCell::new(sink));
+    let write_sink = sink.clone();
+    let write = move |_zelf: PyObjectRef, s: PyStringRef, vm: &VirtualMachine| {
+        write_sink
+            .borrow_mut()
+            .write_all(s.as_str().as_bytes())
+            .map_err(|err| vm.new_os_error(err.to_string()))?;
+        Ok(s.as_str().chars().count())
+    };
+    let flush = move |_zelf: PyObjectRef, vm: &VirtualMachine| {
+        sink.borrow_mut()
+            .flush()
+            .map_err(|err| vm.new_os_error(err.to_string()))
+    };
+    let writable = |_zelf: PyObjectRef, _vm: &VirtualMachine| true;
+
+    let ctx = &vm.ctx;
+    let class = ctx.new_class("OutputFile", ctx.object());
+    extend_class!(ctx, &class, {
+        "write" => ctx.new_rustfunc(write),
+        "flush" => ctx.new_rustfunc(flush),
+        "writable" => ctx.new_rustfunc(writable),
+    });
+    ctx.new_instance(class, None)
+}
+
+/// An interpreter with its own `VirtualMachine`, which runs code in a
+/// persistent `__main__` module and reports errors as `PythonError`s.
+pub struct Interpreter {
+    vm: VirtualMachine,
+    scope: Scope,
+}
+
+impl Interpreter {
+    /// An interpreter with the default configuration.
+    pub fn new() -> Result<Interpreter, PythonError> {
+        Interpreter::builder().build()
+    }
+
+    pub fn builder() -> InterpreterBuilder {
+        InterpreterBuilder::default()
+    }
+
+    pub fn vm(&self) -> &VirtualMachine {
+        &self.vm
+    }
+
+    /// The scope of the `__main__` module.
+    pub fn scope(&self) -> &Scope {
+        &self.scope
+    }
+
+    /// Execute the statements in `source` in the `__main__` module.
+    pub fn run_string(&self, source: &str) -> Result<(), PythonError> {
+        self.run_source(source, "<string>".to_string())
+    }
+
+    /// Execute the Python file at `path` in the `__main__` module.
+    pub fn run_file<P: AsRef<Path>>(&self, path: P) -> Result<(), PythonError> {
+        let path = path.as_ref();
+        let source_path = path.to_string_lossy().into_owned();
+        let source = util::read_file(path).map_err(|err| {
+            #[cfg(not(target_arch = "wasm32"))]
+            let exc = os::convert_io_error_with_path(&self.vm, err, &source_path);
+            #[cfg(target_arch = "wasm32")]
+            let exc = self
+                .vm
+                .new_os_error(format!("can't open file '{}': {}", source_path, err));
+            self.error(exc)
+        })?;
+        let source = encoding::decode_source(&source).map_err(|mut err| {
+            err.update_source_path(&source_path);
+            self.error(self.vm.new_syntax_error(&err))
+        })?;
+        let file = self.vm.new_str(source_path.clone());
+        self.check(self.scope.globals.set_item("__file__", file, &self.vm))?;
+        self.run_source(&source, source_path)
+    }
+
+    fn run_source(&self, source: &str, source_path: String) -> Result<(), PythonError> {
+        let code = self.compile(source, compile::Mode::Exec, source_path)?;
+        self.check(self.vm.run_code_obj(code, self.scope.clone()))?;
+        Ok(())
+    }
+
+    /// Evaluate the expression `source` in the `__main__` module and convert
+    /// its value.
+    pub fn eval<T: TryFromObject>(&self, source: &str) -> Result<T, PythonError> {
+        let code = self.compile(source, compile::Mode::Eval, "<string>".to_string())?;
+        let value = self.check(self.vm.run_code_obj(code, self.scope.clone()))?;
+        self.check(T::try_from_object(&self.vm, value))
+    }
+
+    fn compile(
+        &self,
+        source: &str,
+        mode: compile::Mode,
+        source_path: String,
+    ) -> Result<PyCodeRef, PythonError> {
+        // This works around https://github.com/RustPython/RustPython/issues/17
+        let mut source = source.to_string();
+        if !source.ends_with('\n') {
+            source.push('\n');
+        }
+        self.vm
+            .compile(&source, &mode, source_path)
+            .map_err(|err| self.error(self.vm.new_syntax_error(&err)))
+    }
+
+    /// Call the global `name` of the `__main__` module, such as a function
+    /// defined by `run_string`, and convert what it returns.
+    pub fn call<T, A>(&self, name: &str, args: A) -> Result<T, PythonError>
+    where
+        T: TryFromObject,
+        A: IntoFuncArgs,
+    {
+        let vm = &self.vm;
+        self.check((|| {
+            let func = self.get_global::<PyObjectRef>(name)?;
+            let result = vm.invoke(func, args.into_func_args(vm)?)?;
+            T::try_from_object(vm, result)
+        })())
+    }
+
+    /// Set the global `name` of the `__main__` module.
+    pub fn set_global<T: IntoPyObject>(&self, name: &str, value: T) -> Result<(), PythonError> {
+        let vm = &self.vm;
+        self.check(
+            value
+                .into_pyobject(vm)
+                .and_then(|value| self.scope.globals.set_item(name, value, vm)),
+        )?;
+        Ok(())
+    }
+
+    /// Get and convert the global `name` of the `__main__` module.
+    pub fn global<T: TryFromObject>(&self, name: &str) -> Result<T, PythonError> {
+        self.check(self.get_global(name))
+    }
+
+    fn get_global<T: TryFromObject>(&self, name: &str) -> PyResult<T> {
+        let vm = &self.vm;
+        match self.scope.globals.get_item_option(name, vm)? {
+            Some(value) => T::try_from_object(vm, value),
+            None => Err(vm.new_name_error(format!("name '{}' is not defined", name))),
+        }
+    }
+
+    fn check<T>(&self, result: PyResult<T>) -> Result<T, PythonError> {
+        result.map_err(|exc| self.error(exc))
+    }
+
+    fn error(&self, exception: PyObjectRef) -> PythonError {
+        PythonError::new(&self.vm, exception)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Interpreter;
+    use crate::sandbox::{Access, ResourceLimits};
+    use std::cell::{Cell, RefCell};
+    use std::env;
+    use std::fs;
+    use std::io::{self, Write};
+    use std::path::Path;
+    use std::process;
+    use std::rc::Rc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_run_and_convert() {
+        let interp = Interpreter::builder()
+            .argv(vec!["prog", "x"])
+            .build()
+            .unwrap();
+        interp.set_global("base", 10).unwrap();
+        interp
+            .run_string("import sys\nargs = sys.argv\ndef scale(x, y): return base * x + y")
+            .unwrap();
+        let args: Vec<String> = interp.global("args").unwrap();
+        assert_eq!(args, vec!["prog", "x"]);
+        assert_eq!(interp.call::<i64, _>("scale", (4, 2)).unwrap(), 42);
+        assert_eq!(interp.eval::<f64>("scale(0.5, 0)").unwrap(), 5.0);
+        assert_eq!(interp.eval::<Option<String>>("None").unwrap(), None);
+    }
+
+    #[test]
+    fn test_output_sinks() {
+        let stdout = SharedBuffer::default();
+        let stderr = SharedBuffer::default();
+        let interp = Interpreter::builder()
+            .stdout(stdout.clone())
+            .stderr(stderr.clone())
+            .build()
+            .unwrap();
+        interp
+            .run_string("import sys\nprint('hello', 42)\nprint('oops', file=sys.stderr)")
+            .unwrap();
+        assert_eq!(&*stdout.0.borrow(), b"hello 42\n");
+        assert_eq!(&*stderr.0.borrow(), b"oops\n");
+    }
+
+    #[test]
+    fn test_run_file() {
+        let path = env::temp_dir().join(format!("interpreter_run_file_{}.py", process::id()));
+        fs::write(&path, b"# -*- coding: latin-1 -*-\nname = '\xe9t\xe9'\n").unwrap();
+        let interp = Interpreter::new().unwrap();
+        let result = interp.run_file(&path);
+        fs::remove_file(&path).unwrap();
+        result.unwrap();
+        assert_eq!(interp.global::<String>("name").unwrap(), "\u{e9}t\u{e9}");
+        let file: String = interp.global("__file__").unwrap();
+        assert_eq!(Path::new(&file), path);
+
+        let err = interp.run_file(&path).unwrap_err();
+        assert_eq!(err.type_name(), "FileNotFoundError");
+        let filename = interp.vm().get_attribute(err.into_exception(), "filename");
+        assert_eq!(interp.vm().to_pystr(&filename.unwrap()).unwrap(), file);
+    }
+
+    #[test]
+    fn test_errors() {
+        let interp = Interpreter::new().unwrap();
+        interp
+            .run_string("def fail():\n    raise ValueError('bad value')")
+            .unwrap();
+        let err = interp.call::<i32, _>("fail", ()).unwrap_err();
+        assert_eq!(err.type_name(), "ValueError");
+        assert_eq!(err.message(), "bad value");
+        assert!(err
+            .traceback()
+            .starts_with("Traceback (most recent call last):"));
+        assert!(err.to_string().ends_with("ValueError: bad value"));
+
+        let err = interp.eval::<i32>("'text'").unwrap_err();
+        assert_eq!(err.type_name(), "TypeError");
+        let err = interp.run_string("x = (").unwrap_err();
+        assert_eq!(err.type_name(), "SyntaxError");
+        let err = interp.call::<i32, _>("missing", ()).unwrap_err();
+        assert_eq!(err.type_name(), "NameError");
+    }
+
+    #[test]
+    fn test_stdlib_modules() {
+        let interp = Interpreter::builder()
+            .stdlib_modules(vec!["math"])
+            .build()
+            .unwrap();
+        interp.run_string("import math").unwrap();
+        let err = interp.run_string("import hashlib").unwrap_err();
+        assert_eq!(err.type_name(), "ModuleNotFoundError");
+    }
+
+    #[test]
+    fn test_resource_limits() {
+        let interp = Interpreter::builder()
+            .resource_limits(ResourceLimits {
+                max_instructions: Some(10_000),
+                max_recursion_depth: Some(50),
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        let err = interp
+            .run_string("try:\n    while True: pass\nexcept Exception:\n    pass")
+            .unwrap_err();
+        assert_eq!(err.type_name(), "ResourceLimitError");
+        assert_eq!(err.message(), "instruction limit exceeded");
+        let err = interp.run_string("def f(): f()\nf()").unwrap_err();
+        assert_eq!(err.message(), "maximum recursion depth exceeded");
+
+        // The limits apply to each run, so the interpreter stays usable
+        assert_eq!(interp.eval::<i32>("sum(range(10))").unwrap(), 45);
+
+        interp.vm().set_resource_limits(ResourceLimits {
+            timeout: Some(Duration::from_millis(50)),
+            ..Default::default()
+        });
+        let err = interp.run_string("while True: pass").unwrap_err();
+        assert_eq!(err.message(), "time limit exceeded");
+        assert_eq!(interp.eval::<i32>("1 + 1").unwrap(), 2);
+
+        // Blocking calls don't wait past the time limit either
+        let mut sources = vec!["import time\ntime.sleep(3.0)"];
+        if cfg!(unix) {
+            sources.push("import select\nselect.select([], [], [], 3.0)");
+            sources.push(
+                "import _os\nr, w = _os.pipe()\ntry:\n    _os.read(r, 1)\n\
+                 finally:\n    _os.close(r)\n    _os.close(w)",
+            );
+        }
+        for source in sources {
+            let start = Instant::now();
+            let err = interp.run_string(source).unwrap_err();
+            assert_eq!(err.message(), "time limit exceeded");
+            assert!(start.elapsed() < Duration::from_secs(2));
+        }
+
+        interp.vm().set_resource_limits(ResourceLimits {
+            max_memory: Some(1 << 20),
+            ..Default::default()
+        });
+        for source in &[
+            "b'a' * 10**12",
+            "[0] * 10**11",
+            "'a'.ljust(10**12)",
+            "bytes(10**12)",
+            "x = [0]\nwhile True: x += x",
+            "x = []\nwhile True: x.append(0)",
+        ] {
+            let err = interp.run_string(source).unwrap_err();
+            assert_eq!(err.type_name(), "MemoryError");
+        }
+        assert_eq!(interp.eval::<usize>("len('a' * 1000)").unwrap(), 1000);
+    }
+
+    #[test]
+    fn test_sandboxed() {
+        let lib = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .parent()
+            .unwrap()
+            .join("Lib");
+        let lib = lib.to_str().unwrap().to_string();
+        let interp = Interpreter::builder()
+            .path(vec![lib.clone()])
+            .sandboxed()
+            .access_policy(move |_vm, access| match access {
+                Access::Read(path) => path.starts_with(&lib),
+                _ => false,
+            })
+            .build()
+            .unwrap();
+        interp.run_string("import colorsys, _os").unwrap();
+        let err = interp.run_string("open('sandbox.txt', 'w')").unwrap_err();
+        assert_eq!(err.type_name(), "PermissionError");
+        assert_eq!(
+            err.message(),
+            "write access to 'sandbox.txt' denied by the sandbox"
+        );
+        let err = interp.run_string("_os.listdir('/')").unwrap_err();
+        assert_eq!(err.type_name(), "PermissionError");
+        interp.run_string("assert _os.environ == {}").unwrap();
+        for source in &[
+            "_os.close(0)",
+            "_os.write(1, b'x')",
+            "_os.dup2(0, 1)",
+            "_os.umask(0)",
+            "_os.stat('x', dir_fd=1)",
+            "_os.remove('x', dir_fd=1)",
+        ] {
+            let err = interp.run_string(source).unwrap_err();
+            assert_eq!(err.type_name(), "PermissionError");
+        }
+        let err = interp.run_string("_os.write(1, b'')").unwrap_err();
+        assert_eq!(
+            err.message(),
+            "access to file descriptor 1 denied by the sandbox"
+        );
+        interp.run_string("import _io").unwrap();
+        let err = interp.run_string("_io.FileIO(2 ** 70)").unwrap_err();
+        assert_eq!(err.type_name(), "OverflowError");
+        let err = interp.run_string("import socket").unwrap_err();
+        assert_eq!(err.type_name(), "ModuleNotFoundError");
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let interp = Interpreter::new().unwrap();
+        let handle = interp.vm().interrupt_handle();
+        let interrupter = handle.clone();
+        let thread = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            interrupter.interrupt();
+        });
+        let err = interp.run_string("while True: pass").unwrap_err();
+        assert_eq!(err.type_name(), "KeyboardInterrupt");
+        thread.join().unwrap();
+
+        handle.interrupt_with(|vm| {
+            vm.new_exception(
+                vm.ctx.exceptions.timeout_error.clone(),
+                "cancelled".to_string(),
+            )
+        });
+        let err = interp.run_string("x = 1").unwrap_err();
+        assert_eq!(err.type_name(), "TimeoutError");
+        assert_eq!(err.message(), "cancelled");
+        interp.run_string("x = 2").unwrap();
+    }
+
+    #[test]
+    fn test_periodic_callback() {
+        let interp = Interpreter::new().unwrap();
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        interp.vm().set_periodic_callback(100, move |vm| {
+            counter.set(counter.get() + 1);
+            if counter.get() == 3 {
+                Err(vm.new_exception(
+                    vm.ctx.exceptions.runtime_error.clone(),
+                    "cancelled".to_string(),
+                ))
+            } else {
+                Ok(())
+            }
+        });
+        let err = interp.run_string("while True: pass").unwrap_err();
+        assert_eq!(err.type_name(), "RuntimeError");
+        assert_eq!(calls.get(), 3);
+
+        interp.vm().clear_periodic_callback();
+        interp.run_string("for i in range(1000): pass").unwrap();
+        assert_eq!(calls.get(), 3);
+    }
+}
@@ -52,10 +52,13 @@ pub mod frame;
 mod frozen;
 pub mod function;
 pub mod import;
+#[cfg(feature = "rustpython_compiler")]
+pub mod interpreter;
 pub mod obj;
 pub mod py_serde;
 mod pyhash;
 pub mod pyobject;
+pub mod sandbox;
 pub mod stdlib;
 mod sysmodule;
 mod traceback;
@@ -63,8 +66,8 @@ pub mod util;
 mod vm;
 
 // pub use self::pyobject::Executor;
-pub use self::exceptions::print_exception;
-pub use self::vm::VirtualMachine;
+pub use self::exceptions::{print_exception, write_exception};
+pub use self::vm::{InterruptHandle, VirtualMachine, VirtualMachineBuilder};
 pub use rustpython_bytecode::*;
 
 #[doc(hidden)]
@@ -217,7 +217,7 @@ macro_rules! py_namespace {
 /// let obj = PyInt::new(0).into_ref(&vm).into_object();
 ///
 /// let int_value = match_class!(obj,
-///     i @ PyInt => i.as_bigint().clone(),
+///     i @ PyInt => i.as_bigint().into_owned(),
 ///     f @ PyFloat => f.to_f64().to_bigint().unwrap(),
 ///     obj => panic!("non-numeric object {}", obj),
 /// );
@@ -5,6 +5,7 @@ pub mod objbuiltinfunc;
 pub mod objbytearray;
 pub mod objbyteinner;
 pub mod objbytes;
+pub mod objcell;
 pub mod objclassmethod;
 pub mod objcode;
 pub mod objcomplex;
@@ -1,7 +1,9 @@
 use num_traits::Zero;
 
 use crate::function::PyFuncArgs;
-use crate::pyobject::{IntoPyObject, PyContext, PyObjectRef, PyResult, TryFromObject};
+use crate::pyobject::{
+    IntoPyObject, PyContext, PyObjectRef, PyResult, TryFromObject, TypeProtocol,
+};
 use crate::vm::VirtualMachine;
 
 use super::objint::PyInt;
@@ -31,7 +33,23 @@ pub fn boolval(vm: &VirtualMachine, obj: PyObjectRef) -> PyResult<bool> {
                 None => return Err(vm.new_type_error(String::from(""))),
             }
         }
-        None => true,
+        None => match vm.get_method(obj.clone(), "__len__") {
+            // Without __bool__, a container is true when it isn't empty
+            Some(method_or_err) => {
+                let method = method_or_err?;
+                let len_obj = vm.invoke(method, PyFuncArgs::default())?;
+                match len_obj.payload::<PyInt>() {
+                    Some(int_obj) => !int_obj.as_bigint().is_zero(),
+                    None => {
+                        return Err(vm.new_type_error(format!(
+                            "'{}' object cannot be interpreted as an integer",
+                            len_obj.class().name
+                        )))
+                    }
+                }
+            }
+            None => true,
+        },
     };
     Ok(rs_bool)
 }
@@ -113,6 +113,7 @@ impl ByteInnerNewOptions {
                 match_class!(ival.clone(),
                     i @ PyInt => {
                             let size = objint::get_value(&i.into_object()).to_usize().unwrap();
+                            vm.reserve_memory(size, 1)?;
                             Ok(vec![0; size])},
                     _l @ PyString=> {return Err(vm.new_type_error("string argument without an encoding".to_string()));},
                     obj => {
@@ -165,12 +166,12 @@ impl ByteInnerFindOptions {
         };
 
         let start = match self.start {
-            OptionalArg::Present(Some(int)) => Some(int.as_bigint().clone()),
+            OptionalArg::Present(Some(int)) => Some(int.as_bigint().into_owned()),
             _ => None,
         };
 
         let end = match self.end {
-            OptionalArg::Present(Some(int)) => Some(int.as_bigint().clone()),
+            OptionalArg::Present(Some(int)) => Some(int.as_bigint().into_owned()),
             _ => None,
         };
 
@@ -224,6 +225,7 @@ impl ByteInnerPaddingOptions {
         };
 
         let diff: usize = if width != 0 { width - len } else { 0 };
+        vm.reserve_memory(width, 1)?;
 
         Ok((fillbyte, diff))
     }
@@ -334,6 +336,49 @@ impl PyByteInner {
         }
     }
 
+    fn utf8_decode_error(&self, err: std::str::Utf8Error, vm: &VirtualMachine) -> PyObjectRef {
+        let start = err.valid_up_to();
+        let (end, reason) = match err.error_len() {
+            None => (self.elements.len(), "unexpected end of data"),
+            Some(len) => match self.elements[start] {
+                0xc2..=0xf4 => (start + len, "invalid continuation byte"),
+                _ => (start + len, "invalid start byte"),
+            },
+        };
+        let args = vec![
+            vm.new_str("utf-8".to_string()),
+            vm.ctx.new_bytes(self.elements.clone()),
+            vm.ctx.new_int(start),
+            vm.ctx.new_int(end),
+            vm.new_str(reason.to_string()),
+        ];
+        let unicode_decode_error = vm.ctx.exceptions.unicode_decode_error.clone();
+        match vm.invoke(unicode_decode_error.into_object(), args) {
+            Ok(exc) | Err(exc) => exc,
+        }
+    }
+
+    pub fn decode(&self, encoding: &str, errors: &str, vm: &VirtualMachine) -> PyResult<String> {
+        let normalized = normalize_encoding(encoding);
+        if normalized == "utf_8" || normalized == "utf8" || normalized == "u8" {
+            match errors {
+                "strict" => String::from_utf8(self.elements.clone())
+                    .map_err(|err| self.utf8_decode_error(err.utf8_error(), vm)),
+                "replace" => Ok(String::from_utf8_lossy(&self.elements).into_owned()),
+                "ignore" => Ok(String::from_utf8_lossy(&self.elements)
+                    .chars()
+                    .filter(|c| *c != std::char::REPLACEMENT_CHARACTER)
+                    .collect()),
+                _ => Err(vm.new_value_error(format!("unknown error handler name '{}'", errors))),
+            }
+        } else {
+            // TODO: different encoding
+            Err(
+                vm.new_value_error(format!("unknown encoding: {}", encoding)), // should be lookup error
+            )
+        }
+    }
+
     pub fn repr(&self) -> PyResult<String> {
         let mut res = String::with_capacity(self.elements.len());
         for i in self.elements.iter() {
@@ -1096,6 +1141,7 @@ impl PyByteInner {
             Ok(vec![])
         } else {
             let n = usize::try_from(n).unwrap();
+            vm.reserve_memory(n, self.elements.len())?;
 
             let mut new_value = Vec::with_capacity(n * self.elements.len());
             for _ in 0..n {
@@ -1121,6 +1167,7 @@ impl PyByteInner {
         } else {
             let n = usize::try_from(n).unwrap();
 
+            vm.reserve_memory(n - 1, self.elements.len())?;
             let old = self.elements.clone();
 
             self.elements.reserve((n - 1) * old.len());
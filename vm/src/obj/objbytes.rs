@@ -147,7 +147,7 @@ impl PyBytesRef {
     #[pymethod(name = "__add__")]
     fn add(self, other: PyObjectRef, vm: &VirtualMachine) -> PyResult {
         if let Ok(other) = PyByteInner::try_from_object(vm, other) {
-            Ok(vm.ctx.new_bytearray(self.inner.add(other)))
+            Ok(vm.ctx.new_bytes(self.inner.add(other)))
         } else {
             Ok(vm.ctx.not_implemented())
         }
@@ -333,6 +333,22 @@ impl PyBytesRef {
             .new_bytes(self.inner.strip(chars, ByteInnerPosition::Right, vm)?))
     }
 
+    #[pymethod(name = "decode")]
+    fn decode(
+        self,
+        encoding: OptionalArg<PyStringRef>,
+        errors: OptionalArg<PyStringRef>,
+        vm: &VirtualMachine,
+    ) -> PyResult<String> {
+        let encoding = encoding.into_option();
+        let errors = errors.into_option();
+        self.inner.decode(
+            encoding.as_ref().map_or("utf-8", |s| s.as_str()),
+            errors.as_ref().map_or("strict", |s| s.as_str()),
+            vm,
+        )
+    }
+
     #[pymethod(name = "split")]
     fn split(self, options: ByteInnerSplitOptions, vm: &VirtualMachine) -> PyResult {
         let as_bytes = self
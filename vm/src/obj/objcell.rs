@@ -0,0 +1,85 @@
+/*! The python class `cell`, which holds a variable shared between a function
+and the functions nested in it.
+
+*/
+
+use std::cell::RefCell;
+
+use crate::function::OptionalArg;
+use crate::obj::objproperty::PropertyBuilder;
+use crate::obj::objtype::PyClassRef;
+use crate::pyobject::{IdProtocol, PyContext, PyObjectRef, PyRef, PyResult, PyValue, TypeProtocol};
+use crate::vm::VirtualMachine;
+
+pub type PyCellRef = PyRef<PyCell>;
+
+#[derive(Debug, Default)]
+pub struct PyCell {
+    contents: RefCell<Option<PyObjectRef>>,
+}
+
+impl PyCell {
+    pub fn new(contents: Option<PyObjectRef>) -> Self {
+        PyCell {
+            contents: RefCell::new(contents),
+        }
+    }
+
+    pub fn get(&self) -> Option<PyObjectRef> {
+        self.contents.borrow().clone()
+    }
+
+    pub fn set(&self, contents: Option<PyObjectRef>) {
+        *self.contents.borrow_mut() = contents;
+    }
+}
+
+impl PyValue for PyCell {
+    fn class(vm: &VirtualMachine) -> PyClassRef {
+        vm.ctx.cell_type()
+    }
+}
+
+impl PyCellRef {
+    fn create(
+        cls: PyClassRef,
+        contents: OptionalArg<PyObjectRef>,
+        vm: &VirtualMachine,
+    ) -> PyResult<Self> {
+        PyCell::new(contents.into_option()).into_ref_with_type(vm, cls)
+    }
+
+    fn repr(self, _vm: &VirtualMachine) -> String {
+        match self.get() {
+            Some(contents) => format!(
+                "<cell at 0x{:x}: {} object at 0x{:x}>",
+                self.get_id(),
+                contents.class().name,
+                contents.get_id()
+            ),
+            None => format!("<cell at 0x{:x}: empty>", self.get_id()),
+        }
+    }
+
+    fn cell_contents(self, vm: &VirtualMachine) -> PyResult {
+        self.get()
+            .ok_or_else(|| vm.new_value_error("Cell is empty".to_string()))
+    }
+
+    fn set_cell_contents(self, contents: PyObjectRef, vm: &VirtualMachine) -> PyResult {
+        self.set(Some(contents));
+        Ok(vm.get_none())
+    }
+}
+
+pub fn init(context: &PyContext) {
+    extend_class!(context, &context.cell_type, {
+        "__new__" => context.new_rustfunc(PyCellRef::create),
+        "__repr__" => context.new_rustfunc(PyCellRef::repr),
+        "cell_contents" =>
+        PropertyBuilder::new(context)
+            .add_getter(PyCellRef::cell_contents)
+            .add_setter(PyCellRef::set_cell_contents)
+            .create(),
+    });
+}
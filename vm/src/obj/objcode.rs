@@ -2,10 +2,14 @@
 
 */
 
+use std::cell::RefCell;
 use std::fmt;
+use std::ops::Deref;
 
 use crate::bytecode;
+use crate::obj::objstr::PyStringRef;
 use crate::obj::objtype::PyClassRef;
+use crate::obj::objweakref::PyWeak;
 use crate::pyobject::{IdProtocol, PyContext, PyObjectRef, PyRef, PyResult, PyValue};
 use crate::vm::VirtualMachine;
 
@@ -13,11 +17,59 @@ pub type PyCodeRef = PyRef<PyCode>;
 
 pub struct PyCode {
     pub code: bytecode::CodeObject,
+    /// The objects of the constants, created once for all executions.
+    pub consts: Vec<PyObjectRef>,
+    /// The interned strings of the names.
+    pub interned_names: Vec<PyStringRef>,
+    /// Inline caches of the instructions which look up names, by name index.
+    pub name_caches: RefCell<Vec<NameCache>>,
+}
+
+/// The result of the last lookup of a name by the code, valid as long as
+/// the versions it was found with are current. The objects found are only
+/// referenced weakly, since they stay alive while the versions do not change.
+pub enum NameCache {
+    Empty,
+    /// A global or builtin variable found by `LoadName`.
+    Global {
+        globals_version: u64,
+        builtins_version: u64,
+        value: PyWeak,
+    },
+    /// The attribute found by `LoadMethod` on the type of an object.
+    Method {
+        type_version: u64,
+        /// Whether the type looks up attributes with `object.__getattribute__`.
+        generic_getattribute: bool,
+        attribute: Option<PyWeak>,
+        /// Whether the attribute is a function, which would be bound to the
+        /// object it is looked up on.
+        is_function: bool,
+    },
 }
 
 impl PyCode {
-    pub fn new(code: bytecode::CodeObject) -> PyCode {
-        PyCode { code }
+    pub fn new(code: bytecode::CodeObject, ctx: &PyContext) -> PyCode {
+        let consts = code
+            .constants
+            .iter()
+            .map(|constant| ctx.unwrap_constant(constant))
+            .collect();
+        let interned_names = code.names.iter().map(|name| ctx.intern_str(name)).collect();
+        let name_caches = RefCell::new(code.names.iter().map(|_| NameCache::Empty).collect());
+        PyCode {
+            code,
+            consts,
+            interned_names,
+            name_caches,
+        }
+    }
+}
+
+impl Deref for PyCode {
+    type Target = bytecode::CodeObject;
+    fn deref(&self) -> &Self::Target {
+        &self.code
     }
 }
 
@@ -66,18 +118,43 @@ impl PyCodeRef {
         self.code.kwonlyarg_names.len()
     }
 
+    fn co_posonlyargcount(self, _vm: &VirtualMachine) -> usize {
+        self.code.posonlyarg_count
+    }
+
     fn co_consts(self, vm: &VirtualMachine) -> PyObjectRef {
-        let consts = self
-            .code
-            .get_constants()
-            .map(|x| vm.ctx.unwrap_constant(x))
-            .collect();
-        vm.ctx.new_tuple(consts)
+        vm.ctx.new_tuple(self.consts.clone())
     }
 
     fn co_name(self, _vm: &VirtualMachine) -> String {
         self.code.obj_name.clone()
     }
+
+    fn co_names(self, vm: &VirtualMachine) -> PyObjectRef {
+        let names = self
+            .interned_names
+            .iter()
+            .map(|name| name.clone().into_object())
+            .collect();
+        vm.ctx.new_tuple(names)
+    }
+
+    fn co_varnames(self, vm: &VirtualMachine) -> PyObjectRef {
+        names_tuple(&self.code.varnames, vm)
+    }
+
+    fn co_cellvars(self, vm: &VirtualMachine) -> PyObjectRef {
+        names_tuple(&self.code.cellvars, vm)
+    }
+
+    fn co_freevars(self, vm: &VirtualMachine) -> PyObjectRef {
+        names_tuple(&self.code.freevars, vm)
+    }
+}
+
+fn names_tuple(names: &[String], vm: &VirtualMachine) -> PyObjectRef {
+    let names = names.iter().map(|name| vm.new_str(name.clone())).collect();
+    vm.ctx.new_tuple(names)
 }
 
 pub fn init(context: &PyContext) {
@@ -86,10 +163,15 @@ pub fn init(context: &PyContext) {
         "__repr__" => context.new_rustfunc(PyCodeRef::repr),
 
         "co_argcount" => context.new_property(PyCodeRef::co_argcount),
+        "co_cellvars" => context.new_property(PyCodeRef::co_cellvars),
         "co_consts" => context.new_property(PyCodeRef::co_consts),
         "co_filename" => context.new_property(PyCodeRef::co_filename),
         "co_firstlineno" => context.new_property(PyCodeRef::co_firstlineno),
+        "co_freevars" => context.new_property(PyCodeRef::co_freevars),
         "co_kwonlyargcount" => context.new_property(PyCodeRef::co_kwonlyargcount),
         "co_name" => context.new_property(PyCodeRef::co_name),
+        "co_names" => context.new_property(PyCodeRef::co_names),
+        "co_posonlyargcount" => context.new_property(PyCodeRef::co_posonlyargcount),
+        "co_varnames" => context.new_property(PyCodeRef::co_varnames),
     });
 }
@@ -306,6 +306,12 @@ impl PyDictRef {
     pub fn size(&self) -> dictdatatype::DictSize {
         self.entries.borrow().size()
     }
+
+    /// Identifies the contents of the dict, so that lookups can be cached
+    /// until it changes.
+    pub fn version(&self) -> u64 {
+        self.entries.borrow().version()
+    }
 }
 
 impl ItemProtocol for PyDictRef {
@@ -33,7 +33,7 @@ fn enumerate_new(
     vm: &VirtualMachine,
 ) -> PyResult<PyEnumerateRef> {
     let counter = match start {
-        OptionalArg::Present(start) => start.as_bigint().clone(),
+        OptionalArg::Present(start) => start.as_bigint().into_owned(),
         OptionalArg::Missing => BigInt::zero(),
     };
 
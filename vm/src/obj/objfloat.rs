@@ -8,7 +8,7 @@ use crate::obj::objtype::PyClassRef;
 use crate::pyhash;
 use crate::pyobject::{
     IdProtocol, IntoPyObject, PyClassImpl, PyContext, PyObjectRef, PyRef, PyResult, PyValue,
-    TypeProtocol,
+    TryFromObject, TypeProtocol,
 };
 use crate::vm::VirtualMachine;
 use hexf;
@@ -41,6 +41,14 @@ impl IntoPyObject for f64 {
     }
 }
 
+impl TryFromObject for f64 {
+    fn try_from_object(vm: &VirtualMachine, obj: PyObjectRef) -> PyResult<Self> {
+        try_float(&obj, vm)?.ok_or_else(|| {
+            vm.new_type_error(format!("must be real number, not {}", obj.class().name))
+        })
+    }
+}
+
 impl From<f64> for PyFloat {
     fn from(value: f64) -> Self {
         PyFloat { value }
@@ -167,7 +175,7 @@ impl PyFloat {
         } else if objtype::isinstance(&i2, &vm.ctx.int_type()) {
             let other_int = objint::get_value(&i2);
 
-            vm.ctx.new_bool(inner_lt_int(self.value, other_int))
+            vm.ctx.new_bool(inner_lt_int(self.value, &other_int))
         } else {
             vm.ctx.not_implemented()
         }
@@ -187,7 +195,7 @@ impl PyFloat {
                 self.value <= other_float && self_int <= *other_int
             } else {
                 // certainly not equal, forward to inner_lt_int
-                inner_lt_int(self.value, other_int)
+                inner_lt_int(self.value, &other_int)
             };
 
             vm.ctx.new_bool(result)
@@ -204,7 +212,7 @@ impl PyFloat {
         } else if objtype::isinstance(&i2, &vm.ctx.int_type()) {
             let other_int = objint::get_value(&i2);
 
-            vm.ctx.new_bool(inner_gt_int(self.value, other_int))
+            vm.ctx.new_bool(inner_gt_int(self.value, &other_int))
         } else {
             vm.ctx.not_implemented()
         }
@@ -224,7 +232,7 @@ impl PyFloat {
                 self.value >= other_float && self_int >= *other_int
             } else {
                 // certainly not equal, forward to inner_gt_int
-                inner_gt_int(self.value, other_int)
+                inner_gt_int(self.value, &other_int)
             };
 
             vm.ctx.new_bool(result)
@@ -27,11 +27,11 @@ impl FrameRef {
         "<frame object at .. >".to_string()
     }
 
-    fn flocals(self, _vm: &VirtualMachine) -> PyDictRef {
-        self.scope.get_locals()
+    fn flocals(self, vm: &VirtualMachine) -> PyDictRef {
+        self.get_locals(vm)
     }
 
-    fn fcode(self, vm: &VirtualMachine) -> PyCodeRef {
-        vm.ctx.new_code_object(self.code.clone())
+    fn fcode(self, _vm: &VirtualMachine) -> PyCodeRef {
+        self.code.clone()
     }
 }
@@ -4,7 +4,7 @@
 
 use crate::frame::{ExecutionResult, FrameRef};
 use crate::obj::objtype::{isinstance, PyClassRef};
-use crate::pyobject::{PyClassImpl, PyContext, PyObjectRef, PyRef, PyResult, PyValue};
+use crate::pyobject::{IdProtocol, PyClassImpl, PyContext, PyObjectRef, PyRef, PyResult, PyValue};
 use crate::vm::VirtualMachine;
 
 pub type PyGeneratorRef = PyRef<PyGenerator>;
@@ -66,10 +66,15 @@ impl PyGenerator {
 fn handle_execution_result(result: ExecutionResult, vm: &VirtualMachine) -> PyResult {
     match result {
         ExecutionResult::Yield(value) => Ok(value),
-        ExecutionResult::Return(_value) => {
-            // Stop iteration!
+        ExecutionResult::Return(value) => {
+            // Stop iteration, with the returned value as StopIteration.value
             let stop_iteration = vm.ctx.exceptions.stop_iteration.clone();
-            Err(vm.new_exception(stop_iteration, "End of generator".to_string()))
+            let args = if value.is(&vm.get_none()) {
+                vec![]
+            } else {
+                vec![value]
+            };
+            Err(vm.invoke(stop_iteration.into_object(), args)?)
         }
     }
 }
@@ -1,3 +1,6 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
 use std::fmt;
 
 use num_bigint::{BigInt, Sign};
@@ -8,8 +11,8 @@ use crate::format::FormatSpec;
 use crate::function::{KwArgs, OptionalArg, PyFuncArgs};
 use crate::pyhash;
 use crate::pyobject::{
-    IntoPyObject, PyClassImpl, PyContext, PyObjectRef, PyRef, PyResult, PyValue, TryFromObject,
-    TypeProtocol,
+    IdProtocol, IntoPyObject, PyClassImpl, PyContext, PyObjectRef, PyRef, PyResult, PyValue,
+    TryFromObject, TypeProtocol,
 };
 use crate::vm::VirtualMachine;
 
@@ -36,27 +39,163 @@ use crate::obj::objtype::PyClassRef;
 #[pyclass]
 #[derive(Debug)]
 pub struct PyInt {
-    value: BigInt,
+    value: IntValue,
+}
+
+/// The value of an int, held in a machine word unless it does not fit in
+/// one, so that a value has a single representation.
+#[derive(Debug, Clone)]
+enum IntValue {
+    Small(i64),
+    Big(BigInt),
 }
 
 impl fmt::Display for PyInt {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        BigInt::fmt(&self.value, f)
+        match &self.value {
+            IntValue::Small(value) => i64::fmt(value, f),
+            IntValue::Big(value) => BigInt::fmt(value, f),
+        }
     }
 }
 
 pub type PyIntRef = PyRef<PyInt>;
 
 impl PyInt {
-    pub fn new<T: Into<BigInt>>(i: T) -> Self {
-        PyInt { value: i.into() }
+    pub fn new<T: Into<PyInt>>(i: T) -> Self {
+        i.into()
+    }
+
+    pub fn as_bigint(&self) -> Cow<'_, BigInt> {
+        match &self.value {
+            IntValue::Small(value) => Cow::Owned(BigInt::from(*value)),
+            IntValue::Big(value) => Cow::Borrowed(value),
+        }
+    }
+
+    /// The value of the int, if it fits in an `i64`.
+    pub fn to_i64(&self) -> Option<i64> {
+        match self.value {
+            IntValue::Small(value) => Some(value),
+            IntValue::Big(_) => None,
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        match &self.value {
+            IntValue::Small(value) => *value == 0,
+            IntValue::Big(_) => false,
+        }
+    }
+
+    fn is_negative(&self) -> bool {
+        match &self.value {
+            IntValue::Small(value) => *value < 0,
+            IntValue::Big(value) => value.is_negative(),
+        }
+    }
+}
+
+impl PartialEq for PyInt {
+    fn eq(&self, other: &PyInt) -> bool {
+        match (&self.value, &other.value) {
+            (IntValue::Small(value1), IntValue::Small(value2)) => value1 == value2,
+            (IntValue::Big(value1), IntValue::Big(value2)) => value1 == value2,
+            // Only values which do not fit in a machine word are big ints
+            _ => false,
+        }
+    }
+}
+
+impl Eq for PyInt {}
+
+impl PartialOrd for PyInt {
+    fn partial_cmp(&self, other: &PyInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PyInt {
+    fn cmp(&self, other: &PyInt) -> Ordering {
+        match (&self.value, &other.value) {
+            (IntValue::Small(value1), IntValue::Small(value2)) => value1.cmp(value2),
+            _ => self.as_bigint().cmp(&other.as_bigint()),
+        }
+    }
+}
+
+impl From<BigInt> for PyInt {
+    fn from(value: BigInt) -> PyInt {
+        let value = match value.to_i64() {
+            Some(value) => IntValue::Small(value),
+            None => IntValue::Big(value),
+        };
+        PyInt { value }
+    }
+}
+
+impl From<&BigInt> for PyInt {
+    fn from(value: &BigInt) -> PyInt {
+        let value = match value.to_i64() {
+            Some(value) => IntValue::Small(value),
+            None => IntValue::Big(value.clone()),
+        };
+        PyInt { value }
+    }
+}
+
+macro_rules! impl_from_primitive_int {
+    ($($t:ty)*) => {$(
+        impl From<$t> for PyInt {
+            fn from(value: $t) -> PyInt {
+                match i64::try_from(value) {
+                    Ok(value) => PyInt { value: IntValue::Small(value) },
+                    Err(_) => PyInt { value: IntValue::Big(BigInt::from(value)) },
+                }
+            }
+        }
+    )*};
+}
+
+impl_from_primitive_int!(isize i8 i16 i32 i64 usize u8 u16 u32 u64);
+
+/// Apply a binary operation to the machine words of two ints, or to their big
+/// ints when either is too large or the operation on words overflows.
+fn int_op<F, G>(int1: &PyInt, int2: &PyInt, small_op: F, big_op: G) -> PyInt
+where
+    F: Fn(i64, i64) -> Option<i64>,
+    G: Fn(&BigInt, &BigInt) -> BigInt,
+{
+    if let (IntValue::Small(value1), IntValue::Small(value2)) = (&int1.value, &int2.value) {
+        if let Some(result) = small_op(*value1, *value2) {
+            return PyInt::new(result);
+        }
     }
+    PyInt::new(big_op(&int1.as_bigint(), &int2.as_bigint()))
+}
 
-    pub fn as_bigint(&self) -> &BigInt {
-        &self.value
+/// Floor division of machine words, like the division of big ints.
+fn checked_div_floor(value1: i64, value2: i64) -> Option<i64> {
+    let quotient = value1.checked_div(value2)?;
+    let remainder = value1 % value2;
+    if remainder != 0 && ((remainder < 0) != (value2 < 0)) {
+        Some(quotient - 1)
+    } else {
+        Some(quotient)
     }
 }
 
+/// Left shift of a machine word, unless bits would be shifted out of it.
+fn checked_shl(value: i64, n_bits: i64) -> Option<i64> {
+    if (0..63).contains(&n_bits) {
+        let result = value << n_bits;
+        if result >> n_bits == value {
+            return Some(result);
+        }
+    }
+    None
+}
+
 impl IntoPyObject for BigInt {
     fn into_pyobject(self, vm: &VirtualMachine) -> PyResult {
         Ok(vm.ctx.new_int(self))
@@ -85,7 +224,12 @@ macro_rules! impl_try_from_object_int {
     ($(($t:ty, $to_prim:ident),)*) => {$(
         impl TryFromObject for $t {
             fn try_from_object(vm: &VirtualMachine, obj: PyObjectRef) -> PyResult<Self> {
-                match PyRef::<PyInt>::try_from_object(vm, obj)?.value.$to_prim() {
+                let int = PyRef::<PyInt>::try_from_object(vm, obj)?;
+                let value = match &int.value {
+                    IntValue::Small(value) => value.$to_prim(),
+                    IntValue::Big(value) => value.$to_prim(),
+                };
+                match value {
                     Some(value) => Ok(value),
                     None => Err(
                         vm.new_overflow_error(concat!(
@@ -114,17 +258,23 @@ impl_try_from_object_int!(
 
 #[allow(clippy::collapsible_if)]
 fn inner_pow(int1: &PyInt, int2: &PyInt, vm: &VirtualMachine) -> PyResult {
-    let result = if int2.value.is_negative() {
+    let result = if int2.is_negative() {
         let v1 = int1.float(vm)?;
         let v2 = int2.float(vm)?;
         vm.ctx.new_float(v1.pow(v2))
     } else {
-        if let Some(v2) = int2.value.to_u64() {
-            vm.ctx.new_int(int1.value.pow(v2))
-        } else if int1.value.is_one() || int1.value.is_zero() {
-            vm.ctx.new_int(int1.value.clone())
-        } else if int1.value == BigInt::from(-1) {
-            if int2.value.is_odd() {
+        if let (Some(v1), Some(v2)) = (int1.to_i64(), int2.to_i64()) {
+            if let Some(result) = u32::try_from(v2).ok().and_then(|v2| v1.checked_pow(v2)) {
+                return Ok(vm.ctx.new_int(result));
+            }
+        }
+        let (value1, value2) = (int1.as_bigint(), int2.as_bigint());
+        if let Some(v2) = value2.to_u64() {
+            vm.ctx.new_int(value1.pow(v2))
+        } else if value1.is_one() || value1.is_zero() {
+            vm.ctx.new_int(value1.into_owned())
+        } else if *value1 == BigInt::from(-1) {
+            if value2.is_odd() {
                 vm.ctx.new_int(-1)
             } else {
                 vm.ctx.new_int(1)
@@ -139,8 +289,10 @@ fn inner_pow(int1: &PyInt, int2: &PyInt, vm: &VirtualMachine) -> PyResult {
 }
 
 fn inner_mod(int1: &PyInt, int2: &PyInt, vm: &VirtualMachine) -> PyResult {
-    if int2.value != BigInt::zero() {
-        Ok(vm.ctx.new_int(&int1.value % &int2.value))
+    if !int2.is_zero() {
+        Ok(vm
+            .ctx
+            .new_int(int_op(int1, int2, i64::checked_rem, |v1, v2| v1 % v2)))
     } else {
         Err(vm.new_zero_division_error("integer modulo by zero".to_string()))
     }
@@ -149,63 +301,66 @@ fn inner_mod(int1: &PyInt, int2: &PyInt, vm: &VirtualMachine) -> PyResult {
 #[pyimpl]
 impl PyInt {
     #[pymethod(name = "__eq__")]
-    fn eq(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
+    pub fn eq(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
         if objtype::isinstance(&other, &vm.ctx.int_type()) {
-            vm.ctx.new_bool(self.value == *get_value(&other))
+            vm.ctx.new_bool(self == get_int(&other))
         } else {
             vm.ctx.not_implemented()
         }
     }
 
     #[pymethod(name = "__ne__")]
-    fn ne(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
+    pub fn ne(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
         if objtype::isinstance(&other, &vm.ctx.int_type()) {
-            vm.ctx.new_bool(self.value != *get_value(&other))
+            vm.ctx.new_bool(self != get_int(&other))
         } else {
             vm.ctx.not_implemented()
         }
     }
 
     #[pymethod(name = "__lt__")]
-    fn lt(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
+    pub fn lt(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
         if objtype::isinstance(&other, &vm.ctx.int_type()) {
-            vm.ctx.new_bool(self.value < *get_value(&other))
+            vm.ctx.new_bool(self < get_int(&other))
         } else {
             vm.ctx.not_implemented()
         }
     }
 
     #[pymethod(name = "__le__")]
-    fn le(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
+    pub fn le(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
         if objtype::isinstance(&other, &vm.ctx.int_type()) {
-            vm.ctx.new_bool(self.value <= *get_value(&other))
+            vm.ctx.new_bool(self <= get_int(&other))
         } else {
             vm.ctx.not_implemented()
         }
     }
 
     #[pymethod(name = "__gt__")]
-    fn gt(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
+    pub fn gt(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
         if objtype::isinstance(&other, &vm.ctx.int_type()) {
-            vm.ctx.new_bool(self.value > *get_value(&other))
+            vm.ctx.new_bool(self > get_int(&other))
         } else {
             vm.ctx.not_implemented()
         }
     }
 
     #[pymethod(name = "__ge__")]
-    fn ge(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
+    pub fn ge(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
         if objtype::isinstance(&other, &vm.ctx.int_type()) {
-            vm.ctx.new_bool(self.value >= *get_value(&other))
+            vm.ctx.new_bool(self >= get_int(&other))
         } else {
             vm.ctx.not_implemented()
         }
     }
 
     #[pymethod(name = "__add__")]
-    fn add(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
+    pub fn add(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
         if objtype::isinstance(&other, &vm.ctx.int_type()) {
-            vm.ctx.new_int((&self.value) + get_value(&other))
+            vm.ctx
+                .new_int(int_op(self, get_int(&other), i64::checked_add, |v1, v2| {
+                    v1 + v2
+                }))
         } else {
             vm.ctx.not_implemented()
         }
@@ -217,9 +372,12 @@ impl PyInt {
     }
 
     #[pymethod(name = "__sub__")]
-    fn sub(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
+    pub fn sub(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
         if objtype::isinstance(&other, &vm.ctx.int_type()) {
-            vm.ctx.new_int((&self.value) - get_value(&other))
+            vm.ctx
+                .new_int(int_op(self, get_int(&other), i64::checked_sub, |v1, v2| {
+                    v1 - v2
+                }))
         } else {
             vm.ctx.not_implemented()
         }
@@ -228,16 +386,22 @@ impl PyInt {
     #[pymethod(name = "__rsub__")]
     fn rsub(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
         if objtype::isinstance(&other, &vm.ctx.int_type()) {
-            vm.ctx.new_int(get_value(&other) - (&self.value))
+            vm.ctx
+                .new_int(int_op(get_int(&other), self, i64::checked_sub, |v1, v2| {
+                    v1 - v2
+                }))
         } else {
             vm.ctx.not_implemented()
         }
     }
 
     #[pymethod(name = "__mul__")]
-    fn mul(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
+    pub fn mul(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
         if objtype::isinstance(&other, &vm.ctx.int_type()) {
-            vm.ctx.new_int((&self.value) * get_value(&other))
+            vm.ctx
+                .new_int(int_op(self, get_int(&other), i64::checked_mul, |v1, v2| {
+                    v1 * v2
+                }))
         } else {
             vm.ctx.not_implemented()
         }
@@ -249,9 +413,9 @@ impl PyInt {
     }
 
     #[pymethod(name = "__truediv__")]
-    fn truediv(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyResult {
+    pub fn truediv(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyResult {
         if objtype::isinstance(&other, &vm.ctx.int_type()) {
-            div_ints(vm, &self.value, &get_value(&other))
+            div_ints(vm, self, get_int(&other))
         } else {
             Ok(vm.ctx.not_implemented())
         }
@@ -260,19 +424,23 @@ impl PyInt {
     #[pymethod(name = "__rtruediv__")]
     fn rtruediv(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyResult {
         if objtype::isinstance(&other, &vm.ctx.int_type()) {
-            div_ints(vm, &get_value(&other), &self.value)
+            div_ints(vm, get_int(&other), self)
         } else {
             Ok(vm.ctx.not_implemented())
         }
     }
 
     #[pymethod(name = "__floordiv__")]
-    fn floordiv(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyResult {
+    pub fn floordiv(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyResult {
         if objtype::isinstance(&other, &vm.ctx.int_type()) {
-            let v2 = get_value(&other);
-            if *v2 != BigInt::zero() {
-                let modulo = (&self.value % v2 + v2) % v2;
-                Ok(vm.ctx.new_int((&self.value - modulo) / v2))
+            let other = get_int(&other);
+            if !other.is_zero() {
+                Ok(vm
+                    .ctx
+                    .new_int(int_op(self, other, checked_div_floor, |v1, v2| {
+                        let modulo = (v1 % v2 + v2) % v2;
+                        (v1 - modulo) / v2
+                    })))
             } else {
                 Err(vm.new_zero_division_error("integer floordiv by zero".to_string()))
             }
@@ -282,13 +450,18 @@ impl PyInt {
     }
 
     #[pymethod(name = "__lshift__")]
-    fn lshift(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyResult {
+    pub fn lshift(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyResult {
         if !objtype::isinstance(&other, &vm.ctx.int_type()) {
             return Ok(vm.ctx.not_implemented());
         }
 
+        if let (Some(value), Some(n_bits)) = (self.to_i64(), get_int(&other).to_i64()) {
+            if let Some(result) = checked_shl(value, n_bits) {
+                return Ok(vm.ctx.new_int(result));
+            }
+        }
         if let Some(n_bits) = get_value(&other).to_usize() {
-            return Ok(vm.ctx.new_int((&self.value) << n_bits));
+            return Ok(vm.ctx.new_int(&*self.as_bigint() << n_bits));
         }
 
         // i2 failed `to_usize()` conversion
@@ -302,13 +475,18 @@ impl PyInt {
     }
 
     #[pymethod(name = "__rshift__")]
-    fn rshift(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyResult {
+    pub fn rshift(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyResult {
         if !objtype::isinstance(&other, &vm.ctx.int_type()) {
             return Ok(vm.ctx.not_implemented());
         }
 
+        if let (Some(value), Some(n_bits)) = (self.to_i64(), get_int(&other).to_i64()) {
+            if n_bits >= 0 {
+                return Ok(vm.ctx.new_int(value >> n_bits.min(63)));
+            }
+        }
         if let Some(n_bits) = get_value(&other).to_usize() {
-            return Ok(vm.ctx.new_int((&self.value) >> n_bits));
+            return Ok(vm.ctx.new_int(&*self.as_bigint() >> n_bits));
         }
 
         // i2 failed `to_usize()` conversion
@@ -324,7 +502,12 @@ impl PyInt {
     #[pymethod(name = "__xor__")]
     pub fn xor(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
         if objtype::isinstance(&other, &vm.ctx.int_type()) {
-            vm.ctx.new_int((&self.value) ^ get_value(&other))
+            vm.ctx.new_int(int_op(
+                self,
+                get_int(&other),
+                |v1, v2| Some(v1 ^ v2),
+                |v1, v2| v1 ^ v2,
+            ))
         } else {
             vm.ctx.not_implemented()
         }
@@ -338,7 +521,12 @@ impl PyInt {
     #[pymethod(name = "__or__")]
     pub fn or(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
         if objtype::isinstance(&other, &vm.ctx.int_type()) {
-            vm.ctx.new_int((&self.value) | get_value(&other))
+            vm.ctx.new_int(int_op(
+                self,
+                get_int(&other),
+                |v1, v2| Some(v1 | v2),
+                |v1, v2| v1 | v2,
+            ))
         } else {
             vm.ctx.not_implemented()
         }
@@ -347,15 +535,19 @@ impl PyInt {
     #[pymethod(name = "__and__")]
     pub fn and(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyObjectRef {
         if objtype::isinstance(&other, &vm.ctx.int_type()) {
-            let v2 = get_value(&other);
-            vm.ctx.new_int((&self.value) & v2)
+            vm.ctx.new_int(int_op(
+                self,
+                get_int(&other),
+                |v1, v2| Some(v1 & v2),
+                |v1, v2| v1 & v2,
+            ))
         } else {
             vm.ctx.not_implemented()
         }
     }
 
     #[pymethod(name = "__pow__")]
-    fn pow(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyResult {
+    pub fn pow(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyResult {
         if objtype::isinstance(&other, &vm.ctx.int_type()) {
             let other = other.payload::<PyInt>().unwrap();
             inner_pow(self, &other, vm)
@@ -375,7 +567,7 @@ impl PyInt {
     }
 
     #[pymethod(name = "__mod__")]
-    fn mod_(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyResult {
+    pub fn mod_(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyResult {
         if objtype::isinstance(&other, &vm.ctx.int_type()) {
             let other = other.payload::<PyInt>().unwrap();
             inner_mod(self, &other, vm)
@@ -397,12 +589,13 @@ impl PyInt {
     #[pymethod(name = "__divmod__")]
     fn divmod(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyResult {
         if objtype::isinstance(&other, &vm.ctx.int_type()) {
-            let v2 = get_value(&other);
-            if *v2 != BigInt::zero() {
-                let (r1, r2) = self.value.div_rem(v2);
+            let other = get_int(&other);
+            if !other.is_zero() {
+                let quotient = int_op(self, other, i64::checked_div, |v1, v2| v1 / v2);
+                let remainder = int_op(self, other, i64::checked_rem, |v1, v2| v1 % v2);
                 Ok(vm
                     .ctx
-                    .new_tuple(vec![vm.ctx.new_int(r1), vm.ctx.new_int(r2)]))
+                    .new_tuple(vec![vm.ctx.new_int(quotient), vm.ctx.new_int(remainder)]))
             } else {
                 Err(vm.new_zero_division_error("integer divmod by zero".to_string()))
             }
@@ -412,21 +605,27 @@ impl PyInt {
     }
 
     #[pymethod(name = "__neg__")]
-    fn neg(&self, _vm: &VirtualMachine) -> BigInt {
-        -(&self.value)
+    fn neg(&self, vm: &VirtualMachine) -> PyObjectRef {
+        match self.to_i64().and_then(i64::checked_neg) {
+            Some(value) => vm.ctx.new_int(value),
+            None => vm.ctx.new_int(-&*self.as_bigint()),
+        }
     }
 
     #[pymethod(name = "__hash__")]
     pub fn hash(&self, _vm: &VirtualMachine) -> pyhash::PyHash {
-        match self.value.to_i64() {
-            Some(value) => (value % pyhash::MODULUS as i64),
-            None => (&self.value % pyhash::MODULUS).to_i64().unwrap(),
+        match &self.value {
+            IntValue::Small(value) => pyhash::hash_int(*value),
+            IntValue::Big(value) => pyhash::hash_bigint(value),
         }
     }
 
     #[pymethod(name = "__abs__")]
-    fn abs(&self, _vm: &VirtualMachine) -> BigInt {
-        self.value.abs()
+    fn abs(&self, vm: &VirtualMachine) -> PyObjectRef {
+        match self.to_i64().and_then(i64::checked_abs) {
+            Some(value) => vm.ctx.new_int(value),
+            None => vm.ctx.new_int(self.as_bigint().abs()),
+        }
     }
 
     #[pymethod(name = "__round__")]
@@ -450,7 +649,10 @@ impl PyInt {
 
     #[pymethod(name = "__float__")]
     fn float(&self, vm: &VirtualMachine) -> PyResult<f64> {
-        self.value
+        if let Some(value) = self.to_i64() {
+            return Ok(value as f64);
+        }
+        self.as_bigint()
             .to_f64()
             .ok_or_else(|| vm.new_overflow_error("int too large to convert to float".to_string()))
     }
@@ -476,19 +678,26 @@ impl PyInt {
     }
 
     #[pymethod(name = "__invert__")]
-    fn invert(&self, _vm: &VirtualMachine) -> BigInt {
-        !(&self.value)
+    fn invert(&self, vm: &VirtualMachine) -> PyObjectRef {
+        match &self.value {
+            IntValue::Small(value) => vm.ctx.new_int(!value),
+            IntValue::Big(value) => vm.ctx.new_int(!value),
+        }
     }
 
     #[pymethod(name = "__repr__")]
     fn repr(&self, _vm: &VirtualMachine) -> String {
-        self.value.to_string()
+        self.to_string()
     }
 
     #[pymethod(name = "__format__")]
-    fn format(&self, spec: PyStringRef, vm: &VirtualMachine) -> PyResult<String> {
+    fn format(zelf: PyRef<Self>, spec: PyStringRef, vm: &VirtualMachine) -> PyResult<String> {
+        // An empty spec means str(self), which differs from the number for bool
+        if spec.value.is_empty() {
+            return Ok(vm.to_str(zelf.as_object())?.value.clone());
+        }
         let format_spec = FormatSpec::parse(&spec.value);
-        match format_spec.format_int(&self.value) {
+        match format_spec.format_int(&zelf.as_bigint()) {
             Ok(string) => Ok(string),
             Err(err) => Err(vm.new_value_error(err.to_string())),
         }
@@ -496,12 +705,12 @@ impl PyInt {
 
     #[pymethod(name = "__bool__")]
     fn bool(&self, _vm: &VirtualMachine) -> bool {
-        !self.value.is_zero()
+        !self.is_zero()
     }
 
     #[pymethod]
     fn bit_length(&self, _vm: &VirtualMachine) -> usize {
-        self.value.bits()
+        self.as_bigint().bits()
     }
 
     #[pymethod]
@@ -656,7 +865,13 @@ impl IntOptions {
 }
 
 fn int_new(cls: PyClassRef, options: IntOptions, vm: &VirtualMachine) -> PyResult<PyIntRef> {
-    PyInt::new(options.get_int_value(vm)?).into_ref_with_type(vm, cls)
+    let value = PyInt::new(options.get_int_value(vm)?);
+    if cls.is(&vm.ctx.int_type()) {
+        if let Some(small_int) = value.to_i64().and_then(|value| vm.ctx.small_int(value)) {
+            return Ok(small_int);
+        }
+    }
+    value.into_ref_with_type(vm, cls)
 }
 
 // Casting function:
@@ -676,7 +891,7 @@ pub fn to_int(vm: &VirtualMachine, obj: &PyObjectRef, base: u32) -> PyResult<Big
             })?;
             let result = vm.invoke(method, PyFuncArgs::default())?;
             match result.payload::<PyInt>() {
-                Some(int_obj) => Ok(int_obj.as_bigint().clone()),
+                Some(int_obj) => Ok(int_obj.as_bigint().into_owned()),
                 None => Err(vm.new_type_error(format!(
                     "TypeError: __int__ returned non-int (type '{}')", result.class().name))),
             }
@@ -685,8 +900,12 @@ pub fn to_int(vm: &VirtualMachine, obj: &PyObjectRef, base: u32) -> PyResult<Big
 }
 
 // Retrieve inner int value:
-pub fn get_value(obj: &PyObjectRef) -> &BigInt {
-    &obj.payload::<PyInt>().unwrap().value
+pub fn get_value(obj: &PyObjectRef) -> Cow<'_, BigInt> {
+    get_int(obj).as_bigint()
+}
+
+fn get_int(obj: &PyObjectRef) -> &PyInt {
+    obj.payload::<PyInt>().unwrap()
 }
 
 pub fn get_float_value(obj: &PyObjectRef, vm: &VirtualMachine) -> PyResult<f64> {
@@ -694,16 +913,27 @@ pub fn get_float_value(obj: &PyObjectRef, vm: &VirtualMachine) -> PyResult<f64>
 }
 
 #[inline]
-fn div_ints(vm: &VirtualMachine, i1: &BigInt, i2: &BigInt) -> PyResult {
-    if i2.is_zero() {
+fn div_ints(vm: &VirtualMachine, int1: &PyInt, int2: &PyInt) -> PyResult {
+    if int2.is_zero() {
         return Err(vm.new_zero_division_error("integer division by zero".to_string()));
     }
 
+    // Machine words up to 2**53 convert to floats exactly
+    const EXACT_FLOAT_MAX: i64 = 1 << 53;
+    if let (Some(v1), Some(v2)) = (int1.to_i64(), int2.to_i64()) {
+        let exact = -EXACT_FLOAT_MAX..=EXACT_FLOAT_MAX;
+        if exact.contains(&v1) && exact.contains(&v2) {
+            return Ok(vm.ctx.new_float(v1 as f64 / v2 as f64));
+        }
+    }
+
+    let (i1, i2) = (int1.as_bigint(), int2.as_bigint());
+
     if let (Some(f1), Some(f2)) = (i1.to_f64(), i2.to_f64()) {
         Ok(vm.ctx.new_float(f1 / f2))
     } else {
-        let (quotient, mut rem) = i1.div_rem(i2);
-        let mut divisor = i2.clone();
+        let (quotient, mut rem) = i1.div_rem(&i2);
+        let mut divisor = i2.into_owned();
 
         if let Some(quotient) = quotient.to_f64() {
             let rem_part = loop {
@@ -1,5 +1,7 @@
 use std::cell::{Cell, RefCell};
+use std::cmp;
 use std::fmt;
+use std::mem;
 
 use std::ops::Range;
 
@@ -88,6 +90,20 @@ impl PyList {
         }
     }
 
+    /// Make room for `additional` more elements, accounting for the memory
+    /// of a larger buffer if the list has to grow.
+    fn reserve(&self, additional: usize, vm: &VirtualMachine) -> PyResult<()> {
+        let mut elements = self.elements.borrow_mut();
+        let len = elements.len();
+        let capacity = elements.capacity();
+        if len.saturating_add(additional) > capacity {
+            let new_capacity = cmp::max(len.saturating_add(additional), capacity * 2);
+            vm.reserve_memory(new_capacity, mem::size_of::<PyObjectRef>())?;
+            elements.reserve_exact(new_capacity - len);
+        }
+        Ok(())
+    }
+
     pub fn get_slice_range(&self, start: &Option<BigInt>, stop: &Option<BigInt>) -> Range<usize> {
         let start = start.as_ref().map(|x| self.get_slice_pos(x)).unwrap_or(0);
         let stop = stop
@@ -102,17 +118,21 @@ impl PyList {
 pub type PyListRef = PyRef<PyList>;
 
 impl PyListRef {
-    pub fn append(self, x: PyObjectRef, _vm: &VirtualMachine) {
+    pub fn append(self, x: PyObjectRef, vm: &VirtualMachine) -> PyResult<()> {
+        self.reserve(1, vm)?;
         self.elements.borrow_mut().push(x);
+        Ok(())
     }
 
     fn extend(self, x: PyObjectRef, vm: &VirtualMachine) -> PyResult<()> {
         let mut new_elements = vm.extract_elements(&x)?;
+        self.reserve(new_elements.len(), vm)?;
         self.elements.borrow_mut().append(&mut new_elements);
         Ok(())
     }
 
-    fn insert(self, position: isize, element: PyObjectRef, _vm: &VirtualMachine) {
+    fn insert(self, position: isize, element: PyObjectRef, vm: &VirtualMachine) -> PyResult<()> {
+        self.reserve(1, vm)?;
         let mut vec = self.elements.borrow_mut();
         let vec_len = vec.len().to_isize().unwrap();
         // This unbounded position can be < 0 or > vec.len()
@@ -124,6 +144,7 @@ impl PyListRef {
         // Bound it by [0, vec.len()]
         let position = unbounded_position.max(0).min(vec_len).to_usize().unwrap();
         vec.insert(position, element.clone());
+        Ok(())
     }
 
     fn add(self, other: PyObjectRef, vm: &VirtualMachine) -> PyResult {
@@ -139,9 +160,9 @@ impl PyListRef {
 
     fn iadd(self, other: PyObjectRef, vm: &VirtualMachine) -> PyResult {
         if objtype::isinstance(&other, &vm.ctx.list_type()) {
-            self.elements
-                .borrow_mut()
-                .extend_from_slice(&get_elements_list(&other));
+            let other = get_elements_list(&other).clone();
+            self.reserve(other.len(), vm)?;
+            self.elements.borrow_mut().extend_from_slice(&other);
             Ok(self.into_object())
         } else {
             Ok(vm.ctx.not_implemented())
@@ -391,15 +412,15 @@ impl PyListRef {
         Err(vm.new_type_error("unhashable type".to_string()))
     }
 
-    fn mul(self, counter: isize, vm: &VirtualMachine) -> PyObjectRef {
-        let new_elements = seq_mul(&self.elements.borrow(), counter);
-        vm.ctx.new_list(new_elements)
+    fn mul(self, counter: isize, vm: &VirtualMachine) -> PyResult {
+        let new_elements = seq_mul(vm, &self.elements.borrow(), counter)?;
+        Ok(vm.ctx.new_list(new_elements))
     }
 
-    fn imul(self, counter: isize, _vm: &VirtualMachine) -> Self {
-        let new_elements = seq_mul(&self.elements.borrow(), counter);
+    fn imul(self, counter: isize, vm: &VirtualMachine) -> PyResult<Self> {
+        let new_elements = seq_mul(vm, &self.elements.borrow(), counter)?;
         self.elements.replace(new_elements);
-        self
+        Ok(self)
     }
 
     fn count(self, needle: PyObjectRef, vm: &VirtualMachine) -> PyResult<usize> {
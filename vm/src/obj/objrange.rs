@@ -1,4 +1,5 @@
 use std::cell::Cell;
+use std::convert::TryFrom;
 
 use num_bigint::{BigInt, Sign};
 use num_integer::Integer;
@@ -40,19 +41,19 @@ impl PyValue for PyRange {
 impl PyRange {
     #[inline]
     fn offset(&self, value: &BigInt) -> Option<BigInt> {
-        let start = self.start.as_bigint();
-        let stop = self.stop.as_bigint();
-        let step = self.step.as_bigint();
+        let start = &*self.start.as_bigint();
+        let stop = &*self.stop.as_bigint();
+        let step = &*self.step.as_bigint();
         match step.sign() {
             Sign::Plus if value >= start && value < stop => Some(value - start),
-            Sign::Minus if value <= self.start.as_bigint() && value > stop => Some(start - value),
+            Sign::Minus if value <= start && value > stop => Some(start - value),
             _ => None,
         }
     }
 
     #[inline]
     pub fn index_of(&self, value: &BigInt) -> Option<BigInt> {
-        let step = self.step.as_bigint();
+        let step = &*self.step.as_bigint();
         match self.offset(value) {
             Some(ref offset) if offset.is_multiple_of(step) => Some((offset / step).abs()),
             Some(_) | None => None,
@@ -61,9 +62,9 @@ impl PyRange {
 
     #[inline]
     pub fn is_empty(&self) -> bool {
-        let start = self.start.as_bigint();
-        let stop = self.stop.as_bigint();
-        let step = self.step.as_bigint();
+        let start = &*self.start.as_bigint();
+        let stop = &*self.stop.as_bigint();
+        let step = &*self.step.as_bigint();
         (start <= stop && step.is_negative()) || (start >= stop && step.is_positive())
     }
 
@@ -72,11 +73,26 @@ impl PyRange {
         self.start.as_bigint() < self.stop.as_bigint()
     }
 
+    /// Like `get` for a non-negative index, but on machine words, if the
+    /// bounds of the range fit in them.
+    #[inline]
+    fn get_small(&self, index: usize) -> Option<Option<i64>> {
+        let start = self.start.to_i64()?;
+        let stop = self.stop.to_i64()?;
+        let step = self.step.to_i64()?;
+        // An element which does not fit is beyond the stop
+        let result = i64::try_from(index)
+            .ok()
+            .and_then(|index| step.checked_mul(index))
+            .and_then(|offset| start.checked_add(offset));
+        Some(result.filter(|result| (step > 0 && *result < stop) || (step < 0 && *result > stop)))
+    }
+
     #[inline]
     pub fn get(&self, index: &BigInt) -> Option<BigInt> {
-        let start = self.start.as_bigint();
-        let stop = self.stop.as_bigint();
-        let step = self.step.as_bigint();
+        let start = &*self.start.as_bigint();
+        let stop = &*self.stop.as_bigint();
+        let step = &*self.step.as_bigint();
 
         let index = if index < &BigInt::zero() {
             let index = stop + index;
@@ -162,9 +178,9 @@ impl PyRange {
 
     #[pymethod(name = "__reversed__")]
     fn reversed(&self, vm: &VirtualMachine) -> PyRangeIterator {
-        let start = self.start.as_bigint();
-        let stop = self.stop.as_bigint();
-        let step = self.step.as_bigint();
+        let start = &*self.start.as_bigint();
+        let stop = &*self.stop.as_bigint();
+        let step = &*self.step.as_bigint();
 
         // compute the last element that is actually contained within the range
         // this is the new start
@@ -195,9 +211,9 @@ impl PyRange {
 
     #[pymethod(name = "__len__")]
     fn len(&self, _vm: &VirtualMachine) -> PyInt {
-        let start = self.start.as_bigint();
-        let stop = self.stop.as_bigint();
-        let step = self.step.as_bigint();
+        let start = &*self.start.as_bigint();
+        let stop = &*self.stop.as_bigint();
+        let step = &*self.step.as_bigint();
 
         match step.sign() {
             Sign::Plus if start < stop => PyInt::new((stop - start - 1usize) / step + 1),
@@ -224,8 +240,8 @@ impl PyRange {
     #[pymethod(name = "__contains__")]
     fn contains(&self, needle: PyObjectRef, _vm: &VirtualMachine) -> bool {
         if let Ok(int) = needle.downcast::<PyInt>() {
-            match self.offset(int.as_bigint()) {
-                Some(ref offset) => offset.is_multiple_of(self.step.as_bigint()),
+            match self.offset(&int.as_bigint()) {
+                Some(ref offset) => offset.is_multiple_of(&self.step.as_bigint()),
                 None => false,
             }
         } else {
@@ -248,7 +264,7 @@ impl PyRange {
     #[pymethod(name = "index")]
     fn index(&self, needle: PyObjectRef, vm: &VirtualMachine) -> PyResult<PyInt> {
         if let Ok(int) = needle.downcast::<PyInt>() {
-            match self.index_of(int.as_bigint()) {
+            match self.index_of(&int.as_bigint()) {
                 Some(idx) => Ok(PyInt::new(idx)),
                 None => Err(vm.new_value_error(format!("{} is not in range", int))),
             }
@@ -260,7 +276,7 @@ impl PyRange {
     #[pymethod(name = "count")]
     fn count(&self, item: PyObjectRef, _vm: &VirtualMachine) -> PyInt {
         if let Ok(int) = item.downcast::<PyInt>() {
-            if self.index_of(int.as_bigint()).is_some() {
+            if self.index_of(&int.as_bigint()).is_some() {
                 PyInt::new(1)
             } else {
                 PyInt::new(0)
@@ -274,8 +290,8 @@ impl PyRange {
     fn getitem(&self, subscript: RangeIndex, vm: &VirtualMachine) -> PyResult {
         match subscript {
             RangeIndex::Int(index) => {
-                if let Some(value) = self.get(index.as_bigint()) {
-                    Ok(PyInt::new(value).into_ref(vm).into_object())
+                if let Some(value) = self.get(&index.as_bigint()) {
+                    Ok(vm.new_int(value))
                 } else {
                     Err(vm.new_index_error("range object index out of range".to_string()))
                 }
@@ -302,7 +318,7 @@ impl PyRange {
                 };
 
                 let new_step = if let Some(int) = slice.step_index(vm)? {
-                    PyInt::new(int * self.step.as_bigint()).into_ref(vm)
+                    PyInt::new(int * &*self.step.as_bigint()).into_ref(vm)
                 } else {
                     self.step.clone()
                 };
@@ -350,10 +366,17 @@ type PyRangeIteratorRef = PyRef<PyRangeIterator>;
 #[pyimpl]
 impl PyRangeIterator {
     #[pymethod(name = "__next__")]
-    fn next(&self, vm: &VirtualMachine) -> PyResult<BigInt> {
-        let position = BigInt::from(self.position.get());
-        if let Some(int) = self.range.get(&position) {
-            self.position.set(self.position.get() + 1);
+    fn next(&self, vm: &VirtualMachine) -> PyResult {
+        let position = self.position.get();
+        let int = match self.range.get_small(position) {
+            Some(int) => int.map(|int| vm.new_int(int)),
+            None => self
+                .range
+                .get(&BigInt::from(position))
+                .map(|int| vm.new_int(int)),
+        };
+        if let Some(int) = int {
+            self.position.set(position + 1);
             Ok(int)
         } else {
             Err(objiter::new_stop_iteration(vm))
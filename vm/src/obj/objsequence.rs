@@ -2,6 +2,7 @@ use crate::function::OptionalArg;
 use crate::obj::objnone::PyNone;
 use std::cell::RefCell;
 use std::marker::Sized;
+use std::mem;
 use std::ops::{Deref, DerefMut, Range};
 
 use crate::pyobject::{IdProtocol, PyObject, PyObjectRef, PyResult, TryFromObject, TypeProtocol};
@@ -332,16 +333,23 @@ pub fn seq_le(
     Ok(seq_lt(vm, zelf, other)? || seq_equal(vm, zelf, other)?)
 }
 
-pub fn seq_mul(elements: &[PyObjectRef], counter: isize) -> Vec<PyObjectRef> {
-    let current_len = elements.len();
-    let new_len = counter.max(0) as usize * current_len;
-    let mut new_elements = Vec::with_capacity(new_len);
+pub fn seq_mul(
+    vm: &VirtualMachine,
+    elements: &[PyObjectRef],
+    counter: isize,
+) -> PyResult<Vec<PyObjectRef>> {
+    if elements.is_empty() || counter <= 0 {
+        return Ok(vec![]);
+    }
+    let counter = counter as usize;
+    vm.reserve_memory(counter, mem::size_of_val(elements))?;
+    let mut new_elements = Vec::with_capacity(counter * elements.len());
 
     for _ in 0..counter {
         new_elements.extend(elements.to_owned());
     }
 
-    new_elements
+    Ok(new_elements)
 }
 
 pub fn get_elements_cell<'a>(obj: &'a PyObjectRef) -> &'a RefCell<Vec<PyObjectRef>> {
@@ -379,7 +387,7 @@ pub fn is_valid_slice_arg(
 ) -> Result<Option<BigInt>, PyObjectRef> {
     if let OptionalArg::Present(value) = arg {
         match_class!(value,
-        i @ PyInt => Ok(Some(i.as_bigint().clone())),
+        i @ PyInt => Ok(Some(i.as_bigint().into_owned())),
         _obj @ PyNone => Ok(None),
         _=> {return Err(vm.new_type_error("slice indices must be integers or None or have an __index__ method".to_string()));}
         // TODO: check for an __index__ method
@@ -95,13 +95,13 @@ fn to_index_value(vm: &VirtualMachine, obj: &PyObjectRef) -> PyResult<Option<Big
     }
 
     if let Some(val) = obj.payload::<PyInt>() {
-        Ok(Some(val.as_bigint().clone()))
+        Ok(Some(val.as_bigint().into_owned()))
     } else {
         let cls = obj.class();
         if class_has_attr(&cls, "__index__") {
             let index_result = vm.call_method(obj, "__index__", vec![])?;
             if let Some(val) = index_result.payload::<PyInt>() {
-                Ok(Some(val.as_bigint().clone()))
+                Ok(Some(val.as_bigint().into_owned()))
             } else {
                 Err(vm.new_type_error("__index__ method returned non integer".to_string()))
             }
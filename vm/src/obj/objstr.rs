@@ -196,14 +196,15 @@ impl PyString {
         if !objtype::isinstance(&val, &vm.ctx.int_type()) {
             return Err(vm.new_type_error(format!("Cannot multiply {} and {}", self, val)));
         }
-        objint::get_value(&val)
+        let multiplier = objint::get_value(&val)
             .to_isize()
             .map(|multiplier| multiplier.max(0))
             .and_then(|multiplier| multiplier.to_usize())
-            .map(|multiplier| self.value.repeat(multiplier))
             .ok_or_else(|| {
                 vm.new_overflow_error("cannot fit 'int' into an index-sized integer".to_string())
-            })
+            })?;
+        vm.reserve_memory(multiplier, self.value.len())?;
+        Ok(self.value.repeat(multiplier))
     }
 
     #[pymethod(name = "__rmul__")]
@@ -779,12 +780,13 @@ impl PyString {
     }
 
     #[pymethod]
-    fn zfill(&self, len: usize, _vm: &VirtualMachine) -> String {
+    fn zfill(&self, len: usize, vm: &VirtualMachine) -> PyResult<String> {
         let value = &self.value;
         if len <= value.len() {
-            value.to_string()
+            Ok(value.to_string())
         } else {
-            format!("{}{}", "0".repeat(len - value.len()), value)
+            vm.reserve_memory(len, 1)?;
+            Ok(format!("{}{}", "0".repeat(len - value.len()), value))
         }
     }
 
@@ -817,6 +819,7 @@ impl PyString {
         if len <= value.len() {
             Ok(value.to_string())
         } else {
+            vm.reserve_memory(len - value.len(), rep_char.len())?;
             Ok(format!("{}{}", value, rep_char.repeat(len - value.len())))
         }
     }
@@ -833,6 +836,7 @@ impl PyString {
         if len <= value.len() {
             Ok(value.to_string())
         } else {
+            vm.reserve_memory(len - value.len(), rep_char.len())?;
             Ok(format!("{}{}", rep_char.repeat(len - value.len()), value))
         }
     }
@@ -852,6 +856,7 @@ impl PyString {
             return Ok(value.to_string());
         }
         let diff: usize = len - value_len;
+        vm.reserve_memory(diff, rep_char.len())?;
         let mut left_buff: usize = diff / 2;
         let mut right_buff: usize = left_buff;
 
@@ -1039,6 +1044,12 @@ impl IntoPyObject for String {
     }
 }
 
+impl TryFromObject for String {
+    fn try_from_object(vm: &VirtualMachine, obj: PyObjectRef) -> PyResult<Self> {
+        Ok(PyStringRef::try_from_object(vm, obj)?.value.clone())
+    }
+}
+
 impl IntoPyObject for &str {
     fn into_pyobject(self, vm: &VirtualMachine) -> PyResult {
         Ok(vm.ctx.new_str(self.to_string()))
@@ -1122,7 +1133,7 @@ fn do_cformat_specifier(
                     obj.class()
                 )));
             }
-            Ok(format_spec.format_number(objint::get_value(&obj)))
+            Ok(format_spec.format_number(&objint::get_value(&obj)))
         }
         CFormatType::Character => {
             let char_string = {
@@ -6,13 +6,12 @@ https://github.com/python/cpython/blob/50b48572d9a90c5bb36e2bef6179548ea927a35a/
 
 */
 
-use crate::frame::NameProtocol;
 use crate::function::{OptionalArg, PyFuncArgs};
 use crate::obj::objfunction::PyMethod;
 use crate::obj::objstr;
 use crate::obj::objtype::{PyClass, PyClassRef};
 use crate::pyobject::{
-    ItemProtocol, PyContext, PyObjectRef, PyRef, PyResult, PyValue, TryFromObject, TypeProtocol,
+    PyContext, PyObjectRef, PyRef, PyResult, PyValue, TryFromObject, TypeProtocol,
 };
 use crate::vm::VirtualMachine;
 
@@ -100,9 +99,16 @@ fn super_new(
     let py_type = if let OptionalArg::Present(ty) = py_type {
         ty.clone()
     } else {
-        match vm.current_scope().load_cell(vm, "__class__") {
-            Some(obj) => PyClassRef::try_from_object(vm, obj)?,
-            _ => {
+        let frame = vm.current_frame().expect("no current frame for super()");
+        match frame.load_free("__class__") {
+            Some(Some(obj)) => PyClassRef::try_from_object(vm, obj)?,
+            Some(None) => {
+                return Err(vm.new_exception(
+                    vm.ctx.exceptions.runtime_error.clone(),
+                    "super(): empty __class__ cell".to_string(),
+                ));
+            }
+            None => {
                 return Err(vm.new_type_error(
                     "super must be called with 1 argument or from inside class method".to_string(),
                 ));
@@ -124,7 +130,7 @@ fn super_new(
     } else {
         let frame = vm.current_frame().expect("no current frame for super()");
         if let Some(first_arg) = frame.code.arg_names.get(0) {
-            match vm.get_locals().get_item_option(first_arg, vm)? {
+            match frame.load_local(vm, first_arg) {
                 Some(obj) => obj.clone(),
                 _ => {
                     return Err(vm.new_type_error(format!(
@@ -163,14 +163,14 @@ impl PyTupleRef {
         Ok(s)
     }
 
-    fn mul(self, counter: isize, vm: &VirtualMachine) -> PyObjectRef {
-        let new_elements = seq_mul(&self.elements, counter);
-        vm.ctx.new_tuple(new_elements)
+    fn mul(self, counter: isize, vm: &VirtualMachine) -> PyResult {
+        let new_elements = seq_mul(vm, &self.elements, counter)?;
+        Ok(vm.ctx.new_tuple(new_elements))
     }
 
-    fn rmul(self, counter: isize, vm: &VirtualMachine) -> PyObjectRef {
-        let new_elements = seq_mul(&self.elements, counter);
-        vm.ctx.new_tuple(new_elements)
+    fn rmul(self, counter: isize, vm: &VirtualMachine) -> PyResult {
+        let new_elements = seq_mul(vm, &self.elements, counter)?;
+        Ok(vm.ctx.new_tuple(new_elements))
     }
 
     fn getitem(self, needle: PyObjectRef, vm: &VirtualMachine) -> PyResult {
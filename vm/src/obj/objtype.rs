@@ -1,6 +1,7 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use crate::function::{Args, KwArgs, PyFuncArgs};
 use crate::pyobject::{
@@ -23,6 +24,37 @@ pub struct PyClass {
     pub mro: Vec<PyClassRef>,
     pub subclasses: RefCell<Vec<PyWeak>>,
     pub attributes: RefCell<PyAttributes>,
+    /// Unique among all classes, and renewed whenever an attribute of this
+    /// class or of a class in its MRO is set or deleted.
+    pub version_tag: Cell<u64>,
+    /// Results of attribute lookups along the MRO, cleared together with
+    /// the renewal of the version tag.
+    pub attribute_cache: RefCell<HashMap<String, Option<PyObjectRef>>>,
+}
+
+/// The number of attribute names a class caches lookups for, at most.
+const ATTRIBUTE_CACHE_SIZE: usize = 4096;
+
+static NEXT_VERSION_TAG: AtomicU64 = AtomicU64::new(1);
+
+pub fn next_version_tag() -> u64 {
+    NEXT_VERSION_TAG.fetch_add(1, Ordering::Relaxed)
+}
+
+impl PyClass {
+    /// Invalidate the cached attribute lookups of this class and of its
+    /// subclasses, after one of its attributes changed.
+    pub fn modified(&self) {
+        self.version_tag.set(next_version_tag());
+        self.attribute_cache.borrow_mut().clear();
+        for subclass in self.subclasses.borrow().iter() {
+            if let Some(subclass) = subclass.upgrade() {
+                if let Some(subclass) = subclass.payload::<PyClass>() {
+                    subclass.modified();
+                }
+            }
+        }
+    }
 }
 
 impl fmt::Display for PyClass {
@@ -164,6 +196,30 @@ impl PyClassRef {
         self.attributes
             .borrow_mut()
             .insert(attr_name.to_string(), value);
+        self.modified();
+        Ok(())
+    }
+
+    fn del_attr(self, attr_name: PyStringRef, vm: &VirtualMachine) -> PyResult<()> {
+        if let Some(attr) = class_get_attr(&self.class(), &attr_name.value) {
+            if let Some(descriptor) = class_get_attr(&attr.class(), "__delete__") {
+                vm.invoke(descriptor, vec![attr, self.into_object()])?;
+                return Ok(());
+            }
+        }
+
+        if self
+            .attributes
+            .borrow_mut()
+            .remove(&attr_name.value)
+            .is_none()
+        {
+            return Err(vm.new_attribute_error(format!(
+                "type object '{}' has no attribute '{}'",
+                self.name, &attr_name.value
+            )));
+        }
+        self.modified();
         Ok(())
     }
 
@@ -172,6 +228,7 @@ impl PyClassRef {
         self.attributes
             .borrow_mut()
             .insert(attr_name.to_string(), value.into());
+        self.modified();
     }
 
     fn subclasses(self, _vm: &VirtualMachine) -> PyList {
@@ -213,6 +270,7 @@ pub fn init(ctx: &PyContext) {
         "__prepare__" => ctx.new_rustfunc(PyClassRef::prepare),
         "__getattribute__" => ctx.new_rustfunc(PyClassRef::getattribute),
         "__setattr__" => ctx.new_rustfunc(PyClassRef::set_attr),
+        "__delattr__" => ctx.new_rustfunc(PyClassRef::del_attr),
         "__subclasses__" => ctx.new_rustfunc(PyClassRef::subclasses),
         "__getattribute__" => ctx.new_rustfunc(PyClassRef::getattribute),
         "__instancecheck__" => ctx.new_rustfunc(PyClassRef::instance_check),
@@ -292,6 +350,19 @@ fn type_dict_setter(_instance: PyClassRef, _value: PyObjectRef, vm: &VirtualMach
 
 /// This is the internal get_attr implementation for fast lookup on a class.
 pub fn class_get_attr(class: &PyClassRef, attr_name: &str) -> Option<PyObjectRef> {
+    let mut cache = class.attribute_cache.borrow_mut();
+    if let Some(item) = cache.get(attr_name) {
+        return item.clone();
+    }
+    if cache.len() >= ATTRIBUTE_CACHE_SIZE {
+        cache.clear();
+    }
+    let item = iter_mro_get_attr(class, attr_name);
+    cache.insert(attr_name.to_string(), item.clone());
+    item
+}
+
+fn iter_mro_get_attr(class: &PyClassRef, attr_name: &str) -> Option<PyObjectRef> {
     if let Some(item) = class.attributes.borrow().get(attr_name).cloned() {
         return Some(item);
     }
@@ -305,11 +376,7 @@ pub fn class_get_attr(class: &PyClassRef, attr_name: &str) -> Option<PyObjectRef
 
 // This is the internal has_attr implementation for fast lookup on a class.
 pub fn class_has_attr(class: &PyClassRef, attr_name: &str) -> bool {
-    class.attributes.borrow().contains_key(attr_name)
-        || class
-            .mro
-            .iter()
-            .any(|c| c.attributes.borrow().contains_key(attr_name))
+    class_get_attr(class, attr_name).is_some()
 }
 
 pub fn get_attributes(cls: PyClassRef) -> PyAttributes {
@@ -384,6 +451,8 @@ pub fn new(
             mro,
             subclasses: RefCell::new(vec![]),
             attributes: RefCell::new(dict),
+            version_tag: Cell::new(next_version_tag()),
+            attribute_cache: RefCell::new(HashMap::new()),
         },
         dict: None,
         typ,
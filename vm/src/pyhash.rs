@@ -1,5 +1,8 @@
 use std::hash::{Hash, Hasher};
 
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+
 use crate::obj::objfloat;
 use crate::pyobject::PyObjectRef;
 use crate::pyobject::PyResult;
@@ -64,6 +67,17 @@ pub fn hash_float(value: f64) -> PyHash {
     x as PyHash * value.signum() as PyHash
 }
 
+pub fn hash_int(value: i64) -> PyHash {
+    value % MODULUS as PyHash
+}
+
+pub fn hash_bigint(value: &BigInt) -> PyHash {
+    match value.to_i64() {
+        Some(value) => hash_int(value),
+        None => (value % MODULUS).to_i64().unwrap(),
+    }
+}
+
 pub fn hash_value<T: Hash>(data: &T) -> PyHash {
     let mut hasher = std::collections::hash_map::DefaultHasher::new();
     data.hash(&mut hasher);
@@ -21,6 +21,7 @@ use crate::obj::objbool;
 use crate::obj::objbuiltinfunc::PyBuiltinFunction;
 use crate::obj::objbytearray;
 use crate::obj::objbytes;
+use crate::obj::objcell;
 use crate::obj::objclassmethod::{self, PyClassMethod};
 use crate::obj::objcode;
 use crate::obj::objcode::PyCodeRef;
@@ -49,7 +50,7 @@ use crate::obj::objrange;
 use crate::obj::objset::{self, PySet};
 use crate::obj::objslice;
 use crate::obj::objstaticmethod;
-use crate::obj::objstr;
+use crate::obj::objstr::{self, PyStringRef};
 use crate::obj::objsuper;
 use crate::obj::objtuple::{self, PyTuple, PyTupleRef};
 use crate::obj::objtype::{self, PyClass, PyClassRef};
@@ -117,6 +118,7 @@ pub struct PyContext {
     pub bytearray_type: PyClassRef,
     pub bytearrayiterator_type: PyClassRef,
     pub bool_type: PyClassRef,
+    pub cell_type: PyClassRef,
     pub classmethod_type: PyClassRef,
     pub code_type: PyClassRef,
     pub dict_type: PyClassRef,
@@ -168,8 +170,17 @@ pub struct PyContext {
     pub mappingproxy_type: PyClassRef,
     pub object: PyClassRef,
     pub exceptions: exceptions::ExceptionZoo,
+    /// The preallocated ints from `SMALL_INT_MIN` to `SMALL_INT_MAX`.
+    small_ints: Vec<PyIntRef>,
+    /// The interned strings, which are shared by all equal names.
+    interned_strs: RefCell<HashMap<String, PyStringRef>>,
 }
 
+/// The range of ints which are allocated once and shared, since they are
+/// created all the time by loops, indexing and arithmetic.
+const SMALL_INT_MIN: i64 = -5;
+const SMALL_INT_MAX: i64 = 256;
+
 pub fn create_type(name: &str, type_type: &PyClassRef, base: &PyClassRef) -> PyClassRef {
     let dict = PyAttributes::new();
     objtype::new(type_type.clone(), name, vec![base.clone()], dict).unwrap()
@@ -211,6 +222,8 @@ fn init_type_hierarchy() -> (PyClassRef, PyClassRef) {
                 mro: vec![],
                 subclasses: RefCell::new(vec![]),
                 attributes: RefCell::new(PyAttributes::new()),
+                version_tag: Cell::new(objtype::next_version_tag()),
+                attribute_cache: RefCell::new(HashMap::new()),
             },
         }
         .into_ref();
@@ -223,6 +236,8 @@ fn init_type_hierarchy() -> (PyClassRef, PyClassRef) {
                 mro: vec![object_type.clone().downcast().unwrap()],
                 subclasses: RefCell::new(vec![]),
                 attributes: RefCell::new(PyAttributes::new()),
+                version_tag: Cell::new(objtype::next_version_tag()),
+                attribute_cache: RefCell::new(HashMap::new()),
             },
         }
         .into_ref();
@@ -296,6 +311,7 @@ impl PyContext {
         let bool_type = create_type("bool", &type_type, &int_type);
         let memoryview_type = create_type("memoryview", &type_type, &object_type);
         let code_type = create_type("code", &type_type, &object_type);
+        let cell_type = create_type("cell", &type_type, &object_type);
         let range_type = create_type("range", &type_type, &object_type);
         let rangeiterator_type = create_type("range_iterator", &type_type, &object_type);
         let slice_type = create_type("slice", &type_type, &object_type);
@@ -320,6 +336,9 @@ impl PyContext {
 
         let true_value = create_object(PyInt::new(BigInt::one()), &bool_type);
         let false_value = create_object(PyInt::new(BigInt::zero()), &bool_type);
+        let small_ints = (SMALL_INT_MIN..=SMALL_INT_MAX)
+            .map(|i| create_object(PyInt::new(i), &int_type))
+            .collect();
         let context = PyContext {
             bool_type,
             memoryview_type,
@@ -327,6 +346,7 @@ impl PyContext {
             bytearrayiterator_type,
             bytes_type,
             bytesiterator_type,
+            cell_type,
             code_type,
             complex_type,
             classmethod_type,
@@ -377,6 +397,8 @@ impl PyContext {
             weakproxy_type,
             type_type,
             exceptions,
+            small_ints,
+            interned_strs: RefCell::new(HashMap::new()),
         };
         objtype::init(&context);
         objlist::init(&context);
@@ -408,6 +430,7 @@ impl PyContext {
         objzip::init(&context);
         objbool::init(&context);
         objcode::init(&context);
+        objcell::init(&context);
         objframe::init(&context);
         objweakref::init(&context);
         objweakproxy::init(&context);
@@ -435,6 +458,10 @@ impl PyContext {
         self.bytesiterator_type.clone()
     }
 
+    pub fn cell_type(&self) -> PyClassRef {
+        self.cell_type.clone()
+    }
+
     pub fn code_type(&self) -> PyClassRef {
         self.code_type.clone()
     }
@@ -599,8 +626,21 @@ impl PyContext {
         self.object.clone()
     }
 
-    pub fn new_int<T: Into<BigInt>>(&self, i: T) -> PyObjectRef {
-        PyObject::new(PyInt::new(i), self.int_type(), None)
+    pub fn new_int<T: Into<PyInt>>(&self, i: T) -> PyObjectRef {
+        let i = i.into();
+        match i.to_i64().and_then(|value| self.small_int(value)) {
+            Some(small_int) => small_int.into_object(),
+            None => PyObject::new(i, self.int_type(), None),
+        }
+    }
+
+    /// The shared int object for `value`, if it is in the small int range.
+    pub fn small_int(&self, value: i64) -> Option<PyIntRef> {
+        if (SMALL_INT_MIN..=SMALL_INT_MAX).contains(&value) {
+            Some(self.small_ints[(value - SMALL_INT_MIN) as usize].clone())
+        } else {
+            None
+        }
     }
 
     pub fn new_float(&self, value: f64) -> PyObjectRef {
@@ -615,6 +655,28 @@ impl PyContext {
         PyObject::new(objstr::PyString { value: s }, self.str_type(), None)
     }
 
+    /// The interned string equal to `s`, which is created on first use.
+    pub fn intern_str(&self, s: &str) -> PyStringRef {
+        if let Some(interned) = self.interned_strs.borrow().get(s) {
+            return interned.clone();
+        }
+        let interned: PyStringRef = self.new_str(s.to_string()).downcast().unwrap();
+        self.interned_strs
+            .borrow_mut()
+            .insert(s.to_string(), interned.clone());
+        interned
+    }
+
+    /// Intern the given string, unless an equal string is interned already,
+    /// in which case that one is returned instead.
+    pub fn intern_string(&self, s: PyStringRef) -> PyStringRef {
+        self.interned_strs
+            .borrow_mut()
+            .entry(s.value.clone())
+            .or_insert(s)
+            .clone()
+    }
+
     pub fn new_bytes(&self, data: Vec<u8>) -> PyObjectRef {
         PyObject::new(objbytes::PyBytes::new(data), self.bytes_type(), None)
     }
@@ -705,7 +767,7 @@ impl PyContext {
     }
 
     pub fn new_code_object(&self, code: bytecode::CodeObject) -> PyCodeRef {
-        PyObject::new(objcode::PyCode::new(code), self.code_type(), None)
+        PyObject::new(objcode::PyCode::new(code, self), self.code_type(), None)
             .downcast()
             .unwrap()
     }
@@ -716,9 +778,10 @@ impl PyContext {
         scope: Scope,
         defaults: Option<PyTupleRef>,
         kw_only_defaults: Option<PyDictRef>,
+        closure: Option<PyTupleRef>,
     ) -> PyObjectRef {
         PyObject::new(
-            PyFunction::new(code_obj, scope, defaults, kw_only_defaults),
+            PyFunction::new(code_obj, scope, defaults, kw_only_defaults, closure),
             self.function_type(),
             Some(self.new_dict()),
         )
@@ -746,7 +809,15 @@ impl PyContext {
             bytecode::Constant::Integer { ref value } => self.new_int(value.clone()),
             bytecode::Constant::Float { ref value } => self.new_float(*value),
             bytecode::Constant::Complex { ref value } => self.new_complex(*value),
-            bytecode::Constant::String { ref value } => self.new_str(value.clone()),
+            bytecode::Constant::String { ref value } => {
+                // Like names, strings which look like identifiers are likely
+                // to be used as attribute names or dict keys.
+                if value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                    self.intern_str(value).into_object()
+                } else {
+                    self.new_str(value.clone())
+                }
+            }
             bytecode::Constant::Bytes { ref value } => self.new_bytes(value.clone()),
             bytecode::Constant::Boolean { ref value } => self.new_bool(value.clone()),
             bytecode::Constant::Code { ref code } => {
@@ -1158,6 +1229,14 @@ where
     }
 }
 
+impl<T: TryFromObject> TryFromObject for Vec<T> {
+    fn try_from_object(vm: &VirtualMachine, obj: PyObjectRef) -> PyResult<Self> {
+        PyIterable::<T>::try_from_object(vm, obj)?
+            .iter(vm)?
+            .collect()
+    }
+}
+
 impl TryFromObject for PyObjectRef {
     fn try_from_object(_vm: &VirtualMachine, obj: PyObjectRef) -> PyResult<Self> {
         Ok(obj)
@@ -0,0 +1,196 @@
+//! Restrictions for running untrusted code: a policy on what the os, io,
+//! socket and subprocess modules may access, and limits on the resources
+//! the code may use.
+
+use std::cell::{Cell, RefCell};
+use std::cmp;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::pyobject::{PyObjectRef, PyResult};
+use crate::vm::VirtualMachine;
+
+/// An operation reaching outside the interpreter, which the access policy of
+/// the vm may deny.
+#[derive(Debug, Clone, Copy)]
+pub enum Access<'a> {
+    /// Opening a file for reading, or reading the contents or metadata of a
+    /// file or directory.
+    Read(&'a str),
+    /// Creating, changing or removing a file or directory.
+    Write(&'a str),
+    /// Using a file descriptor which the vm didn't open itself, such as
+    /// those of the standard streams.
+    Descriptor(i64),
+    /// Connecting to or listening on the network.
+    Network,
+    /// Starting, signalling or replacing a process, including the one of
+    /// the interpreter, or reading or changing its environment.
+    Process,
+}
+
+impl fmt::Display for Access<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Access::Read(path) => write!(f, "read access to '{}'", path),
+            Access::Write(path) => write!(f, "write access to '{}'", path),
+            Access::Descriptor(fd) => write!(f, "access to file descriptor {}", fd),
+            Access::Network => f.write_str("network access"),
+            Access::Process => f.write_str("process control"),
+        }
+    }
+}
+
+/// Decides whether an `Access` is allowed.
+pub type AccessPolicy = Box<dyn Fn(&VirtualMachine, Access) -> bool>;
+
+/// The modules that `VirtualMachineBuilder::sandboxed` disables, since they
+/// reach outside the interpreter without an access policy.
+pub const UNSAFE_MODULES: &[&str] = &["_posixsubprocess", "pwd", "select", "signal", "socket"];
+
+/// Limits on the resources used by each call into the vm from the host, such
+/// as running code with `run_code_obj`. Python code exceeding a limit gets a
+/// `ResourceLimitError`, which is a `BaseException`, or a `MemoryError` for
+/// the memory limit.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceLimits {
+    /// The number of bytecode instructions that may run.
+    pub max_instructions: Option<u64>,
+    /// How long the code may run. The time is checked between instructions,
+    /// and `time.sleep`, `select.select` and `os.read` don't wait past it,
+    /// but other blocking calls, such as reading from a socket or waiting
+    /// for a process, may overrun it.
+    pub timeout: Option<Duration>,
+    /// How many bytes the vm may allocate for the contents of strings, bytes
+    /// and sequences. Large allocations, such as repeating a sequence, are
+    /// checked before they are made and raise `MemoryError` if they would
+    /// exceed the limit. Memory freed by the code isn't counted back.
+    pub max_memory: Option<usize>,
+    /// How many frames may be on the call stack.
+    pub max_recursion_depth: Option<usize>,
+}
+
+/// How many instructions run between checks of the time limit.
+const CHECK_INTERVAL: u64 = 1000;
+
+/// The resources used by the current call into the vm.
+#[derive(Default)]
+pub(crate) struct LimitState {
+    limits: RefCell<ResourceLimits>,
+    /// Instructions left to run before the next check.
+    countdown: Cell<u64>,
+    /// The instructions run until the last check.
+    executed: Cell<u64>,
+    /// The instructions which could run between the last check and the next.
+    period: Cell<u64>,
+    deadline: Cell<Option<Instant>>,
+    /// The bytes allocated through `reserve_memory`.
+    memory_used: Cell<usize>,
+}
+
+impl LimitState {
+    pub fn set_limits(&self, limits: ResourceLimits) {
+        self.limits.replace(limits);
+        self.reset();
+    }
+
+    pub fn max_recursion_depth(&self) -> Option<usize> {
+        self.limits.borrow().max_recursion_depth
+    }
+
+    /// Start measuring the resources used from now.
+    pub fn reset(&self) {
+        let limits = self.limits.borrow();
+        self.countdown.set(0);
+        self.executed.set(0);
+        self.period.set(0);
+        self.deadline
+            .set(limits.timeout.map(|timeout| Instant::now() + timeout));
+        self.memory_used.set(0);
+    }
+
+    /// Count `count` items of `size` bytes which are about to be allocated.
+    pub fn reserve_memory(&self, vm: &VirtualMachine, count: usize, size: usize) -> PyResult<()> {
+        let used = count
+            .checked_mul(size)
+            .filter(|&bytes| bytes <= isize::max_value() as usize)
+            .and_then(|bytes| self.memory_used.get().checked_add(bytes));
+        match (used, self.limits.borrow().max_memory) {
+            (Some(used), Some(max_memory)) if used > max_memory => Err(vm.new_memory_error()),
+            (Some(used), _) => {
+                self.memory_used.set(used);
+                Ok(())
+            }
+            (None, _) => Err(vm.new_memory_error()),
+        }
+    }
+
+    /// Count an instruction about to run, and check the limits periodically.
+    #[inline]
+    pub fn check(&self, vm: &VirtualMachine) -> PyResult<()> {
+        let countdown = self.countdown.get();
+        if countdown > 0 {
+            self.countdown.set(countdown - 1);
+            Ok(())
+        } else {
+            self.check_all(vm)
+        }
+    }
+
+    fn check_all(&self, vm: &VirtualMachine) -> PyResult<()> {
+        let executed = self.executed.get() + self.period.get();
+        self.executed.set(executed);
+        self.period.set(0);
+
+        let limits = self.limits.borrow();
+        let mut period = CHECK_INTERVAL;
+        if let Some(max_instructions) = limits.max_instructions {
+            if executed >= max_instructions {
+                return Err(limit_error(vm, "instruction limit exceeded"));
+            }
+            period = cmp::min(period, max_instructions - executed);
+        }
+        self.check_deadline(vm)?;
+        // This instruction is the first of the period
+        self.period.set(period);
+        self.countdown.set(period - 1);
+        Ok(())
+    }
+
+    /// Raise the time limit error if the deadline has passed.
+    pub fn check_deadline(&self, vm: &VirtualMachine) -> PyResult<()> {
+        match self.deadline.get() {
+            Some(deadline) if Instant::now() >= deadline => {
+                Err(limit_error(vm, "time limit exceeded"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Shorten the `timeout` of a blocking call, which waits forever if it is
+    /// `None`, to the time left before the deadline. Callers whose wait was
+    /// cut short should `check_deadline` when it ends.
+    pub fn clamp_timeout(
+        &self,
+        vm: &VirtualMachine,
+        timeout: Option<Duration>,
+    ) -> PyResult<Option<Duration>> {
+        let now = Instant::now();
+        match self.deadline.get() {
+            Some(deadline) if now >= deadline => Err(limit_error(vm, "time limit exceeded")),
+            Some(deadline) => {
+                let left = deadline - now;
+                let timeout = timeout.map_or(left, |timeout| cmp::min(timeout, left));
+                Ok(Some(timeout))
+            }
+            None => Ok(timeout),
+        }
+    }
+}
+
+pub(crate) fn limit_error(vm: &VirtualMachine, msg: &str) -> PyObjectRef {
+    vm.new_exception(
+        vm.ctx.exceptions.resource_limit_error.clone(),
+        msg.to_string(),
+    )
+}
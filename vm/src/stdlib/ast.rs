@@ -199,6 +199,25 @@ fn statement_to_ast(
         ast::Statement::Expression { expression } => node!(vm, Expr, {
             value => expression_to_ast(vm, expression)?
         }),
+        ast::Statement::AnnAssign {
+            target,
+            annotation,
+            value,
+            simple,
+        } => {
+            let py_value = if let Some(value) = value {
+                expression_to_ast(vm, value)?.into_object()
+            } else {
+                vm.ctx.none()
+            };
+
+            node!(vm, AnnAssign, {
+                target => expression_to_ast(vm, target)?,
+                annotation => expression_to_ast(vm, annotation)?,
+                value => py_value,
+                simple => vm.ctx.new_int(*simple as i32)
+            })
+        }
         x => {
             return Err(vm.new_type_error(format!("Ast not implemented: {:?}", x)));
         }
@@ -323,6 +342,10 @@ fn expression_to_ast(vm: &VirtualMachine, expression: &ast::Expression) -> PyRes
             body => expression_to_ast(vm, body)?,
             or_else => expression_to_ast(vm, orelse)?,
         }),
+        ast::Expression::NamedExpression { left, right } => node!(vm, NamedExpr, {
+            target => expression_to_ast(vm, left)?,
+            value => expression_to_ast(vm, right)?,
+        }),
         ast::Expression::Number { value } => {
             let py_n = match value {
                 ast::Number::Integer { value } => vm.ctx.new_int(value.clone()),
@@ -437,8 +460,12 @@ fn expression_to_ast(vm: &VirtualMachine, expression: &ast::Expression) -> PyRes
 }
 
 fn parameters_to_ast(vm: &VirtualMachine, args: &ast::Parameters) -> PyResult<AstNodeRef> {
+    let posonlyargs = map_ast(parameter_to_ast, vm, &args.posonlyargs)?;
     let args = map_ast(parameter_to_ast, vm, &args.args)?;
-    node!(vm, arguments, { args => args })
+    node!(vm, arguments, {
+        posonlyargs => posonlyargs,
+        args => args
+    })
 }
 
 fn parameter_to_ast(vm: &VirtualMachine, parameter: &ast::Parameter) -> PyResult<AstNodeRef> {
@@ -480,8 +507,27 @@ fn string_to_ast(vm: &VirtualMachine, string: &ast::StringGroup) -> PyResult<Ast
         ast::StringGroup::Constant { value } => {
             node!(vm, Str, { s => vm.ctx.new_str(value.clone()) })
         }
-        ast::StringGroup::FormattedValue { value, .. } => {
-            node!(vm, FormattedValue, { value => expression_to_ast(vm, value)? })
+        ast::StringGroup::FormattedValue {
+            value,
+            conversion,
+            spec,
+        } => {
+            // Matches CPython's encoding: -1 for none, otherwise the flag's character
+            let conversion = match conversion {
+                None => -1,
+                Some(ast::ConversionFlag::Str) => 's' as i32,
+                Some(ast::ConversionFlag::Repr) => 'r' as i32,
+                Some(ast::ConversionFlag::Ascii) => 'a' as i32,
+            };
+            let format_spec = match spec {
+                Some(spec) => string_to_ast(vm, spec)?.into_object(),
+                None => vm.ctx.none(),
+            };
+            node!(vm, FormattedValue, {
+                value => expression_to_ast(vm, value)?,
+                conversion => vm.ctx.new_int(conversion),
+                format_spec => format_spec,
+            })
         }
         ast::StringGroup::Joined { values } => {
             let py_values = map_ast(string_to_ast, vm, &values)?;
@@ -507,6 +553,7 @@ pub fn make_module(vm: &VirtualMachine) -> PyObjectRef {
         // TODO: There's got to be a better way!
         "arg" => py_class!(ctx, "_ast.arg", ast_base.clone(), {}),
         "arguments" => py_class!(ctx, "_ast.arguments", ast_base.clone(), {}),
+        "AnnAssign" => py_class!(ctx, "_ast.AnnAssign", ast_base.clone(), {}),
         "AsyncFor" => py_class!(ctx, "_ast.AsyncFor", ast_base.clone(), {}),
         "AsyncFunctionDef" => py_class!(ctx, "_ast.AsyncFunctionDef", ast_base.clone(), {}),
         "Assert" => py_class!(ctx, "_ast.Assert", ast_base.clone(), {}),
@@ -538,6 +585,7 @@ pub fn make_module(vm: &VirtualMachine) -> PyObjectRef {
         "List" => py_class!(ctx, "_ast.List", ast_base.clone(), {}),
         "ListComp" => py_class!(ctx, "_ast.ListComp", ast_base.clone(), {}),
         "Module" => py_class!(ctx, "_ast.Module", ast_base.clone(), {}),
+        "NamedExpr" => py_class!(ctx, "_ast.NamedExpr", ast_base.clone(), {}),
         "NameConstant" => py_class!(ctx, "_ast.NameConstant", ast_base.clone(), {}),
         "NameConstant" => py_class!(ctx, "_ast.NameConstant", ast_base.clone(), {}),
         "NameConstant" => py_class!(ctx, "_ast.NameConstant", ast_base.clone(), {}),
@@ -2,20 +2,30 @@ use crate::function::{OptionalArg, PyFuncArgs};
 use crate::obj::objbytes::{PyBytes, PyBytesRef};
 use crate::obj::objstr::PyStringRef;
 use crate::obj::objtype::PyClassRef;
-use crate::pyobject::{PyClassImpl, PyObjectRef, PyResult, PyValue};
+use crate::pyobject::{Either, PyClassImpl, PyObjectRef, PyResult, PyValue};
 use crate::vm::VirtualMachine;
 use std::cell::RefCell;
 use std::fmt;
 
-use blake2::{Blake2b, Blake2s};
-use digest::DynDigest;
+use blake2::{Blake2b, Blake2s, VarBlake2b, VarBlake2s};
+use digest::generic_array::typenum::Unsigned;
+use digest::{BlockInput, DynDigest, ExtendableOutput, FixedOutput, Input, VariableOutput};
+use hmac::{Hmac, Mac};
 use md5::Md5;
+use pbkdf2::pbkdf2;
+use scrypt::{scrypt, ScryptParams};
 use sha1::Sha1;
 use sha2::{Sha224, Sha256, Sha384, Sha512};
-use sha3::{Sha3_224, Sha3_256, Sha3_384, Sha3_512}; // TODO: , Shake128, Shake256};
+use sha3::{Sha3_224, Sha3_256, Sha3_384, Sha3_512, Shake128, Shake256};
+
+/// The largest key length and iteration count that CPython accepts, the
+/// maximum of a C `int`.
+const MAX_INT: usize = i32::max_value() as usize;
+/// The longest digest of a SHAKE hash that CPython computes.
+const MAX_XOF_LENGTH: usize = 1 << 29;
 
 #[pyclass(name = "hasher")]
-struct PyHasher {
+pub(super) struct PyHasher {
     name: String,
     buffer: RefCell<HashWrapper>,
 }
@@ -49,7 +59,7 @@ impl PyHasher {
     }
 
     #[pyproperty(name = "name")]
-    fn name(&self, _vm: &VirtualMachine) -> String {
+    pub(super) fn name(&self, _vm: &VirtualMachine) -> String {
         self.name.clone()
     }
 
@@ -58,117 +68,354 @@ impl PyHasher {
         Ok(vm.ctx.new_int(self.buffer.borrow().digest_size()))
     }
 
+    #[pyproperty(name = "block_size")]
+    fn block_size(&self, vm: &VirtualMachine) -> PyResult {
+        Ok(vm.ctx.new_int(self.buffer.borrow().block_size()))
+    }
+
     #[pymethod(name = "update")]
     fn update(&self, data: PyBytesRef, vm: &VirtualMachine) -> PyResult {
         self.buffer.borrow_mut().input(data.get_value());
         Ok(vm.get_none())
     }
 
+    #[pymethod(name = "copy")]
+    fn copy(&self, _vm: &VirtualMachine) -> PyHasher {
+        PyHasher::new(&self.name, self.hash_state())
+    }
+
     #[pymethod(name = "digest")]
-    fn digest(&self, _vm: &VirtualMachine) -> PyBytes {
-        let result = self.get_digest();
-        PyBytes::new(result)
+    fn digest(&self, length: OptionalArg<usize>, vm: &VirtualMachine) -> PyResult<PyBytes> {
+        let result = self.get_digest(length, vm)?;
+        Ok(PyBytes::new(result))
     }
 
     #[pymethod(name = "hexdigest")]
-    fn hexdigest(&self, _vm: &VirtualMachine) -> String {
-        let result = self.get_digest();
-        hex::encode(result)
+    fn hexdigest(&self, length: OptionalArg<usize>, vm: &VirtualMachine) -> PyResult<String> {
+        let result = self.get_digest(length, vm)?;
+        Ok(hex::encode(result))
     }
 
-    fn get_digest(&self) -> Vec<u8> {
-        self.buffer.borrow().get_digest()
+    /// Returns a copy of the current hash state, leaving this object untouched.
+    fn hash_state(&self) -> HashWrapper {
+        self.buffer.borrow().clone()
+    }
+
+    fn get_digest(&self, length: OptionalArg<usize>, vm: &VirtualMachine) -> PyResult<Vec<u8>> {
+        let buffer = self.buffer.borrow();
+        match (buffer.is_xof(), length) {
+            (true, OptionalArg::Present(length)) if length >= MAX_XOF_LENGTH => {
+                Err(vm.new_value_error("length is too large".to_string()))
+            }
+            (true, OptionalArg::Present(length)) => Ok(buffer.get_digest(length)),
+            (true, OptionalArg::Missing) => {
+                Err(vm.new_type_error("digest() missing required argument 'length'".to_string()))
+            }
+            (false, OptionalArg::Present(_)) => {
+                Err(vm.new_type_error("digest() takes no arguments".to_string()))
+            }
+            (false, OptionalArg::Missing) => Ok(buffer.get_digest(buffer.digest_size())),
+        }
     }
 }
 
-fn hashlib_new(
-    name: PyStringRef,
+fn init(
+    hasher: PyHasher,
     data: OptionalArg<PyBytesRef>,
     vm: &VirtualMachine,
 ) -> PyResult<PyHasher> {
-    let hasher = match name.value.as_ref() {
-        "md5" => Ok(PyHasher::new("md5", HashWrapper::md5())),
-        "sha1" => Ok(PyHasher::new("sha1", HashWrapper::sha1())),
-        "sha224" => Ok(PyHasher::new("sha224", HashWrapper::sha224())),
-        "sha256" => Ok(PyHasher::new("sha256", HashWrapper::sha256())),
-        "sha384" => Ok(PyHasher::new("sha384", HashWrapper::sha384())),
-        "sha512" => Ok(PyHasher::new("sha512", HashWrapper::sha512())),
-        "sha3_224" => Ok(PyHasher::new("sha3_224", HashWrapper::sha3_224())),
-        "sha3_256" => Ok(PyHasher::new("sha3_256", HashWrapper::sha3_256())),
-        "sha3_384" => Ok(PyHasher::new("sha3_384", HashWrapper::sha3_384())),
-        "sha3_512" => Ok(PyHasher::new("sha3_512", HashWrapper::sha3_512())),
-        // TODO: "shake128" => Ok(PyHasher::new("shake128", HashWrapper::shake128())),
-        // TODO: "shake256" => Ok(PyHasher::new("shake256", HashWrapper::shake256())),
-        "blake2b" => Ok(PyHasher::new("blake2b", HashWrapper::blake2b())),
-        "blake2s" => Ok(PyHasher::new("blake2s", HashWrapper::blake2s())),
-        other => Err(vm.new_value_error(format!("Unknown hashing algorithm: {}", other))),
-    }?;
-
     if let OptionalArg::Present(data) = data {
         hasher.update(data, vm)?;
     }
-
     Ok(hasher)
 }
 
-fn md5(_vm: &VirtualMachine) -> PyResult<PyHasher> {
-    Ok(PyHasher::new("md5", HashWrapper::md5()))
+fn hashlib_new(
+    name: PyStringRef,
+    data: OptionalArg<PyBytesRef>,
+    vm: &VirtualMachine,
+) -> PyResult<PyHasher> {
+    let hasher = HashWrapper::from_name(name.as_str()).ok_or_else(|| {
+        vm.new_value_error(format!("Unknown hashing algorithm: {}", name.as_str()))
+    })?;
+    init(PyHasher::new(name.as_str(), hasher), data, vm)
+}
+
+fn md5(data: OptionalArg<PyBytesRef>, vm: &VirtualMachine) -> PyResult<PyHasher> {
+    init(PyHasher::new("md5", HashWrapper::md5()), data, vm)
+}
+
+fn sha1(data: OptionalArg<PyBytesRef>, vm: &VirtualMachine) -> PyResult<PyHasher> {
+    init(PyHasher::new("sha1", HashWrapper::sha1()), data, vm)
+}
+
+fn sha224(data: OptionalArg<PyBytesRef>, vm: &VirtualMachine) -> PyResult<PyHasher> {
+    init(PyHasher::new("sha224", HashWrapper::sha224()), data, vm)
+}
+
+fn sha256(data: OptionalArg<PyBytesRef>, vm: &VirtualMachine) -> PyResult<PyHasher> {
+    init(PyHasher::new("sha256", HashWrapper::sha256()), data, vm)
+}
+
+fn sha384(data: OptionalArg<PyBytesRef>, vm: &VirtualMachine) -> PyResult<PyHasher> {
+    init(PyHasher::new("sha384", HashWrapper::sha384()), data, vm)
+}
+
+fn sha512(data: OptionalArg<PyBytesRef>, vm: &VirtualMachine) -> PyResult<PyHasher> {
+    init(PyHasher::new("sha512", HashWrapper::sha512()), data, vm)
+}
+
+fn sha3_224(data: OptionalArg<PyBytesRef>, vm: &VirtualMachine) -> PyResult<PyHasher> {
+    init(PyHasher::new("sha3_224", HashWrapper::sha3_224()), data, vm)
+}
+
+fn sha3_256(data: OptionalArg<PyBytesRef>, vm: &VirtualMachine) -> PyResult<PyHasher> {
+    init(PyHasher::new("sha3_256", HashWrapper::sha3_256()), data, vm)
 }
 
-fn sha1(_vm: &VirtualMachine) -> PyResult<PyHasher> {
-    Ok(PyHasher::new("sha1", HashWrapper::sha1()))
+fn sha3_384(data: OptionalArg<PyBytesRef>, vm: &VirtualMachine) -> PyResult<PyHasher> {
+    init(PyHasher::new("sha3_384", HashWrapper::sha3_384()), data, vm)
 }
 
-fn sha224(_vm: &VirtualMachine) -> PyResult<PyHasher> {
-    Ok(PyHasher::new("sha224", HashWrapper::sha224()))
+fn sha3_512(data: OptionalArg<PyBytesRef>, vm: &VirtualMachine) -> PyResult<PyHasher> {
+    init(PyHasher::new("sha3_512", HashWrapper::sha3_512()), data, vm)
 }
 
-fn sha256(_vm: &VirtualMachine) -> PyResult<PyHasher> {
-    Ok(PyHasher::new("sha256", HashWrapper::sha256()))
+fn shake128(data: OptionalArg<PyBytesRef>, vm: &VirtualMachine) -> PyResult<PyHasher> {
+    init(
+        PyHasher::new("shake_128", HashWrapper::shake128()),
+        data,
+        vm,
+    )
 }
 
-fn sha384(_vm: &VirtualMachine) -> PyResult<PyHasher> {
-    Ok(PyHasher::new("sha384", HashWrapper::sha384()))
+fn shake256(data: OptionalArg<PyBytesRef>, vm: &VirtualMachine) -> PyResult<PyHasher> {
+    init(
+        PyHasher::new("shake_256", HashWrapper::shake256()),
+        data,
+        vm,
+    )
 }
 
-fn sha512(_vm: &VirtualMachine) -> PyResult<PyHasher> {
-    Ok(PyHasher::new("sha512", HashWrapper::sha512()))
+#[derive(FromArgs)]
+struct Blake2Args {
+    #[pyarg(positional_only, optional = true)]
+    data: OptionalArg<PyBytesRef>,
+    #[pyarg(keyword_only, optional = true)]
+    digest_size: OptionalArg<usize>,
+    #[pyarg(keyword_only, optional = true)]
+    key: OptionalArg<PyBytesRef>,
+    #[pyarg(keyword_only, optional = true)]
+    salt: OptionalArg<PyBytesRef>,
+    #[pyarg(keyword_only, optional = true)]
+    person: OptionalArg<PyBytesRef>,
 }
 
-fn sha3_224(_vm: &VirtualMachine) -> PyResult<PyHasher> {
-    Ok(PyHasher::new("sha3_224", HashWrapper::sha3_224()))
+impl Blake2Args {
+    /// Validates the parameters against the limits of the given blake2 variant,
+    /// which has `max_bytes` digest/key bytes and `max_bytes / 4` salt/person bytes.
+    fn params(&self, max_bytes: usize, vm: &VirtualMachine) -> PyResult<Blake2Params> {
+        let digest_size = match self.digest_size {
+            OptionalArg::Present(digest_size) => digest_size,
+            OptionalArg::Missing => max_bytes,
+        };
+        if digest_size == 0 || digest_size > max_bytes {
+            return Err(vm.new_value_error(format!(
+                "digest_size must be between 1 and {} bytes",
+                max_bytes
+            )));
+        }
+        let bytes_param = |arg: &OptionalArg<PyBytesRef>, name: &str, max: usize| {
+            let value = match arg {
+                OptionalArg::Present(bytes) => bytes.get_value().to_vec(),
+                OptionalArg::Missing => vec![],
+            };
+            if value.len() > max {
+                Err(vm.new_value_error(format!("maximum {} length is {} bytes", name, max)))
+            } else {
+                Ok(value)
+            }
+        };
+        Ok(Blake2Params {
+            digest_size,
+            key: bytes_param(&self.key, "key", max_bytes)?,
+            salt: bytes_param(&self.salt, "salt", max_bytes / 4)?,
+            person: bytes_param(&self.person, "person", max_bytes / 4)?,
+        })
+    }
 }
 
-fn sha3_256(_vm: &VirtualMachine) -> PyResult<PyHasher> {
-    Ok(PyHasher::new("sha3_256", HashWrapper::sha3_256()))
+fn blake2b(args: Blake2Args, vm: &VirtualMachine) -> PyResult<PyHasher> {
+    let params = args.params(64, vm)?;
+    let hasher = HashWrapper::blake2b_with_params(&params);
+    init(PyHasher::new("blake2b", hasher), args.data, vm)
 }
 
-fn sha3_384(_vm: &VirtualMachine) -> PyResult<PyHasher> {
-    Ok(PyHasher::new("sha3_384", HashWrapper::sha3_384()))
+fn blake2s(args: Blake2Args, vm: &VirtualMachine) -> PyResult<PyHasher> {
+    let params = args.params(32, vm)?;
+    let hasher = HashWrapper::blake2s_with_params(&params);
+    init(PyHasher::new("blake2s", hasher), args.data, vm)
 }
 
-fn sha3_512(_vm: &VirtualMachine) -> PyResult<PyHasher> {
-    Ok(PyHasher::new("sha3_512", HashWrapper::sha3_512()))
+#[derive(FromArgs)]
+struct Pbkdf2Args {
+    #[pyarg(positional_or_keyword, optional = false)]
+    hash_name: PyStringRef,
+    #[pyarg(positional_or_keyword, optional = false)]
+    password: PyBytesRef,
+    #[pyarg(positional_or_keyword, optional = false)]
+    salt: PyBytesRef,
+    #[pyarg(positional_or_keyword, optional = false)]
+    iterations: usize,
+    #[pyarg(positional_or_keyword, default = "None")]
+    dklen: Option<usize>,
 }
 
-fn shake128(vm: &VirtualMachine) -> PyResult<PyHasher> {
-    Err(vm.new_not_implemented_error("shake256".to_string()))
-    // Ok(PyHasher::new("shake128", HashWrapper::shake128()))
+fn hashlib_pbkdf2_hmac(args: Pbkdf2Args, vm: &VirtualMachine) -> PyResult<PyBytes> {
+    let name = args.hash_name.as_str();
+    let unsupported = || vm.new_value_error(format!("unsupported hash type {}", name));
+    let hasher = HashWrapper::from_name(name)
+        .filter(|hasher| !hasher.is_xof())
+        .ok_or_else(unsupported)?;
+    if args.iterations == 0 {
+        return Err(vm.new_value_error("iteration value must be greater than 0.".to_string()));
+    }
+    if args.iterations > MAX_INT {
+        return Err(vm.new_overflow_error("iteration value is too great.".to_string()));
+    }
+    let dklen = args.dklen.unwrap_or_else(|| hasher.digest_size());
+    if dklen == 0 {
+        return Err(vm.new_value_error("key length must be greater than 0.".to_string()));
+    }
+    if dklen > MAX_INT {
+        return Err(vm.new_overflow_error("key length is too great.".to_string()));
+    }
+    let key = pbkdf2_hmac(
+        name,
+        args.password.get_value(),
+        args.salt.get_value(),
+        args.iterations,
+        dklen,
+    )
+    .ok_or_else(unsupported)?;
+    Ok(PyBytes::new(key))
 }
 
-fn shake256(vm: &VirtualMachine) -> PyResult<PyHasher> {
-    Err(vm.new_not_implemented_error("shake256".to_string()))
-    // TODO: Ok(PyHasher::new("shake256", HashWrapper::shake256()))
+#[derive(FromArgs)]
+struct ScryptArgs {
+    #[pyarg(positional_only, optional = false)]
+    password: PyBytesRef,
+    #[pyarg(keyword_only, optional = true)]
+    salt: OptionalArg<PyBytesRef>,
+    #[pyarg(keyword_only, optional = true)]
+    n: OptionalArg<usize>,
+    #[pyarg(keyword_only, optional = true)]
+    r: OptionalArg<usize>,
+    #[pyarg(keyword_only, optional = true)]
+    p: OptionalArg<usize>,
+    #[pyarg(keyword_only, default = "0")]
+    maxmem: usize,
+    #[pyarg(keyword_only, default = "64")]
+    dklen: usize,
 }
 
-fn blake2b(_vm: &VirtualMachine) -> PyResult<PyHasher> {
-    // TODO: handle parameters
-    Ok(PyHasher::new("blake2b", HashWrapper::blake2b()))
+fn hashlib_scrypt(args: ScryptArgs, vm: &VirtualMachine) -> PyResult<PyBytes> {
+    let required = |arg: OptionalArg<usize>, name: &str| {
+        arg.into_option().ok_or_else(|| {
+            vm.new_type_error(format!("scrypt() missing required argument '{}'", name))
+        })
+    };
+    let salt = args
+        .salt
+        .into_option()
+        .ok_or_else(|| vm.new_type_error("salt is required".to_string()))?;
+    let n = required(args.n, "n")?;
+    let r = required(args.r, "r")?;
+    let p = required(args.p, "p")?;
+
+    if n < 2 || !n.is_power_of_two() {
+        return Err(vm.new_value_error("n must be a power of 2.".to_string()));
+    }
+    if args.maxmem > MAX_INT {
+        return Err(vm.new_value_error(format!(
+            "maxmem must be positive and smaller than {}",
+            MAX_INT
+        )));
+    }
+    if args.dklen == 0 || args.dklen > MAX_INT {
+        return Err(vm.new_value_error(format!(
+            "dklen must be greater than 0 and smaller than {}",
+            MAX_INT
+        )));
+    }
+    let invalid_params =
+        || vm.new_value_error("Invalid parameter combination for n, r, p, maxmem.".to_string());
+    if r == 0 || p == 0 {
+        return Err(invalid_params());
+    }
+    // Same default memory limit as OpenSSL's EVP_PBE_scrypt.
+    let maxmem = if args.maxmem == 0 {
+        32 * 1024 * 1024
+    } else {
+        args.maxmem
+    };
+    let required_mem = 128usize
+        .checked_mul(r)
+        .and_then(|block| block.checked_mul(n.checked_add(p)?.checked_add(2)?));
+    match required_mem {
+        Some(mem) if mem <= maxmem => {}
+        _ => return Err(invalid_params()),
+    }
+
+    // The memory check above keeps r and p well within u32.
+    let params = ScryptParams::new(n.trailing_zeros() as u8, r as u32, p as u32)
+        .map_err(|_| invalid_params())?;
+    let mut key = vec![0; args.dklen];
+    scrypt(
+        args.password.get_value(),
+        salt.get_value(),
+        &params,
+        &mut key,
+    )
+    .map_err(|_| invalid_params())?;
+    Ok(PyBytes::new(key))
 }
 
-fn blake2s(_vm: &VirtualMachine) -> PyResult<PyHasher> {
-    // TODO: handle parameters
-    Ok(PyHasher::new("blake2s", HashWrapper::blake2s()))
+/// Compares two bytes-like objects or two ASCII strings in constant time,
+/// mirroring `hmac.compare_digest`.
+pub(super) fn compare_digest(
+    a: Either<PyBytesRef, PyStringRef>,
+    b: Either<PyBytesRef, PyStringRef>,
+    vm: &VirtualMachine,
+) -> PyResult<bool> {
+    let (a, b) = match (a, b) {
+        (Either::A(a), Either::A(b)) => (a.get_value().to_vec(), b.get_value().to_vec()),
+        (Either::B(a), Either::B(b)) => {
+            if !a.as_str().is_ascii() || !b.as_str().is_ascii() {
+                return Err(vm.new_type_error(
+                    "comparing strings with non-ASCII characters is not supported".to_string(),
+                ));
+            }
+            (
+                a.as_str().as_bytes().to_vec(),
+                b.as_str().as_bytes().to_vec(),
+            )
+        }
+        _ => {
+            return Err(vm.new_type_error(
+                "unsupported operand types(s) or combination of types".to_string(),
+            ));
+        }
+    };
+
+    // Don't bail out early on a length mismatch, so that the time taken
+    // only depends on the length of `b`.
+    let mut result = (a.len() != b.len()) as u8;
+    for (i, y) in b.iter().enumerate() {
+        let x = a.get(i).cloned().unwrap_or(!*y);
+        result |= x ^ y;
+    }
+    Ok(result == 0)
 }
 
 pub fn make_module(vm: &VirtualMachine) -> PyObjectRef {
@@ -188,95 +435,298 @@ pub fn make_module(vm: &VirtualMachine) -> PyObjectRef {
         "sha3_256" => ctx.new_rustfunc(sha3_256),
         "sha3_384" => ctx.new_rustfunc(sha3_384),
         "sha3_512" => ctx.new_rustfunc(sha3_512),
-        "shake128" => ctx.new_rustfunc(shake128),
-        "shake256" => ctx.new_rustfunc(shake256),
+        "shake_128" => ctx.new_rustfunc(shake128),
+        "shake_256" => ctx.new_rustfunc(shake256),
         "blake2b" => ctx.new_rustfunc(blake2b),
         "blake2s" => ctx.new_rustfunc(blake2s),
+        "pbkdf2_hmac" => ctx.new_rustfunc(hashlib_pbkdf2_hmac),
+        "scrypt" => ctx.new_rustfunc(hashlib_scrypt),
         "hasher" => hasher_type,
     })
 }
 
+struct Blake2Params {
+    digest_size: usize,
+    key: Vec<u8>,
+    salt: Vec<u8>,
+    person: Vec<u8>,
+}
+
 /// Generic wrapper patching around the hashing libraries.
-struct HashWrapper {
-    inner: Box<DynDigest>,
+#[derive(Clone)]
+enum HashWrapper {
+    Fixed {
+        inner: Box<DynDigest>,
+        block_size: usize,
+    },
+    Blake2b(VarBlake2b),
+    Blake2s(VarBlake2s),
+    Shake128(Shake128),
+    Shake256(Shake256),
 }
 
 impl HashWrapper {
-    fn new<D: 'static>(d: D) -> Self
+    fn new<D: 'static>(d: D, block_size: usize) -> Self
     where
         D: DynDigest,
         D: Sized,
     {
-        HashWrapper { inner: Box::new(d) }
+        HashWrapper::Fixed {
+            inner: Box::new(d),
+            block_size,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        let hasher = match name {
+            "md5" => Self::md5(),
+            "sha1" => Self::sha1(),
+            "sha224" => Self::sha224(),
+            "sha256" => Self::sha256(),
+            "sha384" => Self::sha384(),
+            "sha512" => Self::sha512(),
+            "sha3_224" => Self::sha3_224(),
+            "sha3_256" => Self::sha3_256(),
+            "sha3_384" => Self::sha3_384(),
+            "sha3_512" => Self::sha3_512(),
+            "shake_128" => Self::shake128(),
+            "shake_256" => Self::shake256(),
+            "blake2b" => Self::blake2b(),
+            "blake2s" => Self::blake2s(),
+            _ => return None,
+        };
+        Some(hasher)
     }
 
     fn md5() -> Self {
-        Self::new(Md5::default())
+        Self::new(Md5::default(), 64)
     }
 
     fn sha1() -> Self {
-        Self::new(Sha1::default())
+        Self::new(Sha1::default(), 64)
     }
 
     fn sha224() -> Self {
-        Self::new(Sha224::default())
+        Self::new(Sha224::default(), 64)
     }
 
     fn sha256() -> Self {
-        Self::new(Sha256::default())
+        Self::new(Sha256::default(), 64)
     }
 
     fn sha384() -> Self {
-        Self::new(Sha384::default())
+        Self::new(Sha384::default(), 128)
     }
 
     fn sha512() -> Self {
-        Self::new(Sha512::default())
+        Self::new(Sha512::default(), 128)
     }
 
     fn sha3_224() -> Self {
-        Self::new(Sha3_224::default())
+        Self::new(Sha3_224::default(), 144)
     }
 
     fn sha3_256() -> Self {
-        Self::new(Sha3_256::default())
+        Self::new(Sha3_256::default(), 136)
     }
 
     fn sha3_384() -> Self {
-        Self::new(Sha3_384::default())
+        Self::new(Sha3_384::default(), 104)
     }
 
     fn sha3_512() -> Self {
-        Self::new(Sha3_512::default())
+        Self::new(Sha3_512::default(), 72)
     }
 
-    /* TODO:
-        fn shake128() -> Self {
-            Self::new(Shake128::default())
-        }
+    fn shake128() -> Self {
+        HashWrapper::Shake128(Shake128::default())
+    }
+
+    fn shake256() -> Self {
+        HashWrapper::Shake256(Shake256::default())
+    }
 
-        fn shake256() -> Self {
-            Self::new(Shake256::default())
-        }
-    */
     fn blake2b() -> Self {
-        Self::new(Blake2b::default())
+        Self::new(Blake2b::default(), 128)
     }
 
     fn blake2s() -> Self {
-        Self::new(Blake2s::default())
+        Self::new(Blake2s::default(), 64)
+    }
+
+    fn blake2b_with_params(params: &Blake2Params) -> Self {
+        let mut block = [0u64; 8];
+        block[0] = params.digest_size as u64 | (params.key.len() as u64) << 8 | 1 << 16 | 1 << 24;
+        copy_le_words(&params.salt, &mut block[4..6]);
+        copy_le_words(&params.person, &mut block[6..8]);
+        let mut hasher = HashWrapper::Blake2b(VarBlake2b::with_parameter_block(&block));
+        hasher.input_key_block(&params.key);
+        hasher
+    }
+
+    fn blake2s_with_params(params: &Blake2Params) -> Self {
+        let mut block = [0u32; 8];
+        block[0] = params.digest_size as u32 | (params.key.len() as u32) << 8 | 1 << 16 | 1 << 24;
+        copy_le_words(&params.salt, &mut block[4..6]);
+        copy_le_words(&params.person, &mut block[6..8]);
+        let mut hasher = HashWrapper::Blake2s(VarBlake2s::with_parameter_block(&block));
+        hasher.input_key_block(&params.key);
+        hasher
+    }
+
+    /// A blake2 key is processed as a first, zero padded, message block.
+    fn input_key_block(&mut self, key: &[u8]) {
+        if !key.is_empty() {
+            let mut block = key.to_vec();
+            block.resize(self.block_size(), 0);
+            self.input(&block);
+        }
+    }
+
+    /// Whether this is an extendable-output function, whose digest length
+    /// is chosen by the caller.
+    pub(super) fn is_xof(&self) -> bool {
+        match self {
+            HashWrapper::Shake128(_) | HashWrapper::Shake256(_) => true,
+            _ => false,
+        }
     }
 
-    fn input(&mut self, data: &[u8]) {
-        self.inner.input(data);
+    pub(super) fn input(&mut self, data: &[u8]) {
+        match self {
+            HashWrapper::Fixed { inner, .. } => inner.input(data),
+            HashWrapper::Blake2b(hasher) => Input::input(hasher, data),
+            HashWrapper::Blake2s(hasher) => Input::input(hasher, data),
+            HashWrapper::Shake128(hasher) => Input::input(hasher, data),
+            HashWrapper::Shake256(hasher) => Input::input(hasher, data),
+        }
+    }
+
+    /// The digest size in bytes, or 0 for extendable-output functions.
+    pub(super) fn digest_size(&self) -> usize {
+        match self {
+            HashWrapper::Fixed { inner, .. } => inner.output_size(),
+            HashWrapper::Blake2b(hasher) => VariableOutput::output_size(hasher),
+            HashWrapper::Blake2s(hasher) => VariableOutput::output_size(hasher),
+            HashWrapper::Shake128(_) | HashWrapper::Shake256(_) => 0,
+        }
+    }
+
+    pub(super) fn block_size(&self) -> usize {
+        match self {
+            HashWrapper::Fixed { block_size, .. } => *block_size,
+            HashWrapper::Blake2b(_) => 128,
+            HashWrapper::Blake2s(_) => 64,
+            HashWrapper::Shake128(_) => 168,
+            HashWrapper::Shake256(_) => 136,
+        }
     }
 
-    fn digest_size(&self) -> usize {
-        self.inner.output_size()
+    /// Computes the digest of the data fed so far, without consuming the state.
+    ///
+    /// `length` is only used by extendable-output functions; all other hashes
+    /// always produce `digest_size()` bytes.
+    pub(super) fn get_digest(&self, length: usize) -> Vec<u8> {
+        match self {
+            HashWrapper::Fixed { inner, .. } => inner.clone().result().to_vec(),
+            HashWrapper::Blake2b(hasher) => VariableOutput::vec_result(hasher.clone()),
+            HashWrapper::Blake2s(hasher) => VariableOutput::vec_result(hasher.clone()),
+            HashWrapper::Shake128(hasher) => ExtendableOutput::vec_result(hasher.clone(), length),
+            HashWrapper::Shake256(hasher) => ExtendableOutput::vec_result(hasher.clone(), length),
+        }
     }
+}
 
-    fn get_digest(&self) -> Vec<u8> {
-        let cloned = self.inner.clone();
-        cloned.result().to_vec()
+/// Fills `words` from `bytes` in little endian order, zero padding the rest.
+fn copy_le_words<T>(bytes: &[u8], words: &mut [T])
+where
+    T: Copy + From<u8> + std::ops::Shl<usize, Output = T> + std::ops::BitOr<Output = T>,
+{
+    let word_size = std::mem::size_of::<T>();
+    for (i, byte) in bytes.iter().enumerate() {
+        let word = &mut words[i / word_size];
+        *word = *word | T::from(*byte) << (8 * (i % word_size));
     }
 }
+
+macro_rules! hmac_wrapper {
+    ($($name:expr => $variant:ident($digest:ty),)*) => {
+        /// HMAC (RFC 2104) on top of any of the fixed size hashes above.
+        #[derive(Clone)]
+        pub(super) enum HmacWrapper {
+            $($variant(Hmac<$digest>),)*
+        }
+
+        impl HmacWrapper {
+            /// Returns `None` if `name` isn't the name of a fixed size hash.
+            pub(super) fn new(name: &str, key: &[u8]) -> Option<Self> {
+                let mac = match name {
+                    $($name => HmacWrapper::$variant(
+                        Hmac::new_varkey(key).expect("HMAC accepts keys of any length"),
+                    ),)*
+                    _ => return None,
+                };
+                Some(mac)
+            }
+
+            pub(super) fn input(&mut self, data: &[u8]) {
+                match self {
+                    $(HmacWrapper::$variant(mac) => mac.input(data),)*
+                }
+            }
+
+            pub(super) fn digest_size(&self) -> usize {
+                match self {
+                    $(HmacWrapper::$variant(_) => {
+                        <$digest as FixedOutput>::OutputSize::to_usize()
+                    })*
+                }
+            }
+
+            pub(super) fn block_size(&self) -> usize {
+                match self {
+                    $(HmacWrapper::$variant(_) => {
+                        <$digest as BlockInput>::BlockSize::to_usize()
+                    })*
+                }
+            }
+
+            pub(super) fn get_digest(&self) -> Vec<u8> {
+                match self {
+                    $(HmacWrapper::$variant(mac) => mac.clone().result().code().to_vec(),)*
+                }
+            }
+        }
+
+        /// PBKDF2 (RFC 8018) with HMAC on top of the fixed size hash called `name`.
+        fn pbkdf2_hmac(
+            name: &str,
+            password: &[u8],
+            salt: &[u8],
+            iterations: usize,
+            dklen: usize,
+        ) -> Option<Vec<u8>> {
+            let mut key = vec![0; dklen];
+            match name {
+                $($name => pbkdf2::<Hmac<$digest>>(password, salt, iterations, &mut key),)*
+                _ => return None,
+            }
+            Some(key)
+        }
+    };
+}
+
+hmac_wrapper! {
+    "md5" => Md5(Md5),
+    "sha1" => Sha1(Sha1),
+    "sha224" => Sha224(Sha224),
+    "sha256" => Sha256(Sha256),
+    "sha384" => Sha384(Sha384),
+    "sha512" => Sha512(Sha512),
+    "sha3_224" => Sha3_224(Sha3_224),
+    "sha3_256" => Sha3_256(Sha3_256),
+    "sha3_384" => Sha3_384(Sha3_384),
+    "sha3_512" => Sha3_512(Sha3_512),
+    "blake2b" => Blake2b(Blake2b),
+    "blake2s" => Blake2s(Blake2s),
+}
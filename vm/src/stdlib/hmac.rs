@@ -0,0 +1,152 @@
+use std::cell::RefCell;
+use std::fmt;
+
+use super::hashlib::{compare_digest, HmacWrapper, PyHasher};
+use crate::obj::objbytes::{PyBytes, PyBytesRef};
+use crate::obj::objstr::PyString;
+use crate::obj::objtype::PyClassRef;
+use crate::pyobject::{PyClassImpl, PyObjectRef, PyRef, PyResult, PyValue, TypeProtocol};
+use crate::vm::VirtualMachine;
+
+#[pyclass(name = "HMAC")]
+struct PyHmac {
+    name: String,
+    mac: RefCell<HmacWrapper>,
+}
+
+impl fmt::Debug for PyHmac {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HMAC {}", self.name)
+    }
+}
+
+impl PyValue for PyHmac {
+    fn class(vm: &VirtualMachine) -> PyClassRef {
+        vm.class("hmac", "HMAC")
+    }
+}
+
+#[derive(FromArgs)]
+struct HmacArgs {
+    #[pyarg(positional_or_keyword, optional = false)]
+    key: PyBytesRef,
+    #[pyarg(positional_or_keyword, default = "None")]
+    msg: Option<PyBytesRef>,
+    #[pyarg(positional_or_keyword, default = "None")]
+    digestmod: Option<PyObjectRef>,
+}
+
+#[pyimpl]
+impl PyHmac {
+    #[pymethod(name = "__new__")]
+    fn py_new(cls: PyClassRef, args: HmacArgs, vm: &VirtualMachine) -> PyResult<PyRef<Self>> {
+        hmac_new(args, vm)?.into_ref_with_type(vm, cls)
+    }
+
+    #[pyproperty(name = "name")]
+    fn name(&self, _vm: &VirtualMachine) -> String {
+        format!("hmac-{}", self.name)
+    }
+
+    #[pyproperty(name = "digest_size")]
+    fn digest_size(&self, _vm: &VirtualMachine) -> usize {
+        self.mac.borrow().digest_size()
+    }
+
+    #[pyproperty(name = "block_size")]
+    fn block_size(&self, _vm: &VirtualMachine) -> usize {
+        self.mac.borrow().block_size()
+    }
+
+    #[pymethod(name = "update")]
+    fn update(&self, msg: PyBytesRef, _vm: &VirtualMachine) {
+        self.mac.borrow_mut().input(msg.get_value());
+    }
+
+    #[pymethod(name = "copy")]
+    fn copy(&self, _vm: &VirtualMachine) -> PyHmac {
+        PyHmac {
+            name: self.name.clone(),
+            mac: RefCell::new(self.mac.borrow().clone()),
+        }
+    }
+
+    #[pymethod(name = "digest")]
+    fn digest(&self, _vm: &VirtualMachine) -> PyBytes {
+        PyBytes::new(self.mac.borrow().get_digest())
+    }
+
+    #[pymethod(name = "hexdigest")]
+    fn hexdigest(&self, _vm: &VirtualMachine) -> String {
+        hex::encode(self.mac.borrow().get_digest())
+    }
+}
+
+/// Keys a new HMAC with the hash named by `digestmod`, which is either a
+/// hash name such as `'sha256'` or a callable such as `hashlib.sha256`
+/// returning a fresh hash object.
+fn new_mac(
+    digestmod: PyObjectRef,
+    key: &[u8],
+    vm: &VirtualMachine,
+) -> PyResult<(String, HmacWrapper)> {
+    let name = if let Some(name) = digestmod.payload::<PyString>() {
+        name.as_str().to_string()
+    } else if vm.is_callable(&digestmod) {
+        let hasher = vm.invoke(digestmod.clone(), vec![])?;
+        match hasher.payload::<PyHasher>() {
+            Some(hasher) => hasher.name(vm),
+            None => {
+                return Err(
+                    vm.new_type_error("digestmod must return a hashlib hash object".to_string())
+                );
+            }
+        }
+    } else {
+        return Err(vm.new_type_error(format!(
+            "expected a hash name or constructor as digestmod, not {}",
+            digestmod.class()
+        )));
+    };
+    let mac = HmacWrapper::new(&name, key)
+        .ok_or_else(|| vm.new_value_error(format!("unsupported hash type {}", name)))?;
+    Ok((name, mac))
+}
+
+fn hmac_new(args: HmacArgs, vm: &VirtualMachine) -> PyResult<PyHmac> {
+    let digestmod = args
+        .digestmod
+        .ok_or_else(|| vm.new_type_error("Missing required parameter 'digestmod'.".to_string()))?;
+    let (name, mut mac) = new_mac(digestmod, args.key.get_value(), vm)?;
+    if let Some(msg) = args.msg {
+        mac.input(msg.get_value());
+    }
+    Ok(PyHmac {
+        name,
+        mac: RefCell::new(mac),
+    })
+}
+
+fn hmac_digest(
+    key: PyBytesRef,
+    msg: PyBytesRef,
+    digest: PyObjectRef,
+    vm: &VirtualMachine,
+) -> PyResult<PyBytes> {
+    let (_, mut mac) = new_mac(digest, key.get_value(), vm)?;
+    mac.input(msg.get_value());
+    Ok(PyBytes::new(mac.get_digest()))
+}
+
+pub fn make_module(vm: &VirtualMachine) -> PyObjectRef {
+    let ctx = &vm.ctx;
+
+    let hmac_type = PyHmac::make_class(ctx);
+
+    py_module!(vm, "hmac", {
+        "new" => ctx.new_rustfunc(hmac_new),
+        "digest" => ctx.new_rustfunc(hmac_digest),
+        "compare_digest" => ctx.new_rustfunc(compare_digest),
+        "HMAC" => hmac_type,
+    })
+}
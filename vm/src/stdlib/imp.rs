@@ -61,7 +61,7 @@ fn imp_get_frozen_object(name: PyStringRef, vm: &VirtualMachine) -> PyResult<PyC
         .map(|frozen| {
             let mut frozen = frozen.clone();
             frozen.source_path = format!("frozen {}", name.as_str());
-            PyCode::new(frozen)
+            PyCode::new(frozen, &vm.ctx)
         })
         .ok_or_else(|| {
             vm.new_import_error(format!("No such frozen object named {}", name.as_str()))
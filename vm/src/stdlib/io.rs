@@ -319,6 +319,10 @@ fn file_io_init(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
         ];
         os::os_open(vm, PyFuncArgs::new(args, vec![]))?
     } else if objtype::isinstance(&name, &vm.ctx.int_type()) {
+        let fd = objint::get_value(name).to_i64().ok_or_else(|| {
+            vm.new_overflow_error("Python int too large to convert to C int".to_string())
+        })?;
+        vm.check_fd_access(fd)?;
         name.clone()
     } else {
         return Err(vm.new_type_error("name parameter must be string or int".to_string()));
@@ -97,11 +97,11 @@ impl PyItertoolsCount {
         vm: &VirtualMachine,
     ) -> PyResult {
         let start = match start.into_option() {
-            Some(int) => int.as_bigint().clone(),
+            Some(int) => int.as_bigint().into_owned(),
             None => BigInt::from(0),
         };
         let step = match step.into_option() {
-            Some(int) => int.as_bigint().clone(),
+            Some(int) => int.as_bigint().into_owned(),
             None => BigInt::from(1),
         };
 
@@ -150,7 +150,7 @@ impl PyItertoolsRepeat {
         vm: &VirtualMachine,
     ) -> PyResult {
         let times = match times.into_option() {
-            Some(int) => Some(RefCell::new(int.as_bigint().clone())),
+            Some(int) => Some(RefCell::new(int.as_bigint().into_owned())),
             None => None,
         };
 
@@ -10,7 +10,7 @@ fn marshal_dumps(co: PyCodeRef, vm: &VirtualMachine) -> PyResult {
 
 fn marshal_loads(code_bytes: PyBytesRef, vm: &VirtualMachine) -> PyResult {
     let code = bincode::deserialize::<bytecode::CodeObject>(&code_bytes).unwrap();
-    let pycode = PyCode { code };
+    let pycode = PyCode::new(code, &vm.ctx);
     pycode.into_pyobject(vm)
 }
 
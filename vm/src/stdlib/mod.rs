@@ -3,6 +3,7 @@ mod ast;
 mod binascii;
 mod dis;
 mod hashlib;
+mod hmac;
 mod imp;
 mod itertools;
 mod json;
@@ -30,9 +31,15 @@ use crate::vm::VirtualMachine;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod io;
 #[cfg(not(target_arch = "wasm32"))]
-mod os;
+pub(crate) mod os;
+#[cfg(unix)]
+mod posixsubprocess;
 #[cfg(all(unix, not(target_os = "android")))]
 mod pwd;
+#[cfg(unix)]
+mod select;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod signal;
 
 use crate::pyobject::PyObjectRef;
 
@@ -44,6 +51,7 @@ pub fn get_module_inits() -> HashMap<String, StdlibInitFunc> {
         "binascii".to_string() => Box::new(binascii::make_module) as StdlibInitFunc,
         "dis".to_string() => Box::new(dis::make_module) as StdlibInitFunc,
         "hashlib".to_string() => Box::new(hashlib::make_module),
+        "hmac".to_string() => Box::new(hmac::make_module),
         "itertools".to_string() => Box::new(itertools::make_module),
         "json".to_string() => Box::new(json::make_module),
         "marshal".to_string() => Box::new(marshal::make_module),
@@ -77,10 +85,20 @@ pub fn get_module_inits() -> HashMap<String, StdlibInitFunc> {
     {
         modules.insert("_io".to_string(), Box::new(io::make_module));
         modules.insert("_os".to_string(), Box::new(os::make_module));
+        modules.insert("signal".to_string(), Box::new(signal::make_module));
         modules.insert("socket".to_string(), Box::new(socket::make_module));
     }
 
     // Unix-only
+    #[cfg(unix)]
+    {
+        modules.insert(
+            "_posixsubprocess".to_string(),
+            Box::new(posixsubprocess::make_module),
+        );
+        modules.insert("select".to_string(), Box::new(select::make_module));
+    }
+
     #[cfg(all(unix, not(target_os = "android")))]
     {
         modules.insert("pwd".to_string(), Box::new(pwd::make_module));
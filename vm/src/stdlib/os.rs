@@ -1,24 +1,28 @@
 use std::cell::RefCell;
 use std::fs::File;
 use std::fs::OpenOptions;
-use std::io::{self, ErrorKind, Read, Write};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
 use std::time::{Duration, SystemTime};
 use std::{env, fs};
 
 use bitflags::bitflags;
 use num_traits::cast::ToPrimitive;
 
-use crate::function::{IntoPyNativeFunc, PyFuncArgs};
+use crate::function::{IntoPyNativeFunc, OptionalArg, PyFuncArgs};
 use crate::obj::objbytes::PyBytesRef;
 use crate::obj::objdict::PyDictRef;
+use crate::obj::objfloat;
 use crate::obj::objint::{self, PyInt, PyIntRef};
 use crate::obj::objiter;
 use crate::obj::objset::PySet;
 use crate::obj::objstr::{self, PyString, PyStringRef};
+use crate::obj::objtuple::PyTupleRef;
 use crate::obj::objtype::{self, PyClassRef};
 use crate::pyobject::{
-    ItemProtocol, PyClassImpl, PyObjectRef, PyRef, PyResult, PyValue, TryIntoRef, TypeProtocol,
+    ItemProtocol, PyClassImpl, PyIterable, PyObjectRef, PyRef, PyResult, PyValue, TryFromObject,
+    TryIntoRef, TypeProtocol,
 };
+use crate::sandbox::Access;
 use crate::vm::VirtualMachine;
 
 #[cfg(unix)]
@@ -61,23 +65,25 @@ pub fn raw_file_number(handle: File) -> i64 {
     unimplemented!();
 }
 
-fn make_path(_vm: &VirtualMachine, path: PyStringRef, dir_fd: &DirFd) -> PyStringRef {
+fn make_path(vm: &VirtualMachine, path: PyStringRef, dir_fd: &DirFd) -> PyResult<PyStringRef> {
     if dir_fd.dir_fd.is_some() {
-        unimplemented!();
+        Err(vm.new_not_implemented_error("dir_fd unavailable on this platform".to_string()))
     } else {
-        path
+        Ok(path)
     }
 }
 
 pub fn os_close(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
     arg_check!(vm, args, required = [(fileno, Some(vm.ctx.int_type()))]);
 
-    let raw_fileno = objint::get_value(&fileno);
+    let raw_fileno = objint::get_value(fileno).to_i64().unwrap();
+    vm.check_fd_access(raw_fileno)?;
+    vm.remove_opened_fd(raw_fileno);
 
     //The File type automatically closes when it goes out of scope.
     //To enable us to close these file descriptors (and hence prevent leaks)
     //we seek to create the relevant File and simply let it pass out of scope!
-    rust_file(raw_fileno.to_i64().unwrap());
+    rust_file(raw_fileno);
 
     Ok(vm.get_none())
 }
@@ -117,11 +123,21 @@ pub fn os_open(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
     } else {
         DirFd::default()
     };
-    let fname = &make_path(vm, name, &dir_fd).value;
 
     let flags = FileCreationFlags::from_bits(objint::get_value(flags).to_u32().unwrap())
         .ok_or(vm.new_value_error("Unsupported flag".to_string()))?;
 
+    let writes = FileCreationFlags::O_WRONLY
+        | FileCreationFlags::O_RDWR
+        | FileCreationFlags::O_APPEND
+        | FileCreationFlags::O_CREAT;
+    if flags.intersects(writes) {
+        vm.check_access(Access::Write(&name.value))?;
+    } else {
+        vm.check_access(Access::Read(&name.value))?;
+    }
+    let fname = &make_path(vm, name, &dir_fd)?.value;
+
     let mut options = &mut OpenOptions::new();
 
     if flags.contains(FileCreationFlags::O_WRONLY) {
@@ -146,31 +162,80 @@ pub fn os_open(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
 
     let handle = options
         .open(&fname)
-        .map_err(|err| convert_io_error(vm, err))?;
+        .map_err(|err| convert_io_error_with_path(vm, err, fname))?;
 
-    Ok(vm.ctx.new_int(raw_file_number(handle)))
+    let fd = raw_file_number(handle);
+    vm.add_opened_fd(fd);
+    Ok(vm.ctx.new_int(fd))
 }
 
-fn convert_io_error(vm: &VirtualMachine, err: io::Error) -> PyObjectRef {
-    let os_error = match err.kind() {
-        ErrorKind::NotFound => {
-            let exc_type = vm.ctx.exceptions.file_not_found_error.clone();
-            vm.new_exception(exc_type, err.to_string())
-        }
-        ErrorKind::PermissionDenied => {
-            let exc_type = vm.ctx.exceptions.permission_error.clone();
-            vm.new_exception(exc_type, err.to_string())
-        }
-        ErrorKind::AlreadyExists => {
-            let exc_type = vm.ctx.exceptions.file_exists_error.clone();
-            vm.new_exception(exc_type, err.to_string())
+pub fn convert_io_error(vm: &VirtualMachine, err: io::Error) -> PyObjectRef {
+    new_os_error_from_io(vm, err, None, None)
+}
+
+/// Like `convert_io_error`, for a call on the file at `path`, which the
+/// exception reports as its `filename`.
+pub fn convert_io_error_with_path(vm: &VirtualMachine, err: io::Error, path: &str) -> PyObjectRef {
+    new_os_error_from_io(vm, err, Some(path), None)
+}
+
+/// Like `convert_io_error`, for a call on the files at `src` and `dst`.
+fn convert_io_error_with_paths(
+    vm: &VirtualMachine,
+    err: io::Error,
+    src: &str,
+    dst: &str,
+) -> PyObjectRef {
+    new_os_error_from_io(vm, err, Some(src), Some(dst))
+}
+
+fn new_os_error_from_io(
+    vm: &VirtualMachine,
+    err: io::Error,
+    filename: Option<&str>,
+    filename2: Option<&str>,
+) -> PyObjectRef {
+    // OSError(errno, strerror[, filename, winerror, filename2]) picks the
+    // subclass matching errno
+    #[cfg(unix)]
+    {
+        if let Some(errno) = err.raw_os_error() {
+            let mut args = vec![vm.ctx.new_int(errno), vm.new_str(os_strerror(errno, vm))];
+            if let Some(filename) = filename {
+                args.push(vm.new_str(filename.to_string()));
+                if let Some(filename2) = filename2 {
+                    args.push(vm.get_none());
+                    args.push(vm.new_str(filename2.to_string()));
+                }
+            }
+            let os_error = vm.ctx.exceptions.os_error.clone().into_object();
+            return vm.invoke(os_error, args).unwrap();
         }
-        _ => vm.new_os_error(err.to_string()),
+    }
+    let exc_type = match err.kind() {
+        ErrorKind::NotFound => &vm.ctx.exceptions.file_not_found_error,
+        ErrorKind::PermissionDenied => &vm.ctx.exceptions.permission_error,
+        ErrorKind::AlreadyExists => &vm.ctx.exceptions.file_exists_error,
+        ErrorKind::WouldBlock => &vm.ctx.exceptions.blocking_io_error,
+        ErrorKind::BrokenPipe => &vm.ctx.exceptions.broken_pipe_error,
+        ErrorKind::ConnectionAborted => &vm.ctx.exceptions.connection_aborted_error,
+        ErrorKind::ConnectionRefused => &vm.ctx.exceptions.connection_refused_error,
+        ErrorKind::ConnectionReset => &vm.ctx.exceptions.connection_reset_error,
+        ErrorKind::Interrupted => &vm.ctx.exceptions.interrupted_error,
+        ErrorKind::TimedOut => &vm.ctx.exceptions.timeout_error,
+        _ => &vm.ctx.exceptions.os_error,
     };
+    let os_error = vm.new_exception(exc_type.clone(), err.to_string());
     if let Some(errno) = err.raw_os_error() {
         vm.set_attr(&os_error, "errno", vm.ctx.new_int(errno))
             .unwrap();
     }
+    for (name, value) in &[("filename", filename), ("filename2", filename2)] {
+        if let Some(value) = value {
+            vm.set_attr(&os_error, *name, vm.new_str(value.to_string()))
+                .unwrap();
+        }
+    }
     os_error
 }
 
@@ -192,7 +257,9 @@ fn os_error(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
 }
 
 fn os_fsync(fd: PyIntRef, vm: &VirtualMachine) -> PyResult<()> {
-    let file = rust_file(fd.as_bigint().to_i64().unwrap());
+    let fd = fd.as_bigint().to_i64().unwrap();
+    vm.check_fd_access(fd)?;
+    let file = rust_file(fd);
     file.sync_all().map_err(|err| convert_io_error(vm, err))?;
     // Avoid closing the fd
     raw_file_number(file);
@@ -200,69 +267,125 @@ fn os_fsync(fd: PyIntRef, vm: &VirtualMachine) -> PyResult<()> {
 }
 
 fn os_read(fd: PyIntRef, n: PyIntRef, vm: &VirtualMachine) -> PyResult {
+    let fd = fd.as_bigint().to_i64().unwrap();
+    vm.check_fd_access(fd)?;
+    #[cfg(unix)]
+    wait_readable(fd, vm)?;
     let mut buffer = vec![0u8; n.as_bigint().to_usize().unwrap()];
-    let mut file = rust_file(fd.as_bigint().to_i64().unwrap());
-    file.read_exact(&mut buffer)
-        .map_err(|err| convert_io_error(vm, err))?;
+    let mut file = rust_file(fd);
+    // A single read, like read(2): pipes and sockets may return less than `n`
+    let count = loop {
+        match file.read(&mut buffer) {
+            Ok(count) => break count,
+            Err(ref err) if err.kind() == ErrorKind::Interrupted => {
+                if let Err(exc) = vm.check_signals() {
+                    raw_file_number(file);
+                    return Err(exc);
+                }
+            }
+            Err(err) => {
+                raw_file_number(file);
+                return Err(convert_io_error(vm, err));
+            }
+        }
+    };
+    buffer.truncate(count);
 
     // Avoid closing the fd
     raw_file_number(file);
     Ok(vm.ctx.new_bytes(buffer))
 }
 
+/// Waits until `fd` has data to read if the vm has a time limit, so that
+/// reading from a pipe doesn't block past it.
+#[cfg(unix)]
+fn wait_readable(fd: i64, vm: &VirtualMachine) -> PyResult<()> {
+    let timeout = match vm.limits.clamp_timeout(vm, None)? {
+        Some(timeout) => timeout,
+        None => return Ok(()),
+    };
+    let mut pollfd = libc::pollfd {
+        fd: fd as libc::c_int,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // Errors, including being interrupted, are left for the read to report
+    if unsafe { libc::poll(&mut pollfd, 1, super::select::poll_timeout(timeout)) } == 0 {
+        vm.limits.check_deadline(vm)?;
+    }
+    Ok(())
+}
+
 fn os_write(fd: PyIntRef, data: PyBytesRef, vm: &VirtualMachine) -> PyResult {
-    let mut file = rust_file(fd.as_bigint().to_i64().unwrap());
-    let written = file.write(&data).map_err(|err| convert_io_error(vm, err))?;
+    let fd = fd.as_bigint().to_i64().unwrap();
+    vm.check_fd_access(fd)?;
+    let mut file = rust_file(fd);
+    let written = file.write(&data);
 
     // Avoid closing the fd
     raw_file_number(file);
+    let written = written.map_err(|err| convert_io_error(vm, err))?;
     Ok(vm.ctx.new_int(written))
 }
 
 fn os_remove(path: PyStringRef, dir_fd: DirFd, vm: &VirtualMachine) -> PyResult<()> {
-    let path = make_path(vm, path, &dir_fd);
-    fs::remove_file(&path.value).map_err(|err| convert_io_error(vm, err))
+    vm.check_access(Access::Write(&path.value))?;
+    let path = make_path(vm, path, &dir_fd)?;
+    fs::remove_file(&path.value).map_err(|err| convert_io_error_with_path(vm, err, &path.value))
 }
 
 fn os_mkdir(path: PyStringRef, dir_fd: DirFd, vm: &VirtualMachine) -> PyResult<()> {
-    let path = make_path(vm, path, &dir_fd);
-    fs::create_dir(&path.value).map_err(|err| convert_io_error(vm, err))
+    vm.check_access(Access::Write(&path.value))?;
+    let path = make_path(vm, path, &dir_fd)?;
+    fs::create_dir(&path.value).map_err(|err| convert_io_error_with_path(vm, err, &path.value))
 }
 
 fn os_mkdirs(path: PyStringRef, vm: &VirtualMachine) -> PyResult<()> {
-    fs::create_dir_all(&path.value).map_err(|err| convert_io_error(vm, err))
+    vm.check_access(Access::Write(&path.value))?;
+    fs::create_dir_all(&path.value).map_err(|err| convert_io_error_with_path(vm, err, &path.value))
 }
 
 fn os_rmdir(path: PyStringRef, dir_fd: DirFd, vm: &VirtualMachine) -> PyResult<()> {
-    let path = make_path(vm, path, &dir_fd);
-    fs::remove_dir(&path.value).map_err(|err| convert_io_error(vm, err))
+    vm.check_access(Access::Write(&path.value))?;
+    let path = make_path(vm, path, &dir_fd)?;
+    fs::remove_dir(&path.value).map_err(|err| convert_io_error_with_path(vm, err, &path.value))
 }
 
 fn os_listdir(path: PyStringRef, vm: &VirtualMachine) -> PyResult {
+    vm.check_access(Access::Read(&path.value))?;
     match fs::read_dir(&path.value) {
         Ok(iter) => {
             let res: PyResult<Vec<PyObjectRef>> = iter
                 .map(|entry| match entry {
                     Ok(path) => Ok(vm.ctx.new_str(path.file_name().into_string().unwrap())),
-                    Err(s) => Err(convert_io_error(vm, s)),
+                    Err(s) => Err(convert_io_error_with_path(vm, s, &path.value)),
                 })
                 .collect();
             Ok(vm.ctx.new_list(res?))
         }
-        Err(s) => Err(vm.new_os_error(s.to_string())),
+        Err(s) => Err(convert_io_error_with_path(vm, s, &path.value)),
     }
 }
 
-fn os_putenv(key: PyStringRef, value: PyStringRef, _vm: &VirtualMachine) {
-    env::set_var(&key.value, &value.value)
+fn os_putenv(key: PyStringRef, value: PyStringRef, vm: &VirtualMachine) -> PyResult<()> {
+    vm.check_access(Access::Process)?;
+    env::set_var(&key.value, &value.value);
+    Ok(())
 }
 
-fn os_unsetenv(key: PyStringRef, _vm: &VirtualMachine) {
-    env::remove_var(&key.value)
+fn os_unsetenv(key: PyStringRef, vm: &VirtualMachine) -> PyResult<()> {
+    vm.check_access(Access::Process)?;
+    env::remove_var(&key.value);
+    Ok(())
 }
 
+/// The environment of the process, which is left empty unless the access
+/// policy allows `Access::Process`.
 fn _os_environ(vm: &VirtualMachine) -> PyDictRef {
     let environ = vm.ctx.new_dict();
+    if vm.check_access(Access::Process).is_err() {
+        return environ;
+    }
     for (key, value) in env::vars() {
         environ.set_item(&key, vm.new_str(value), vm).unwrap();
     }
@@ -342,12 +465,7 @@ impl DirEntryRef {
             .is_symlink())
     }
 
-    fn stat(
-        self,
-        dir_fd: DirFd,
-        follow_symlinks: FollowSymlinks,
-        vm: &VirtualMachine,
-    ) -> PyResult<StatResult> {
+    fn stat(self, dir_fd: DirFd, follow_symlinks: FollowSymlinks, vm: &VirtualMachine) -> PyResult {
         os_stat(self.path(vm).try_into_ref(vm)?, dir_fd, follow_symlinks, vm)
     }
 }
@@ -355,7 +473,7 @@ impl DirEntryRef {
 #[pyclass]
 #[derive(Debug)]
 struct ScandirIterator {
-    entries: RefCell<fs::ReadDir>,
+    entries: RefCell<Option<fs::ReadDir>>,
 }
 
 impl PyValue for ScandirIterator {
@@ -368,12 +486,19 @@ impl PyValue for ScandirIterator {
 impl ScandirIterator {
     #[pymethod(name = "__next__")]
     fn next(&self, vm: &VirtualMachine) -> PyResult {
-        match self.entries.borrow_mut().next() {
+        let entry = match *self.entries.borrow_mut() {
+            Some(ref mut entries) => entries.next(),
+            None => None,
+        };
+        match entry {
             Some(entry) => match entry {
                 Ok(entry) => Ok(DirEntry { entry }.into_ref(vm).into_object()),
                 Err(s) => Err(convert_io_error(vm, s)),
             },
-            None => Err(objiter::new_stop_iteration(vm)),
+            None => {
+                self.close(vm);
+                Err(objiter::new_stop_iteration(vm))
+            }
         }
     }
 
@@ -381,19 +506,36 @@ impl ScandirIterator {
     fn iter(zelf: PyRef<Self>, _vm: &VirtualMachine) -> PyRef<Self> {
         zelf
     }
+
+    #[pymethod(name = "close")]
+    fn close(&self, _vm: &VirtualMachine) {
+        self.entries.replace(None);
+    }
+
+    #[pymethod(name = "__enter__")]
+    fn enter(zelf: PyRef<Self>, _vm: &VirtualMachine) -> PyRef<Self> {
+        zelf
+    }
+
+    #[pymethod(name = "__exit__")]
+    fn exit(zelf: PyRef<Self>, _args: PyFuncArgs, vm: &VirtualMachine) {
+        zelf.close(vm)
+    }
 }
 
 fn os_scandir(path: PyStringRef, vm: &VirtualMachine) -> PyResult {
+    vm.check_access(Access::Read(&path.value))?;
     match fs::read_dir(&path.value) {
         Ok(iter) => Ok(ScandirIterator {
-            entries: RefCell::new(iter),
+            entries: RefCell::new(Some(iter)),
         }
         .into_ref(vm)
         .into_object()),
-        Err(s) => Err(convert_io_error(vm, s)),
+        Err(s) => Err(convert_io_error_with_path(vm, s, &path.value)),
     }
 }
 
+#[pystruct_sequence(name = "stat_result")]
 #[derive(Debug)]
 struct StatResult {
     st_mode: u32,
@@ -404,57 +546,18 @@ struct StatResult {
     st_gid: u32,
     st_size: u64,
     st_atime: f64,
-    st_ctime: f64,
     st_mtime: f64,
+    st_ctime: f64,
+    st_atime_ns: i64,
+    st_mtime_ns: i64,
+    st_ctime_ns: i64,
 }
 
-impl PyValue for StatResult {
-    fn class(vm: &VirtualMachine) -> PyClassRef {
-        vm.class("_os", "stat_result")
-    }
-}
-
-type StatResultRef = PyRef<StatResult>;
-
-impl StatResultRef {
-    fn st_mode(self, _vm: &VirtualMachine) -> u32 {
-        self.st_mode
-    }
-
-    fn st_ino(self, _vm: &VirtualMachine) -> u64 {
-        self.st_ino
-    }
-
-    fn st_dev(self, _vm: &VirtualMachine) -> u64 {
-        self.st_dev
-    }
-
-    fn st_nlink(self, _vm: &VirtualMachine) -> u64 {
-        self.st_nlink
-    }
-
-    fn st_uid(self, _vm: &VirtualMachine) -> u32 {
-        self.st_uid
-    }
-
-    fn st_gid(self, _vm: &VirtualMachine) -> u32 {
-        self.st_gid
-    }
-
-    fn st_size(self, _vm: &VirtualMachine) -> u64 {
-        self.st_size
-    }
-
-    fn st_atime(self, _vm: &VirtualMachine) -> f64 {
-        self.st_atime
-    }
-
-    fn st_ctime(self, _vm: &VirtualMachine) -> f64 {
-        self.st_ctime
-    }
-
-    fn st_mtime(self, _vm: &VirtualMachine) -> f64 {
-        self.st_mtime
+impl StatResult {
+    fn into_obj(self, vm: &VirtualMachine) -> PyResult {
+        Ok(self
+            .into_struct_sequence(vm, vm.class("_os", "stat_result"))?
+            .into_object())
     }
 }
 
@@ -470,6 +573,17 @@ fn to_seconds_from_unix_epoch(sys_time: SystemTime) -> f64 {
     }
 }
 
+fn duration_as_nanos(duration: Duration) -> i64 {
+    (duration.as_secs() as i64) * 1_000_000_000 + i64::from(duration.subsec_nanos())
+}
+
+fn to_nanos_from_unix_epoch(sys_time: SystemTime) -> i64 {
+    match sys_time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(duration) => duration_as_nanos(duration),
+        Err(err) => -duration_as_nanos(err.duration()),
+    }
+}
+
 #[cfg(unix)]
 fn to_seconds_from_nanos(secs: i64, nanos: i64) -> f64 {
     let duration = Duration::new(secs as u64, nanos as u32);
@@ -497,47 +611,50 @@ macro_rules! os_unix_stat_inner {
                 st_atime: to_seconds_from_unix_epoch(meta.accessed()?),
                 st_mtime: to_seconds_from_unix_epoch(meta.modified()?),
                 st_ctime: to_seconds_from_nanos(meta.st_ctime(), meta.st_ctime_nsec()),
+                st_atime_ns: to_nanos_from_unix_epoch(meta.accessed()?),
+                st_mtime_ns: to_nanos_from_unix_epoch(meta.modified()?),
+                st_ctime_ns: meta.st_ctime() * 1_000_000_000 + meta.st_ctime_nsec(),
             })
         }
 
         get_stats(&$path.value, $follow_symlinks.follow_symlinks)
-            .map_err(|err| convert_io_error($vm, err))
+            .map_err(|err| convert_io_error_with_path($vm, err, &$path.value))
     }};
 }
 
 #[cfg(target_os = "linux")]
-fn os_stat(
+fn stat_inner(
     path: PyStringRef,
     dir_fd: DirFd,
     follow_symlinks: FollowSymlinks,
     vm: &VirtualMachine,
 ) -> PyResult<StatResult> {
     use std::os::linux::fs::MetadataExt;
-    let path = make_path(vm, path, &dir_fd);
+    let path = make_path(vm, path, &dir_fd)?;
     os_unix_stat_inner!(path, follow_symlinks, vm)
 }
 
 #[cfg(target_os = "macos")]
-fn os_stat(
+fn stat_inner(
     path: PyStringRef,
     dir_fd: DirFd,
     follow_symlinks: FollowSymlinks,
     vm: &VirtualMachine,
 ) -> PyResult<StatResult> {
     use std::os::macos::fs::MetadataExt;
-    let path = make_path(vm, path, &dir_fd);
+    let path = make_path(vm, path, &dir_fd)?;
     os_unix_stat_inner!(path, follow_symlinks, vm)
 }
 
 #[cfg(target_os = "android")]
-fn os_stat(
+fn stat_inner(
     path: PyStringRef,
     dir_fd: DirFd,
     follow_symlinks: FollowSymlinks,
     vm: &VirtualMachine,
 ) -> PyResult<StatResult> {
     use std::os::android::fs::MetadataExt;
-    let path = make_path(vm, path, &dir_fd);
+    let path = make_path(vm, path, &dir_fd)?;
     os_unix_stat_inner!(path, follow_symlinks, vm)
 }
 
@@ -563,7 +680,7 @@ fn attributes_to_mode(attr: u32) -> u32 {
 }
 
 #[cfg(windows)]
-fn os_stat(
+fn stat_inner(
     path: PyStringRef,
     _dir_fd: DirFd, // TODO: error
     follow_symlinks: FollowSymlinks,
@@ -588,11 +705,14 @@ fn os_stat(
             st_atime: to_seconds_from_unix_epoch(meta.accessed()?),
             st_mtime: to_seconds_from_unix_epoch(meta.modified()?),
             st_ctime: to_seconds_from_unix_epoch(meta.created()?),
+            st_atime_ns: to_nanos_from_unix_epoch(meta.accessed()?),
+            st_mtime_ns: to_nanos_from_unix_epoch(meta.modified()?),
+            st_ctime_ns: to_nanos_from_unix_epoch(meta.created()?),
         })
     }
 
     get_stats(&path.value, follow_symlinks.follow_symlinks)
-        .map_err(|s| vm.new_os_error(s.to_string()))
+        .map_err(|err| convert_io_error_with_path(vm, err, &path.value))
 }
 
 #[cfg(not(any(
@@ -601,11 +721,26 @@ fn os_stat(
     target_os = "android",
     windows
 )))]
-fn os_stat(path: PyStringRef, vm: &VirtualMachine) -> PyResult {
+fn stat_inner(
+    path: PyStringRef,
+    dir_fd: DirFd,
+    follow_symlinks: FollowSymlinks,
+    vm: &VirtualMachine,
+) -> PyResult<StatResult> {
     unimplemented!();
 }
 
-fn os_lstat(path: PyStringRef, dir_fd: DirFd, vm: &VirtualMachine) -> PyResult<StatResult> {
+fn os_stat(
+    path: PyStringRef,
+    dir_fd: DirFd,
+    follow_symlinks: FollowSymlinks,
+    vm: &VirtualMachine,
+) -> PyResult {
+    vm.check_access(Access::Read(&path.value))?;
+    stat_inner(path, dir_fd, follow_symlinks, vm)?.into_obj(vm)
+}
+
+fn os_lstat(path: PyStringRef, dir_fd: DirFd, vm: &VirtualMachine) -> PyResult {
     os_stat(
         path,
         dir_fd,
@@ -624,8 +759,10 @@ fn os_symlink(
     vm: &VirtualMachine,
 ) -> PyResult<()> {
     use std::os::unix::fs as unix_fs;
-    let dst = make_path(vm, dst, &dir_fd);
-    unix_fs::symlink(&src.value, &dst.value).map_err(|err| convert_io_error(vm, err))
+    vm.check_access(Access::Write(&dst.value))?;
+    let dst = make_path(vm, dst, &dir_fd)?;
+    unix_fs::symlink(&src.value, &dst.value)
+        .map_err(|err| convert_io_error_with_paths(vm, err, &src.value, &dst.value))
 }
 
 #[cfg(windows)]
@@ -636,6 +773,7 @@ fn os_symlink(
     vm: &VirtualMachine,
 ) -> PyResult<()> {
     use std::os::windows::fs as win_fs;
+    vm.check_access(Access::Write(&dst.value))?;
     let ret = match fs::metadata(&dst.value) {
         Ok(meta) => {
             if meta.is_file() {
@@ -648,7 +786,7 @@ fn os_symlink(
         }
         Err(_) => win_fs::symlink_file(&src.value, &dst.value),
     };
-    ret.map_err(|err| convert_io_error(vm, err))
+    ret.map_err(|err| convert_io_error_with_paths(vm, err, &src.value, &dst.value))
 }
 
 #[cfg(all(not(unix), not(windows)))]
@@ -671,7 +809,9 @@ fn os_getcwd(vm: &VirtualMachine) -> PyResult<String> {
 }
 
 fn os_chdir(path: PyStringRef, vm: &VirtualMachine) -> PyResult<()> {
-    env::set_current_dir(&path.value).map_err(|err| convert_io_error(vm, err))
+    vm.check_access(Access::Read(&path.value))?;
+    env::set_current_dir(&path.value)
+        .map_err(|err| convert_io_error_with_path(vm, err, &path.value))
 }
 
 fn os_fspath(path: PyObjectRef, vm: &VirtualMachine) -> PyResult {
@@ -688,7 +828,576 @@ fn os_fspath(path: PyObjectRef, vm: &VirtualMachine) -> PyResult {
 }
 
 fn os_rename(src: PyStringRef, dst: PyStringRef, vm: &VirtualMachine) -> PyResult<()> {
-    fs::rename(&src.value, &dst.value).map_err(|err| convert_io_error(vm, err))
+    vm.check_access(Access::Write(&src.value))?;
+    vm.check_access(Access::Write(&dst.value))?;
+    fs::rename(&src.value, &dst.value)
+        .map_err(|err| convert_io_error_with_paths(vm, err, &src.value, &dst.value))
+}
+
+fn os_link(src: PyStringRef, dst: PyStringRef, vm: &VirtualMachine) -> PyResult<()> {
+    vm.check_access(Access::Write(&dst.value))?;
+    fs::hard_link(&src.value, &dst.value)
+        .map_err(|err| convert_io_error_with_paths(vm, err, &src.value, &dst.value))
+}
+
+fn os_readlink(path: PyStringRef, dir_fd: DirFd, vm: &VirtualMachine) -> PyResult<String> {
+    vm.check_access(Access::Read(&path.value))?;
+    let path = make_path(vm, path, &dir_fd)?;
+    let target = fs::read_link(&path.value)
+        .map_err(|err| convert_io_error_with_path(vm, err, &path.value))?;
+    Ok(target.to_string_lossy().into_owned())
+}
+
+#[cfg(unix)]
+fn os_chmod(
+    path: PyStringRef,
+    mode: u32,
+    dir_fd: DirFd,
+    follow_symlinks: FollowSymlinks,
+    vm: &VirtualMachine,
+) -> PyResult<()> {
+    use std::os::unix::fs::PermissionsExt;
+    if !follow_symlinks.follow_symlinks {
+        return Err(vm.new_not_implemented_error(
+            "chmod: follow_symlinks unavailable on this platform".to_string(),
+        ));
+    }
+    vm.check_access(Access::Write(&path.value))?;
+    let path = make_path(vm, path, &dir_fd)?;
+    fs::set_permissions(&path.value, fs::Permissions::from_mode(mode))
+        .map_err(|err| convert_io_error_with_path(vm, err, &path.value))
+}
+
+/// Windows only knows about the read-only attribute, which follows the owner's write bit.
+#[cfg(windows)]
+fn os_chmod(
+    path: PyStringRef,
+    mode: u32,
+    _dir_fd: DirFd,
+    _follow_symlinks: FollowSymlinks,
+    vm: &VirtualMachine,
+) -> PyResult<()> {
+    vm.check_access(Access::Write(&path.value))?;
+    let mut permissions = fs::metadata(&path.value)
+        .map_err(|err| convert_io_error_with_path(vm, err, &path.value))?
+        .permissions();
+    permissions.set_readonly(mode & 0o200 == 0);
+    fs::set_permissions(&path.value, permissions)
+        .map_err(|err| convert_io_error_with_path(vm, err, &path.value))
+}
+
+#[cfg(all(not(unix), not(windows)))]
+fn os_chmod(
+    _path: PyStringRef,
+    _mode: u32,
+    _dir_fd: DirFd,
+    _follow_symlinks: FollowSymlinks,
+    vm: &VirtualMachine,
+) -> PyResult<()> {
+    Err(vm.new_not_implemented_error("chmod unavailable on this platform".to_string()))
+}
+
+fn os_truncate(path: PyStringRef, length: u64, vm: &VirtualMachine) -> PyResult<()> {
+    vm.check_access(Access::Write(&path.value))?;
+    let file = OpenOptions::new()
+        .write(true)
+        .open(&path.value)
+        .map_err(|err| convert_io_error_with_path(vm, err, &path.value))?;
+    file.set_len(length)
+        .map_err(|err| convert_io_error_with_path(vm, err, &path.value))
+}
+
+fn os_ftruncate(fd: i64, length: u64, vm: &VirtualMachine) -> PyResult<()> {
+    vm.check_fd_access(fd)?;
+    let file = rust_file(fd);
+    let result = file.set_len(length);
+    // Avoid closing the fd
+    raw_file_number(file);
+    result.map_err(|err| convert_io_error(vm, err))
+}
+
+fn os_lseek(fd: i64, position: i64, how: i32, vm: &VirtualMachine) -> PyResult<u64> {
+    vm.check_fd_access(fd)?;
+    let position = match how {
+        0 if position >= 0 => SeekFrom::Start(position as u64),
+        1 => SeekFrom::Current(position),
+        2 => SeekFrom::End(position),
+        _ => {
+            let err = io::Error::new(ErrorKind::InvalidInput, "Invalid argument");
+            return Err(convert_io_error(vm, err));
+        }
+    };
+    let mut file = rust_file(fd);
+    let result = file.seek(position);
+    // Avoid closing the fd
+    raw_file_number(file);
+    result.map_err(|err| convert_io_error(vm, err))
+}
+
+fn os_urandom(size: usize, vm: &VirtualMachine) -> PyResult {
+    use rand::{rngs::OsRng, RngCore};
+
+    let mut buffer = vec![0u8; size];
+    OsRng::new()
+        .and_then(|mut rng| rng.try_fill_bytes(&mut buffer))
+        .map_err(|err| vm.new_os_error(err.to_string()))?;
+    Ok(vm.ctx.new_bytes(buffer))
+}
+
+#[cfg(unix)]
+fn os_cpu_count(_vm: &VirtualMachine) -> Option<usize> {
+    let count = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) };
+    if count > 0 {
+        Some(count as usize)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn os_cpu_count(_vm: &VirtualMachine) -> Option<usize> {
+    env::var("NUMBER_OF_PROCESSORS")
+        .ok()
+        .and_then(|count| count.parse().ok())
+}
+
+const F_OK: u8 = 0;
+const R_OK: u8 = 4;
+const W_OK: u8 = 2;
+const X_OK: u8 = 1;
+
+#[cfg(unix)]
+fn os_access(path: PyStringRef, mode: u8, dir_fd: DirFd, vm: &VirtualMachine) -> PyResult<bool> {
+    vm.check_access(Access::Read(&path.value))?;
+    let path = make_path(vm, path, &dir_fd)?;
+    let path = std::ffi::CString::new(path.as_str())
+        .map_err(|_| vm.new_value_error("embedded null byte".to_string()))?;
+    Ok(unsafe { libc::access(path.as_ptr(), i32::from(mode)) } == 0)
+}
+
+/// There are no execute permissions on Windows, so only existence and the
+/// read-only attribute are checked.
+#[cfg(not(unix))]
+fn os_access(path: PyStringRef, mode: u8, _dir_fd: DirFd, vm: &VirtualMachine) -> PyResult<bool> {
+    vm.check_access(Access::Read(&path.value))?;
+    match fs::metadata(&path.value) {
+        Ok(meta) => Ok(mode & W_OK == 0 || !meta.permissions().readonly()),
+        Err(_) => Ok(false),
+    }
+}
+
+fn os_getpid(_vm: &VirtualMachine) -> u32 {
+    std::process::id()
+}
+
+#[cfg(unix)]
+pub fn errno_error(vm: &VirtualMachine) -> PyObjectRef {
+    convert_io_error(vm, io::Error::last_os_error())
+}
+
+#[cfg(unix)]
+fn os_getppid(_vm: &VirtualMachine) -> libc::pid_t {
+    unsafe { libc::getppid() }
+}
+
+#[cfg(unix)]
+fn os_strerror(code: i32, _vm: &VirtualMachine) -> String {
+    let msg = unsafe { std::ffi::CStr::from_ptr(libc::strerror(code)) };
+    msg.to_string_lossy().into_owned()
+}
+
+#[cfg(unix)]
+pub fn set_inheritable(fd: i32, inheritable: bool) -> io::Result<()> {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    if flags < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let new_flags = if inheritable {
+        flags & !libc::FD_CLOEXEC
+    } else {
+        flags | libc::FD_CLOEXEC
+    };
+    if new_flags != flags && unsafe { libc::fcntl(fd, libc::F_SETFD, new_flags) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Like CPython, file descriptors created by `os.pipe()` are non-inheritable.
+#[cfg(unix)]
+fn os_pipe(vm: &VirtualMachine) -> PyResult {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } < 0 {
+        return Err(errno_error(vm));
+    }
+    for fd in fds.iter() {
+        vm.add_opened_fd(i64::from(*fd));
+        set_inheritable(*fd, false).map_err(|err| convert_io_error(vm, err))?;
+    }
+    Ok(vm
+        .ctx
+        .new_tuple(vec![vm.ctx.new_int(fds[0]), vm.ctx.new_int(fds[1])]))
+}
+
+#[cfg(unix)]
+fn os_dup2(
+    fd: i32,
+    fd2: i32,
+    inheritable: OptionalArg<bool>,
+    vm: &VirtualMachine,
+) -> PyResult<i32> {
+    vm.check_fd_access(i64::from(fd))?;
+    vm.check_fd_access(i64::from(fd2))?;
+    if unsafe { libc::dup2(fd, fd2) } < 0 {
+        return Err(errno_error(vm));
+    }
+    if !inheritable.unwrap_or(true) {
+        set_inheritable(fd2, false).map_err(|err| convert_io_error(vm, err))?;
+    }
+    Ok(fd2)
+}
+
+#[cfg(unix)]
+fn os_get_inheritable(fd: i32, vm: &VirtualMachine) -> PyResult<bool> {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    if flags < 0 {
+        return Err(errno_error(vm));
+    }
+    Ok(flags & libc::FD_CLOEXEC == 0)
+}
+
+#[cfg(unix)]
+fn os_set_inheritable(fd: i32, inheritable: bool, vm: &VirtualMachine) -> PyResult<()> {
+    vm.check_fd_access(i64::from(fd))?;
+    set_inheritable(fd, inheritable).map_err(|err| convert_io_error(vm, err))
+}
+
+#[cfg(unix)]
+fn os_fork(vm: &VirtualMachine) -> PyResult<libc::pid_t> {
+    vm.check_access(Access::Process)?;
+    let pid = unsafe { libc::fork() };
+    if pid < 0 {
+        Err(errno_error(vm))
+    } else {
+        Ok(pid)
+    }
+}
+
+#[cfg(unix)]
+fn os_execv(path: PyStringRef, argv: PyIterable<PyStringRef>, vm: &VirtualMachine) -> PyResult<()> {
+    use std::ffi::CString;
+
+    vm.check_access(Access::Process)?;
+    let to_cstring =
+        |s: &str| CString::new(s).map_err(|_| vm.new_value_error("embedded null byte".to_string()));
+    let path = to_cstring(path.as_str())?;
+    let argv = argv
+        .iter(vm)?
+        .map(|arg| to_cstring(arg?.as_str()))
+        .collect::<PyResult<Vec<_>>>()?;
+    if argv.is_empty() || argv[0].as_bytes().is_empty() {
+        return Err(vm.new_value_error("execv() arg 2 first element cannot be empty".to_string()));
+    }
+    let mut argv_ptrs: Vec<*const libc::c_char> = argv.iter().map(|arg| arg.as_ptr()).collect();
+    argv_ptrs.push(std::ptr::null());
+
+    unsafe { libc::execv(path.as_ptr(), argv_ptrs.as_ptr()) };
+    // execv only returns on failure
+    Err(errno_error(vm))
+}
+
+#[cfg(unix)]
+fn os_exit(code: i32, vm: &VirtualMachine) -> PyResult<()> {
+    vm.check_access(Access::Process)?;
+    unsafe { libc::_exit(code) }
+}
+
+/// Waits for `pid`, retrying when interrupted by a signal whose handler
+/// doesn't raise, and returns the `(pid, status)` pair reported by waitpid(2).
+#[cfg(unix)]
+fn os_waitpid(pid: libc::pid_t, options: i32, vm: &VirtualMachine) -> PyResult {
+    let mut status = 0;
+    let res = loop {
+        let res = unsafe { libc::waitpid(pid, &mut status, options) };
+        if res >= 0 {
+            break res;
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != ErrorKind::Interrupted {
+            return Err(convert_io_error(vm, err));
+        }
+        vm.check_signals()?;
+    };
+    Ok(vm
+        .ctx
+        .new_tuple(vec![vm.ctx.new_int(res), vm.ctx.new_int(status)]))
+}
+
+#[cfg(unix)]
+fn os_kill(pid: libc::pid_t, sig: i32, vm: &VirtualMachine) -> PyResult<()> {
+    vm.check_access(Access::Process)?;
+    if unsafe { libc::kill(pid, sig) } < 0 {
+        Err(errno_error(vm))
+    } else {
+        Ok(())
+    }
+}
+
+// Decoding of wait(2) statuses; the encoding is shared by Linux and the BSDs.
+#[cfg(unix)]
+fn os_wifexited(status: i32, _vm: &VirtualMachine) -> bool {
+    status & 0x7f == 0
+}
+
+#[cfg(unix)]
+fn os_wexitstatus(status: i32, _vm: &VirtualMachine) -> i32 {
+    (status >> 8) & 0xff
+}
+
+#[cfg(unix)]
+fn os_wifsignaled(status: i32, _vm: &VirtualMachine) -> bool {
+    let sig = status & 0x7f;
+    sig != 0 && sig != 0x7f
+}
+
+#[cfg(unix)]
+fn os_wtermsig(status: i32, _vm: &VirtualMachine) -> i32 {
+    status & 0x7f
+}
+
+#[cfg(unix)]
+fn os_wifstopped(status: i32, _vm: &VirtualMachine) -> bool {
+    status & 0xff == 0x7f
+}
+
+#[cfg(unix)]
+fn os_wstopsig(status: i32, _vm: &VirtualMachine) -> i32 {
+    (status >> 8) & 0xff
+}
+
+#[cfg(unix)]
+fn os_chown(
+    path: PyStringRef,
+    uid: i64,
+    gid: i64,
+    dir_fd: DirFd,
+    follow_symlinks: FollowSymlinks,
+    vm: &VirtualMachine,
+) -> PyResult<()> {
+    vm.check_access(Access::Write(&path.value))?;
+    let path = make_path(vm, path, &dir_fd)?;
+    let c_path = std::ffi::CString::new(path.as_str())
+        .map_err(|_| vm.new_value_error("embedded null byte".to_string()))?;
+    // -1 leaves the id unchanged, as the cast to an unsigned id_t preserves
+    let (uid, gid) = (uid as libc::uid_t, gid as libc::gid_t);
+    let res = if follow_symlinks.follow_symlinks {
+        unsafe { libc::chown(c_path.as_ptr(), uid, gid) }
+    } else {
+        unsafe { libc::lchown(c_path.as_ptr(), uid, gid) }
+    };
+    if res < 0 {
+        Err(convert_io_error_with_path(
+            vm,
+            io::Error::last_os_error(),
+            &path.value,
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+#[derive(FromArgs)]
+struct UtimeArgs {
+    #[pyarg(positional_or_keyword, default = "None")]
+    times: Option<PyTupleRef>,
+    #[pyarg(keyword_only, default = "None")]
+    ns: Option<PyTupleRef>,
+}
+
+#[cfg(unix)]
+fn os_utime(
+    path: PyStringRef,
+    args: UtimeArgs,
+    dir_fd: DirFd,
+    follow_symlinks: FollowSymlinks,
+    vm: &VirtualMachine,
+) -> PyResult<()> {
+    let pair = |tuple: &PyTupleRef, name: &str| {
+        if tuple.elements.len() == 2 {
+            Ok((tuple.elements[0].clone(), tuple.elements[1].clone()))
+        } else {
+            Err(vm.new_type_error(format!("utime: '{}' must be a tuple of two ints", name)))
+        }
+    };
+    let from_nanos = |nanos: i64| {
+        let (mut secs, mut nanos) = (nanos / 1_000_000_000, nanos % 1_000_000_000);
+        if nanos < 0 {
+            secs -= 1;
+            nanos += 1_000_000_000;
+        }
+        libc::timespec {
+            tv_sec: secs as libc::time_t,
+            tv_nsec: nanos as libc::c_long,
+        }
+    };
+    let times = match (args.times, args.ns) {
+        (Some(_), Some(_)) => {
+            return Err(vm.new_value_error(
+                "utime: you may specify either 'times' or 'ns' but not both".to_string(),
+            ));
+        }
+        (Some(times), None) => {
+            let (atime, mtime) = pair(&times, "times")?;
+            let to_nanos = |secs: PyObjectRef| -> PyResult<i64> {
+                Ok((objfloat::make_float(vm, &secs)? * 1e9).round() as i64)
+            };
+            [from_nanos(to_nanos(atime)?), from_nanos(to_nanos(mtime)?)]
+        }
+        (None, Some(ns)) => {
+            let (atime, mtime) = pair(&ns, "ns")?;
+            [
+                from_nanos(i64::try_from_object(vm, atime)?),
+                from_nanos(i64::try_from_object(vm, mtime)?),
+            ]
+        }
+        (None, None) => {
+            let now = libc::timespec {
+                tv_sec: 0,
+                tv_nsec: libc::UTIME_NOW,
+            };
+            [now, now]
+        }
+    };
+
+    vm.check_access(Access::Write(&path.value))?;
+    let path = make_path(vm, path, &dir_fd)?;
+    let c_path = std::ffi::CString::new(path.as_str())
+        .map_err(|_| vm.new_value_error("embedded null byte".to_string()))?;
+    let flags = if follow_symlinks.follow_symlinks {
+        0
+    } else {
+        libc::AT_SYMLINK_NOFOLLOW
+    };
+    if unsafe { libc::utimensat(libc::AT_FDCWD, c_path.as_ptr(), times.as_ptr(), flags) } < 0 {
+        Err(convert_io_error_with_path(
+            vm,
+            io::Error::last_os_error(),
+            &path.value,
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Like CPython, the duplicate is non-inheritable.
+#[cfg(unix)]
+fn os_dup(fd: i32, vm: &VirtualMachine) -> PyResult<i32> {
+    vm.check_fd_access(i64::from(fd))?;
+    let new_fd = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0) };
+    if new_fd < 0 {
+        Err(errno_error(vm))
+    } else {
+        vm.add_opened_fd(i64::from(new_fd));
+        Ok(new_fd)
+    }
+}
+
+#[cfg(unix)]
+fn os_getuid(_vm: &VirtualMachine) -> libc::uid_t {
+    unsafe { libc::getuid() }
+}
+
+#[cfg(unix)]
+fn os_geteuid(_vm: &VirtualMachine) -> libc::uid_t {
+    unsafe { libc::geteuid() }
+}
+
+#[cfg(unix)]
+fn os_getgid(_vm: &VirtualMachine) -> libc::gid_t {
+    unsafe { libc::getgid() }
+}
+
+#[cfg(unix)]
+fn os_getegid(_vm: &VirtualMachine) -> libc::gid_t {
+    unsafe { libc::getegid() }
+}
+
+#[cfg(unix)]
+fn os_umask(mask: libc::mode_t, vm: &VirtualMachine) -> PyResult<libc::mode_t> {
+    vm.check_access(Access::Process)?;
+    Ok(unsafe { libc::umask(mask) })
+}
+
+#[cfg(unix)]
+fn os_isatty(fd: i32, _vm: &VirtualMachine) -> bool {
+    unsafe { libc::isatty(fd) == 1 }
+}
+
+#[cfg(unix)]
+#[pystruct_sequence(name = "terminal_size")]
+#[derive(Debug)]
+struct TerminalSize {
+    columns: usize,
+    lines: usize,
+}
+
+#[cfg(unix)]
+fn os_get_terminal_size(fd: OptionalArg<i32>, vm: &VirtualMachine) -> PyResult {
+    let fd = fd.unwrap_or(libc::STDOUT_FILENO);
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) } < 0 {
+        return Err(errno_error(vm));
+    }
+    let size = TerminalSize {
+        columns: usize::from(size.ws_col),
+        lines: usize::from(size.ws_row),
+    };
+    Ok(size
+        .into_struct_sequence(vm, vm.class("_os", "terminal_size"))?
+        .into_object())
+}
+
+#[cfg(unix)]
+fn extend_module_platform_specific(vm: &VirtualMachine, module: PyObjectRef) -> PyObjectRef {
+    let ctx = &vm.ctx;
+    extend_module!(vm, module, {
+        "getppid" => ctx.new_rustfunc(os_getppid),
+        "getuid" => ctx.new_rustfunc(os_getuid),
+        "geteuid" => ctx.new_rustfunc(os_geteuid),
+        "getgid" => ctx.new_rustfunc(os_getgid),
+        "getegid" => ctx.new_rustfunc(os_getegid),
+        "umask" => ctx.new_rustfunc(os_umask),
+        "isatty" => ctx.new_rustfunc(os_isatty),
+        "terminal_size" => TerminalSize::make_class(ctx),
+        "get_terminal_size" => ctx.new_rustfunc(os_get_terminal_size),
+        "dup" => ctx.new_rustfunc(os_dup),
+        "strerror" => ctx.new_rustfunc(os_strerror),
+        "pipe" => ctx.new_rustfunc(os_pipe),
+        "dup2" => ctx.new_rustfunc(os_dup2),
+        "get_inheritable" => ctx.new_rustfunc(os_get_inheritable),
+        "set_inheritable" => ctx.new_rustfunc(os_set_inheritable),
+        "fork" => ctx.new_rustfunc(os_fork),
+        "execv" => ctx.new_rustfunc(os_execv),
+        "_exit" => ctx.new_rustfunc(os_exit),
+        "waitpid" => ctx.new_rustfunc(os_waitpid),
+        "kill" => ctx.new_rustfunc(os_kill),
+        "WIFEXITED" => ctx.new_rustfunc(os_wifexited),
+        "WEXITSTATUS" => ctx.new_rustfunc(os_wexitstatus),
+        "WIFSIGNALED" => ctx.new_rustfunc(os_wifsignaled),
+        "WTERMSIG" => ctx.new_rustfunc(os_wtermsig),
+        "WIFSTOPPED" => ctx.new_rustfunc(os_wifstopped),
+        "WSTOPSIG" => ctx.new_rustfunc(os_wstopsig),
+        "WNOHANG" => ctx.new_int(libc::WNOHANG),
+        "WUNTRACED" => ctx.new_int(libc::WUNTRACED),
+    });
+
+    module
+}
+
+#[cfg(not(unix))]
+fn extend_module_platform_specific(_vm: &VirtualMachine, module: PyObjectRef) -> PyObjectRef {
+    module
 }
 
 pub fn make_module(vm: &VirtualMachine) -> PyObjectRef {
@@ -714,18 +1423,7 @@ pub fn make_module(vm: &VirtualMachine) -> PyObjectRef {
          "stat" => ctx.new_rustfunc(DirEntryRef::stat),
     });
 
-    let stat_result = py_class!(ctx, "stat_result", ctx.object(), {
-         "st_mode" => ctx.new_property(StatResultRef::st_mode),
-         "st_ino" => ctx.new_property(StatResultRef::st_ino),
-         "st_dev" => ctx.new_property(StatResultRef::st_dev),
-         "st_nlink" => ctx.new_property(StatResultRef::st_nlink),
-         "st_uid" => ctx.new_property(StatResultRef::st_uid),
-         "st_gid" => ctx.new_property(StatResultRef::st_gid),
-         "st_size" => ctx.new_property(StatResultRef::st_size),
-         "st_atime" => ctx.new_property(StatResultRef::st_atime),
-         "st_ctime" => ctx.new_property(StatResultRef::st_ctime),
-         "st_mtime" => ctx.new_property(StatResultRef::st_mtime),
-    });
+    let stat_result = StatResult::make_class(ctx);
 
     struct SupportFunc<'a> {
         name: &'a str,
@@ -756,31 +1454,36 @@ pub fn make_module(vm: &VirtualMachine) -> PyObjectRef {
             }
         }
     }
-    let support_funcs = vec![
+    #[allow(unused_mut)]
+    let mut support_funcs = vec![
         SupportFunc::new(vm, "open", os_open, None, Some(false), None),
-        // access Some Some None
+        SupportFunc::new(vm, "access", os_access, Some(false), Some(false), None),
         SupportFunc::new(vm, "chdir", os_chdir, Some(false), None, None),
         // chflags Some, None Some
-        // chmod Some Some Some
-        // chown Some Some Some
+        SupportFunc::new(vm, "chmod", os_chmod, Some(false), Some(false), Some(false)),
         // chroot Some None None
+        SupportFunc::new(vm, "link", os_link, None, Some(false), Some(false)),
         SupportFunc::new(vm, "listdir", os_listdir, Some(false), None, None),
         SupportFunc::new(vm, "mkdir", os_mkdir, Some(false), Some(false), None),
         // mkfifo Some Some None
         // mknod Some Some None
         // pathconf Some None None
-        // readlink Some Some None
+        SupportFunc::new(vm, "readlink", os_readlink, Some(false), Some(false), None),
         SupportFunc::new(vm, "remove", os_remove, Some(false), Some(false), None),
         SupportFunc::new(vm, "rename", os_rename, Some(false), Some(false), None),
-        SupportFunc::new(vm, "replace", os_rename, Some(false), Some(false), None), // TODO: Fix replace
+        SupportFunc::new(vm, "replace", os_rename, Some(false), Some(false), None),
         SupportFunc::new(vm, "rmdir", os_rmdir, Some(false), Some(false), None),
         SupportFunc::new(vm, "scandir", os_scandir, Some(false), None, None),
         SupportFunc::new(vm, "stat", os_stat, Some(false), Some(false), Some(false)),
         SupportFunc::new(vm, "symlink", os_symlink, None, Some(false), None),
-        // truncate Some None None
+        SupportFunc::new(vm, "truncate", os_truncate, Some(false), None, None),
         SupportFunc::new(vm, "unlink", os_remove, Some(false), Some(false), None),
-        // utime Some Some Some
     ];
+    #[cfg(unix)]
+    support_funcs.extend(vec![
+        SupportFunc::new(vm, "chown", os_chown, Some(false), Some(false), Some(true)),
+        SupportFunc::new(vm, "utime", os_utime, Some(false), Some(false), Some(false)),
+    ]);
     let supports_fd = PySet::default().into_ref(vm);
     let supports_dir_fd = PySet::default().into_ref(vm);
     let supports_follow_symlinks = PySet::default().into_ref(vm);
@@ -803,6 +1506,18 @@ pub fn make_module(vm: &VirtualMachine) -> PyObjectRef {
         "getcwd" => ctx.new_rustfunc(os_getcwd),
         "chdir" => ctx.new_rustfunc(os_chdir),
         "fspath" => ctx.new_rustfunc(os_fspath),
+        "getpid" => ctx.new_rustfunc(os_getpid),
+        "ftruncate" => ctx.new_rustfunc(os_ftruncate),
+        "lseek" => ctx.new_rustfunc(os_lseek),
+        "urandom" => ctx.new_rustfunc(os_urandom),
+        "cpu_count" => ctx.new_rustfunc(os_cpu_count),
+        "SEEK_SET" => ctx.new_int(0),
+        "SEEK_CUR" => ctx.new_int(1),
+        "SEEK_END" => ctx.new_int(2),
+        "F_OK" => ctx.new_int(F_OK),
+        "R_OK" => ctx.new_int(R_OK),
+        "W_OK" => ctx.new_int(W_OK),
+        "X_OK" => ctx.new_int(X_OK),
         "O_RDONLY" => ctx.new_int(FileCreationFlags::O_RDONLY.bits()),
         "O_WRONLY" => ctx.new_int(FileCreationFlags::O_WRONLY.bits()),
         "O_RDWR" => ctx.new_int(FileCreationFlags::O_RDWR.bits()),
@@ -841,5 +1556,5 @@ pub fn make_module(vm: &VirtualMachine) -> PyObjectRef {
         "supports_follow_symlinks" => supports_follow_symlinks.into_object(),
     });
 
-    module
+    extend_module_platform_specific(vm, module)
 }
@@ -0,0 +1,316 @@
+//! Implementation of the `_posixsubprocess` module, the native half of `subprocess`.
+//!
+//! Everything the child needs is prepared before `fork()`, so that between
+//! `fork()` and `exec()` the child only makes async-signal-safe system calls
+//! and never allocates.
+
+use std::ffi::CString;
+use std::fs;
+use std::io;
+
+use super::os::errno_error;
+use crate::obj::objbytes::PyBytesRef;
+use crate::obj::objstr::PyStringRef;
+use crate::pyobject::{Either, PyIterable, PyObjectRef, PyResult};
+use crate::sandbox::Access;
+use crate::vm::VirtualMachine;
+
+type PathLike = Either<PyStringRef, PyBytesRef>;
+
+#[derive(FromArgs)]
+struct ForkExecArgs {
+    #[pyarg(positional_only, optional = false)]
+    args: PyIterable<PathLike>,
+    #[pyarg(positional_only, optional = false)]
+    executable_list: PyIterable<PathLike>,
+    #[pyarg(positional_only, optional = false)]
+    close_fds: bool,
+    #[pyarg(positional_only, optional = false)]
+    fds_to_keep: PyIterable<i32>,
+    #[pyarg(positional_only, optional = false)]
+    cwd: Option<PathLike>,
+    #[pyarg(positional_only, optional = false)]
+    env_list: Option<PyIterable<PathLike>>,
+    #[pyarg(positional_only, optional = false)]
+    p2cread: i32,
+    #[pyarg(positional_only, optional = false)]
+    p2cwrite: i32,
+    #[pyarg(positional_only, optional = false)]
+    c2pread: i32,
+    #[pyarg(positional_only, optional = false)]
+    c2pwrite: i32,
+    #[pyarg(positional_only, optional = false)]
+    errread: i32,
+    #[pyarg(positional_only, optional = false)]
+    errwrite: i32,
+    #[pyarg(positional_only, optional = false)]
+    errpipe_read: i32,
+    #[pyarg(positional_only, optional = false)]
+    errpipe_write: i32,
+    #[pyarg(positional_only, optional = false)]
+    restore_signals: bool,
+    #[pyarg(positional_only, optional = false)]
+    call_setsid: bool,
+    #[pyarg(positional_only, optional = false)]
+    preexec_fn: Option<PyObjectRef>,
+}
+
+fn to_cstring(path: PathLike, vm: &VirtualMachine) -> PyResult<CString> {
+    let bytes = match path {
+        Either::A(s) => s.as_str().as_bytes().to_vec(),
+        Either::B(b) => b.get_value().to_vec(),
+    };
+    CString::new(bytes).map_err(|_| vm.new_value_error("embedded null byte".to_string()))
+}
+
+fn to_cstrings(paths: PyIterable<PathLike>, vm: &VirtualMachine) -> PyResult<Vec<CString>> {
+    paths.iter(vm)?.map(|path| to_cstring(path?, vm)).collect()
+}
+
+/// A NULL terminated array of pointers into `strings`, as expected by `execve()`.
+fn to_ptr_array(strings: &[CString]) -> Vec<*const libc::c_char> {
+    strings
+        .iter()
+        .map(|s| s.as_ptr())
+        .chain(std::iter::once(std::ptr::null()))
+        .collect()
+}
+
+/// File descriptors currently open in this process, used to implement
+/// `close_fds` without probing every possible descriptor in the child.
+fn open_fds() -> Vec<i32> {
+    let listing = fs::read_dir("/proc/self/fd").or_else(|_| fs::read_dir("/dev/fd"));
+    match listing {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+            .collect(),
+        Err(_) => {
+            let max_fd = unsafe { libc::sysconf(libc::_SC_OPEN_MAX) };
+            let max_fd = if max_fd < 0 { 256 } else { max_fd as i32 };
+            (0..max_fd).collect()
+        }
+    }
+}
+
+struct ChildConfig {
+    argv: Vec<*const libc::c_char>,
+    executables: Vec<CString>,
+    envp: Option<Vec<*const libc::c_char>>,
+    cwd: Option<CString>,
+    /// Descriptors the child closes when `close_fds` is set.
+    fds_to_close: Vec<i32>,
+    fds_to_keep: Vec<i32>,
+    p2cread: i32,
+    p2cwrite: i32,
+    c2pread: i32,
+    c2pwrite: i32,
+    errread: i32,
+    errwrite: i32,
+    errpipe_read: i32,
+    errpipe_write: i32,
+    restore_signals: bool,
+    call_setsid: bool,
+}
+
+fn posixsubprocess_fork_exec(args: ForkExecArgs, vm: &VirtualMachine) -> PyResult<libc::pid_t> {
+    if args.preexec_fn.is_some() {
+        return Err(vm.new_value_error("preexec_fn is not supported".to_string()));
+    }
+    vm.check_access(Access::Process)?;
+
+    let argv = to_cstrings(args.args, vm)?;
+    let executables = to_cstrings(args.executable_list, vm)?;
+    let env = args
+        .env_list
+        .map(|env_list| to_cstrings(env_list, vm))
+        .transpose()?;
+    let cwd = args.cwd.map(|cwd| to_cstring(cwd, vm)).transpose()?;
+
+    let mut fds_to_keep = args.fds_to_keep.iter(vm)?.collect::<PyResult<Vec<i32>>>()?;
+    fds_to_keep.push(args.errpipe_write);
+    fds_to_keep.sort();
+    let fds_to_close = if args.close_fds {
+        open_fds()
+            .into_iter()
+            .filter(|fd| *fd > 2 && fds_to_keep.binary_search(fd).is_err())
+            .collect()
+    } else {
+        vec![]
+    };
+
+    let config = ChildConfig {
+        argv: to_ptr_array(&argv),
+        executables,
+        envp: env.as_ref().map(|env| to_ptr_array(env)),
+        cwd,
+        fds_to_close,
+        fds_to_keep,
+        p2cread: args.p2cread,
+        p2cwrite: args.p2cwrite,
+        c2pread: args.c2pread,
+        c2pwrite: args.c2pwrite,
+        errread: args.errread,
+        errwrite: args.errwrite,
+        errpipe_read: args.errpipe_read,
+        errpipe_write: args.errpipe_write,
+        restore_signals: args.restore_signals,
+        call_setsid: args.call_setsid,
+    };
+
+    match unsafe { libc::fork() } {
+        -1 => Err(errno_error(vm)),
+        0 => unsafe { child_exec(&config) },
+        pid => Ok(pid),
+    }
+}
+
+fn last_errno() -> i32 {
+    io::Error::last_os_error().raw_os_error().unwrap_or(0)
+}
+
+/// Runs in the forked child and never returns: either one of the executables
+/// replaces the process image, or the failure is reported to the parent on
+/// `errpipe_write` as `OSError:<hex errno>:` (followed by `noexec` when the
+/// failure happened before exec was attempted) and the child exits with 255.
+unsafe fn child_exec(config: &ChildConfig) -> ! {
+    let p2cread = config.p2cread;
+    let mut c2pwrite = config.c2pwrite;
+    let mut errwrite = config.errwrite;
+
+    // The parent's ends of the pipes are of no use to the child
+    for fd in &[
+        config.p2cwrite,
+        config.c2pread,
+        config.errread,
+        config.errpipe_read,
+    ] {
+        if *fd != -1 {
+            libc::close(*fd);
+        }
+    }
+
+    macro_rules! check {
+        ($result:expr) => {
+            if $result == -1 {
+                report_error(config.errpipe_write, last_errno(), true);
+            }
+        };
+    }
+
+    // Make sure dup2() below doesn't overwrite a descriptor that is still needed
+    if c2pwrite == 0 {
+        c2pwrite = libc::fcntl(c2pwrite, libc::F_DUPFD_CLOEXEC, 0);
+        check!(c2pwrite);
+    }
+    while errwrite == 0 || errwrite == 1 {
+        errwrite = libc::fcntl(errwrite, libc::F_DUPFD_CLOEXEC, 0);
+        check!(errwrite);
+    }
+
+    check!(dup_std_fd(p2cread, 0));
+    check!(dup_std_fd(c2pwrite, 1));
+    check!(dup_std_fd(errwrite, 2));
+
+    if let Some(ref cwd) = config.cwd {
+        check!(libc::chdir(cwd.as_ptr()));
+    }
+
+    if config.restore_signals {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+        libc::signal(libc::SIGXFSZ, libc::SIG_DFL);
+    }
+
+    if config.call_setsid {
+        check!(libc::setsid());
+    }
+
+    for fd in &config.fds_to_keep {
+        if *fd != config.errpipe_write {
+            check!(clear_cloexec(*fd));
+        }
+    }
+    for fd in &config.fds_to_close {
+        libc::close(*fd);
+    }
+
+    // Try each executable in turn, remembering the most relevant error
+    let mut saved_errno = 0;
+    for executable in &config.executables {
+        match config.envp {
+            Some(ref envp) => {
+                libc::execve(executable.as_ptr(), config.argv.as_ptr(), envp.as_ptr())
+            }
+            None => libc::execv(executable.as_ptr(), config.argv.as_ptr()),
+        };
+        let errno = last_errno();
+        if errno != libc::ENOENT && errno != libc::ENOTDIR && saved_errno == 0 {
+            saved_errno = errno;
+        }
+    }
+    if saved_errno == 0 {
+        saved_errno = last_errno();
+    }
+    report_error(config.errpipe_write, saved_errno, false)
+}
+
+unsafe fn clear_cloexec(fd: i32) -> i32 {
+    let flags = libc::fcntl(fd, libc::F_GETFD);
+    if flags == -1 {
+        return -1;
+    }
+    libc::fcntl(fd, libc::F_SETFD, flags & !libc::FD_CLOEXEC)
+}
+
+/// Installs `fd` as the standard descriptor `target`. dup2() clears
+/// FD_CLOEXEC on the copy, except when it is a no-op.
+unsafe fn dup_std_fd(fd: i32, target: i32) -> i32 {
+    if fd == target {
+        clear_cloexec(fd)
+    } else if fd != -1 {
+        libc::dup2(fd, target)
+    } else {
+        0
+    }
+}
+
+/// Writes the error record for the parent without allocating, then exits.
+unsafe fn report_error(errpipe_write: i32, errno: i32, noexec: bool) -> ! {
+    const HEX: &[u8] = b"0123456789abcdef";
+    let mut buf = [0u8; 32];
+    let mut len = 0;
+    for b in b"OSError:" {
+        buf[len] = *b;
+        len += 1;
+    }
+    let errno = errno as u32;
+    let mut shift = 28;
+    while shift > 0 && (errno >> shift) == 0 {
+        shift -= 4;
+    }
+    loop {
+        buf[len] = HEX[((errno >> shift) & 0xf) as usize];
+        len += 1;
+        if shift == 0 {
+            break;
+        }
+        shift -= 4;
+    }
+    buf[len] = b':';
+    len += 1;
+    if noexec {
+        for b in b"noexec" {
+            buf[len] = *b;
+            len += 1;
+        }
+    }
+    libc::write(errpipe_write, buf.as_ptr() as *const libc::c_void, len);
+    libc::_exit(255)
+}
+
+pub fn make_module(vm: &VirtualMachine) -> PyObjectRef {
+    let ctx = &vm.ctx;
+
+    py_module!(vm, "_posixsubprocess", {
+        "fork_exec" => ctx.new_rustfunc(posixsubprocess_fork_exec),
+    })
+}
@@ -0,0 +1,153 @@
+use std::cmp;
+use std::io;
+use std::time::{Duration, Instant};
+
+use super::os::convert_io_error;
+use crate::function::OptionalArg;
+use crate::obj::objfloat;
+use crate::obj::objint::PyInt;
+use crate::pyobject::{PyIterable, PyObjectRef, PyResult, TryFromObject};
+use crate::vm::VirtualMachine;
+
+/// Resolves an entry of a select() list, which is either a file descriptor
+/// or an object with a `fileno()` method.
+fn get_fd(obj: &PyObjectRef, vm: &VirtualMachine) -> PyResult<i32> {
+    let fd = if obj.payload::<PyInt>().is_some() {
+        i32::try_from_object(vm, obj.clone())?
+    } else {
+        let fileno = vm.call_method(obj, "fileno", vec![])?;
+        i32::try_from_object(vm, fileno)?
+    };
+    if fd < 0 {
+        return Err(vm.new_value_error(format!(
+            "file descriptor cannot be a negative integer ({})",
+            fd
+        )));
+    }
+    Ok(fd)
+}
+
+fn select_select(
+    rlist: PyIterable,
+    wlist: PyIterable,
+    xlist: PyIterable,
+    timeout: OptionalArg<Option<PyObjectRef>>,
+    vm: &VirtualMachine,
+) -> PyResult {
+    let timeout = match timeout.into_option().and_then(|timeout| timeout) {
+        Some(timeout) => Some(objfloat::make_float(vm, &timeout)?),
+        None => None,
+    };
+    let mut timeout = match timeout {
+        Some(timeout) if timeout < 0.0 => {
+            return Err(vm.new_value_error("timeout must be non-negative".to_string()));
+        }
+        Some(timeout) => Some(Duration::from_millis((timeout * 1000.0).ceil() as u64)),
+        None => None,
+    };
+
+    // (object, fd, requested events) for each entry of the three lists in turn
+    let mut entries = vec![];
+    for (list, events) in vec![
+        (rlist, libc::POLLIN),
+        (wlist, libc::POLLOUT),
+        (xlist, libc::POLLPRI),
+    ] {
+        for obj in list.iter(vm)? {
+            let obj = obj?;
+            let fd = get_fd(&obj, vm)?;
+            entries.push((obj, fd, events));
+        }
+    }
+
+    let mut pollfds: Vec<libc::pollfd> = entries
+        .iter()
+        .map(|(_, fd, events)| libc::pollfd {
+            fd: *fd,
+            events: *events,
+            revents: 0,
+        })
+        .collect();
+    let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+    loop {
+        // Don't wait past the time limit of the vm
+        let limited = vm.limits.clamp_timeout(vm, timeout)?;
+        let res = unsafe {
+            libc::poll(
+                pollfds.as_mut_ptr(),
+                pollfds.len() as libc::nfds_t,
+                limited.map_or(-1, poll_timeout),
+            )
+        };
+        if res >= 0 {
+            if res == 0 && limited != timeout {
+                vm.limits.check_deadline(vm)?;
+            }
+            break;
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(convert_io_error(vm, err));
+        }
+        vm.check_signals()?;
+        // As in PEP 475, retry with whatever is left of the timeout
+        if let Some(deadline) = deadline {
+            let now = Instant::now();
+            timeout = Some(if now < deadline {
+                deadline - now
+            } else {
+                Duration::from_secs(0)
+            });
+        }
+    }
+
+    // Like select(2), a hang-up or error makes a descriptor ready for reading and writing
+    let mut ready = vec![vec![], vec![], vec![]];
+    for ((obj, _, events), pollfd) in entries.into_iter().zip(pollfds) {
+        if pollfd.revents & libc::POLLNVAL != 0 {
+            return Err(convert_io_error(
+                vm,
+                io::Error::from_raw_os_error(libc::EBADF),
+            ));
+        }
+        let index = match events {
+            libc::POLLIN => 0,
+            libc::POLLOUT => 1,
+            _ => 2,
+        };
+        let mask = if index == 2 {
+            events
+        } else {
+            events | libc::POLLHUP | libc::POLLERR
+        };
+        if pollfd.revents & mask != 0 {
+            ready[index].push(obj);
+        }
+    }
+
+    Ok(vm.ctx.new_tuple(
+        ready
+            .into_iter()
+            .map(|objs| vm.ctx.new_list(objs))
+            .collect(),
+    ))
+}
+
+/// Converts `timeout` to the milliseconds that poll(2) takes, rounding up so
+/// that it doesn't return before the time is up.
+pub(super) fn poll_timeout(timeout: Duration) -> libc::c_int {
+    let millis = timeout
+        .checked_add(Duration::from_nanos(999_999))
+        .unwrap_or(timeout)
+        .as_millis();
+    cmp::min(millis, libc::c_int::max_value() as u128) as libc::c_int
+}
+
+pub fn make_module(vm: &VirtualMachine) -> PyObjectRef {
+    let ctx = &vm.ctx;
+
+    py_module!(vm, "select", {
+        "select" => ctx.new_rustfunc(select_select),
+        "error" => ctx.exceptions.os_error.clone(),
+    })
+}
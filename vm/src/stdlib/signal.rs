@@ -0,0 +1,345 @@
+//! Implementation of the `signal` module.
+//!
+//! The C-level handler only records that a signal arrived; the Python-level
+//! handler runs later, between bytecode instructions, when the interpreter
+//! calls `check_signals`.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use arr_macro::arr;
+
+use super::os::convert_io_error;
+use crate::function::{OptionalArg, PyFuncArgs};
+use crate::obj::objfloat;
+use crate::obj::objint::PyInt;
+use crate::pyobject::{PyObjectRef, PyResult, TryFromObject};
+use crate::vm::VirtualMachine;
+
+// One more than the largest signal number, SIGRTMAX on Linux
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const NSIG: usize = 65;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+const NSIG: usize = 64;
+
+// The values of `signal.SIG_DFL` and `signal.SIG_IGN`
+const SIG_DFL: i32 = 0;
+const SIG_IGN: i32 = 1;
+
+static ANY_TRIGGERED: AtomicBool = AtomicBool::new(false);
+#[cfg(any(target_os = "linux", target_os = "android"))]
+static TRIGGERS: [AtomicBool; NSIG] = arr![AtomicBool::new(false); 65];
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+static TRIGGERS: [AtomicBool; NSIG] = arr![AtomicBool::new(false); 64];
+
+extern "C" fn run_signal(signum: libc::c_int) {
+    TRIGGERS[signum as usize].store(true, Ordering::Relaxed);
+    ANY_TRIGGERED.store(true, Ordering::SeqCst);
+    // signal() resets the disposition to SIG_DFL before calling the handler
+    #[cfg(windows)]
+    unsafe {
+        libc::signal(
+            signum,
+            run_signal as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+}
+
+#[cfg(unix)]
+unsafe fn set_os_handler(signum: i32, handler: libc::sighandler_t) -> io::Result<()> {
+    let mut action: libc::sigaction = std::mem::zeroed();
+    action.sa_sigaction = handler;
+    libc::sigemptyset(&mut action.sa_mask);
+    // No SA_RESTART: blocking system calls fail with EINTR, so that
+    // the functions making them get a chance to run the Python handler
+    action.sa_flags = libc::SA_ONSTACK;
+    if libc::sigaction(signum, &action, std::ptr::null_mut()) == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+unsafe fn get_os_handler(signum: i32) -> libc::sighandler_t {
+    let mut action: libc::sigaction = std::mem::zeroed();
+    if libc::sigaction(signum, std::ptr::null(), &mut action) == -1 {
+        libc::SIG_DFL
+    } else {
+        action.sa_sigaction
+    }
+}
+
+#[cfg(windows)]
+unsafe fn set_os_handler(signum: i32, handler: libc::sighandler_t) -> io::Result<()> {
+    if libc::signal(signum, handler) == libc::SIG_ERR {
+        Err(io::Error::from_raw_os_error(libc::EINVAL))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+unsafe fn get_os_handler(signum: i32) -> libc::sighandler_t {
+    // The only way to query the disposition is to replace it
+    let old = libc::signal(signum, libc::SIG_DFL);
+    if old != libc::SIG_ERR {
+        libc::signal(signum, old);
+    }
+    old
+}
+
+fn check_signum(signum: i32, vm: &VirtualMachine) -> PyResult<()> {
+    if signum < 1 || signum as usize >= NSIG {
+        Err(vm.new_value_error("signal number out of range".to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+fn signal_signal(signum: i32, handler: PyObjectRef, vm: &VirtualMachine) -> PyResult {
+    check_signum(signum, vm)?;
+    let os_handler = match handler.payload::<PyInt>() {
+        Some(_) => match i32::try_from_object(vm, handler.clone())? {
+            SIG_DFL => libc::SIG_DFL,
+            SIG_IGN => libc::SIG_IGN,
+            _ => return Err(invalid_handler_error(vm)),
+        },
+        None if vm.is_callable(&handler) => {
+            run_signal as extern "C" fn(libc::c_int) as libc::sighandler_t
+        }
+        None => return Err(invalid_handler_error(vm)),
+    };
+    unsafe { set_os_handler(signum, os_handler) }.map_err(|err| convert_io_error(vm, err))?;
+    let old_handler = vm.signal_handlers.borrow_mut().insert(signum, handler);
+    Ok(old_handler.unwrap_or_else(|| vm.get_none()))
+}
+
+fn invalid_handler_error(vm: &VirtualMachine) -> PyObjectRef {
+    vm.new_type_error(
+        "signal handler must be signal.SIG_IGN, signal.SIG_DFL, or a callable object".to_string(),
+    )
+}
+
+fn signal_getsignal(signum: i32, vm: &VirtualMachine) -> PyResult {
+    check_signum(signum, vm)?;
+    let handler = vm.signal_handlers.borrow().get(&signum).cloned();
+    Ok(handler.unwrap_or_else(|| vm.get_none()))
+}
+
+fn signal_default_int_handler(_args: PyFuncArgs, vm: &VirtualMachine) -> PyResult {
+    Err(vm.new_empty_exception(vm.ctx.exceptions.keyboard_interrupt.clone())?)
+}
+
+#[cfg(unix)]
+fn signal_alarm(seconds: u32, _vm: &VirtualMachine) -> u32 {
+    unsafe { libc::alarm(seconds) }
+}
+
+#[cfg(unix)]
+fn signal_pause(vm: &VirtualMachine) -> PyResult<()> {
+    unsafe { libc::pause() };
+    vm.check_signals()
+}
+
+#[cfg(unix)]
+fn timeval_from_f64(seconds: f64) -> libc::timeval {
+    let sec = seconds.trunc();
+    let mut tv = libc::timeval {
+        tv_sec: sec as libc::time_t,
+        tv_usec: ((seconds - sec) * 1e6) as libc::suseconds_t,
+    };
+    // Don't disarm a timer because the value got rounded down to zero
+    if tv.tv_sec == 0 && tv.tv_usec == 0 && seconds > 0.0 {
+        tv.tv_usec = 1;
+    }
+    tv
+}
+
+#[cfg(unix)]
+fn timeval_to_f64(tv: &libc::timeval) -> f64 {
+    tv.tv_sec as f64 + tv.tv_usec as f64 / 1e6
+}
+
+#[cfg(unix)]
+fn itimer_tuple(value: &libc::itimerval, vm: &VirtualMachine) -> PyObjectRef {
+    vm.ctx.new_tuple(vec![
+        vm.ctx.new_float(timeval_to_f64(&value.it_value)),
+        vm.ctx.new_float(timeval_to_f64(&value.it_interval)),
+    ])
+}
+
+#[cfg(unix)]
+fn signal_setitimer(
+    which: i32,
+    seconds: PyObjectRef,
+    interval: OptionalArg<PyObjectRef>,
+    vm: &VirtualMachine,
+) -> PyResult {
+    let interval = match interval {
+        OptionalArg::Present(interval) => objfloat::make_float(vm, &interval)?,
+        OptionalArg::Missing => 0.0,
+    };
+    let new = libc::itimerval {
+        it_value: timeval_from_f64(objfloat::make_float(vm, &seconds)?),
+        it_interval: timeval_from_f64(interval),
+    };
+    let mut old: libc::itimerval = unsafe { std::mem::zeroed() };
+    if unsafe { libc::setitimer(which, &new, &mut old) } == -1 {
+        return Err(convert_io_error(vm, io::Error::last_os_error()));
+    }
+    Ok(itimer_tuple(&old, vm))
+}
+
+#[cfg(unix)]
+fn signal_getitimer(which: i32, vm: &VirtualMachine) -> PyResult {
+    let mut value: libc::itimerval = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getitimer(which, &mut value) } == -1 {
+        return Err(convert_io_error(vm, io::Error::last_os_error()));
+    }
+    Ok(itimer_tuple(&value, vm))
+}
+
+/// Runs the Python-level handlers of the signals that arrived since the
+/// last call. An exception raised by a handler is propagated, and the
+/// signals that are still pending are handled on the next call.
+pub fn check_signals(vm: &VirtualMachine) -> PyResult<()> {
+    if !ANY_TRIGGERED.load(Ordering::Relaxed) || !ANY_TRIGGERED.swap(false, Ordering::SeqCst) {
+        return Ok(());
+    }
+    for (signum, trigger) in TRIGGERS.iter().enumerate().skip(1) {
+        if trigger.swap(false, Ordering::Relaxed) {
+            if let Err(exc) = run_handler(signum as i32, vm) {
+                ANY_TRIGGERED.store(true, Ordering::SeqCst);
+                return Err(exc);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Handles an interrupt requested with an `InterruptHandle` as if SIGINT
+/// had been received. Without a Python-level handler, `KeyboardInterrupt`
+/// is raised anyway, since the embedder explicitly asked for it.
+pub fn handle_interrupt(vm: &VirtualMachine) -> PyResult<()> {
+    let handler = vm.signal_handlers.borrow().get(&libc::SIGINT).cloned();
+    match handler {
+        Some(ref handler) if vm.is_callable(handler) => run_handler(libc::SIGINT, vm),
+        Some(ref handler) if is_sig_ign(handler, vm) => Ok(()),
+        _ => Err(vm.new_empty_exception(vm.ctx.exceptions.keyboard_interrupt.clone())?),
+    }
+}
+
+fn is_sig_ign(handler: &PyObjectRef, vm: &VirtualMachine) -> bool {
+    handler.payload::<PyInt>().is_some()
+        && i32::try_from_object(vm, handler.clone()).ok() == Some(SIG_IGN)
+}
+
+fn run_handler(signum: i32, vm: &VirtualMachine) -> PyResult<()> {
+    let handler = vm.signal_handlers.borrow().get(&signum).cloned();
+    if let Some(handler) = handler {
+        if vm.is_callable(&handler) {
+            let frame = match vm.current_frame() {
+                Some(frame) => frame.clone().into_object(),
+                None => vm.get_none(),
+            };
+            vm.invoke(handler, vec![vm.new_int(signum), frame])?;
+        }
+    }
+    Ok(())
+}
+
+pub fn make_module(vm: &VirtualMachine) -> PyObjectRef {
+    let ctx = &vm.ctx;
+
+    let default_int_handler = ctx.new_rustfunc(signal_default_int_handler);
+
+    let module = py_module!(vm, "signal", {
+        "signal" => ctx.new_rustfunc(signal_signal),
+        "getsignal" => ctx.new_rustfunc(signal_getsignal),
+        "default_int_handler" => default_int_handler.clone(),
+        "SIG_DFL" => ctx.new_int(SIG_DFL),
+        "SIG_IGN" => ctx.new_int(SIG_IGN),
+        "NSIG" => ctx.new_int(NSIG),
+        "SIGABRT" => ctx.new_int(libc::SIGABRT),
+        "SIGFPE" => ctx.new_int(libc::SIGFPE),
+        "SIGILL" => ctx.new_int(libc::SIGILL),
+        "SIGINT" => ctx.new_int(libc::SIGINT),
+        "SIGSEGV" => ctx.new_int(libc::SIGSEGV),
+        "SIGTERM" => ctx.new_int(libc::SIGTERM),
+    });
+    let module = extend_module_platform_specific(vm, module);
+
+    // Record the handlers the process started with, like CPython does
+    let mut handlers = vm.signal_handlers.borrow_mut();
+    for signum in 1..NSIG as i32 {
+        let handler = unsafe { get_os_handler(signum) };
+        if handler == libc::SIG_DFL {
+            handlers.insert(signum, ctx.new_int(SIG_DFL));
+        } else if handler == libc::SIG_IGN {
+            handlers.insert(signum, ctx.new_int(SIG_IGN));
+        }
+    }
+
+    // Ctrl-C raises KeyboardInterrupt, unless SIGINT is being ignored
+    if handlers
+        .get(&libc::SIGINT)
+        .map_or(false, |handler| !is_sig_ign(handler, vm))
+        && unsafe {
+            set_os_handler(
+                libc::SIGINT,
+                run_signal as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            )
+        }
+        .is_ok()
+    {
+        handlers.insert(libc::SIGINT, default_int_handler);
+    }
+
+    module
+}
+
+#[cfg(unix)]
+fn extend_module_platform_specific(vm: &VirtualMachine, module: PyObjectRef) -> PyObjectRef {
+    let ctx = &vm.ctx;
+
+    extend_module!(vm, module, {
+        "alarm" => ctx.new_rustfunc(signal_alarm),
+        "pause" => ctx.new_rustfunc(signal_pause),
+        "setitimer" => ctx.new_rustfunc(signal_setitimer),
+        "getitimer" => ctx.new_rustfunc(signal_getitimer),
+        "ItimerError" => ctx.exceptions.os_error.clone(),
+        "ITIMER_REAL" => ctx.new_int(libc::ITIMER_REAL),
+        "ITIMER_VIRTUAL" => ctx.new_int(libc::ITIMER_VIRTUAL),
+        "ITIMER_PROF" => ctx.new_int(libc::ITIMER_PROF),
+        "SIGHUP" => ctx.new_int(libc::SIGHUP),
+        "SIGQUIT" => ctx.new_int(libc::SIGQUIT),
+        "SIGTRAP" => ctx.new_int(libc::SIGTRAP),
+        "SIGBUS" => ctx.new_int(libc::SIGBUS),
+        "SIGKILL" => ctx.new_int(libc::SIGKILL),
+        "SIGUSR1" => ctx.new_int(libc::SIGUSR1),
+        "SIGUSR2" => ctx.new_int(libc::SIGUSR2),
+        "SIGPIPE" => ctx.new_int(libc::SIGPIPE),
+        "SIGALRM" => ctx.new_int(libc::SIGALRM),
+        "SIGCHLD" => ctx.new_int(libc::SIGCHLD),
+        "SIGCONT" => ctx.new_int(libc::SIGCONT),
+        "SIGSTOP" => ctx.new_int(libc::SIGSTOP),
+        "SIGTSTP" => ctx.new_int(libc::SIGTSTP),
+        "SIGTTIN" => ctx.new_int(libc::SIGTTIN),
+        "SIGTTOU" => ctx.new_int(libc::SIGTTOU),
+        "SIGURG" => ctx.new_int(libc::SIGURG),
+        "SIGXCPU" => ctx.new_int(libc::SIGXCPU),
+        "SIGXFSZ" => ctx.new_int(libc::SIGXFSZ),
+        "SIGVTALRM" => ctx.new_int(libc::SIGVTALRM),
+        "SIGPROF" => ctx.new_int(libc::SIGPROF),
+        "SIGWINCH" => ctx.new_int(libc::SIGWINCH),
+        "SIGIO" => ctx.new_int(libc::SIGIO),
+        "SIGSYS" => ctx.new_int(libc::SIGSYS),
+    });
+    module
+}
+
+#[cfg(not(unix))]
+fn extend_module_platform_specific(_vm: &VirtualMachine, module: PyObjectRef) -> PyObjectRef {
+    module
+}
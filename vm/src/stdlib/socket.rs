@@ -9,6 +9,7 @@ use crate::obj::objint::PyIntRef;
 use crate::obj::objstr::PyStringRef;
 use crate::obj::objtuple::PyTupleRef;
 use crate::pyobject::{PyObjectRef, PyRef, PyResult, PyValue, TryFromObject};
+use crate::sandbox::Access;
 use crate::vm::VirtualMachine;
 
 use crate::obj::objtype::PyClassRef;
@@ -168,10 +169,12 @@ impl SocketRef {
         kind: SocketKind,
         vm: &VirtualMachine,
     ) -> PyResult<SocketRef> {
+        vm.check_access(Access::Network)?;
         Socket::new(family, kind).into_ref_with_type(vm, cls)
     }
 
     fn connect(self, address: Address, vm: &VirtualMachine) -> PyResult<()> {
+        vm.check_access(Access::Network)?;
         let address_string = address.get_address_string();
 
         match self.socket_kind {
@@ -196,6 +199,7 @@ impl SocketRef {
     }
 
     fn bind(self, address: Address, vm: &VirtualMachine) -> PyResult<()> {
+        vm.check_access(Access::Network)?;
         let address_string = address.get_address_string();
 
         match self.socket_kind {
@@ -14,7 +14,12 @@ fn time_sleep(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
     let secs: u64 = seconds.trunc() as u64;
     let nanos: u32 = (seconds.fract() * 1e9) as u32;
     let duration = Duration::new(secs, nanos);
-    thread::sleep(duration);
+    // Don't sleep past the time limit of the vm
+    let limited = vm.limits.clamp_timeout(vm, Some(duration))?.unwrap();
+    thread::sleep(limited);
+    if limited < duration {
+        vm.limits.check_deadline(vm)?;
+    }
     Ok(vm.get_none())
 }
 
@@ -1,10 +1,11 @@
 use std::rc::Rc;
 use std::{env, mem};
 
+use crate::builtins;
 use crate::frame::FrameRef;
 use crate::function::{OptionalArg, PyFuncArgs};
 use crate::obj::objstr::PyStringRef;
-use crate::pyobject::{IntoPyObject, ItemProtocol, PyClassImpl, PyContext, PyObjectRef, PyResult};
+use crate::pyobject::{IdProtocol, ItemProtocol, PyClassImpl, PyContext, PyObjectRef, PyResult};
 use crate::vm::VirtualMachine;
 
 /*
@@ -17,6 +18,23 @@ fn argv(ctx: &PyContext) -> PyObjectRef {
     ctx.new_list(argv)
 }
 
+/// sys.builtin_module_names, from the native modules registered in the vm.
+fn builtin_module_names(vm: &VirtualMachine) -> PyObjectRef {
+    let mut module_names: Vec<_> = vm.stdlib_inits.borrow().keys().cloned().collect();
+    module_names.push("sys".to_string());
+    module_names.push("builtins".to_string());
+    module_names.sort();
+    vm.ctx
+        .new_tuple(module_names.into_iter().map(|v| vm.new_str(v)).collect())
+}
+
+/// Refresh sys.builtin_module_names after native modules were registered.
+pub(crate) fn update_builtin_module_names(vm: &VirtualMachine) {
+    let module_names = builtin_module_names(vm);
+    vm.set_attr(&vm.sys_module, "builtin_module_names", module_names)
+        .unwrap();
+}
+
 fn getframe(offset: OptionalArg<usize>, vm: &VirtualMachine) -> PyResult<FrameRef> {
     let offset = offset.into_option().unwrap_or(0);
     if offset > vm.frames.borrow().len() - 1 {
@@ -93,9 +111,12 @@ fn sys_getfilesystemencodeerrors(_vm: &VirtualMachine) -> String {
     "surrogatepass".to_string()
 }
 
-// TODO implement string interning, this will be key for performance
-fn sys_intern(value: PyStringRef, _vm: &VirtualMachine) -> PyStringRef {
-    value
+fn sys_intern(value: PyStringRef, vm: &VirtualMachine) -> PyResult<PyStringRef> {
+    let cls = value.typ();
+    if !cls.is(&vm.ctx.str_type()) {
+        return Err(vm.new_type_error(format!("can't intern {}", cls.name)));
+    }
+    Ok(vm.ctx.intern_string(value))
 }
 
 pub fn make_module(vm: &VirtualMachine, module: PyObjectRef, builtins: PyObjectRef) {
@@ -229,16 +250,13 @@ setprofile() -- set the global profiling function
 setrecursionlimit() -- set the max recursion depth for the interpreter
 settrace() -- set the global debug tracing function
 ";
-    let mut module_names: Vec<_> = vm.stdlib_inits.borrow().keys().cloned().collect();
-    module_names.push("sys".to_string());
-    module_names.push("builtins".to_string());
-    module_names.sort();
     let modules = ctx.new_dict();
     extend_module!(vm, module, {
       "__name__" => ctx.new_str(String::from("sys")),
       "argv" => argv(ctx),
-      "builtin_module_names" => ctx.new_tuple(module_names.iter().map(|v| v.into_pyobject(vm).unwrap()).collect()),
+      "builtin_module_names" => builtin_module_names(vm),
       "byteorder" => ctx.new_str(bytorder),
+      "exit" => ctx.new_rustfunc(builtins::builtin_exit),
       "flags" => flags,
       "getrefcount" => ctx.new_rustfunc(sys_getrefcount),
       "getsizeof" => ctx.new_rustfunc(sys_getsizeof),
@@ -2,12 +2,13 @@ use std::fs::File;
 use std::io::{Read, Result};
 use std::path::Path;
 
-/// Read a file at `path` into a String
-pub fn read_file(path: &Path) -> Result<String> {
+/// Read a file at `path` into a byte buffer. Python source files may
+/// declare their own encoding, so decoding is left to the caller.
+pub fn read_file(path: &Path) -> Result<Vec<u8>> {
     info!("Loading file {:?}", path);
     let mut f = File::open(&path)?;
-    let mut buffer = String::new();
-    f.read_to_string(&mut buffer)?;
+    let mut buffer = Vec::new();
+    f.read_to_end(&mut buffer)?;
 
     Ok(buffer)
 }
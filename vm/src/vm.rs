@@ -4,11 +4,13 @@
 //!   https://github.com/ProgVal/pythonvm-rust/blob/master/src/processor/mod.rs
 //!
 
-use std::cell::{Ref, RefCell};
+use std::cell::{Cell, Ref, RefCell};
+use std::cmp;
 use std::collections::hash_map::HashMap;
 use std::collections::hash_set::HashSet;
 use std::rc::Rc;
-use std::sync::{Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
 
 use crate::builtins;
 use crate::bytecode;
@@ -33,9 +35,9 @@ use crate::pyobject::{
     IdProtocol, ItemProtocol, PyContext, PyObjectRef, PyResult, PyValue, TryFromObject, TryIntoRef,
     TypeProtocol,
 };
+use crate::sandbox::{self, Access, AccessPolicy, LimitState, ResourceLimits};
 use crate::stdlib;
 use crate::sysmodule;
-use num_bigint::BigInt;
 #[cfg(feature = "rustpython_compiler")]
 use rustpython_compiler::compile;
 #[cfg(feature = "rustpython_compiler")]
@@ -57,19 +59,149 @@ pub struct VirtualMachine {
     pub exceptions: RefCell<Vec<PyObjectRef>>,
     pub frozen: RefCell<HashMap<String, bytecode::CodeObject>>,
     pub import_func: RefCell<PyObjectRef>,
+    /// Python-level signal handlers, as set by `signal.signal()`
+    pub signal_handlers: RefCell<HashMap<i32, PyObjectRef>>,
+    pub interrupt_requested: Arc<AtomicBool>,
+    /// Makes the exception requested with `InterruptHandle::interrupt_with`.
+    interrupt_exception: Arc<Mutex<Option<MakeException>>>,
+    periodic_callback: RefCell<Option<Rc<PeriodicCallback>>>,
+    access_policy: RefCell<Option<AccessPolicy>>,
+    /// The file descriptors opened by the vm, which it may use without asking
+    /// the access policy.
+    opened_fds: RefCell<HashSet<i64>>,
+    pub(crate) limits: LimitState,
 }
 
-impl VirtualMachine {
-    /// Create a new `VirtualMachine` structure.
-    pub fn new() -> VirtualMachine {
+type MakeException = Box<dyn FnOnce(&VirtualMachine) -> PyObjectRef + Send>;
+type Callback = Box<dyn Fn(&VirtualMachine) -> PyResult<()>>;
+
+/// A handle to request an interrupt of the Python code running in a
+/// `VirtualMachine` from another thread. The interrupt happens at the next
+/// instruction boundary, which may be in code run after the request.
+#[derive(Clone)]
+pub struct InterruptHandle {
+    requested: Arc<AtomicBool>,
+    exception: Arc<Mutex<Option<MakeException>>>,
+}
+
+impl InterruptHandle {
+    /// Interrupt with the same effect as Ctrl-C: the SIGINT handler runs,
+    /// which by default raises `KeyboardInterrupt`.
+    pub fn interrupt(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+    }
+
+    /// Interrupt by raising the exception made by `make_exception`, which
+    /// runs in the thread of the vm.
+    pub fn interrupt_with<F>(&self, make_exception: F)
+    where
+        F: FnOnce(&VirtualMachine) -> PyObjectRef + Send + 'static,
+    {
+        self.exception
+            .lock()
+            .unwrap()
+            .replace(Box::new(make_exception));
+        self.interrupt();
+    }
+}
+
+struct PeriodicCallback {
+    interval: u64,
+    /// Instructions left to run before the next call.
+    countdown: Cell<u64>,
+    callback: Callback,
+}
+
+/// Registers the native and frozen modules of a `VirtualMachine` before it
+/// is created, starting from the ones of the standard library.
+pub struct VirtualMachineBuilder {
+    stdlib_inits: HashMap<String, stdlib::StdlibInitFunc>,
+    frozen: HashMap<String, bytecode::CodeObject>,
+    access_policy: Option<AccessPolicy>,
+    limits: ResourceLimits,
+}
+
+impl Default for VirtualMachineBuilder {
+    fn default() -> Self {
+        VirtualMachineBuilder {
+            stdlib_inits: stdlib::get_module_inits(),
+            frozen: frozen::get_module_inits(),
+            access_policy: None,
+            limits: ResourceLimits::default(),
+        }
+    }
+}
+
+impl VirtualMachineBuilder {
+    /// Register a module implemented in rust, replacing any module of that
+    /// name. `init` creates the module when it is first imported.
+    pub fn native_module<F>(mut self, name: &str, init: F) -> Self
+    where
+        F: Fn(&VirtualMachine) -> PyObjectRef + 'static,
+    {
+        self.stdlib_inits.insert(name.to_string(), Box::new(init));
+        self
+    }
+
+    /// Register a module compiled to bytecode ahead of time, such as with
+    /// `py_compile_bytecode!`, replacing any module of that name.
+    pub fn frozen_module(mut self, name: &str, code: bytecode::CodeObject) -> Self {
+        self.frozen.insert(name.to_string(), code);
+        self
+    }
+
+    /// Remove a native or frozen module, so that it cannot be imported. Note
+    /// that the import machinery needs `_imp`, `_io`, `_os`, `_thread`,
+    /// `_warnings`, `_weakref` and `marshal` to be initialized.
+    pub fn disable_module(mut self, name: &str) -> Self {
+        self.stdlib_inits.remove(name);
+        self.frozen.remove(name);
+        self
+    }
+
+    /// Decide which accesses to files, the network and processes the os, io,
+    /// socket and subprocess modules may make. Denied accesses raise
+    /// `PermissionError`.
+    pub fn access_policy<F>(mut self, policy: F) -> Self
+    where
+        F: Fn(&VirtualMachine, Access) -> bool + 'static,
+    {
+        self.access_policy = Some(Box::new(policy));
+        self
+    }
+
+    pub fn resource_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Restrict the vm for running untrusted code: disable the modules which
+    /// reach outside the interpreter without an access policy, and deny all
+    /// accesses unless an access policy was given.
+    pub fn sandboxed(mut self) -> Self {
+        for name in sandbox::UNSAFE_MODULES {
+            self = self.disable_module(name);
+        }
+        if self.access_policy.is_none() {
+            self = self.access_policy(|_vm, _access| false);
+        }
+        self
+    }
+
+    /// The names of the native modules registered so far.
+    pub fn native_module_names(&self) -> impl Iterator<Item = &str> {
+        self.stdlib_inits.keys().map(String::as_str)
+    }
+
+    pub fn build(self) -> VirtualMachine {
         let ctx = PyContext::new();
 
         // Hard-core modules:
         let builtins = ctx.new_module("builtins", ctx.new_dict());
         let sysmod = ctx.new_module("sys", ctx.new_dict());
 
-        let stdlib_inits = RefCell::new(stdlib::get_module_inits());
-        let frozen = RefCell::new(frozen::get_module_inits());
+        let stdlib_inits = RefCell::new(self.stdlib_inits);
+        let frozen = RefCell::new(self.frozen);
         let import_func = RefCell::new(ctx.none());
         let vm = VirtualMachine {
             builtins: builtins.clone(),
@@ -81,15 +213,104 @@ impl VirtualMachine {
             exceptions: RefCell::new(vec![]),
             frozen,
             import_func,
+            signal_handlers: RefCell::new(HashMap::new()),
+            interrupt_requested: Arc::new(AtomicBool::new(false)),
+            interrupt_exception: Arc::new(Mutex::new(None)),
+            periodic_callback: RefCell::new(None),
+            access_policy: RefCell::new(self.access_policy),
+            opened_fds: RefCell::new(HashSet::new()),
+            limits: LimitState::default(),
         };
+        vm.limits.set_limits(self.limits);
 
         builtins::make_module(&vm, builtins.clone());
         sysmodule::make_module(&vm, sysmod, builtins);
         vm
     }
+}
+
+impl VirtualMachine {
+    /// Create a new `VirtualMachine` structure.
+    pub fn new() -> VirtualMachine {
+        VirtualMachine::builder().build()
+    }
+
+    /// Configure the modules of a new `VirtualMachine`.
+    pub fn builder() -> VirtualMachineBuilder {
+        VirtualMachineBuilder::default()
+    }
+
+    /// Register a module implemented in rust after the `VirtualMachine` was
+    /// created, replacing any module of that name that wasn't imported yet.
+    pub fn add_native_module<F>(&self, name: &str, init: F)
+    where
+        F: Fn(&VirtualMachine) -> PyObjectRef + 'static,
+    {
+        self.stdlib_inits
+            .borrow_mut()
+            .insert(name.to_string(), Box::new(init));
+        sysmodule::update_builtin_module_names(self);
+    }
+
+    /// Set the limits on the resources used by each call into the vm, which
+    /// apply from the next one.
+    pub fn set_resource_limits(&self, limits: ResourceLimits) {
+        self.limits.set_limits(limits);
+    }
+
+    /// Replace the access policy, as with `VirtualMachineBuilder::access_policy`.
+    pub fn set_access_policy<F>(&self, policy: F)
+    where
+        F: Fn(&VirtualMachine, Access) -> bool + 'static,
+    {
+        self.access_policy.replace(Some(Box::new(policy)));
+    }
+
+    /// Raise `PermissionError` if the access policy denies `access`.
+    pub fn check_access(&self, access: Access) -> PyResult<()> {
+        match &*self.access_policy.borrow() {
+            Some(policy) if !policy(self, access) => Err(self.new_exception(
+                self.ctx.exceptions.permission_error.clone(),
+                format!("{} denied by the sandbox", access),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Account for `count` items of `size` bytes which the vm is about to
+    /// allocate for the contents of an object. Raise `MemoryError` if they
+    /// can't be allocated or would exceed `ResourceLimits::max_memory`.
+    pub fn reserve_memory(&self, count: usize, size: usize) -> PyResult<()> {
+        self.limits.reserve_memory(self, count, size)
+    }
+
+    /// Raise `PermissionError` if the vm didn't open the file descriptor `fd`
+    /// and the access policy denies using it.
+    pub fn check_fd_access(&self, fd: i64) -> PyResult<()> {
+        if self.opened_fds.borrow().contains(&fd) {
+            Ok(())
+        } else {
+            self.check_access(Access::Descriptor(fd))
+        }
+    }
+
+    /// Record that the vm opened the file descriptor `fd`.
+    pub(crate) fn add_opened_fd(&self, fd: i64) {
+        self.opened_fds.borrow_mut().insert(fd);
+    }
+
+    /// Record that the file descriptor `fd` was closed.
+    pub(crate) fn remove_opened_fd(&self, fd: i64) {
+        self.opened_fds.borrow_mut().remove(&fd);
+    }
+
+    /// Register a frozen module after the `VirtualMachine` was created.
+    pub fn add_frozen_module(&self, name: &str, code: bytecode::CodeObject) {
+        self.frozen.borrow_mut().insert(name.to_string(), code);
+    }
 
     pub fn run_code_obj(&self, code: PyCodeRef, scope: Scope) -> PyResult {
-        let frame = Frame::new(code, scope).into_ref(self);
+        let frame = Frame::new(code, scope, None, self).into_ref(self);
         self.run_frame_full(frame)
     }
 
@@ -101,23 +322,117 @@ impl VirtualMachine {
     }
 
     pub fn run_frame(&self, frame: FrameRef) -> PyResult<ExecutionResult> {
-        self.frames.borrow_mut().push(frame.clone());
+        self.push_frame(frame.clone())?;
         let result = frame.run(self);
         self.frames.borrow_mut().pop();
         result
     }
 
+    /// Push a frame on the call stack, enforcing the recursion limit. The
+    /// first frame starts a new call into the vm, so the resource usage is
+    /// measured from there.
+    fn push_frame(&self, frame: FrameRef) -> PyResult<()> {
+        let depth = self.frames.borrow().len();
+        if depth == 0 {
+            self.limits.reset();
+        }
+        if let Some(max_depth) = self.limits.max_recursion_depth() {
+            if depth >= max_depth {
+                return Err(sandbox::limit_error(
+                    self,
+                    "maximum recursion depth exceeded",
+                ));
+            }
+        }
+        self.frames.borrow_mut().push(frame);
+        Ok(())
+    }
+
     pub fn frame_throw(
         &self,
         frame: FrameRef,
         exception: PyObjectRef,
     ) -> PyResult<ExecutionResult> {
-        self.frames.borrow_mut().push(frame.clone());
+        self.push_frame(frame.clone())?;
         let result = frame.throw(self, exception);
         self.frames.borrow_mut().pop();
         result
     }
 
+    /// Returns a handle that can be sent to another thread to interrupt
+    /// this virtual machine.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle {
+            requested: self.interrupt_requested.clone(),
+            exception: self.interrupt_exception.clone(),
+        }
+    }
+
+    /// Call `callback` every `interval` instructions of Python code, so that
+    /// the host can handle events while the code runs, or pause it by
+    /// blocking in the callback until it should resume. An exception returned
+    /// by the callback is raised in the code, which cancels it unless it is
+    /// caught.
+    pub fn set_periodic_callback<F>(&self, interval: u64, callback: F)
+    where
+        F: Fn(&VirtualMachine) -> PyResult<()> + 'static,
+    {
+        let interval = cmp::max(interval, 1);
+        self.periodic_callback
+            .replace(Some(Rc::new(PeriodicCallback {
+                interval,
+                countdown: Cell::new(interval),
+                callback: Box::new(callback),
+            })));
+    }
+
+    pub fn clear_periodic_callback(&self) {
+        self.periodic_callback.replace(None);
+    }
+
+    /// Count an instruction about to run, calling the periodic callback when
+    /// it is due.
+    #[inline]
+    pub(crate) fn check_periodic_callback(&self) -> PyResult<()> {
+        let periodic = match &*self.periodic_callback.borrow() {
+            Some(periodic) => {
+                let countdown = periodic.countdown.get() - 1;
+                if countdown > 0 {
+                    periodic.countdown.set(countdown);
+                    return Ok(());
+                }
+                periodic.countdown.set(periodic.interval);
+                periodic.clone()
+            }
+            None => return Ok(()),
+        };
+        // Not borrowed while called, so that the callback can replace itself
+        (periodic.callback)(self)
+    }
+
+    /// Runs the handlers of the signals received since the last check, and
+    /// handles an interrupt requested through an `InterruptHandle`. Called
+    /// between bytecode instructions and by blocking functions that were
+    /// interrupted by a signal.
+    pub fn check_signals(&self) -> PyResult<()> {
+        #[cfg(not(target_arch = "wasm32"))]
+        stdlib::signal::check_signals(self)?;
+
+        if self.interrupt_requested.load(Ordering::Relaxed)
+            && self.interrupt_requested.swap(false, Ordering::SeqCst)
+        {
+            let make_exception = self.interrupt_exception.lock().unwrap().take();
+            if let Some(make_exception) = make_exception {
+                return Err(make_exception(self));
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            return stdlib::signal::handle_interrupt(self);
+            #[cfg(target_arch = "wasm32")]
+            return Err(self.new_empty_exception(self.ctx.exceptions.keyboard_interrupt.clone())?);
+        }
+        Ok(())
+    }
+
     pub fn current_frame(&self) -> Option<Ref<FrameRef>> {
         let frames = self.frames.borrow();
         if frames.is_empty() {
@@ -160,7 +475,7 @@ impl VirtualMachine {
     }
 
     /// Create a new python int object.
-    pub fn new_int<T: Into<BigInt>>(&self, i: T) -> PyObjectRef {
+    pub fn new_int<T: Into<PyInt>>(&self, i: T) -> PyObjectRef {
         self.ctx.new_int(i)
     }
 
@@ -231,6 +546,11 @@ impl VirtualMachine {
         self.new_exception_obj(key_error, vec![obj]).unwrap()
     }
 
+    pub fn new_memory_error(&self) -> PyObjectRef {
+        let memory_error = self.ctx.exceptions.memory_error.clone();
+        self.new_empty_exception(memory_error).unwrap()
+    }
+
     pub fn new_index_error(&self, msg: String) -> PyObjectRef {
         let index_error = self.ctx.exceptions.index_error.clone();
         self.new_exception(index_error, msg)
@@ -253,11 +573,28 @@ impl VirtualMachine {
 
     #[cfg(feature = "rustpython_compiler")]
     pub fn new_syntax_error(&self, error: &CompileError) -> PyObjectRef {
-        let syntax_error_type = self.ctx.exceptions.syntax_error.clone();
-        let syntax_error = self.new_exception(syntax_error_type, error.to_string());
-        let lineno = self.new_int(error.location.row());
-        self.set_attr(&syntax_error, "lineno", lineno).unwrap();
-        syntax_error
+        let syntax_error_type = if error.is_tab_error() {
+            self.ctx.exceptions.tab_error.clone()
+        } else if error.is_indentation_error() {
+            self.ctx.exceptions.indentation_error.clone()
+        } else {
+            self.ctx.exceptions.syntax_error.clone()
+        };
+        let optional_str = |value: &Option<String>| {
+            value
+                .as_ref()
+                .map_or_else(|| self.get_none(), |value| self.new_str(value.clone()))
+        };
+        // SyntaxError(msg, (filename, lineno, offset, text))
+        let details = self.ctx.new_tuple(vec![
+            optional_str(&error.source_path),
+            self.new_int(error.location.row()),
+            self.new_int(error.location.column()),
+            optional_str(&error.statement),
+        ]);
+        let msg = self.new_str(error.error.to_string());
+        self.new_exception_obj(syntax_error_type, vec![msg, details])
+            .unwrap()
     }
 
     pub fn new_import_error(&self, msg: String) -> PyObjectRef {
@@ -282,7 +619,9 @@ impl VirtualMachine {
     }
 
     pub fn get_locals(&self) -> PyDictRef {
-        self.current_scope().get_locals().clone()
+        self.current_frame()
+            .expect("called get_locals but no frames on the stack")
+            .get_locals(self)
     }
 
     pub fn context(&self) -> &PyContext {
@@ -396,9 +735,10 @@ impl VirtualMachine {
             ref scope,
             ref defaults,
             ref kw_only_defaults,
+            ref closure,
         }) = func_ref.payload()
         {
-            self.invoke_python_function(code, scope, defaults, kw_only_defaults, args)
+            self.invoke_python_function(code, scope, defaults, kw_only_defaults, closure, args)
         } else if let Some(PyMethod {
             ref function,
             ref object,
@@ -429,19 +769,15 @@ impl VirtualMachine {
         scope: &Scope,
         defaults: &Option<PyTupleRef>,
         kw_only_defaults: &Option<PyDictRef>,
+        closure: &Option<PyTupleRef>,
         func_args: PyFuncArgs,
     ) -> PyResult {
         let scope = scope.new_child_scope(&self.ctx);
-        self.fill_locals_from_args(
-            &code.code,
-            &scope.get_locals(),
-            func_args,
-            defaults,
-            kw_only_defaults,
-        )?;
 
         // Construct frame:
-        let frame = Frame::new(code.clone(), scope).into_ref(self);
+        let frame = Frame::new(code.clone(), scope, closure.as_ref(), self);
+        self.fill_locals_from_args(&code.code, &frame, func_args, defaults, kw_only_defaults)?;
+        let frame = frame.into_ref(self);
 
         // If we have a generator, create a new generator
         if code.code.is_generator {
@@ -451,17 +787,16 @@ impl VirtualMachine {
         }
     }
 
-    pub fn invoke_with_locals(
-        &self,
-        function: PyObjectRef,
-        cells: PyDictRef,
-        locals: PyDictRef,
-    ) -> PyResult {
-        if let Some(PyFunction { code, scope, .. }) = &function.payload() {
-            let scope = scope
-                .new_child_scope_with_locals(cells)
-                .new_child_scope_with_locals(locals);
-            let frame = Frame::new(code.clone(), scope).into_ref(self);
+    pub fn invoke_with_locals(&self, function: PyObjectRef, locals: PyDictRef) -> PyResult {
+        if let Some(PyFunction {
+            code,
+            scope,
+            closure,
+            ..
+        }) = &function.payload()
+        {
+            let scope = scope.new_child_scope_with_locals(locals);
+            let frame = Frame::new(code.clone(), scope, closure.as_ref(), self).into_ref(self);
             return self.run_frame_full(frame);
         }
         panic!(
@@ -473,7 +808,7 @@ impl VirtualMachine {
     fn fill_locals_from_args(
         &self,
         code_object: &bytecode::CodeObject,
-        locals: &PyDictRef,
+        frame: &Frame,
         func_args: PyFuncArgs,
         defaults: &Option<PyTupleRef>,
         kw_only_defaults: &Option<PyDictRef>,
@@ -497,7 +832,7 @@ impl VirtualMachine {
         for i in 0..n {
             let arg_name = &code_object.arg_names[i];
             let arg = &func_args.args[i];
-            locals.set_item(arg_name, arg.clone(), self)?;
+            frame.store_local(self, arg_name, arg.clone());
         }
 
         // Pack other positional arguments in to *args:
@@ -510,7 +845,7 @@ impl VirtualMachine {
                 }
                 let vararg_value = self.ctx.new_tuple(last_args);
 
-                locals.set_item(vararg_name, vararg_value, self)?;
+                frame.store_local(self, vararg_name, vararg_value);
             }
             bytecode::Varargs::Unnamed | bytecode::Varargs::None => {
                 // Check the number of positional arguments
@@ -527,7 +862,7 @@ impl VirtualMachine {
         let kwargs = match code_object.varkeywords {
             bytecode::Varargs::Named(ref kwargs_name) => {
                 let d = self.ctx.new_dict();
-                locals.set_item(kwargs_name, d.as_object().clone(), self)?;
+                frame.store_local(self, kwargs_name, d.as_object().clone());
                 Some(d)
             }
             bytecode::Varargs::Unnamed => Some(self.ctx.new_dict()),
@@ -535,17 +870,27 @@ impl VirtualMachine {
         };
 
         // Handle keyword arguments
+        let posonly_names = &code_object.arg_names[..code_object.posonlyarg_count];
+        let mut posonly_passed_as_keyword = vec![];
         for (name, value) in func_args.kwargs {
+            // Positional-only parameters can't be passed by keyword:
+            if posonly_names.contains(&name) {
+                if let Some(d) = &kwargs {
+                    d.set_item(&name, value, self)?;
+                } else {
+                    posonly_passed_as_keyword.push(name);
+                }
             // Check if we have a parameter with this name:
-            if code_object.arg_names.contains(&name) || code_object.kwonlyarg_names.contains(&name)
+            } else if code_object.arg_names.contains(&name)
+                || code_object.kwonlyarg_names.contains(&name)
             {
-                if locals.contains_key(&name, self) {
+                if frame.load_local(self, &name).is_some() {
                     return Err(
                         self.new_type_error(format!("Got multiple values for argument '{}'", name))
                     );
                 }
 
-                locals.set_item(&name, value, self)?;
+                frame.store_local(self, &name, value);
             } else if let Some(d) = &kwargs {
                 d.set_item(&name, value, self)?;
             } else {
@@ -555,6 +900,14 @@ impl VirtualMachine {
             }
         }
 
+        if !posonly_passed_as_keyword.is_empty() {
+            return Err(self.new_type_error(format!(
+                "{}() got some positional-only arguments passed as keyword arguments: '{}'",
+                code_object.obj_name,
+                posonly_passed_as_keyword.join(", ")
+            )));
+        }
+
         // Add missing positional arguments, if we have fewer positional arguments than the
         // function definition calls for
         if nargs < nexpected_args {
@@ -566,7 +919,7 @@ impl VirtualMachine {
             let mut missing = vec![];
             for i in 0..required_args {
                 let variable_name = &code_object.arg_names[i];
-                if !locals.contains_key(variable_name, self) {
+                if frame.load_local(self, variable_name).is_none() {
                     missing.push(variable_name)
                 }
             }
@@ -583,8 +936,8 @@ impl VirtualMachine {
                 // the default if we don't already have a value
                 for (default_index, i) in (required_args..nexpected_args).enumerate() {
                     let arg_name = &code_object.arg_names[i];
-                    if !locals.contains_key(arg_name, self) {
-                        locals.set_item(arg_name, defaults[default_index].clone(), self)?;
+                    if frame.load_local(self, arg_name).is_none() {
+                        frame.store_local(self, arg_name, defaults[default_index].clone());
                     }
                 }
             }
@@ -592,10 +945,10 @@ impl VirtualMachine {
 
         // Check if kw only arguments are all present:
         for arg_name in &code_object.kwonlyarg_names {
-            if !locals.contains_key(arg_name, self) {
+            if frame.load_local(self, arg_name).is_none() {
                 if let Some(kw_only_defaults) = kw_only_defaults {
                     if let Some(default) = kw_only_defaults.get_item_option(arg_name, self)? {
-                        locals.set_item(arg_name, default, self)?;
+                        frame.store_local(self, arg_name, default);
                         continue;
                     }
                 }
@@ -741,7 +1094,18 @@ impl VirtualMachine {
         source_path: String,
     ) -> Result<PyCodeRef, CompileError> {
         compile::compile(source, mode, source_path)
-            .map(|codeobj| PyCode::new(codeobj).into_ref(self))
+            .map(|codeobj| PyCode::new(codeobj, &self.ctx).into_ref(self))
+    }
+
+    #[cfg(feature = "rustpython_compiler")]
+    pub fn compile_bytes(
+        &self,
+        source: &[u8],
+        mode: &compile::Mode,
+        source_path: String,
+    ) -> Result<PyCodeRef, CompileError> {
+        compile::compile_bytes(source, mode, source_path)
+            .map(|codeobj| PyCode::new(codeobj, &self.ctx).into_ref(self))
     }
 
     pub fn _sub(&self, a: PyObjectRef, b: PyObjectRef) -> PyResult {
@@ -1005,6 +1369,40 @@ impl VirtualMachine {
     pub fn pop_exception(&self) -> Option<PyObjectRef> {
         self.exceptions.borrow_mut().pop()
     }
+
+    /// The exception currently being handled, if any.
+    pub fn current_exception(&self) -> Option<PyObjectRef> {
+        self.exceptions.borrow().last().cloned()
+    }
+
+    /// Set the implicit `__context__` of an exception that is being raised
+    /// to the exception currently being handled, like CPython does.
+    pub fn contextualize_exception(&self, exception: &PyObjectRef) -> PyResult<()> {
+        let context = match self.current_exception() {
+            Some(context) => context,
+            None => return Ok(()),
+        };
+        if context.is(exception) {
+            return Ok(());
+        }
+        // Avoid creating a cycle by cutting the chain where it would
+        // reach the raised exception again.
+        let mut seen = HashSet::new();
+        let mut current = context.clone();
+        while seen.insert(current.get_id()) {
+            let next = self.get_attribute(current.clone(), "__context__")?;
+            if self.get_none().is(&next) {
+                break;
+            }
+            if next.is(exception) {
+                self.set_attr(&current, "__context__", self.get_none())?;
+                break;
+            }
+            current = next;
+        }
+        self.set_attr(exception, "__context__", context)?;
+        Ok(())
+    }
 }
 
 impl Default for VirtualMachine {
@@ -1052,9 +1450,49 @@ impl Drop for ReprGuard {
 #[cfg(test)]
 mod tests {
     use super::VirtualMachine;
+    use crate::import;
     use crate::obj::{objint, objstr};
+    use crate::pyobject::TryFromObject;
     use num_bigint::ToBigInt;
 
+    fn builtin_module_names(vm: &VirtualMachine) -> Vec<String> {
+        let names = vm
+            .get_attribute(vm.sys_module.clone(), "builtin_module_names")
+            .unwrap();
+        Vec::try_from_object(vm, names).unwrap()
+    }
+
+    #[test]
+    fn test_builder_modules() {
+        let vm = VirtualMachine::builder()
+            .native_module("_answer", |vm| {
+                py_module!(vm, "_answer", {
+                    "answer" => vm.new_int(42),
+                })
+            })
+            .frozen_module(
+                "frozen_answer",
+                py_compile_bytecode!(source = "answer = 42\n", module_name = "frozen_answer"),
+            )
+            .disable_module("socket")
+            .build();
+        let names = builtin_module_names(&vm);
+        assert!(names.iter().any(|name| name == "_answer"));
+        assert!(!names.iter().any(|name| name == "socket"));
+        assert!(import::import_builtin(&vm, "socket").is_err());
+
+        for module in &[
+            import::import_builtin(&vm, "_answer").unwrap(),
+            import::import_frozen(&vm, "frozen_answer").unwrap(),
+        ] {
+            let answer = vm.get_attribute(module.clone(), "answer").unwrap();
+            assert_eq!(i32::try_from_object(&vm, answer).unwrap(), 42);
+        }
+
+        vm.add_native_module("_late", |vm| py_module!(vm, "_late", {}));
+        assert!(builtin_module_names(&vm).iter().any(|name| name == "_late"));
+    }
+
     #[test]
     fn test_add_py_integers() {
         let vm = VirtualMachine::new();
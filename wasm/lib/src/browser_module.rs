@@ -13,7 +13,7 @@ use rustpython_vm::obj::{
 use rustpython_vm::pyobject::{
     PyCallable, PyClassImpl, PyObject, PyObjectRef, PyRef, PyResult, PyValue,
 };
-use rustpython_vm::VirtualMachine;
+use rustpython_vm::{VirtualMachine, VirtualMachineBuilder};
 
 use crate::{convert, vm_class::weak_vm, wasm_builtins::window};
 
@@ -377,12 +377,8 @@ pub fn make_module(vm: &VirtualMachine) -> PyObjectRef {
     })
 }
 
-pub fn setup_browser_module(vm: &VirtualMachine) {
-    vm.stdlib_inits
-        .borrow_mut()
-        .insert("_browser".to_string(), Box::new(make_module));
-    vm.frozen.borrow_mut().insert(
-        "browser".to_string(),
-        py_compile_bytecode!(file = "src/browser.py"),
-    );
+pub fn setup_browser_module(builder: VirtualMachineBuilder) -> VirtualMachineBuilder {
+    builder
+        .native_module("_browser", make_module)
+        .frozen_module("browser", py_compile_bytecode!(file = "src/browser.py"))
 }
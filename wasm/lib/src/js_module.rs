@@ -4,7 +4,7 @@ use rustpython_vm::obj::{objfloat::PyFloatRef, objstr::PyStringRef, objtype::PyC
 use rustpython_vm::pyobject::{
     create_type, PyClassImpl, PyObjectRef, PyRef, PyResult, PyValue, TryFromObject,
 };
-use rustpython_vm::VirtualMachine;
+use rustpython_vm::{VirtualMachine, VirtualMachineBuilder};
 use wasm_bindgen::{prelude::*, JsCast};
 
 #[wasm_bindgen(inline_js = "
@@ -248,8 +248,6 @@ pub fn make_module(vm: &VirtualMachine) -> PyObjectRef {
     })
 }
 
-pub fn setup_js_module(vm: &VirtualMachine) {
-    vm.stdlib_inits
-        .borrow_mut()
-        .insert("_js".to_string(), Box::new(make_module));
+pub fn setup_js_module(builder: VirtualMachineBuilder) -> VirtualMachineBuilder {
+    builder.native_module("_js", make_module)
 }
@@ -5,7 +5,7 @@ use std::rc::{Rc, Weak};
 use js_sys::{Object, Reflect, SyntaxError, TypeError};
 use wasm_bindgen::prelude::*;
 
-use rustpython_compiler::{compile, error::CompileErrorType};
+use rustpython_compiler::compile;
 use rustpython_vm::frame::{NameProtocol, Scope};
 use rustpython_vm::function::PyFuncArgs;
 use rustpython_vm::pyobject::{PyObject, PyObjectPayload, PyObjectRef, PyResult, PyValue};
@@ -26,22 +26,18 @@ pub(crate) struct StoredVirtualMachine {
 
 impl StoredVirtualMachine {
     fn new(id: String, inject_browser_module: bool) -> StoredVirtualMachine {
-        let mut vm = VirtualMachine::new();
-        vm.wasm_id = Some(id);
-        let scope = vm.new_scope_with_builtins();
-
-        js_module::setup_js_module(&vm);
+        let mut builder = js_module::setup_js_module(VirtualMachine::builder());
         if inject_browser_module {
-            vm.stdlib_inits.borrow_mut().insert(
-                "_window".to_string(),
-                Box::new(|vm| {
-                    py_module!(vm, "_window", {
-                        "window" => js_module::PyJsValue::new(wasm_builtins::window()).into_ref(vm),
-                    })
-                }),
-            );
-            setup_browser_module(&vm);
+            builder = builder.native_module("_window", |vm| {
+                py_module!(vm, "_window", {
+                    "window" => js_module::PyJsValue::new(wasm_builtins::window()).into_ref(vm),
+                })
+            });
+            builder = setup_browser_module(builder);
         }
+        let mut vm = builder.build();
+        vm.wasm_id = Some(id);
+        let scope = vm.new_scope_with_builtins();
 
         *vm.import_func.borrow_mut() = vm.ctx.new_rustfunc(wasm_builtins::builtin_import);
 
@@ -262,9 +258,7 @@ impl WASMVirtualMachine {
                 module
             };
 
-            vm.stdlib_inits
-                .borrow_mut()
-                .insert(mod_name, Box::new(stdlib_init_fn));
+            vm.add_native_module(&mod_name, stdlib_init_fn);
 
             Ok(())
         })?
@@ -280,30 +274,13 @@ impl WASMVirtualMachine {
                 let code = vm.compile(&source, &mode, "<wasm>".to_string());
                 let code = code.map_err(|err| {
                     let js_err = SyntaxError::new(&format!("Error parsing Python code: {}", err));
-                    if let CompileErrorType::Parse(ref parse_error) = err.error {
-                        use rustpython_parser::error::ParseError;
-                        if let ParseError::EOF(Some(ref loc))
-                        | ParseError::ExtraToken((ref loc, ..))
-                        | ParseError::InvalidToken(ref loc)
-                        | ParseError::UnrecognizedToken((ref loc, ..), _) = parse_error
-                        {
-                            let _ =
-                                Reflect::set(&js_err, &"row".into(), &(loc.row() as u32).into());
-                            let _ =
-                                Reflect::set(&js_err, &"col".into(), &(loc.column() as u32).into());
-                        }
-                        if let ParseError::ExtraToken((_, _, ref loc))
-                        | ParseError::UnrecognizedToken((_, _, ref loc), _) = parse_error
-                        {
-                            let _ =
-                                Reflect::set(&js_err, &"endrow".into(), &(loc.row() as u32).into());
-                            let _ = Reflect::set(
-                                &js_err,
-                                &"endcol".into(),
-                                &(loc.column() as u32).into(),
-                            );
-                        }
-                    }
+                    let _ =
+                        Reflect::set(&js_err, &"row".into(), &(err.location.row() as u32).into());
+                    let _ = Reflect::set(
+                        &js_err,
+                        &"col".into(),
+                        &(err.location.column() as u32).into(),
+                    );
                     js_err
                 })?;
                 let result = vm.run_code_obj(code, scope.borrow().clone());